@@ -0,0 +1,385 @@
+-- 0001 baseline: the schema as it stood when numbered migrations were
+-- introduced (everything the old run_migrations built, with the columns
+-- it used to ALTER in folded into the CREATEs).
+--
+-- Every statement is IF NOT EXISTS so the same file also adopts a
+-- pre-migration database: migrations::adopt_legacy_db first adds any
+-- columns an old file is missing, then this runs as a no-op for the
+-- tables that already exist.
+
+-- Users table - stores student information.
+-- first_name and last_name are plaintext for teacher queries; the secret
+-- key is stored client-side only.
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    classroom TEXT,
+    data_consent INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    recovery_encrypted_key TEXT,
+    role TEXT NOT NULL DEFAULT 'student',
+    teacher_terms_accepted_at TEXT,
+    attention_cleared_at TEXT,
+    activity_cleared_at TEXT,
+    name_corrected_at TEXT,
+    -- Persistent join-URL codes: host_code backs the teacher's evergreen
+    -- /play/:hostCode URL, invite_code a player's /table/:inviteCode URL.
+    host_code TEXT,
+    invite_code TEXT
+);
+
+-- Viewers table - teachers, partners, admin who can view student data.
+-- Each viewer has an RSA keypair for receiving encrypted sharing grants.
+CREATE TABLE IF NOT EXISTS viewers (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    public_key TEXT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'teacher',
+    created_at TEXT NOT NULL,
+    recovery_encrypted_private_key TEXT
+);
+
+-- Sharing grants - links students to viewers. encrypted_payload contains
+-- the student's secret key encrypted with the viewer's public key.
+CREATE TABLE IF NOT EXISTS sharing_grants (
+    id TEXT PRIMARY KEY,
+    grantor_id TEXT NOT NULL,
+    grantee_id TEXT NOT NULL,
+    encrypted_payload TEXT NOT NULL,
+    granted_at TEXT NOT NULL,
+    expires_at TEXT,
+    revoked INTEGER NOT NULL DEFAULT 0,
+    revoked_at TEXT,
+    FOREIGN KEY (grantor_id) REFERENCES users(id),
+    FOREIGN KEY (grantee_id) REFERENCES viewers(id),
+    UNIQUE(grantor_id, grantee_id)
+);
+
+-- Observations table - encrypted practice data. encrypted_data is
+-- AES-256-GCM encrypted with the student's secret key.
+-- status / wilderness are populated by the recompute walker; exercise_id,
+-- assignment_id and jungle are set by the client at insert time
+-- (CORRECTNESS_AND_MASTERY.md §16). time_taken_ms is per-play time on the
+-- board (issue #7).
+CREATE TABLE IF NOT EXISTS observations (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    skill_path TEXT NOT NULL,
+    correct INTEGER NOT NULL,
+    classroom TEXT,
+    deal_subfolder TEXT,
+    deal_number INTEGER,
+    encrypted_data TEXT NOT NULL,
+    iv TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    board_result TEXT,
+    status TEXT,
+    wilderness TEXT,
+    exercise_id TEXT,
+    assignment_id TEXT,
+    jungle INTEGER NOT NULL DEFAULT 0,
+    time_taken_ms INTEGER,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
+CREATE INDEX IF NOT EXISTS idx_users_classroom ON users(classroom);
+CREATE INDEX IF NOT EXISTS idx_viewers_email ON viewers(email);
+CREATE INDEX IF NOT EXISTS idx_grants_grantor ON sharing_grants(grantor_id);
+CREATE INDEX IF NOT EXISTS idx_grants_grantee ON sharing_grants(grantee_id);
+CREATE INDEX IF NOT EXISTS idx_observations_user_id ON observations(user_id);
+CREATE INDEX IF NOT EXISTS idx_observations_classroom ON observations(classroom);
+CREATE INDEX IF NOT EXISTS idx_observations_timestamp ON observations(timestamp);
+CREATE INDEX IF NOT EXISTS idx_observations_skill_path ON observations(skill_path);
+-- Assignment progress queries
+CREATE INDEX IF NOT EXISTS idx_observations_deal_user
+    ON observations(deal_subfolder, deal_number, user_id);
+-- Exercise usage rollup (issue #15)
+CREATE INDEX IF NOT EXISTS idx_observations_exercise ON observations(exercise_id);
+-- §C10: assignment_id is the hot filter for the assignment rollups
+CREATE INDEX IF NOT EXISTS idx_observations_assignment ON observations(assignment_id);
+-- "Is the board cold?" check (CORRECTNESS_AND_MASTERY.md §7.2)
+CREATE INDEX IF NOT EXISTS idx_observations_user_board_ts
+    ON observations(user_id, deal_subfolder, deal_number, timestamp);
+-- SQLite can't add a UNIQUE column via ALTER TABLE, so join-code
+-- uniqueness is enforced with partial unique indexes.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_host_code
+    ON users(host_code) WHERE host_code IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_invite_code
+    ON users(invite_code) WHERE invite_code IS NOT NULL;
+
+-- Recovery tokens table - for email-based account recovery
+CREATE TABLE IF NOT EXISTS recovery_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used INTEGER NOT NULL DEFAULT 0,
+    recovery_code_hash TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_recovery_tokens_user_id ON recovery_tokens(user_id);
+
+-- Account-merge handoff: after an admin merges a duplicate account onto a
+-- keeper, this row lets the merged-away device switch itself to the keeper
+-- on next load. No FK to users — the merged-away user row gets deleted.
+CREATE TABLE IF NOT EXISTS account_handoff (
+    from_user_id      TEXT PRIMARY KEY,
+    encrypted_payload TEXT NOT NULL,
+    iv                TEXT NOT NULL,
+    created_at        TEXT NOT NULL,
+    expires_at        TEXT NOT NULL,
+    used              INTEGER NOT NULL DEFAULT 0
+);
+
+-- Convention cards. owner_id is NULL for system cards (templates/samples).
+CREATE TABLE IF NOT EXISTS convention_cards (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    format TEXT NOT NULL DEFAULT 'bridge_classroom',
+    owner_id TEXT REFERENCES users(id),
+    card_data TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- User-card links. Partners can share a card (multiple users, same card).
+CREATE TABLE IF NOT EXISTS user_convention_cards (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id),
+    card_id TEXT NOT NULL REFERENCES convention_cards(id),
+    is_primary INTEGER NOT NULL DEFAULT 0,
+    label TEXT,
+    linked_at TEXT NOT NULL,
+    UNIQUE(user_id, card_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cards_owner ON convention_cards(owner_id);
+CREATE INDEX IF NOT EXISTS idx_cards_visibility ON convention_cards(visibility);
+CREATE INDEX IF NOT EXISTS idx_user_cards_user ON user_convention_cards(user_id);
+CREATE INDEX IF NOT EXISTS idx_user_cards_card ON user_convention_cards(card_id);
+
+-- Exercises. deleted_at is a soft-delete tombstone (issue #15) so
+-- observation history keeps its exercise_id.
+CREATE TABLE IF NOT EXISTS exercises (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    created_by TEXT REFERENCES users(id),
+    curriculum_path TEXT,
+    visibility TEXT NOT NULL DEFAULT 'public',
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_exercises_created_by ON exercises(created_by);
+CREATE INDEX IF NOT EXISTS idx_exercises_curriculum ON exercises(curriculum_path);
+CREATE INDEX IF NOT EXISTS idx_exercises_visibility ON exercises(visibility);
+
+CREATE TABLE IF NOT EXISTS exercise_boards (
+    exercise_id TEXT NOT NULL REFERENCES exercises(id) ON DELETE CASCADE,
+    deal_subfolder TEXT NOT NULL,
+    deal_number INTEGER NOT NULL,
+    sort_order INTEGER NOT NULL,
+    collection_id TEXT,
+    PRIMARY KEY (exercise_id, deal_subfolder, deal_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_exercise_boards_deal
+    ON exercise_boards(deal_subfolder, deal_number);
+
+CREATE TABLE IF NOT EXISTS classrooms (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    teacher_id TEXT NOT NULL REFERENCES users(id),
+    join_code TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_classrooms_teacher ON classrooms(teacher_id);
+CREATE INDEX IF NOT EXISTS idx_classrooms_join_code ON classrooms(join_code);
+
+CREATE TABLE IF NOT EXISTS classroom_members (
+    classroom_id TEXT NOT NULL REFERENCES classrooms(id) ON DELETE CASCADE,
+    student_id TEXT NOT NULL REFERENCES users(id),
+    joined_at TEXT NOT NULL,
+    PRIMARY KEY (classroom_id, student_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_classroom_members_student ON classroom_members(student_id);
+
+CREATE TABLE IF NOT EXISTS assignments (
+    id TEXT PRIMARY KEY,
+    exercise_id TEXT NOT NULL REFERENCES exercises(id),
+    classroom_id TEXT REFERENCES classrooms(id),
+    student_id TEXT REFERENCES users(id),
+    assigned_by TEXT NOT NULL REFERENCES users(id),
+    assigned_at TEXT NOT NULL,
+    due_at TEXT,
+    sort_order INTEGER,
+    CHECK (
+        (classroom_id IS NOT NULL AND student_id IS NULL) OR
+        (classroom_id IS NULL AND student_id IS NOT NULL)
+    )
+);
+
+CREATE INDEX IF NOT EXISTS idx_assignments_classroom ON assignments(classroom_id);
+CREATE INDEX IF NOT EXISTS idx_assignments_student ON assignments(student_id);
+CREATE INDEX IF NOT EXISTS idx_assignments_exercise ON assignments(exercise_id);
+CREATE INDEX IF NOT EXISTS idx_assignments_assigned_by ON assignments(assigned_by);
+
+CREATE TABLE IF NOT EXISTS announcements (
+    id TEXT PRIMARY KEY,
+    message TEXT NOT NULL,
+    type TEXT NOT NULL DEFAULT 'info',
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    active INTEGER NOT NULL DEFAULT 1
+);
+
+-- Per-board status (CORRECTNESS_AND_MASTERY.md §6, §7). The old
+-- `achievement` column is left in place; code reads the
+-- (max_stars, wild_achievement) pair instead.
+CREATE TABLE IF NOT EXISTS board_status (
+    user_id TEXT NOT NULL,
+    deal_subfolder TEXT NOT NULL,
+    deal_number INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'not_attempted',
+    achievement TEXT NOT NULL DEFAULT 'none',
+    last_observation_at TEXT,
+    updated_at TEXT NOT NULL,
+    wilderness TEXT NOT NULL DEFAULT 'Tame',
+    last_error_date TEXT,
+    star_count INTEGER NOT NULL DEFAULT 0,
+    max_stars INTEGER NOT NULL DEFAULT 0,
+    last_star_update TEXT,
+    wild_achievement TEXT,
+    PRIMARY KEY (user_id, deal_subfolder, deal_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_board_status_user_subfolder
+    ON board_status(user_id, deal_subfolder);
+
+-- Assignment-scoped board status: the §5 status of the work the student
+-- did INSIDE an assignment, so the progress bar reads a rollup instead of
+-- querying observations.
+CREATE TABLE IF NOT EXISTS assignment_board_status (
+    user_id TEXT NOT NULL,
+    assignment_id TEXT NOT NULL,
+    deal_subfolder TEXT NOT NULL,
+    deal_number INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'not_attempted',
+    last_observation_at TEXT,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, assignment_id, deal_subfolder, deal_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_abs_user_assignment
+    ON assignment_board_status(user_id, assignment_id);
+
+-- §C9: always present so merge_accounts can DELETE from it. The admin
+-- decrypt endpoint still DROP+CREATEs it on each run.
+CREATE TABLE IF NOT EXISTS observations_decrypted (
+    observation_id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    timestamp TEXT,
+    deal_subfolder TEXT,
+    deal_number INTEGER,
+    prompt_index INTEGER,
+    correct INTEGER,
+    board_result_metadata TEXT,
+    board_result_payload TEXT,
+    inferred_board_result TEXT,
+    had_wrong_prompt INTEGER,
+    prompt_count INTEGER,
+    student_bid TEXT,
+    expected_bid TEXT,
+    student_hand TEXT,
+    full_auction TEXT,
+    auction_so_far TEXT,
+    skill_path TEXT,
+    session_id TEXT,
+    decrypted_json TEXT,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+-- Multiplayer table sessions. Boards are NOT stored here; live table
+-- state lives in bridge-table-service memory. seat_policy is the JSON
+-- blob the service interprets.
+CREATE TABLE IF NOT EXISTS table_sessions (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('teacher_set', 'adhoc')),
+    owner_user_id TEXT NOT NULL REFERENCES users(id),
+    classroom_id TEXT REFERENCES classrooms(id),
+    exercise_id TEXT REFERENCES exercises(id),
+    status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed')),
+    seat_policy TEXT NOT NULL,
+    table_count INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    closed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_table_sessions_owner_status
+    ON table_sessions(owner_user_id, status);
+
+-- Type-a-name guests. No FK on session_id: guests may join demo/dev
+-- sessions that have no table_sessions row.
+CREATE TABLE IF NOT EXISTS guest_users (
+    id TEXT PRIMARY KEY,
+    display_name TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    linked_user_id TEXT REFERENCES users(id),
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_guest_users_session ON guest_users(session_id);
+
+-- Flags for the one-shot data backfills that predate numbered migrations.
+-- Kept so databases that already ran them don't run them again.
+CREATE TABLE IF NOT EXISTS schema_meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    completed_at TEXT NOT NULL
+);
+
+-- Denormalised per-student rollup (§14.2). A cache, rebuildable from
+-- board_status and observations at any time.
+CREATE TABLE IF NOT EXISTS student_summary (
+    user_id              TEXT PRIMARY KEY,
+    last_observation_at  TEXT,
+    total_observations   INTEGER NOT NULL DEFAULT 0,
+    distinct_lessons     INTEGER NOT NULL DEFAULT 0,
+    distinct_boards_seen INTEGER NOT NULL DEFAULT 0,
+
+    boards_not_attempted INTEGER NOT NULL DEFAULT 0,
+    boards_failed        INTEGER NOT NULL DEFAULT 0,
+    boards_corrected     INTEGER NOT NULL DEFAULT 0,
+    boards_close_correct INTEGER NOT NULL DEFAULT 0,
+    boards_clean_correct INTEGER NOT NULL DEFAULT 0,
+
+    boards_silver        INTEGER NOT NULL DEFAULT 0,
+    boards_gold          INTEGER NOT NULL DEFAULT 0,
+    on_star_track        INTEGER NOT NULL DEFAULT 0,
+
+    boards_recent_paw    INTEGER NOT NULL DEFAULT 0,
+    boards_fresh_paw     INTEGER NOT NULL DEFAULT 0,
+
+    lessons_exploring    INTEGER NOT NULL DEFAULT 0,
+    lessons_learning     INTEGER NOT NULL DEFAULT 0,
+    lessons_retaining    INTEGER NOT NULL DEFAULT 0,
+    lessons_mastering    INTEGER NOT NULL DEFAULT 0,
+
+    updated_at           TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,2 @@
+DROP VIEW IF EXISTS board_status_by_name;
+DROP VIEW IF EXISTS grants_by_name;
@@ -0,0 +1,37 @@
+-- 0002 browse views: human-readable views for the DB browser, where user
+-- ids aren't memorable. These used to be dropped and recreated on every
+-- boot; a column change now means a new migration.
+
+DROP VIEW IF EXISTS board_status_by_name;
+CREATE VIEW board_status_by_name AS
+SELECT
+    bs.user_id,
+    u.first_name || ' ' || u.last_name AS student_name,
+    u.email                            AS student_email,
+    bs.deal_subfolder,
+    bs.deal_number,
+    bs.status,
+    bs.wilderness,
+    bs.last_error_date,
+    bs.star_count,
+    bs.max_stars,
+    bs.last_star_update,
+    bs.wild_achievement,
+    bs.last_observation_at,
+    bs.updated_at
+FROM board_status bs
+JOIN users u ON u.id = bs.user_id;
+
+DROP VIEW IF EXISTS grants_by_name;
+CREATE VIEW grants_by_name AS
+SELECT
+    g.id,
+    u.first_name || ' ' || u.last_name AS grantor_name,
+    u.email AS grantor_email,
+    v.name AS grantee_name,
+    v.role AS grantee_role,
+    g.granted_at,
+    g.revoked
+FROM sharing_grants g
+JOIN users u ON u.id = g.grantor_id
+JOIN viewers v ON v.id = g.grantee_id;
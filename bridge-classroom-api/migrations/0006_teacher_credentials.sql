@@ -0,0 +1,24 @@
+-- 0006 teacher credentials: per-teacher Argon2id password hashes with a
+-- failure counter and lockout window (replaces the shared
+-- TEACHER_PASSWORD; see credentials.rs), and emailed password-reset links
+-- in the same shape as recovery_tokens.
+
+CREATE TABLE IF NOT EXISTS teacher_credentials (
+    user_id         TEXT PRIMARY KEY REFERENCES users(id),
+    password_hash   TEXT NOT NULL,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    locked_until    TEXT,
+    updated_at      TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    id         TEXT PRIMARY KEY,
+    user_id    TEXT NOT NULL REFERENCES users(id),
+    token_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used       INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_user
+    ON password_reset_tokens(user_id);
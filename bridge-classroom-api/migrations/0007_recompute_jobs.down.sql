@@ -0,0 +1 @@
+DROP TABLE IF EXISTS recompute_jobs;
@@ -0,0 +1,28 @@
+-- 0007 recompute jobs: durable queue for the board / assignment / summary
+-- rebuilds that submit_observations used to run inline (see jobs.rs).
+-- A job is deleted when it succeeds; one that exhausts its attempts stays
+-- behind as 'failed' for the admin queue view.
+
+CREATE TABLE IF NOT EXISTS recompute_jobs (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind         TEXT NOT NULL,            -- board | assignment | summary
+    user_id      TEXT NOT NULL,
+    target       TEXT NOT NULL DEFAULT '', -- "subfolder/deal", assignment id, or ''
+    dedupe_key   TEXT NOT NULL,            -- kind:user_id:target
+    priority     INTEGER NOT NULL,         -- lower runs first
+    status       TEXT NOT NULL DEFAULT 'pending', -- pending | running | failed
+    attempts     INTEGER NOT NULL DEFAULT 0,
+    run_after    TEXT NOT NULL,
+    last_error   TEXT,
+    created_at   TEXT NOT NULL,
+    updated_at   TEXT NOT NULL
+);
+
+-- At most one pending job per key: re-enqueueing while one waits is a no-op.
+-- A running job doesn't block a new pending one, so data that lands
+-- mid-recompute still gets picked up.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_recompute_jobs_pending_key
+    ON recompute_jobs(dedupe_key) WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS idx_recompute_jobs_claim
+    ON recompute_jobs(status, priority, run_after);
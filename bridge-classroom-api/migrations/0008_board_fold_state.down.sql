@@ -0,0 +1,4 @@
+ALTER TABLE recompute_jobs DROP COLUMN full_replay;
+ALTER TABLE board_status DROP COLUMN latest_observed_at;
+ALTER TABLE board_status DROP COLUMN last_observation_id;
+ALTER TABLE board_status DROP COLUMN folded_count;
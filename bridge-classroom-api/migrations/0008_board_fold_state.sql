@@ -0,0 +1,11 @@
+-- 0008 board fold state: where the last board_status walk stopped, so
+-- fold_board_observations can apply appended observations without
+-- replaying the board's whole history. NULL means "never recorded" and
+-- forces a full replay on the next fold.
+ALTER TABLE board_status ADD COLUMN folded_count INTEGER;
+ALTER TABLE board_status ADD COLUMN last_observation_id TEXT;
+ALTER TABLE board_status ADD COLUMN latest_observed_at TEXT;
+
+-- Board jobs for an observation that was edited in place (upsert of an
+-- existing id) can't be folded; they ask for a full replay instead.
+ALTER TABLE recompute_jobs ADD COLUMN full_replay INTEGER NOT NULL DEFAULT 0;
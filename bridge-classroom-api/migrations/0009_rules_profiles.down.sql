@@ -0,0 +1,3 @@
+ALTER TABLE board_status DROP COLUMN rules_profile_id;
+ALTER TABLE classrooms DROP COLUMN rules_profile_id;
+DROP TABLE IF EXISTS rules_profiles;
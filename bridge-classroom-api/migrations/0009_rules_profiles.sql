@@ -0,0 +1,28 @@
+-- 0009 rules profiles: the mastery thresholds as versioned, immutable
+-- parameter sets (see rules.rs). 'default' reproduces the constants the
+-- walkers used before this migration; its params must stay in step with
+-- RulesParams::default().
+CREATE TABLE IF NOT EXISTS rules_profiles (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    params TEXT NOT NULL,
+    created_by TEXT REFERENCES users(id),
+    created_at TEXT NOT NULL,
+    UNIQUE (name, version)
+);
+
+INSERT OR IGNORE INTO rules_profiles (id, name, version, params, created_by, created_at)
+VALUES (
+    'default', 'default', 1,
+    '{"cooldown_secs":3600,"achievement_spacing_days":6,"wild_share_below":0.25,"learning_silver_share":0.5,"retaining_gold_share":0.8,"mastering_deep_share":0.8,"mastering_fresh_share":0.25}',
+    NULL, '2026-10-17T00:00:00+00:00'
+);
+
+-- NULL: the classroom uses the default profile. Not a declared foreign
+-- key so the down migration can drop it; rules.rs checks the id.
+ALTER TABLE classrooms ADD COLUMN rules_profile_id TEXT;
+
+-- The profile the board was last walked under. NULL rows predate
+-- profiles and were walked under the defaults.
+ALTER TABLE board_status ADD COLUMN rules_profile_id TEXT;
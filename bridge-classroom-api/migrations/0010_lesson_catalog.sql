@@ -0,0 +1,20 @@
+-- 0010 lesson catalog: the lessons each collection's toc.json lists and
+-- the boards each lesson's PBN declares (see lesson_catalog.rs). Keyed by
+-- deal_subfolder, the toc lesson id observations carry.
+CREATE TABLE IF NOT EXISTS lessons (
+    deal_subfolder TEXT PRIMARY KEY,
+    collection TEXT NOT NULL,
+    name TEXT NOT NULL,
+    category TEXT,
+    description TEXT,
+    sort_order INTEGER,
+    imported_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_lessons_collection ON lessons(collection);
+
+CREATE TABLE IF NOT EXISTS lesson_boards (
+    deal_subfolder TEXT NOT NULL REFERENCES lessons(deal_subfolder) ON DELETE CASCADE,
+    deal_number INTEGER NOT NULL,
+    PRIMARY KEY (deal_subfolder, deal_number)
+);
@@ -0,0 +1,28 @@
+-- 0011 board_status_events: an append-only log of the transitions the
+-- board walk produces (status changes, stars, paws, star-track resets),
+-- one row per observation that caused them, for progress timelines.
+--
+-- A full replay that no longer produces an event (an out-of-order insert,
+-- a rules profile change) stamps superseded_at instead of deleting it;
+-- the live timeline is the rows where it is NULL.
+CREATE TABLE IF NOT EXISTS board_status_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    deal_subfolder TEXT NOT NULL,
+    deal_number INTEGER NOT NULL,
+    observation_id TEXT NOT NULL,
+    occurred_at TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    from_value TEXT,
+    to_value TEXT,
+    rules_profile_id TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    superseded_at TEXT
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_board_status_events_live
+    ON board_status_events(user_id, deal_subfolder, deal_number, observation_id, kind)
+    WHERE superseded_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_board_status_events_user_time
+    ON board_status_events(user_id, occurred_at);
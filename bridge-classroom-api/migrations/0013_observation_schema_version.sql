@@ -0,0 +1,4 @@
+-- 0013 observation_schema_version: the payload schema version the client
+-- declared in the cleartext metadata (see observation_schema.rs). NULL for
+-- observations submitted before clients sent one, i.e. version 1 payloads.
+ALTER TABLE observations ADD COLUMN schema_version INTEGER;
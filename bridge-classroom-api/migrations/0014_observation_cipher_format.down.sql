@@ -0,0 +1 @@
+ALTER TABLE observations DROP COLUMN cipher_format;
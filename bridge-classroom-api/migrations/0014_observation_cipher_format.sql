@@ -0,0 +1,4 @@
+-- 0014 observation_cipher_format: how an observation's payload was sealed
+-- (see obs_crypto.rs). 1 = the payload alone, as every row so far was;
+-- 2 = with the row's cleartext columns authenticated as associated data.
+ALTER TABLE observations ADD COLUMN cipher_format INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN key_rotated_at;
+DROP TABLE IF EXISTS key_rotations;
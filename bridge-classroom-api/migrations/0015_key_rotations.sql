@@ -0,0 +1,30 @@
+-- 0015 key_rotations: user-initiated rotation of a student's AES key (see
+-- routes/key_rotation.rs). The server re-encrypts the user's observations
+-- in batches, recording a cursor after each so a restart resumes where it
+-- stopped, then swaps recovery_encrypted_key and revokes the user's
+-- sharing grants in one final transaction.
+--
+-- Both keys are held under RECOVERY_SECRET, like recovery_encrypted_key,
+-- and are cleared once the rotation completes.
+CREATE TABLE IF NOT EXISTS key_rotations (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id          TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status           TEXT NOT NULL DEFAULT 'running', -- running | failed | completed
+    old_key          TEXT,            -- recovery-encrypted key being replaced
+    new_key          TEXT,            -- recovery-encrypted replacement
+    total            INTEGER NOT NULL DEFAULT 0,
+    reencrypted      INTEGER NOT NULL DEFAULT 0,
+    cursor           TEXT,            -- last observation id re-encrypted
+    revoked_grantees TEXT,            -- JSON array, set on completion
+    error            TEXT,
+    created_at       TEXT NOT NULL,
+    updated_at       TEXT NOT NULL,
+    completed_at     TEXT
+);
+
+-- One unfinished rotation per user: a failed one must be resumed, not
+-- replaced, since some observations are already under its new key.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_key_rotations_unfinished
+    ON key_rotations(user_id) WHERE status IN ('running', 'failed');
+
+ALTER TABLE users ADD COLUMN key_rotated_at TEXT;
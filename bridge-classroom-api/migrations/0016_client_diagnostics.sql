@@ -0,0 +1,26 @@
+-- 0016 client_diagnostics: client-side errors reported through
+-- /api/diagnostics, kept for the admin diagnostics view instead of only
+-- going to the server log (see routes/diagnostics.rs).
+--
+-- fingerprint groups reports of the same problem: a hash of event, context
+-- and the error with ids and numbers normalised away. Frequent fingerprints
+-- are sampled; weight is how many reports a stored row stands for, so
+-- SUM(weight) estimates the true count. Rows older than the retention
+-- window are pruned.
+CREATE TABLE IF NOT EXISTS client_diagnostics (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    fingerprint      TEXT NOT NULL,
+    event            TEXT NOT NULL,
+    context          TEXT NOT NULL,
+    error            TEXT,
+    browser          TEXT NOT NULL,   -- Chrome | Safari | Firefox | Edge | ...
+    user_agent       TEXT,
+    client_timestamp TEXT,
+    weight           INTEGER NOT NULL DEFAULT 1,
+    received_at      TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_client_diagnostics_fingerprint
+    ON client_diagnostics(fingerprint, received_at);
+CREATE INDEX IF NOT EXISTS idx_client_diagnostics_received
+    ON client_diagnostics(received_at);
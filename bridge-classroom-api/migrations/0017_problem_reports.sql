@@ -0,0 +1,38 @@
+-- 0017 problem_reports: "Report a Problem" submissions, stored before they
+-- are delivered so a report is never lost when the sink (GitHub, email) is
+-- unconfigured or unreachable (see routes/reports.rs, report_sink.rs).
+--
+-- request holds the full ReportRequest as JSON; scenario, lesson_id,
+-- display_number and note are copied out for listing. dedupe_key hashes
+-- deal_pbn + scenario: a report of a deal that already has an open report
+-- is stored with duplicate_of pointing at it and is not delivered again.
+--
+-- status: new (not yet delivered) | filed (delivered to the sink) |
+-- resolved (closed by a curator). A new report is retried with backoff from
+-- next_attempt_at until delivered or out of attempts.
+CREATE TABLE IF NOT EXISTS problem_reports (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    dedupe_key      TEXT,
+    duplicate_of    INTEGER REFERENCES problem_reports(id),
+    scenario        TEXT,
+    lesson_id       TEXT,
+    display_number  INTEGER,
+    note            TEXT NOT NULL,
+    request         TEXT NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'new',
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT,
+    last_error      TEXT,
+    sink            TEXT,
+    issue_url       TEXT,
+    issue_number    INTEGER,
+    created_at      TEXT NOT NULL,
+    updated_at      TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_problem_reports_delivery
+    ON problem_reports(status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_problem_reports_dedupe
+    ON problem_reports(dedupe_key, status);
+CREATE INDEX IF NOT EXISTS idx_problem_reports_duplicate_of
+    ON problem_reports(duplicate_of);
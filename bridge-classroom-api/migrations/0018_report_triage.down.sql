@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS boards_under_review;
+DROP INDEX IF EXISTS idx_problem_reports_board;
+ALTER TABLE problem_reports DROP COLUMN reporter_notified_at;
+ALTER TABLE problem_reports DROP COLUMN resolved_at;
+ALTER TABLE problem_reports DROP COLUMN resolved_by;
+ALTER TABLE problem_reports DROP COLUMN resolution_note;
+ALTER TABLE problem_reports DROP COLUMN reporter_user_id;
+ALTER TABLE problem_reports DROP COLUMN board_tag;
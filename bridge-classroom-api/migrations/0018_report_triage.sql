@@ -0,0 +1,32 @@
+-- 0018 report_triage: curator triage of problem reports (see
+-- routes/report_triage.rs).
+--
+-- problem_reports gains the board it's about (board_tag, with lesson_id
+-- the deal_subfolder / deal_number pair observations use), who filed it
+-- (reporter_user_id, when the report came with a session), and the
+-- curator's resolution. reporter_notified_at is set once the reporter has
+-- been emailed about the resolution.
+ALTER TABLE problem_reports ADD COLUMN board_tag TEXT;
+ALTER TABLE problem_reports ADD COLUMN reporter_user_id TEXT;
+ALTER TABLE problem_reports ADD COLUMN resolution_note TEXT;
+ALTER TABLE problem_reports ADD COLUMN resolved_by TEXT;
+ALTER TABLE problem_reports ADD COLUMN resolved_at TEXT;
+ALTER TABLE problem_reports ADD COLUMN reporter_notified_at TEXT;
+
+UPDATE problem_reports SET board_tag = json_extract(request, '$.board_tag');
+
+CREATE INDEX IF NOT EXISTS idx_problem_reports_board
+    ON problem_reports(lesson_id, board_tag);
+
+-- Boards a curator has flagged as broken. Their board_status rows stay,
+-- but lesson mastery leaves the board out (numerator and denominator)
+-- until the flag is cleared.
+CREATE TABLE IF NOT EXISTS boards_under_review (
+    deal_subfolder TEXT NOT NULL,
+    deal_number    INTEGER NOT NULL,
+    reason         TEXT,
+    report_id      INTEGER REFERENCES problem_reports(id),
+    marked_by      TEXT NOT NULL,
+    marked_at      TEXT NOT NULL,
+    PRIMARY KEY (deal_subfolder, deal_number)
+);
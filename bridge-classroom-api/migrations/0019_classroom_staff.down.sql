@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_classroom_staff_user;
+DROP TABLE IF EXISTS classroom_staff;
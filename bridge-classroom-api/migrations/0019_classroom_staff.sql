@@ -0,0 +1,18 @@
+-- 0019 classroom_staff: who teaches a classroom (see routes/classroom_staff.rs).
+--
+-- classrooms.teacher_id stays as the owner, and every classroom has a
+-- matching 'owner' row here. Co-teachers and assistants are extra rows;
+-- what each role may do is decided in policy.rs (StaffRole::can).
+CREATE TABLE IF NOT EXISTS classroom_staff (
+    classroom_id TEXT NOT NULL REFERENCES classrooms(id) ON DELETE CASCADE,
+    user_id      TEXT NOT NULL REFERENCES users(id),
+    role         TEXT NOT NULL CHECK (role IN ('owner', 'co_teacher', 'assistant')),
+    added_by     TEXT,
+    added_at     TEXT NOT NULL,
+    PRIMARY KEY (classroom_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_classroom_staff_user ON classroom_staff(user_id);
+
+INSERT OR IGNORE INTO classroom_staff (classroom_id, user_id, role, added_by, added_at)
+SELECT id, teacher_id, 'owner', NULL, created_at FROM classrooms;
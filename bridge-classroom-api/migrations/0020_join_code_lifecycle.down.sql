@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS classroom_join_requests;
+ALTER TABLE classrooms DROP COLUMN requires_approval;
+ALTER TABLE classrooms DROP COLUMN max_members;
+ALTER TABLE classrooms DROP COLUMN join_code_expires_at;
@@ -0,0 +1,20 @@
+-- 0020 join_code_lifecycle: join codes that expire, cap the class size and
+-- can require the teacher's approval (see routes/classrooms.rs).
+--
+-- NULL join_code_expires_at never expires and NULL max_members is
+-- unlimited, so existing classrooms behave as before.
+ALTER TABLE classrooms ADD COLUMN join_code_expires_at TEXT;
+ALTER TABLE classrooms ADD COLUMN max_members INTEGER;
+ALTER TABLE classrooms ADD COLUMN requires_approval INTEGER NOT NULL DEFAULT 0;
+
+-- A student waiting for approval. They are not a member (nothing that
+-- reads classroom_members sees them) until a teacher approves; the grant
+-- payloads they sent are applied then.
+CREATE TABLE IF NOT EXISTS classroom_join_requests (
+    classroom_id            TEXT NOT NULL REFERENCES classrooms(id) ON DELETE CASCADE,
+    student_id              TEXT NOT NULL REFERENCES users(id),
+    encrypted_grant_payload TEXT NOT NULL,
+    staff_grant_payloads    TEXT NOT NULL DEFAULT '{}',  -- JSON: viewer id -> payload
+    requested_at            TEXT NOT NULL,
+    PRIMARY KEY (classroom_id, student_id)
+);
@@ -0,0 +1,24 @@
+-- 0021 classroom_invitations: students a teacher invited by uploading a
+-- roster (see routes/roster.rs).
+--
+-- One row per classroom and email. Registering with an invited email makes
+-- the new account a member (routes/users.rs); joining with the code marks
+-- the invitation accepted too.
+CREATE TABLE IF NOT EXISTS classroom_invitations (
+    id               TEXT PRIMARY KEY,
+    classroom_id     TEXT NOT NULL REFERENCES classrooms(id) ON DELETE CASCADE,
+    email            TEXT NOT NULL COLLATE NOCASE,
+    first_name       TEXT NOT NULL,
+    last_name        TEXT NOT NULL,
+    status           TEXT NOT NULL DEFAULT 'pending'
+                     CHECK (status IN ('pending', 'sent', 'failed', 'accepted', 'revoked')),
+    invited_by       TEXT NOT NULL,
+    created_at       TEXT NOT NULL,
+    sent_at          TEXT,
+    accepted_at      TEXT,
+    accepted_user_id TEXT,
+    last_error       TEXT,
+    UNIQUE (classroom_id, email)
+);
+
+CREATE INDEX IF NOT EXISTS idx_classroom_invitations_email ON classroom_invitations(email);
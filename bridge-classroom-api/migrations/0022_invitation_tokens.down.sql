@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_classroom_invitations_token;
+ALTER TABLE classroom_invitations DROP COLUMN link_base;
+ALTER TABLE classroom_invitations DROP COLUMN token_hash;
@@ -0,0 +1,16 @@
+-- 0022 invitation_tokens: each roster invitation carries its own link
+-- (see routes/roster.rs).
+--
+-- token_hash is the SHA-256 of the token in the emailed link. Registering
+-- with that token is what makes the new account a member; an email
+-- address alone no longer does. The token is minted when the email is
+-- sent, so re-inviting someone retires their old link. link_base is the
+-- frontend URL the teacher uploaded from, kept for the queued send.
+--
+-- Invitations emailed before this have no token; those students join
+-- with the classroom code like anyone else.
+ALTER TABLE classroom_invitations ADD COLUMN token_hash TEXT;
+ALTER TABLE classroom_invitations ADD COLUMN link_base TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_classroom_invitations_token
+    ON classroom_invitations(token_hash);
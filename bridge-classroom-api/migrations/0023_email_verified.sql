@@ -0,0 +1,13 @@
+-- 0023 email_verified: when the user last proved they read mail at their
+-- address (a recovery claim or a teacher password reset). Registration
+-- takes any email unchecked, so an admin row in `viewers` only promotes a
+-- user whose address is verified (see policy.rs resolve_role).
+--
+-- Accounts that already claimed a recovery or reset link count as
+-- verified; everyone else is verified the next time they do.
+ALTER TABLE users ADD COLUMN email_verified_at TEXT;
+
+UPDATE users
+SET email_verified_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now')
+WHERE id IN (SELECT user_id FROM recovery_tokens WHERE used = 1)
+   OR id IN (SELECT user_id FROM password_reset_tokens WHERE used = 1);
@@ -0,0 +1 @@
+ALTER TABLE account_handoff DROP COLUMN keeper_user_id;
@@ -0,0 +1,8 @@
+-- 0024 handoff_keeper: which account a staged handoff hands over. The
+-- payload carries the keeper's secret key, so a key rotation of the keeper
+-- drops its pending handoffs (see key_rotation.rs rotate) and needs to find
+-- them without the merged-away key that wraps them.
+--
+-- Rows staged before this migration have no keeper recorded and simply run
+-- out their 30-day window.
+ALTER TABLE account_handoff ADD COLUMN keeper_user_id TEXT;
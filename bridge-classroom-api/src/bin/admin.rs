@@ -0,0 +1,633 @@
+//! `bridge-classroom-admin` — operational commands against the API's
+//! SQLite database, without going through HTTP.
+//!
+//! Reads the same `.env` / environment as the server (DATABASE_URL,
+//! RECOVERY_SECRET, ...) and calls the same library functions the admin
+//! endpoints use, so a merge or name correction from the shell behaves
+//! exactly like one from the admin page. Safe to run while the server is
+//! up: SQLite's WAL mode and busy timeout handle the concurrent writer.
+//!
+//! Commands that change data migrate the file first, as the server does.
+//! `check-integrity` and `export` open it read-only instead, so they can
+//! inspect a database without migrating or snapshotting it.
+//!
+//! ```text
+//! bridge-classroom-admin merge --away <id> (--keeper <id> | --dry-run)
+//! bridge-classroom-admin backfill-v2 [--force]
+//! bridge-classroom-admin decrypt-observations
+//! bridge-classroom-admin recovery-keys
+//! bridge-classroom-admin rewrap-recovery
+//! bridge-classroom-admin correct-name <user_id> <first> <last>
+//! bridge-classroom-admin announce "<message>" [--type info] [--expires-at <rfc3339>]
+//! bridge-classroom-admin announce --clear
+//! bridge-classroom-admin recompute (--user <id> | --all)
+//! bridge-classroom-admin export [--user <id>] [--out <file>]
+//! bridge-classroom-admin import-lessons --collection <id> --toc <path|url> [--base <dir|url>]
+//! bridge-classroom-admin vacuum
+//! bridge-classroom-admin check-integrity
+//! ```
+
+use std::io::Write;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Context};
+use axum::http::StatusCode;
+use clap::{Parser, Subcommand};
+use futures_util::TryStreamExt;
+use sqlx::{Pool, Sqlite};
+
+use bridge_classroom_api::config::Config;
+use bridge_classroom_api::routes::{admin, announcements, merge, recovery_rewrap, users};
+use bridge_classroom_api::jobs::{self, Job};
+use bridge_classroom_api::{db, lesson_catalog, migrations, recompute, AppState};
+
+#[derive(Parser)]
+#[command(
+    name = "bridge-classroom-admin",
+    about = "Bridge Classroom operations CLI"
+)]
+struct Cli {
+    /// Overrides DATABASE_URL from the environment / .env
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Merge a duplicate account into a keeper (POST /api/admin/merge-accounts)
+    Merge {
+        /// The account to dissolve
+        #[arg(long)]
+        away: String,
+        /// The surviving account
+        #[arg(long, required_unless_present = "dry_run")]
+        keeper: Option<String>,
+        /// Only verify the away account's observations decrypt and round-trip
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run the Correctness & Mastery v2 backfill
+    BackfillV2 {
+        /// Rerun even if schema_meta records it as done
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rebuild observations_decrypted (POST /api/admin/decrypt-observations)
+    DecryptObservations,
+    /// Count recovery blobs per RECOVERY_SECRET key id (GET /api/admin/recovery-keys)
+    RecoveryKeys,
+    /// Re-wrap every recovery blob under the current RECOVERY_SECRET
+    /// (POST /api/admin/recovery-keys/rewrap)
+    RewrapRecovery,
+    /// Correct a user's name (PATCH /api/admin/users/:id)
+    CorrectName {
+        user_id: String,
+        first_name: String,
+        last_name: String,
+    },
+    /// Set or clear the site-wide announcement
+    Announce {
+        #[arg(required_unless_present = "clear")]
+        message: Option<String>,
+        /// info, warning, ...
+        #[arg(long = "type", default_value = "info")]
+        announcement_type: String,
+        #[arg(long)]
+        expires_at: Option<String>,
+        #[arg(long, conflicts_with = "message")]
+        clear: bool,
+    },
+    /// Rebuild board_status, assignment rollups and student_summary
+    Recompute {
+        #[arg(long, conflicts_with = "all", required_unless_present = "all")]
+        user: Option<String>,
+        #[arg(long)]
+        all: bool,
+    },
+    /// Write observations (metadata and ciphertext) as NDJSON
+    Export {
+        /// Only this user's observations
+        #[arg(long)]
+        user: Option<String>,
+        /// Output file (default: stdout)
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Load a collection's toc.json and lesson PBNs into the lesson catalog
+    ImportLessons {
+        /// Collection id, as in the frontend's collection list
+        #[arg(long)]
+        collection: String,
+        /// Path or URL of the collection's toc.json
+        #[arg(long)]
+        toc: String,
+        /// Directory or URL holding the lesson PBNs (default: the toc's)
+        #[arg(long)]
+        base: Option<String>,
+    },
+    /// Checkpoint the WAL, VACUUM and optimize
+    Vacuum,
+    /// Run SQLite integrity/foreign-key checks and look for orphaned rows
+    CheckIntegrity,
+}
+
+impl Command {
+    /// Commands that only read, and so open the database read-only.
+    fn is_read_only(&self) -> bool {
+        matches!(self, Command::Export { .. } | Command::CheckIntegrity)
+    }
+}
+
+/// Open the database for `command`: migrated for anything that writes,
+/// read-only and untouched for the rest.
+async fn open_database(command: &Command, database_url: &str) -> anyhow::Result<Pool<Sqlite>> {
+    if command.is_read_only() {
+        Ok(db::connect_read_only(database_url).await?)
+    } else {
+        Ok(db::init_db(database_url).await?)
+    }
+}
+
+/// Handler-style errors carry a status code the CLI has no use for.
+fn api_err((status, msg): (StatusCode, String)) -> anyhow::Error {
+    anyhow!("{} ({})", msg, status)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "bridge_classroom_api=info".into()),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(Cli::parse()).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<ExitCode> {
+    let mut config = Config::from_env()?;
+    if let Some(url) = cli.database_url {
+        config.database_url = url;
+    }
+    let db = open_database(&cli.command, &config.database_url).await?;
+    let state = AppState {
+        db,
+        config: Arc::new(config),
+        started_at: Instant::now(),
+    };
+
+    match cli.command {
+        Command::Merge {
+            away,
+            keeper,
+            dry_run,
+        } => {
+            if dry_run {
+                let report = merge::verify_user_decryption(&state, &away)
+                    .await
+                    .map_err(api_err)?;
+                print_json(&report)?;
+                return Ok(exit_for(report.success));
+            }
+            let keeper = keeper.ok_or_else(|| anyhow!("--keeper is required"))?;
+            let report = merge::merge_user_into(&state, &away, &keeper)
+                .await
+                .map_err(api_err)?;
+            print_json(&report)?;
+            return Ok(exit_for(report.success));
+        }
+        Command::BackfillV2 { force } => {
+            if force {
+                sqlx::query("DELETE FROM schema_meta WHERE key = 'correctness_v2_backfill'")
+                    .execute(&state.db)
+                    .await?;
+            }
+            db::run_v2_backfill(&state.db).await?;
+            println!("v2 backfill complete");
+        }
+        Command::DecryptObservations => {
+            let report = admin::decrypt_all_observations(&state)
+                .await
+                .map_err(api_err)?;
+            print_json(&report)?;
+        }
+        Command::RecoveryKeys => {
+            let keyring = state
+                .config
+                .recovery_secret
+                .as_ref()
+                .ok_or_else(|| anyhow!("RECOVERY_SECRET not configured"))?;
+            let status = recovery_rewrap::recovery_key_status(&state.db, keyring).await?;
+            print_json(&status)?;
+        }
+        Command::RewrapRecovery => {
+            let report = recovery_rewrap::rewrap_recovery_blobs(&state)
+                .await
+                .map_err(api_err)?;
+            print_json(&report)?;
+            return Ok(exit_for(report.success));
+        }
+        Command::CorrectName {
+            user_id,
+            first_name,
+            last_name,
+        } => {
+            let info = users::correct_user_name(&state.db, &user_id, &first_name, &last_name)
+                .await
+                .map_err(api_err)?;
+            print_json(&info)?;
+        }
+        Command::Announce { clear: true, .. } => {
+            announcements::deactivate_announcements(&state.db)
+                .await
+                .map_err(api_err)?;
+            println!("Announcement cleared");
+        }
+        Command::Announce {
+            message,
+            announcement_type,
+            expires_at,
+            ..
+        } => {
+            let body = announcements::SetAnnouncementRequest {
+                message: message.unwrap_or_default(),
+                announcement_type,
+                expires_at,
+            };
+            let row = announcements::publish_announcement(&state.db, "admin-cli", &body)
+                .await
+                .map_err(api_err)?;
+            print_json(&row)?;
+        }
+        Command::Recompute { user, all } => {
+            let user_ids = match user {
+                Some(id) => vec![id],
+                None if all => recompute::all_user_ids(&state.db)
+                    .await
+                    .map_err(|e| anyhow!(e))?,
+                None => bail!("pass --user <id> or --all"),
+            };
+            let mut failures = 0;
+            for user_id in &user_ids {
+                let report = recompute::recompute_user(&state.db, user_id)
+                    .await
+                    .map_err(|e| anyhow!(e))?;
+                println!(
+                    "{}: {} boards, {} assignments, {} failures",
+                    user_id, report.boards, report.assignments, report.failures
+                );
+                failures += report.failures;
+            }
+            return Ok(exit_for(failures == 0));
+        }
+        Command::Export { user, out } => {
+            let count = match out {
+                Some(path) => {
+                    let file = std::fs::File::create(&path)
+                        .with_context(|| format!("creating {}", path.display()))?;
+                    export_observations(&state.db, user.as_deref(), std::io::BufWriter::new(file))
+                        .await?
+                }
+                None => {
+                    export_observations(&state.db, user.as_deref(), std::io::stdout().lock())
+                        .await?
+                }
+            };
+            eprintln!("Exported {} observations", count);
+        }
+        Command::ImportLessons {
+            collection,
+            toc,
+            base,
+        } => {
+            let (toc, boards) = lesson_catalog::load_collection(&toc, base.as_deref())
+                .await
+                .map_err(|e| anyhow!(e))?;
+            // Lesson tiers in student_summary depend on the catalog: refresh
+            // everyone with progress in the collection before or after.
+            let mut users = lesson_catalog::users_in_collection(&state.db, &collection)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            let report = lesson_catalog::import_collection(&state.db, &collection, &toc, &boards)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            print_json(&report)?;
+
+            users.extend(
+                lesson_catalog::users_in_collection(&state.db, &collection)
+                    .await
+                    .map_err(|e| anyhow!(e))?,
+            );
+            users.sort();
+            users.dedup();
+            let queued = jobs::enqueue_all(
+                &state.db,
+                users.into_iter().map(|user_id| Job::Summary { user_id }),
+            )
+            .await?;
+            eprintln!("Queued {} summary recomputes", queued);
+            return Ok(exit_for(report.missing_pbn.is_empty()));
+        }
+        Command::Vacuum => vacuum(&state.db).await?,
+        Command::CheckIntegrity => return check_integrity(&state.db).await,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn exit_for(ok: bool) -> ExitCode {
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// One JSON object per line, built by SQLite so every column keeps its
+/// stored type. Ordered by (user, timestamp) for stable diffs. Rows are
+/// written as they are read, so memory stays flat however many there are.
+async fn export_observations(
+    db: &Pool<Sqlite>,
+    user_id: Option<&str>,
+    mut out: impl Write,
+) -> anyhow::Result<usize> {
+    let mut lines = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT json_object(
+            'id', id, 'user_id', user_id, 'timestamp', timestamp,
+            'skill_path', skill_path, 'correct', correct, 'classroom', classroom,
+            'deal_subfolder', deal_subfolder, 'deal_number', deal_number,
+            'board_result', board_result, 'status', status, 'wilderness', wilderness,
+            'exercise_id', exercise_id, 'assignment_id', assignment_id,
+            'jungle', jungle, 'time_taken_ms', time_taken_ms,
+            'schema_version', schema_version, 'cipher_format', cipher_format,
+            'encrypted_data', encrypted_data, 'iv', iv, 'created_at', created_at)
+        FROM observations
+        WHERE ?1 IS NULL OR user_id = ?1
+        ORDER BY user_id, timestamp, id
+        "#,
+    )
+    .bind(user_id)
+    .fetch(db);
+
+    let mut count = 0;
+    while let Some(line) = lines.try_next().await? {
+        writeln!(out, "{}", line)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+async fn database_bytes(db: &Pool<Sqlite>) -> anyhow::Result<i64> {
+    let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+        .fetch_one(db)
+        .await?;
+    let page_size: i64 = sqlx::query_scalar("PRAGMA page_size").fetch_one(db).await?;
+    Ok(page_count * page_size)
+}
+
+async fn vacuum(db: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let before = database_bytes(db).await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(db)
+        .await?;
+    sqlx::query("VACUUM").execute(db).await?;
+    sqlx::query("PRAGMA optimize").execute(db).await?;
+    let after = database_bytes(db).await?;
+    println!(
+        "Vacuumed: {:.1} MB -> {:.1} MB",
+        before as f64 / 1_048_576.0,
+        after as f64 / 1_048_576.0
+    );
+    Ok(())
+}
+
+/// Rows whose owning user no longer exists. Each query returns a count.
+const ORPHAN_CHECKS: &[(&str, &str)] = &[
+    (
+        "observations without a user",
+        "SELECT COUNT(*) FROM observations o LEFT JOIN users u ON u.id = o.user_id WHERE u.id IS NULL",
+    ),
+    (
+        "board_status without a user",
+        "SELECT COUNT(*) FROM board_status b LEFT JOIN users u ON u.id = b.user_id WHERE u.id IS NULL",
+    ),
+    (
+        "student_summary without a user",
+        "SELECT COUNT(*) FROM student_summary s LEFT JOIN users u ON u.id = s.user_id WHERE u.id IS NULL",
+    ),
+    (
+        "classroom members without a user",
+        "SELECT COUNT(*) FROM classroom_members m LEFT JOIN users u ON u.id = m.student_id WHERE u.id IS NULL",
+    ),
+];
+
+async fn check_integrity(db: &Pool<Sqlite>) -> anyhow::Result<ExitCode> {
+    let mut problems = 0usize;
+
+    let integrity: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(db)
+        .await?;
+    if integrity != ["ok"] {
+        problems += integrity.len();
+        for line in &integrity {
+            println!("integrity_check: {}", line);
+        }
+    } else {
+        println!("integrity_check: ok");
+    }
+
+    let fk_violations: Vec<(String, Option<i64>, String)> =
+        sqlx::query_as("SELECT \"table\", rowid, parent FROM pragma_foreign_key_check")
+            .fetch_all(db)
+            .await?;
+    println!("foreign_key_check: {} violations", fk_violations.len());
+    for (table, rowid, parent) in fk_violations.iter().take(20) {
+        println!("  {} rowid {:?} -> missing {}", table, rowid, parent);
+    }
+    problems += fk_violations.len();
+
+    let statuses = migrations::status(db).await?;
+    let pending = migrations::pending(&statuses);
+    let bad: Vec<_> = statuses
+        .iter()
+        .filter(|s| {
+            !matches!(
+                s.state,
+                migrations::MigrationState::Applied { .. } | migrations::MigrationState::Pending
+            )
+        })
+        .collect();
+    println!(
+        "migrations: {} applied, {} pending, {} mismatched",
+        statuses.len() - pending.len() - bad.len(),
+        pending.len(),
+        bad.len()
+    );
+    problems += bad.len();
+
+    for (label, sql) in ORPHAN_CHECKS {
+        let n: i64 = sqlx::query_scalar(sql).fetch_one(db).await?;
+        println!("{}: {}", label, n);
+        if n > 0 {
+            problems += 1;
+        }
+    }
+
+    if problems == 0 {
+        println!("OK");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("{} problem(s) found", problems);
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    /// A fresh, fully migrated database file in its own directory.
+    async fn temp_database() -> (std::path::PathBuf, String, Pool<Sqlite>) {
+        let dir = std::env::temp_dir().join(format!("admin-cli-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("test.db").display());
+        let pool = db::init_db(&url).await.unwrap();
+        (dir, url, pool)
+    }
+
+    async fn insert_observation(db: &Pool<Sqlite>, id: &str, user_id: &str, timestamp: &str) {
+        sqlx::query(
+            "INSERT OR IGNORE INTO users (id, first_name, last_name, email, created_at, updated_at) \
+             VALUES (?1, 'Test', 'Student', ?1 || '@example.com', '', '')",
+        )
+        .bind(user_id)
+        .execute(db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+             encrypted_data, iv, created_at) \
+             VALUES (?, ?, ?, 'bidding/stayman', 1, 'cipher', 'iv', '')",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(timestamp)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("bridge-classroom-admin").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn arguments_parse_and_conflict() {
+        Cli::command().debug_assert();
+
+        assert!(parse(&["merge", "--away", "a", "--dry-run"]).is_ok());
+        assert!(parse(&["merge", "--away", "a"]).is_err());
+        assert!(parse(&["recompute"]).is_err());
+        assert!(parse(&["recompute", "--user", "u", "--all"]).is_err());
+        assert!(parse(&["announce"]).is_err());
+        assert!(parse(&["announce", "hello", "--clear"]).is_err());
+        assert!(parse(&["bogus"]).is_err());
+
+        let cli = parse(&["--database-url", "sqlite:x.db", "export", "--user", "u"]).unwrap();
+        assert_eq!(cli.database_url.as_deref(), Some("sqlite:x.db"));
+        assert!(cli.command.is_read_only());
+        assert!(parse(&["check-integrity"]).unwrap().command.is_read_only());
+        assert!(!parse(&["vacuum"]).unwrap().command.is_read_only());
+    }
+
+    #[tokio::test]
+    async fn read_only_commands_neither_migrate_nor_write() {
+        let (dir, url, pool) = temp_database().await;
+        insert_observation(&pool, "o1", "u1", "2026-01-01T00:00:00Z").await;
+        // Pretend the file predates the newest migration.
+        let latest = migrations::MIGRATIONS.last().unwrap().version;
+        sqlx::query("DELETE FROM schema_migrations WHERE version = ?")
+            .bind(latest)
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let command = parse(&["check-integrity"]).unwrap().command;
+        let read_only = open_database(&command, &url).await.unwrap();
+        // A pending migration is reported, not counted as a problem.
+        assert_eq!(check_integrity(&read_only).await.unwrap(), ExitCode::SUCCESS);
+        let statuses = migrations::status(&read_only).await.unwrap();
+        assert_eq!(migrations::pending(&statuses).len(), 1);
+        assert!(sqlx::query("DELETE FROM observations")
+            .execute(&read_only)
+            .await
+            .is_err());
+        read_only.close().await;
+
+        let snapshots = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().contains(".bak"))
+            .count();
+        assert_eq!(snapshots, 0);
+
+        // A missing file isn't created.
+        let missing = format!("sqlite:{}", dir.join("missing.db").display());
+        assert!(open_database(&command, &missing).await.is_err());
+        assert!(!dir.join("missing.db").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn export_writes_one_json_line_per_observation() {
+        let (dir, _url, pool) = temp_database().await;
+        insert_observation(&pool, "b2", "u2", "2026-01-01T00:00:00Z").await;
+        insert_observation(&pool, "a2", "u1", "2026-01-02T00:00:00Z").await;
+        insert_observation(&pool, "a1", "u1", "2026-01-01T00:00:00Z").await;
+
+        let mut out = Vec::new();
+        assert_eq!(export_observations(&pool, None, &mut out).await.unwrap(), 3);
+        let rows: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<&str> = rows.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["a1", "a2", "b2"]);
+        assert_eq!(rows[0]["correct"], 1);
+        assert_eq!(rows[0]["encrypted_data"], "cipher");
+
+        let mut out = Vec::new();
+        assert_eq!(
+            export_observations(&pool, Some("u2"), &mut out).await.unwrap(),
+            1
+        );
+        let mut out = Vec::new();
+        assert_eq!(
+            export_observations(&pool, Some("nobody"), &mut out).await.unwrap(),
+            0
+        );
+        assert!(out.is_empty());
+        pool.close().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,9 @@
 use std::env;
 
+use crate::email::{EmailTransport, Mailer};
+use crate::recovery_keys::RecoveryKeyring;
+use crate::report_sink::ReportSinkKind;
+
 /// Application configuration loaded from environment variables
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -12,9 +16,6 @@ pub struct Config {
     /// Teacher's public key (base64-encoded SPKI)
     pub teacher_public_key: String,
 
-    /// Teacher's password for dashboard access
-    pub teacher_password: String,
-
     /// Comma-separated list of allowed CORS origins
     pub allowed_origins: Vec<String>,
 
@@ -24,23 +25,35 @@ pub struct Config {
     /// Server port
     pub port: u16,
 
-    /// Secret for encrypting recovery keys (optional)
-    pub recovery_secret: Option<String>,
+    /// Whether a proxy in front sets `CF-Connecting-IP` / `X-Forwarded-For`
+    /// (TRUST_PROXY_HEADERS). When false, rate limits key on the socket
+    /// peer and those headers are ignored (see routes/recovery.rs client_ip).
+    pub trust_proxy_headers: bool,
+
+    /// Secret for encrypting recovery keys (optional): RECOVERY_SECRET,
+    /// plus any comma-separated RECOVERY_SECRET_PREVIOUS still accepted for
+    /// decryption while blobs are re-wrapped (see recovery_keys.rs).
+    pub recovery_secret: Option<RecoveryKeyring>,
 
-    /// Resend API key for sending emails (optional)
-    pub resend_api_key: Option<String>,
+    /// Outgoing email (optional; see email/mod.rs): Resend, SMTP or a
+    /// directory of .eml files, built once from the environment
+    pub mailer: Option<Mailer>,
 
     /// From email address for recovery emails
     pub from_email: String,
 
     /// GitHub token used to file "Report a Problem" issues (optional).
-    /// Scoped to Issues:write on the content repo only. When unset, the
-    /// report endpoint degrades gracefully (503) so the UI can say so.
+    /// Scoped to Issues:write on the content repo only. When unset, reports
+    /// are still queued; they wait in `problem_reports` for curators.
     pub github_issues_token: Option<String>,
 
     /// owner/repo that classroom-feedback issues are filed into.
     pub github_issues_repo: String,
 
+    /// Where queued reports are delivered (REPORT_SINK: github, email or
+    /// none; see report_sink.rs)
+    pub report_sink: ReportSinkKind,
+
     /// HMAC secret for minting table-service join tickets (optional).
     /// Shared with bridge-table-service, which verifies tickets offline.
     /// When unset, POST /api/table-tickets degrades gracefully (503).
@@ -52,6 +65,21 @@ pub struct Config {
     /// session-close admin calls. The Mac↔droplet seam is deliberately
     /// thin: this plus the ticket mint are the only integration points.
     pub table_service_url: String,
+
+    /// HMAC secret for signing per-user session tokens (see session.rs).
+    /// When unset a random per-process secret is used, so sessions are
+    /// invalidated by a restart.
+    pub session_secret: String,
+
+    /// Session token lifetime in seconds (SESSION_TTL_HOURS, default 7 days)
+    pub session_ttl_secs: i64,
+
+    /// Background workers draining the recompute job queue (see jobs.rs).
+    /// 0 disables them — jobs then wait for a process that runs workers.
+    pub recompute_workers: usize,
+
+    /// Days client diagnostics are kept (DIAGNOSTICS_RETENTION_DAYS, default 30)
+    pub diagnostics_retention_days: i64,
 }
 
 impl Config {
@@ -69,9 +97,6 @@ impl Config {
         let teacher_public_key = env::var("TEACHER_PUBLIC_KEY")
             .unwrap_or_default(); // Optional - can be empty initially
 
-        let teacher_password = env::var("TEACHER_PASSWORD")
-            .unwrap_or_else(|_| "changeme".to_string()); // Default for development
-
         let allowed_origins = env::var("ALLOWED_ORIGINS")
             .unwrap_or_else(|_| "http://localhost:5173,http://localhost:4173".to_string())
             .split(',')
@@ -86,10 +111,21 @@ impl Config {
             .parse()
             .map_err(|_| ConfigError::InvalidPort)?;
 
-        let recovery_secret = env::var("RECOVERY_SECRET").ok();
-        let resend_api_key = env::var("RESEND_API_KEY").ok();
+        let trust_proxy_headers = env::var("TRUST_PROXY_HEADERS")
+            .map(|s| matches!(s.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+
+        let recovery_secret = env::var("RECOVERY_SECRET").ok().map(|current| {
+            let previous = env::var("RECOVERY_SECRET_PREVIOUS").unwrap_or_default();
+            RecoveryKeyring::new(&current).with_previous(
+                previous.split(',').map(str::trim).filter(|s| !s.is_empty()),
+            )
+        });
         let from_email = env::var("FROM_EMAIL")
             .unwrap_or_else(|_| "Bridge Classroom <noreply@mail.bridge-classroom.org>".to_string());
+        let mailer = EmailTransport::from_env()
+            .and_then(|transport| transport.map(|t| t.mailer(&from_email)).transpose())
+            .map_err(ConfigError::InvalidEmailTransport)?;
 
         // Treat an empty GITHUB_ISSUES_TOKEN the same as unset.
         let github_issues_token = env::var("GITHUB_ISSUES_TOKEN")
@@ -97,6 +133,8 @@ impl Config {
             .filter(|s| !s.trim().is_empty());
         let github_issues_repo = env::var("GITHUB_ISSUES_REPO")
             .unwrap_or_else(|_| "ADavidBailey/Practice-Bidding-Scenarios".to_string());
+        let report_sink = ReportSinkKind::from_env(github_issues_token.as_deref())
+            .map_err(ConfigError::InvalidReportSink)?;
 
         // Treat an empty TABLE_TICKET_SECRET the same as unset.
         let table_ticket_secret = env::var("TABLE_TICKET_SECRET")
@@ -108,21 +146,52 @@ impl Config {
             .filter(|s| !s.trim().is_empty())
             .unwrap_or_else(|| "https://tables.bridge-craftwork.com".to_string());
 
+        let session_secret = env::var("SESSION_SECRET")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| {
+                tracing::warn!("SESSION_SECRET not set; using a random secret (sessions reset on restart)");
+                crate::session::random_secret()
+            });
+
+        let session_ttl_secs = env::var("SESSION_TTL_HOURS")
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .filter(|h| *h > 0)
+            .unwrap_or(7 * 24)
+            * 3600;
+
+        let recompute_workers = env::var("RECOMPUTE_WORKERS")
+            .ok()
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .unwrap_or(2);
+
+        let diagnostics_retention_days = env::var("DIAGNOSTICS_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .filter(|d| *d > 0)
+            .unwrap_or(30);
+
         Ok(Config {
             database_url,
             api_key,
             teacher_public_key,
-            teacher_password,
             allowed_origins,
             host,
             port,
+            trust_proxy_headers,
             recovery_secret,
-            resend_api_key,
+            mailer,
             from_email,
             github_issues_token,
             github_issues_repo,
+            report_sink,
             table_ticket_secret,
             table_service_url,
+            session_secret,
+            session_ttl_secs,
+            recompute_workers,
+            diagnostics_retention_days,
         })
     }
 
@@ -130,6 +199,32 @@ impl Config {
     pub fn server_addr(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    /// Fixed, env-independent configuration for unit tests.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Config {
+            database_url: "sqlite::memory:".to_string(),
+            api_key: "test-api-key".to_string(),
+            teacher_public_key: String::new(),
+            allowed_origins: vec![],
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            trust_proxy_headers: false,
+            recovery_secret: None,
+            mailer: None,
+            from_email: "test@example.com".to_string(),
+            github_issues_token: None,
+            github_issues_repo: "test/test".to_string(),
+            report_sink: ReportSinkKind::None,
+            table_ticket_secret: None,
+            table_service_url: "http://localhost".to_string(),
+            session_secret: "test-session-secret".to_string(),
+            session_ttl_secs: 3600,
+            recompute_workers: 0,
+            diagnostics_retention_days: 30,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -139,4 +234,10 @@ pub enum ConfigError {
 
     #[error("Invalid port number")]
     InvalidPort,
+
+    #[error("Invalid email configuration: {0}")]
+    InvalidEmailTransport(String),
+
+    #[error("Invalid report sink configuration: {0}")]
+    InvalidReportSink(String),
 }
@@ -0,0 +1,121 @@
+//! Per-teacher password credentials.
+//!
+//! Replaces the single shared `TEACHER_PASSWORD`. Each teacher/admin user
+//! may hold one Argon2id password hash in `teacher_credentials`; repeated
+//! failures lock the credential for a while, and a forgotten password is
+//! reset through an emailed one-time link (`password_reset_tokens`, same
+//! shape as `recovery_tokens`).
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::LazyLock;
+
+/// Failed attempts allowed before the credential locks.
+pub const MAX_FAILED_ATTEMPTS: i64 = 5;
+/// How long a locked credential stays locked.
+pub const LOCKOUT_MINUTES: i64 = 15;
+/// Shortest password we accept when setting or resetting.
+pub const MIN_PASSWORD_LEN: usize = 10;
+/// Lifetime of an emailed password-reset link.
+pub const RESET_TOKEN_HOURS: i64 = 1;
+
+/// Argon2id hash in PHC string form (`$argon2id$v=19$...`).
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let mut salt_bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt_bytes)
+        .map_err(|_| "Failed to generate salt".to_string())?;
+    let salt = SaltString::encode_b64(&salt_bytes).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Check `password` against a stored PHC hash. A malformed hash never
+/// verifies.
+pub fn verify_password(password: &str, stored_hash: &str) -> bool {
+    PasswordHash::new(stored_hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// A hash of nobody's password, for [`verify_dummy_password`].
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("no account has this password").expect("hash dummy password"));
+
+/// Spend as long as [`verify_password`] does on a real hash, without
+/// anything to match. Run it when there is no account to check, so a
+/// login's timing doesn't reveal whether one exists.
+pub fn verify_dummy_password(password: &str) {
+    verify_password(password, &DUMMY_HASH);
+}
+
+/// Reject passwords too short to be worth hashing.
+pub fn validate_new_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a credential with this `locked_until` is locked at `now`.
+pub fn is_locked(locked_until: Option<&str>, now: DateTime<Utc>) -> bool {
+    locked_until
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| t.with_timezone(&Utc) > now)
+}
+
+/// After a failure brings the count to `failed_attempts`, the new
+/// `locked_until` (if this failure trips the lock).
+pub fn lock_after_failure(failed_attempts: i64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (failed_attempts >= MAX_FAILED_ATTEMPTS).then(|| now + Duration::minutes(LOCKOUT_MINUTES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_round_trips_and_rejects_wrong_password() {
+        let hash = hash_password("correct horse battery").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse battery", &hash));
+        assert!(!verify_password("correct horse battery!", &hash));
+        assert!(!verify_password("anything", "not-a-phc-string"));
+        assert!(!verify_password(
+            "no account has this password!",
+            &DUMMY_HASH
+        ));
+        assert!(DUMMY_HASH.starts_with("$argon2id$"));
+    }
+
+    #[test]
+    fn locks_on_the_fifth_failure_and_unlocks_after_the_window() {
+        let now = Utc::now();
+        assert_eq!(lock_after_failure(MAX_FAILED_ATTEMPTS - 1, now), None);
+        let until = lock_after_failure(MAX_FAILED_ATTEMPTS, now).unwrap();
+        let until = until.to_rfc3339();
+        assert!(is_locked(Some(&until), now));
+        assert!(!is_locked(
+            Some(&until),
+            now + Duration::minutes(LOCKOUT_MINUTES + 1)
+        ));
+        assert!(!is_locked(None, now));
+    }
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert!(validate_new_password("short").is_err());
+        assert!(validate_new_password("long enough pw").is_ok());
+    }
+}
@@ -0,0 +1,276 @@
+//! The messages the API sends, each rendered as HTML and plain text.
+//!
+//! Every interpolated value is HTML-escaped in the HTML part, so a name or
+//! classroom title can't inject markup. The text part carries the same
+//! content and links for clients that don't render HTML.
+
+use super::Email;
+
+/// Escape `s` for use in HTML text and attribute values.
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The shared page around every message. `body` is already HTML.
+fn layout(heading: &str, body: &str, footer: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+</head>
+<body style="font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; line-height: 1.6; color: #333;">
+    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+        <h2>{heading}</h2>
+{body}
+        <p style="margin-top: 30px; padding-top: 20px; border-top: 1px solid #eee; font-size: 14px; color: #666;">{footer}<br>— Bridge Classroom</p>
+    </div>
+</body>
+</html>"#,
+        heading = escape_html(heading),
+        body = body,
+        footer = escape_html(footer),
+    )
+}
+
+fn button(url: &str, label: &str) -> String {
+    format!(
+        r#"        <a href="{url}" style="display: inline-block; background-color: #2563eb; color: #ffffff !important; padding: 12px 24px; text-decoration: none; border-radius: 6px; margin: 20px 0;">{label}</a>"#,
+        url = escape_html(url),
+        label = escape_html(label),
+    )
+}
+
+fn paragraph(text: &str) -> String {
+    format!("        <p>{}</p>", escape_html(text))
+}
+
+const IGNORE_FOOTER: &str = "If you didn't request this, you can safely ignore this email.";
+
+/// Account recovery: the magic link plus the code to type in instead.
+pub fn recovery(to: &str, first_name: &str, recovery_url: &str, recovery_code: &str) -> Email {
+    let body = [
+        paragraph(&format!("Hi {},", first_name)),
+        paragraph("You requested to recover your Bridge Classroom account. Click the button below to restore your practice history:"),
+        button(recovery_url, "Restore My Account"),
+        format!(
+            r#"        <div style="text-align: center; margin: 24px 0; padding: 20px; background: #f0f4ff; border-radius: 8px;">
+            <p style="margin: 0 0 8px 0; font-size: 14px; color: #666;">Or enter this code in the app:</p>
+            <p style="margin: 0; font-size: 36px; font-weight: bold; letter-spacing: 8px; color: #1a237e; font-family: monospace;">{}</p>
+        </div>"#,
+            escape_html(recovery_code)
+        ),
+        "        <p><strong>This link and code expire in 24 hours.</strong></p>".to_string(),
+    ]
+    .join("\n");
+
+    Email {
+        to: to.to_string(),
+        subject: "Restore your Bridge Classroom account".to_string(),
+        html: layout("Account Recovery", &body, IGNORE_FOOTER),
+        text: format!(
+            "Hi {first_name},\n\n\
+             You requested to recover your Bridge Classroom account. Open this link to restore your practice history:\n\n\
+             {recovery_url}\n\n\
+             Or enter this code in the app: {recovery_code}\n\n\
+             This link and code expire in 24 hours.\n\n\
+             {IGNORE_FOOTER}\n\
+             — Bridge Classroom\n"
+        ),
+    }
+}
+
+/// A teacher's password-reset link.
+pub fn teacher_password_reset(to: &str, first_name: &str, reset_url: &str, hours: i64) -> Email {
+    let body = [
+        paragraph(&format!("Hi {},", first_name)),
+        paragraph("Someone asked to reset the password for your Bridge Classroom teacher account."),
+        button(reset_url, "Choose a New Password"),
+        format!(
+            "        <p><strong>This link expires in {} hour(s).</strong></p>",
+            hours
+        ),
+    ]
+    .join("\n");
+
+    Email {
+        to: to.to_string(),
+        subject: "Reset your Bridge Classroom teacher password".to_string(),
+        html: layout("Reset your teacher password", &body, IGNORE_FOOTER),
+        text: format!(
+            "Hi {first_name},\n\n\
+             Someone asked to reset the password for your Bridge Classroom teacher account. Choose a new password here:\n\n\
+             {reset_url}\n\n\
+             This link expires in {hours} hour(s).\n\n\
+             {IGNORE_FOOTER}\n\
+             — Bridge Classroom\n"
+        ),
+    }
+}
+
+/// A teacher inviting someone to join their classroom.
+pub fn teacher_invitation(
+    to: &str,
+    teacher_name: &str,
+    classroom_name: &str,
+    join_url: &str,
+) -> Email {
+    let body = [
+        paragraph("Hello,"),
+        paragraph(&format!(
+            "{} has invited you to join the classroom \u{201c}{}\u{201d} on Bridge Classroom, where you can practice bidding and play and your teacher can follow your progress.",
+            teacher_name, classroom_name
+        )),
+        button(join_url, "Join the Classroom"),
+    ]
+    .join("\n");
+
+    Email {
+        to: to.to_string(),
+        subject: format!("{} invited you to {} on Bridge Classroom", teacher_name, classroom_name),
+        html: layout("You're invited", &body, "If you weren't expecting this, you can ignore this email."),
+        text: format!(
+            "Hello,\n\n\
+             {teacher_name} has invited you to join the classroom \u{201c}{classroom_name}\u{201d} on Bridge Classroom, where you can practice bidding and play and your teacher can follow your progress.\n\n\
+             Join here: {join_url}\n\n\
+             If you weren't expecting this, you can ignore this email.\n\
+             — Bridge Classroom\n"
+        ),
+    }
+}
+
+/// A nudge about an assignment that isn't finished yet.
+pub fn assignment_reminder(
+    to: &str,
+    first_name: &str,
+    assignment_name: &str,
+    due_at: Option<&str>,
+    url: &str,
+) -> Email {
+    let due = due_at
+        .map(|d| format!(" It is due {}.", d))
+        .unwrap_or_default();
+    let body = [
+        paragraph(&format!("Hi {},", first_name)),
+        paragraph(&format!(
+            "A reminder that your assignment \u{201c}{}\u{201d} isn't finished yet.{}",
+            assignment_name, due
+        )),
+        button(url, "Continue the Assignment"),
+    ]
+    .join("\n");
+
+    Email {
+        to: to.to_string(),
+        subject: format!("Reminder: {}", assignment_name),
+        html: layout(
+            "Assignment reminder",
+            &body,
+            "You're receiving this because your teacher assigned this practice.",
+        ),
+        text: format!(
+            "Hi {first_name},\n\n\
+             A reminder that your assignment \u{201c}{assignment_name}\u{201d} isn't finished yet.{due}\n\n\
+             Continue here: {url}\n\n\
+             You're receiving this because your teacher assigned this practice.\n\
+             — Bridge Classroom\n"
+        ),
+    }
+}
+
+/// Telling a learner that a problem they reported has been looked at.
+/// `board` names the deal, e.g. "Stayman, deal 3".
+pub fn report_resolved(
+    to: &str,
+    first_name: &str,
+    board: &str,
+    resolution_note: Option<&str>,
+) -> Email {
+    let note = resolution_note.map(str::trim).filter(|n| !n.is_empty());
+    let mut body = vec![
+        paragraph(&format!("Hi {},", first_name)),
+        paragraph(&format!(
+            "Thanks for reporting a problem with {}. Our curators have looked into it and marked it resolved.",
+            board
+        )),
+    ];
+    if let Some(note) = note {
+        body.push(paragraph("Their note:"));
+        body.push(format!(
+            r#"        <blockquote style="margin: 0 0 16px 0; padding: 8px 16px; border-left: 3px solid #2563eb; color: #555;">{}</blockquote>"#,
+            escape_html(note)
+        ));
+    }
+    let note_text = note
+        .map(|n| format!("Their note:\n\n    {}\n\n", n))
+        .unwrap_or_default();
+
+    Email {
+        to: to.to_string(),
+        subject: format!("Your report about {} has been resolved", board),
+        html: layout(
+            "Thanks for your report",
+            &body.join("\n"),
+            "You're receiving this because you used Report a Problem.",
+        ),
+        text: format!(
+            "Hi {first_name},\n\n\
+             Thanks for reporting a problem with {board}. Our curators have looked into it and marked it resolved.\n\n\
+             {note_text}\
+             You're receiving this because you used Report a Problem.\n\
+             — Bridge Classroom\n"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_escape_interpolated_values() {
+        let email = teacher_invitation(
+            "student@example.com",
+            "Ms <b>Smith</b>",
+            "Tuesday & Thursday",
+            "https://example.com/join?code=ABC&x=1",
+        );
+        assert_eq!(
+            email.subject,
+            "Ms <b>Smith</b> invited you to Tuesday & Thursday on Bridge Classroom"
+        );
+        assert!(email.html.contains("Ms &lt;b&gt;Smith&lt;/b&gt;"));
+        assert!(email.html.contains("Tuesday &amp; Thursday"));
+        assert!(email
+            .html
+            .contains(r#"href="https://example.com/join?code=ABC&amp;x=1""#));
+        assert!(!email.html.contains("<b>"));
+        // The text part is not HTML: values go in verbatim.
+        assert!(email
+            .text
+            .contains("Join here: https://example.com/join?code=ABC&x=1"));
+
+        let reminder = assignment_reminder("s@example.com", "Ann", "Stayman", None, "https://x");
+        assert!(!reminder.text.contains("due"));
+        let reminder = assignment_reminder(
+            "s@example.com",
+            "Ann",
+            "Stayman",
+            Some("2026-03-01"),
+            "https://x",
+        );
+        assert!(reminder.text.contains("It is due 2026-03-01."));
+        assert!(reminder.html.contains("It is due 2026-03-01."));
+    }
+}
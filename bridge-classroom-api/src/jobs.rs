@@ -0,0 +1,674 @@
+//! Durable background queue for derived-table recomputes.
+//!
+//! `submit_observations` used to rebuild `board_status`,
+//! `assignment_board_status` and `student_summary` inline, so a large
+//! offline-sync batch held the request open for every touched board. It
+//! now enqueues a [`Job`] per affected board / assignment / user into
+//! `recompute_jobs` and returns; a small pool of tokio workers drains the
+//! table.
+//!
+//! - **Dedupe**: at most one *pending* job per key (partial unique index),
+//!   so fifty observations on one board queue one board recompute.
+//! - **Ordering**: board jobs run before assignment jobs before summaries,
+//!   and a user's summary waits until none of their board jobs are queued
+//!   or running (the summary reads `board_status`). Two jobs with the same
+//!   key never run at once.
+//! - **Incremental boards**: a board job folds only the newly appended
+//!   observations into `board_status` (`fold_board_observations`). One
+//!   queued because an existing observation was re-submitted asks for a
+//!   `full_replay`; the flag is sticky across dedupe, retries and restarts.
+//! - **Retries**: a failed job is retried with exponential backoff up to
+//!   [`MAX_ATTEMPTS`], then parked as `failed` for the admin queue view.
+//! - **Durability**: jobs survive restarts; anything left `running` by a
+//!   crash is requeued at startup.
+//!
+//! The recomputes themselves are idempotent, so running one twice is
+//! harmless.
+//!
+//! Roster invitation emails (`routes/roster.rs`) ride the same queue, so
+//! a large upload returns at once and a failed send is retried. Sending
+//! is not idempotent, but a job only sends while its invitation is still
+//! unsent.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::config::Config;
+use crate::routes::board_status::{
+    fold_board_observations, recompute_assignment_boards, recompute_board_history,
+};
+use crate::routes::roster::deliver_invitation;
+use crate::student_summary::recompute_student_summary;
+
+/// Attempts before a job is parked as `failed`.
+pub const MAX_ATTEMPTS: i64 = 5;
+/// First retry delay; doubles per attempt.
+const BACKOFF_BASE_SECS: i64 = 5;
+/// Ceiling on the retry delay.
+const BACKOFF_MAX_SECS: i64 = 600;
+/// How long an idle worker sleeps before polling again.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// One unit of queued work.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Job {
+    Board {
+        user_id: String,
+        deal_subfolder: String,
+        deal_number: i32,
+        /// Replay the board's whole history rather than folding new
+        /// observations. Not part of the dedupe key: a pending fold is
+        /// upgraded in place instead.
+        full_replay: bool,
+    },
+    Assignment {
+        user_id: String,
+        assignment_id: String,
+    },
+    Summary {
+        user_id: String,
+    },
+    /// Email a roster invitation. `user_id` is the teacher who sent it.
+    Invitation {
+        user_id: String,
+        invitation_id: String,
+    },
+}
+
+impl Job {
+    fn kind(&self) -> &'static str {
+        match self {
+            Job::Board { .. } => "board",
+            Job::Assignment { .. } => "assignment",
+            Job::Summary { .. } => "summary",
+            Job::Invitation { .. } => "invitation",
+        }
+    }
+
+    fn user_id(&self) -> &str {
+        match self {
+            Job::Board { user_id, .. }
+            | Job::Assignment { user_id, .. }
+            | Job::Summary { user_id }
+            | Job::Invitation { user_id, .. } => user_id,
+        }
+    }
+
+    fn target(&self) -> String {
+        match self {
+            Job::Board {
+                deal_subfolder,
+                deal_number,
+                ..
+            } => format!("{}/{}", deal_subfolder, deal_number),
+            Job::Assignment { assignment_id, .. } => assignment_id.clone(),
+            Job::Summary { .. } => String::new(),
+            Job::Invitation { invitation_id, .. } => invitation_id.clone(),
+        }
+    }
+
+    fn priority(&self) -> i64 {
+        match self {
+            // A teacher is waiting on these; they don't queue behind recomputes.
+            Job::Board { .. } | Job::Invitation { .. } => 0,
+            Job::Assignment { .. } => 1,
+            Job::Summary { .. } => 2,
+        }
+    }
+
+    fn dedupe_key(&self) -> String {
+        format!("{}:{}:{}", self.kind(), self.user_id(), self.target())
+    }
+
+    fn full_replay(&self) -> bool {
+        matches!(self, Job::Board { full_replay: true, .. })
+    }
+
+    fn from_row(kind: &str, user_id: String, target: &str, full_replay: bool) -> Option<Job> {
+        match kind {
+            "board" => {
+                // Subfolders never contain '/', but split from the right anyway.
+                let (subfolder, deal) = target.rsplit_once('/')?;
+                Some(Job::Board {
+                    user_id,
+                    deal_subfolder: subfolder.to_string(),
+                    deal_number: deal.parse().ok()?,
+                    full_replay,
+                })
+            }
+            "assignment" => Some(Job::Assignment {
+                user_id,
+                assignment_id: target.to_string(),
+            }),
+            "summary" => Some(Job::Summary { user_id }),
+            "invitation" => Some(Job::Invitation {
+                user_id,
+                invitation_id: target.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Run the work this job stands for.
+    pub async fn run(&self, pool: &Pool<Sqlite>, config: &Config) -> Result<(), String> {
+        match self {
+            Job::Board {
+                user_id,
+                deal_subfolder,
+                deal_number,
+                full_replay: true,
+            } => recompute_board_history(pool, user_id, deal_subfolder, *deal_number).await,
+            Job::Board {
+                user_id,
+                deal_subfolder,
+                deal_number,
+                full_replay: false,
+            } => fold_board_observations(pool, user_id, deal_subfolder, *deal_number).await,
+            Job::Assignment {
+                user_id,
+                assignment_id,
+            } => recompute_assignment_boards(pool, user_id, assignment_id).await,
+            Job::Summary { user_id } => recompute_student_summary(pool, user_id).await,
+            Job::Invitation { invitation_id, .. } => {
+                deliver_invitation(pool, config, invitation_id).await
+            }
+        }
+    }
+}
+
+/// Fixed-width UTC timestamps so `run_after <= now` compares correctly as text.
+fn timestamp(at: chrono::DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Delay before retrying a job that has failed `attempts` times.
+pub fn backoff(attempts: i64) -> chrono::Duration {
+    let exp = attempts.clamp(1, 16) - 1;
+    chrono::Duration::seconds((BACKOFF_BASE_SECS << exp).min(BACKOFF_MAX_SECS))
+}
+
+/// Queue `jobs` in one transaction. Jobs whose key is already pending are
+/// skipped, except that a full-replay request upgrades a pending fold.
+/// Returns how many rows were queued or upgraded.
+pub async fn enqueue_all(
+    pool: &Pool<Sqlite>,
+    jobs: impl IntoIterator<Item = Job>,
+) -> Result<usize, sqlx::Error> {
+    let now = timestamp(Utc::now());
+    let mut tx = pool.begin().await?;
+    let mut queued = 0;
+    for job in jobs {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO recompute_jobs
+                (kind, user_id, target, dedupe_key, priority, status, attempts,
+                 run_after, created_at, updated_at, full_replay)
+            VALUES (?, ?, ?, ?, ?, 'pending', 0, ?, ?, ?, ?)
+            ON CONFLICT(dedupe_key) WHERE status = 'pending' DO UPDATE
+                SET full_replay = 1, updated_at = excluded.updated_at
+                WHERE excluded.full_replay > recompute_jobs.full_replay
+            "#,
+        )
+        .bind(job.kind())
+        .bind(job.user_id())
+        .bind(job.target())
+        .bind(job.dedupe_key())
+        .bind(job.priority())
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .bind(job.full_replay())
+        .execute(&mut *tx)
+        .await?;
+        queued += result.rows_affected() as usize;
+    }
+    tx.commit().await?;
+    Ok(queued)
+}
+
+/// Queue a full rebuild of everything derived for `user_id`: a replay of
+/// every board (not a fold), each assignment rollup and the summary. Used
+/// when the rules the walks ran under change.
+pub async fn enqueue_user_replay(pool: &Pool<Sqlite>, user_id: &str) -> Result<usize, sqlx::Error> {
+    let boards: Vec<(String, i32)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT deal_subfolder, deal_number FROM observations
+        WHERE user_id = ? AND deal_subfolder IS NOT NULL AND deal_number IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    let assignments: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT assignment_id FROM observations WHERE user_id = ? AND assignment_id IS NOT NULL",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let jobs = boards
+        .into_iter()
+        .map(|(deal_subfolder, deal_number)| Job::Board {
+            user_id: user_id.to_string(),
+            deal_subfolder,
+            deal_number,
+            full_replay: true,
+        })
+        .chain(assignments.into_iter().map(|assignment_id| Job::Assignment {
+            user_id: user_id.to_string(),
+            assignment_id,
+        }))
+        .chain(std::iter::once(Job::Summary {
+            user_id: user_id.to_string(),
+        }))
+        .collect::<Vec<_>>();
+    enqueue_all(pool, jobs).await
+}
+
+#[derive(Debug)]
+pub struct ClaimedJob {
+    pub id: i64,
+    pub job: Job,
+    pub attempts: i64,
+}
+
+/// Atomically mark the next runnable job `running` and return it.
+pub async fn claim_next(pool: &Pool<Sqlite>) -> Result<Option<ClaimedJob>, sqlx::Error> {
+    let now = timestamp(Utc::now());
+    loop {
+        let row: Option<(i64, String, String, String, i64, bool)> = sqlx::query_as(
+            r#"
+            UPDATE recompute_jobs
+            SET status = 'running', attempts = attempts + 1, updated_at = ?1
+            WHERE id = (
+                SELECT j.id FROM recompute_jobs j
+                WHERE j.status = 'pending' AND j.run_after <= ?1
+                  AND NOT EXISTS (
+                      SELECT 1 FROM recompute_jobs r
+                      WHERE r.status = 'running' AND r.dedupe_key = j.dedupe_key)
+                  AND (j.kind <> 'summary' OR NOT EXISTS (
+                      SELECT 1 FROM recompute_jobs b
+                      WHERE b.kind = 'board' AND b.user_id = j.user_id
+                        AND b.status IN ('pending', 'running')))
+                ORDER BY j.priority, j.id
+                LIMIT 1
+            )
+            RETURNING id, kind, user_id, target, attempts, full_replay
+            "#,
+        )
+        .bind(&now)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some((id, kind, user_id, target, attempts, full_replay)) = row else {
+            return Ok(None);
+        };
+        match Job::from_row(&kind, user_id, &target, full_replay) {
+            Some(job) => return Ok(Some(ClaimedJob { id, job, attempts })),
+            None => {
+                // Written by a newer build or by hand; park it and move on.
+                mark_failed(pool, id, &format!("unrecognised job {} {}", kind, target)).await?;
+            }
+        }
+    }
+}
+
+/// A job succeeded: drop it.
+pub async fn complete(pool: &Pool<Sqlite>, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM recompute_jobs WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn mark_failed(pool: &Pool<Sqlite>, id: i64, error: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE recompute_jobs SET status = 'failed', last_error = ?, updated_at = ? WHERE id = ?",
+    )
+    .bind(error)
+    .bind(timestamp(Utc::now()))
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A job failed: schedule a retry, or park it once attempts run out. If a
+/// newer pending job with the same key already exists it supersedes this
+/// one, which is simply dropped.
+pub async fn retry_or_fail(
+    pool: &Pool<Sqlite>,
+    claimed: &ClaimedJob,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    if claimed.attempts >= MAX_ATTEMPTS {
+        tracing::error!(
+            "recompute job {} ({:?}) failed permanently after {} attempts: {}",
+            claimed.id,
+            claimed.job,
+            claimed.attempts,
+            error
+        );
+        return mark_failed(pool, claimed.id, error).await;
+    }
+
+    // The pending job inherits a full-replay request before replacing us.
+    if claimed.job.full_replay() {
+        sqlx::query(
+            "UPDATE recompute_jobs SET full_replay = 1 WHERE status = 'pending' AND dedupe_key = ?",
+        )
+        .bind(claimed.job.dedupe_key())
+        .execute(pool)
+        .await?;
+    }
+    let superseded = sqlx::query(
+        r#"
+        DELETE FROM recompute_jobs
+        WHERE id = ?1 AND EXISTS (
+            SELECT 1 FROM recompute_jobs p
+            WHERE p.status = 'pending' AND p.dedupe_key = ?2)
+        "#,
+    )
+    .bind(claimed.id)
+    .bind(claimed.job.dedupe_key())
+    .execute(pool)
+    .await?;
+    if superseded.rows_affected() > 0 {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        UPDATE recompute_jobs
+        SET status = 'pending', run_after = ?, last_error = ?, updated_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(timestamp(now + backoff(claimed.attempts)))
+    .bind(error)
+    .bind(timestamp(now))
+    .bind(claimed.id)
+    .execute(pool)
+    .await?;
+    tracing::warn!(
+        "recompute job {} ({:?}) attempt {} failed, will retry: {}",
+        claimed.id,
+        claimed.job,
+        claimed.attempts,
+        error
+    );
+    Ok(())
+}
+
+/// Put jobs a previous process left `running` back in the queue. Call once
+/// at startup, before workers spawn.
+pub async fn requeue_interrupted(pool: &Pool<Sqlite>) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE recompute_jobs SET full_replay = 1
+        WHERE status = 'pending' AND dedupe_key IN (
+            SELECT dedupe_key FROM recompute_jobs
+            WHERE status = 'running' AND full_replay = 1)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM recompute_jobs
+        WHERE status = 'running' AND dedupe_key IN (
+            SELECT dedupe_key FROM recompute_jobs WHERE status = 'pending')
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    let result = sqlx::query(
+        "UPDATE recompute_jobs SET status = 'pending', updated_at = ? WHERE status = 'running'",
+    )
+    .bind(timestamp(Utc::now()))
+    .execute(pool)
+    .await?;
+    if result.rows_affected() > 0 {
+        tracing::info!(
+            "Requeued {} interrupted recompute jobs",
+            result.rows_affected()
+        );
+    }
+    Ok(result.rows_affected())
+}
+
+/// Claim and run one job. Returns false when nothing was runnable.
+pub async fn run_one(pool: &Pool<Sqlite>, config: &Config) -> Result<bool, sqlx::Error> {
+    let Some(claimed) = claim_next(pool).await? else {
+        return Ok(false);
+    };
+    match claimed.job.run(pool, config).await {
+        Ok(()) => complete(pool, claimed.id).await?,
+        Err(e) => retry_or_fail(pool, &claimed, &e).await?,
+    }
+    Ok(true)
+}
+
+/// Start `count` workers draining the queue for the life of the process.
+pub fn spawn_workers(pool: Pool<Sqlite>, config: Arc<Config>, count: usize) {
+    for worker in 0..count {
+        let pool = pool.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            loop {
+                match run_one(&pool, &config).await {
+                    Ok(true) => {}
+                    Ok(false) => tokio::time::sleep(POLL_INTERVAL).await,
+                    Err(e) => {
+                        tracing::error!("recompute worker {}: queue error: {}", worker, e);
+                        tokio::time::sleep(POLL_INTERVAL * 4).await;
+                    }
+                }
+            }
+        });
+    }
+    if count > 0 {
+        tracing::info!("Started {} recompute workers", count);
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct FailedJob {
+    pub id: i64,
+    pub kind: String,
+    pub user_id: String,
+    pub target: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueStats {
+    pub pending: i64,
+    /// Pending jobs whose backoff has elapsed.
+    pub ready: i64,
+    pub running: i64,
+    pub failed: i64,
+    pub oldest_pending_at: Option<String>,
+    /// Most recent failures first, up to 100.
+    pub failed_jobs: Vec<FailedJob>,
+}
+
+/// Queue depth and parked failures, for the admin view.
+pub async fn queue_stats(pool: &Pool<Sqlite>) -> Result<QueueStats, sqlx::Error> {
+    let (pending, ready, running, failed, oldest_pending_at): (i64, i64, i64, i64, Option<String>) =
+        sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(SUM(status = 'pending'), 0),
+                COALESCE(SUM(status = 'pending' AND run_after <= ?), 0),
+                COALESCE(SUM(status = 'running'), 0),
+                COALESCE(SUM(status = 'failed'), 0),
+                MIN(CASE WHEN status = 'pending' THEN created_at END)
+            FROM recompute_jobs
+            "#,
+        )
+        .bind(timestamp(Utc::now()))
+        .fetch_one(pool)
+        .await?;
+
+    let failed_jobs = sqlx::query_as(
+        r#"
+        SELECT id, kind, user_id, target, attempts, last_error, updated_at
+        FROM recompute_jobs
+        WHERE status = 'failed'
+        ORDER BY updated_at DESC
+        LIMIT 100
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(QueueStats {
+        pending,
+        ready,
+        running,
+        failed,
+        oldest_pending_at,
+        failed_jobs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(user: &str, deal: i32) -> Job {
+        Job::Board {
+            user_id: user.to_string(),
+            deal_subfolder: "Stayman".to_string(),
+            deal_number: deal,
+            full_replay: false,
+        }
+    }
+
+    fn replay(user: &str, deal: i32) -> Job {
+        Job::Board {
+            user_id: user.to_string(),
+            deal_subfolder: "Stayman".to_string(),
+            deal_number: deal,
+            full_replay: true,
+        }
+    }
+
+    fn summary(user: &str) -> Job {
+        Job::Summary {
+            user_id: user.to_string(),
+        }
+    }
+
+    #[test]
+    fn rows_round_trip_and_backoff_is_capped() {
+        for job in [
+            board("u1", 7),
+            replay("u1", 7),
+            summary("u1"),
+            Job::Assignment {
+                user_id: "u1".into(),
+                assignment_id: "a1".into(),
+            },
+            Job::Invitation {
+                user_id: "t1".into(),
+                invitation_id: "i1".into(),
+            },
+        ] {
+            let back = Job::from_row(
+                job.kind(),
+                job.user_id().to_string(),
+                &job.target(),
+                job.full_replay(),
+            );
+            assert_eq!(back, Some(job));
+        }
+        assert_eq!(backoff(1), chrono::Duration::seconds(5));
+        assert_eq!(backoff(2), chrono::Duration::seconds(10));
+        assert_eq!(backoff(40), chrono::Duration::seconds(BACKOFF_MAX_SECS));
+    }
+
+    #[tokio::test]
+    async fn pending_jobs_are_deduplicated() {
+        let pool = crate::db::test_pool().await;
+        let queued = enqueue_all(&pool, [board("u1", 1), board("u1", 1), summary("u1")])
+            .await
+            .unwrap();
+        assert_eq!(queued, 2);
+        assert_eq!(enqueue_all(&pool, [board("u1", 1)]).await.unwrap(), 0);
+        assert_eq!(queue_stats(&pool).await.unwrap().pending, 2);
+    }
+
+    #[tokio::test]
+    async fn full_replay_upgrades_a_pending_fold() {
+        let pool = crate::db::test_pool().await;
+        enqueue_all(&pool, [board("u1", 1)]).await.unwrap();
+        assert_eq!(enqueue_all(&pool, [replay("u1", 1)]).await.unwrap(), 1);
+        // A later fold request doesn't downgrade it.
+        assert_eq!(enqueue_all(&pool, [board("u1", 1)]).await.unwrap(), 0);
+        assert_eq!(queue_stats(&pool).await.unwrap().pending, 1);
+        assert_eq!(claim_next(&pool).await.unwrap().unwrap().job, replay("u1", 1));
+    }
+
+    #[tokio::test]
+    async fn summary_waits_for_the_users_boards() {
+        let pool = crate::db::test_pool().await;
+        enqueue_all(&pool, [summary("u1"), board("u1", 1), summary("u2")])
+            .await
+            .unwrap();
+
+        let first = claim_next(&pool).await.unwrap().unwrap();
+        assert_eq!(first.job, board("u1", 1));
+        // u1's board is running, so u1's summary is held back; u2's isn't.
+        let second = claim_next(&pool).await.unwrap().unwrap();
+        assert_eq!(second.job, summary("u2"));
+        assert!(claim_next(&pool).await.unwrap().is_none());
+
+        complete(&pool, first.id).await.unwrap();
+        let third = claim_next(&pool).await.unwrap().unwrap();
+        assert_eq!(third.job, summary("u1"));
+    }
+
+    #[tokio::test]
+    async fn failures_back_off_then_park() {
+        let pool = crate::db::test_pool().await;
+        enqueue_all(&pool, [board("u1", 1)]).await.unwrap();
+
+        let claimed = claim_next(&pool).await.unwrap().unwrap();
+        retry_or_fail(&pool, &claimed, "boom").await.unwrap();
+        // Backing off: not claimable yet.
+        assert!(claim_next(&pool).await.unwrap().is_none());
+        let stats = queue_stats(&pool).await.unwrap();
+        assert_eq!((stats.pending, stats.ready), (1, 0));
+
+        let last = ClaimedJob {
+            attempts: MAX_ATTEMPTS,
+            ..claimed
+        };
+        retry_or_fail(&pool, &last, "still boom").await.unwrap();
+        let stats = queue_stats(&pool).await.unwrap();
+        assert_eq!((stats.pending, stats.failed), (0, 1));
+        assert_eq!(
+            stats.failed_jobs[0].last_error.as_deref(),
+            Some("still boom")
+        );
+    }
+
+    #[tokio::test]
+    async fn interrupted_jobs_are_requeued() {
+        let pool = crate::db::test_pool().await;
+        enqueue_all(&pool, [board("u1", 1)]).await.unwrap();
+        claim_next(&pool).await.unwrap().unwrap();
+        assert_eq!(requeue_interrupted(&pool).await.unwrap(), 1);
+        assert!(claim_next(&pool).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,338 @@
+//! Server-side lesson catalog: which boards each lesson declares.
+//!
+//! The frontend learns a collection's lessons from its `toc.json` and each
+//! lesson's boards from `<lesson>.pbn`; observations carry the lesson id as
+//! `deal_subfolder` and the PBN `[Board "N"]` as `deal_number`. Importing
+//! the same files here fills `lessons` / `lesson_boards`, so
+//! `documentation/CORRECTNESS_AND_MASTERY.md` §13 can measure mastery
+//! against a lesson's declared boards instead of the boards anyone has
+//! happened to play. Lessons missing from the catalog keep the old
+//! seen-boards approximation.
+//!
+//! Imports run from the admin CLI (`import-lessons`).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+/// The parts of a collection's `toc.json` the catalog needs.
+#[derive(Debug, Deserialize)]
+pub struct TocFile {
+    #[serde(default)]
+    pub categories: Vec<TocCategory>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TocCategory {
+    pub name: String,
+    #[serde(default)]
+    pub lessons: Vec<TocLesson>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TocLesson {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub lessons: usize,
+    pub boards: usize,
+    /// Lessons listed in the toc whose PBN couldn't be read; their boards
+    /// are left as they were.
+    pub missing_pbn: Vec<String>,
+    /// Lessons previously imported for this collection but no longer in
+    /// its toc, now removed.
+    pub removed: usize,
+}
+
+/// The PBN file for a toc lesson id, as the frontend resolves it
+/// ('Bidpractice/Set1' → 'Set1.pbn').
+pub fn pbn_filename(lesson_id: &str) -> String {
+    format!("{}.pbn", lesson_id.rsplit('/').next().unwrap_or(lesson_id))
+}
+
+/// Board numbers declared by a PBN file's `[Board "N"]` tags, sorted and
+/// deduplicated.
+pub fn pbn_board_numbers(pbn: &str) -> Vec<i32> {
+    let mut boards: Vec<i32> = pbn
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("[Board \"")?;
+            rest.split('"').next()?.trim().parse().ok()
+        })
+        .collect();
+    boards.sort_unstable();
+    boards.dedup();
+    boards
+}
+
+/// Read a toc or PBN from a local path or an http(s) URL.
+pub async fn fetch_text(location: &str) -> Result<String, String> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        let response = reqwest::get(location)
+            .await
+            .map_err(|e| format!("{}: {}", location, e))?;
+        if !response.status().is_success() {
+            return Err(format!("{}: HTTP {}", location, response.status()));
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| format!("{}: {}", location, e))
+    } else {
+        tokio::fs::read_to_string(location)
+            .await
+            .map_err(|e| format!("{}: {}", location, e))
+    }
+}
+
+/// Load a collection's toc from `toc_location` and each lesson's boards
+/// from `<base>/<file>.pbn` (`base` defaults to the toc's directory).
+/// Lessons whose PBN can't be read map to `None`.
+pub async fn load_collection(
+    toc_location: &str,
+    base: Option<&str>,
+) -> Result<(TocFile, BTreeMap<String, Option<Vec<i32>>>), String> {
+    let toc: TocFile = serde_json::from_str(&fetch_text(toc_location).await?)
+        .map_err(|e| format!("{}: {}", toc_location, e))?;
+    let base = base
+        .map(str::to_string)
+        .unwrap_or_else(|| match toc_location.rsplit_once('/') {
+            Some((dir, _)) => dir.to_string(),
+            None => ".".to_string(),
+        });
+
+    let mut boards = BTreeMap::new();
+    for lesson in toc.categories.iter().flat_map(|c| &c.lessons) {
+        let location = format!(
+            "{}/{}",
+            base.trim_end_matches('/'),
+            pbn_filename(&lesson.id)
+        );
+        let declared = match fetch_text(&location).await {
+            Ok(pbn) => Some(pbn_board_numbers(&pbn)),
+            Err(e) => {
+                tracing::warn!("Lesson {}: {}", lesson.id, e);
+                None
+            }
+        };
+        boards.insert(lesson.id.clone(), declared);
+    }
+    Ok((toc, boards))
+}
+
+/// Replace `collection`'s catalog with `toc`. Each lesson's declared boards
+/// come from `boards`; a lesson mapped to `None` keeps whatever boards it
+/// had. Lessons this collection no longer lists are dropped.
+pub async fn import_collection(
+    pool: &Pool<Sqlite>,
+    collection: &str,
+    toc: &TocFile,
+    boards: &BTreeMap<String, Option<Vec<i32>>>,
+) -> Result<ImportReport, String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut report = ImportReport::default();
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("begin import tx failed: {}", e))?;
+
+    let mut listed = Vec::new();
+    let mut sort_order = 0i64;
+    for category in &toc.categories {
+        for lesson in &category.lessons {
+            sort_order += 1;
+            sqlx::query(
+                r#"
+                INSERT INTO lessons
+                    (deal_subfolder, collection, name, category, description, sort_order, imported_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(deal_subfolder) DO UPDATE SET
+                    collection  = excluded.collection,
+                    name        = excluded.name,
+                    category    = excluded.category,
+                    description = excluded.description,
+                    sort_order  = excluded.sort_order,
+                    imported_at = excluded.imported_at
+                "#,
+            )
+            .bind(&lesson.id)
+            .bind(collection)
+            .bind(&lesson.name)
+            .bind(&category.name)
+            .bind(&lesson.description)
+            .bind(sort_order)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Lesson upsert failed: {}", e))?;
+            report.lessons += 1;
+            listed.push(lesson.id.as_str());
+
+            let Some(Some(declared)) = boards.get(&lesson.id) else {
+                report.missing_pbn.push(lesson.id.clone());
+                continue;
+            };
+            sqlx::query("DELETE FROM lesson_boards WHERE deal_subfolder = ?")
+                .bind(&lesson.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Lesson board delete failed: {}", e))?;
+            for deal_number in declared {
+                sqlx::query(
+                    "INSERT INTO lesson_boards (deal_subfolder, deal_number) VALUES (?, ?)",
+                )
+                .bind(&lesson.id)
+                .bind(deal_number)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Lesson board insert failed: {}", e))?;
+            }
+            report.boards += declared.len();
+        }
+    }
+
+    let previous: Vec<String> =
+        sqlx::query_scalar("SELECT deal_subfolder FROM lessons WHERE collection = ?")
+            .bind(collection)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Lesson lookup failed: {}", e))?;
+    for stale in previous.iter().filter(|id| !listed.contains(&id.as_str())) {
+        // lesson_boards rows go with it (ON DELETE CASCADE).
+        sqlx::query("DELETE FROM lessons WHERE deal_subfolder = ?")
+            .bind(stale)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Lesson delete failed: {}", e))?;
+        report.removed += 1;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("commit import tx failed: {}", e))?;
+    Ok(report)
+}
+
+/// Users with progress in any of `collection`'s lessons — the summaries an
+/// import invalidates.
+pub async fn users_in_collection(
+    pool: &Pool<Sqlite>,
+    collection: &str,
+) -> Result<Vec<String>, String> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT bs.user_id
+        FROM board_status bs
+        JOIN lessons l ON l.deal_subfolder = bs.deal_subfolder
+        WHERE l.collection = ?
+        "#,
+    )
+    .bind(collection)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("User lookup failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boards_and_filenames_follow_the_frontend() {
+        let pbn = "% PBN 2.1\n[Board \"3\"]\n[Event \"\"]\n[Board \"1\"]\n[Board \"3\"]\n[Deal \"N:...\"]\n";
+        assert_eq!(pbn_board_numbers(pbn), vec![1, 3]);
+        assert_eq!(pbn_filename("Bidpractice/Set1"), "Set1.pbn");
+        assert_eq!(pbn_filename("Stayman"), "Stayman.pbn");
+    }
+
+    fn toc(lessons: &[&str]) -> TocFile {
+        TocFile {
+            categories: vec![TocCategory {
+                name: "Conventions".to_string(),
+                lessons: lessons
+                    .iter()
+                    .map(|id| TocLesson {
+                        id: id.to_string(),
+                        name: id.to_string(),
+                        description: None,
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn reimport_replaces_boards_and_prunes_dropped_lessons() {
+        let pool = crate::db::test_pool().await;
+        let boards = BTreeMap::from([
+            ("Stayman".to_string(), Some(vec![1, 2, 3])),
+            ("Drury".to_string(), Some(vec![1, 2])),
+        ]);
+        let report = import_collection(&pool, "baker", &toc(&["Stayman", "Drury"]), &boards)
+            .await
+            .unwrap();
+        assert_eq!((report.lessons, report.boards), (2, 5));
+
+        // Drury leaves the toc; Stayman's PBN is unreadable this time.
+        let boards = BTreeMap::from([("Stayman".to_string(), None)]);
+        let report = import_collection(&pool, "baker", &toc(&["Stayman"]), &boards)
+            .await
+            .unwrap();
+        assert_eq!(report.missing_pbn, vec!["Stayman".to_string()]);
+        assert_eq!(report.removed, 1);
+
+        let remaining: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT deal_subfolder, COUNT(*) FROM lesson_boards GROUP BY deal_subfolder",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(remaining, vec![("Stayman".to_string(), 3)]);
+    }
+
+    #[tokio::test]
+    async fn summary_counts_tiers_of_catalogued_lessons() {
+        let pool = crate::db::test_pool().await;
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+             VALUES ('u1', 'Test', 'Student', 'u1@example.com', '', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let boards = BTreeMap::from([("Stayman".to_string(), Some(vec![1, 2]))]);
+        import_collection(&pool, "baker", &toc(&["Stayman"]), &boards)
+            .await
+            .unwrap();
+        // Gold on both declared Stayman boards, plus an undeclared board and
+        // a lesson that was never imported.
+        for (subfolder, deal) in [("Stayman", 1), ("Stayman", 2), ("Stayman", 9), ("Drury", 1)] {
+            sqlx::query(
+                "INSERT INTO board_status (user_id, deal_subfolder, deal_number, max_stars, updated_at) \
+                 VALUES ('u1', ?, ?, 2, '')",
+            )
+            .bind(subfolder)
+            .bind(deal)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        crate::student_summary::recompute_student_summary(&pool, "u1")
+            .await
+            .unwrap();
+        let tiers: (i64, i64, i64, i64) = sqlx::query_as(
+            "SELECT lessons_exploring, lessons_learning, lessons_retaining, lessons_mastering \
+             FROM student_summary WHERE user_id = 'u1'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(tiers, (0, 0, 1, 0));
+    }
+}
@@ -0,0 +1,35 @@
+//! Bridge Classroom API library: shared by the HTTP server (`main.rs`) and
+//! the `bridge-classroom-admin` operations CLI (`bin/admin.rs`).
+
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub mod config;
+pub mod credentials;
+pub mod db;
+pub mod email;
+pub mod jobs;
+pub mod lesson_catalog;
+pub mod migrations;
+pub mod models;
+pub mod obs_crypto;
+pub mod observation_schema;
+pub mod policy;
+pub mod recompute;
+pub mod recovery_keys;
+pub mod report_sink;
+pub mod routes;
+pub mod rules;
+pub mod session;
+pub mod student_summary;
+
+use config::Config;
+
+/// Application state shared across handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Pool<Sqlite>,
+    pub config: Arc<Config>,
+    pub started_at: Instant,
+}
@@ -3,7 +3,6 @@ use axum::{
     routing::{delete, get, patch, post, put},
     Router,
 };
-use sqlx::{Pool, Sqlite};
 use std::sync::Arc;
 use std::time::Instant;
 use tower_http::{
@@ -12,22 +11,8 @@ use tower_http::{
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod config;
-mod db;
-mod models;
-mod obs_crypto;
-mod routes;
-mod student_summary;
-
-use config::Config;
-
-/// Application state shared across handlers
-#[derive(Clone)]
-pub struct AppState {
-    pub db: Pool<Sqlite>,
-    pub config: Arc<Config>,
-    pub started_at: Instant,
-}
+use bridge_classroom_api::config::Config;
+use bridge_classroom_api::{db, jobs, migrations, routes, AppState};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -44,12 +29,23 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::from_env()?;
     tracing::info!("Loaded configuration");
 
-    // Initialize database. The v2 backfill (CORRECTNESS_AND_MASTERY.md
-    // §16) runs inside run_migrations and handles populating
-    // board_status and student_summary from existing observations.
+    // Schema-only invocations: `--dry-run`, `--migrate-only`,
+    // `--migrate-down <version>`. These exit without starting the server.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return run_migration_command(&args, &config.database_url).await;
+    }
+
+    // Initialize database. Pending migrations (including the v2 backfill,
+    // CORRECTNESS_AND_MASTERY.md §16) are applied before serving.
     let db = db::init_db(&config.database_url).await?;
     tracing::info!("Database initialized");
 
+    // Jobs a crash left mid-flight go back in the recompute queue before
+    // its workers start (below, once the state is built).
+    jobs::requeue_interrupted(&db).await?;
+    routes::spawn_diagnostics_pruner(db.clone(), config.diagnostics_retention_days);
+
     // Build CORS layer
     let cors = build_cors_layer(&config);
 
@@ -59,6 +55,16 @@ async fn main() -> anyhow::Result<()> {
         config: Arc::new(config.clone()),
         started_at: Instant::now(),
     };
+    // Drain the recompute queue (board_status / student_summary rebuilds
+    // queued by observation submits, and roster invitation emails, which
+    // need the email config).
+    jobs::spawn_workers(state.db.clone(), state.config.clone(), config.recompute_workers);
+
+    // Deliver queued "Report a Problem" submissions to the configured sink.
+    routes::spawn_report_worker(state.clone());
+
+    // Pick up key rotations a restart interrupted (routes/key_rotation.rs).
+    routes::resume_key_rotations(&state).await?;
 
     // Build router
     let app = Router::new()
@@ -67,6 +73,10 @@ async fn main() -> anyhow::Result<()> {
         .route("/health", get(health_check))
         // Auth routes
         .route("/api/auth/teacher", post(routes::authenticate_teacher))
+        .route("/api/auth/teacher/password", post(routes::set_teacher_password))
+        .route("/api/auth/teacher/reset-request", post(routes::request_password_reset))
+        .route("/api/auth/teacher/reset", post(routes::reset_teacher_password))
+        .route("/api/sessions", post(routes::create_session))
         // Keys routes
         .route("/api/keys/admin", get(routes::get_admin_key))
         // User routes
@@ -92,6 +102,10 @@ async fn main() -> anyhow::Result<()> {
             "/api/observations/metadata",
             get(routes::get_observations_metadata),
         )
+        .route(
+            "/api/observations/export",
+            get(routes::export_observations),
+        )
         // Report-a-Problem route (files a classroom-feedback GitHub issue)
         .route("/api/report", post(routes::create_report))
         // Multiplayer table join tickets (verified offline by bridge-table-service)
@@ -129,7 +143,10 @@ async fn main() -> anyhow::Result<()> {
         // Classroom routes
         .route("/api/classrooms", post(routes::create_classroom))
         .route("/api/classrooms", get(routes::list_classrooms))
-        .route("/api/classrooms/:id", get(routes::get_classroom))
+        .route(
+            "/api/classrooms/:id",
+            get(routes::get_classroom).delete(routes::delete_classroom),
+        )
         .route("/api/join/:join_code", get(routes::get_join_info))
         .route("/api/join/:join_code", post(routes::join_classroom))
         .route(
@@ -140,6 +157,40 @@ async fn main() -> anyhow::Result<()> {
             "/api/classrooms/:id/members/leave",
             post(routes::leave_classroom),
         )
+        .route(
+            "/api/classrooms/:id/join-code",
+            post(routes::regenerate_join_code),
+        )
+        .route(
+            "/api/classrooms/:id/join-settings",
+            put(routes::update_join_settings),
+        )
+        .route(
+            "/api/classrooms/:id/join-requests",
+            get(routes::list_join_requests),
+        )
+        .route(
+            "/api/classrooms/:id/join-requests/:student_id",
+            delete(routes::decline_join_request),
+        )
+        .route(
+            "/api/classrooms/:id/join-requests/:student_id/approve",
+            post(routes::approve_join_request),
+        )
+        .route("/api/classrooms/:id/grants", post(routes::share_with_staff))
+        .route("/api/classrooms/:id/staff", get(routes::list_staff))
+        .route(
+            "/api/classrooms/:id/staff/:user_id",
+            put(routes::set_staff).delete(routes::remove_staff),
+        )
+        .route(
+            "/api/classrooms/:id/roster",
+            post(routes::import_roster).get(routes::list_invitations),
+        )
+        .route(
+            "/api/classrooms/:id/roster/:invitation_id",
+            delete(routes::revoke_invitation),
+        )
         // Exercise routes
         .route("/api/exercises", post(routes::create_exercise).get(routes::list_exercises))
         .route(
@@ -156,9 +207,21 @@ async fn main() -> anyhow::Result<()> {
         )
         // Board status routes
         .route("/api/board-status", get(routes::get_board_status))
+        .route("/api/board-status/timeline", get(routes::get_board_timeline))
         .route("/api/assignment-status", get(routes::get_assignment_status))
         .route("/api/student-summaries", get(routes::get_student_summaries))
         .route("/api/lesson-mastery", get(routes::get_lesson_mastery))
+        .route("/api/review-queue", get(routes::get_review_queue))
+        // Mastery rules profiles
+        .route(
+            "/api/rules-profiles",
+            get(routes::list_rules_profiles).post(routes::create_rules_profile),
+        )
+        .route("/api/rules-profiles/what-if", post(routes::rules_what_if))
+        .route(
+            "/api/classrooms/:id/rules-profile",
+            put(routes::set_classroom_rules_profile),
+        )
         // Teacher dashboard routes
         .route("/api/teacher/dashboard", get(routes::teacher_dashboard))
         .route("/api/teacher/dashboard/clear", post(routes::clear_dashboard_panel))
@@ -170,13 +233,27 @@ async fn main() -> anyhow::Result<()> {
         // Admin routes
         .route("/api/admin/stats", get(routes::admin_stats))
         .route("/api/admin/health", get(routes::admin_health))
+        .route("/api/admin/jobs", get(routes::admin_job_queue))
+        .route("/api/admin/diagnostics", get(routes::admin_diagnostics))
+        .route("/api/admin/reports", get(routes::admin_list_reports))
+        .route("/api/admin/reports/boards", get(routes::admin_report_groups))
+        .route("/api/admin/reports/:id", patch(routes::admin_update_report))
+        .route("/api/admin/boards-under-review", get(routes::admin_boards_under_review))
+        .route(
+            "/api/admin/boards-under-review/:deal_subfolder/:deal_number",
+            put(routes::admin_mark_board_under_review).delete(routes::admin_clear_board_review),
+        )
         .route("/api/admin/merge-dryrun", get(routes::merge_dry_run))
         .route("/api/admin/merge-accounts", post(routes::merge_accounts))
         .route("/api/account-handoff", get(routes::get_account_handoff))
         .route("/api/account-handoff/consume", post(routes::consume_account_handoff))
+        .route("/api/key-rotation", post(routes::start_key_rotation))
+        .route("/api/key-rotation", get(routes::get_key_rotation))
         .route("/api/admin/users/search", get(routes::admin_search_user))
         .route("/api/admin/users/:id", patch(routes::admin_correct_name))
         .route("/api/admin/decrypt-observations", post(routes::admin_decrypt_observations))
+        .route("/api/admin/recovery-keys", get(routes::admin_recovery_key_status))
+        .route("/api/admin/recovery-keys/rewrap", post(routes::admin_rewrap_recovery))
         .layer(cors)
         .layer(
             TraceLayer::new_for_http()
@@ -201,11 +278,60 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
+/// Handle the schema-management flags. Returns when done, without
+/// binding the HTTP listener.
+async fn run_migration_command(args: &[String], database_url: &str) -> anyhow::Result<()> {
+    match args.first().map(String::as_str) {
+        Some("--dry-run") => {
+            let pool = db::connect(database_url).await?;
+            let statuses = migrations::status(&pool).await?;
+            for s in &statuses {
+                let state = match &s.state {
+                    migrations::MigrationState::Applied { applied_at } => {
+                        format!("applied {}", applied_at)
+                    }
+                    migrations::MigrationState::Pending => "PENDING".to_string(),
+                    migrations::MigrationState::ChecksumMismatch { recorded } => {
+                        format!("CHECKSUM MISMATCH (recorded {})", recorded)
+                    }
+                    migrations::MigrationState::Unknown => "UNKNOWN to this build".to_string(),
+                };
+                println!("{:>4}  {:<36} {}  {}", s.version, s.name, &s.checksum[..12.min(s.checksum.len())], state);
+            }
+            println!("{} pending", migrations::pending(&statuses).len());
+            Ok(())
+        }
+        Some("--migrate-only") => {
+            db::init_db(database_url).await?;
+            println!("Migrations applied");
+            Ok(())
+        }
+        Some("--migrate-down") => {
+            let target: i64 = args
+                .get(1)
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("usage: --migrate-down <version>"))?;
+            let pool = db::connect(database_url).await?;
+            let undone = migrations::rollback_to(&pool, target).await?;
+            println!("Rolled back {:?}", undone);
+            Ok(())
+        }
+        _ => anyhow::bail!(
+            "unknown arguments {:?}; expected --dry-run, --migrate-only or --migrate-down <version>",
+            args
+        ),
+    }
+}
+
 /// Build CORS layer from configuration
 fn build_cors_layer(config: &Config) -> CorsLayer {
     let origins = config.allowed_origins.clone();
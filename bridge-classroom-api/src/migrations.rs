@@ -0,0 +1,705 @@
+//! Numbered schema migrations.
+//!
+//! Each [`Migration`] has a version, a name, an `up` step and (where it can
+//! be undone) `down` SQL. Applied versions are recorded in
+//! `schema_migrations` with a SHA-256 checksum of what ran, so an upgrade of
+//! the production file is auditable ("what ran, when, from which source")
+//! and an edited-after-release migration is caught at boot instead of
+//! silently diverging.
+//!
+//! SQL migrations live in `migrations/NNNN_name.sql` (and
+//! `NNNN_name.down.sql`). Data migrations that need Rust — recomputes,
+//! seeding — are [`Step::Code`]; their checksum covers a `fingerprint`
+//! string that must be bumped when the function's behaviour changes.
+//!
+//! Databases created before this framework are adopted on first boot: the
+//! columns the old code used to `ALTER` in are added if missing, then the
+//! baseline (all `IF NOT EXISTS`) runs as a no-op for existing tables.
+//! The one-shot backfills keep honouring their old `schema_meta` flags.
+//!
+//! To add a migration: append to [`MIGRATIONS`] with the next version.
+//! Never edit or reorder an entry that has shipped.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
+
+use crate::db::DbError;
+
+pub type MigrationFuture<'a> = Pin<Box<dyn Future<Output = Result<(), DbError>> + Send + 'a>>;
+
+pub enum Step {
+    /// One or more SQL statements, run in a single transaction.
+    Sql(&'static str),
+    /// A data migration implemented in Rust.
+    Code {
+        fingerprint: &'static str,
+        run: fn(&Pool<Sqlite>) -> MigrationFuture<'_>,
+    },
+}
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: Step,
+    /// SQL that reverses `up`; `None` when the migration can't be undone
+    /// (restore the pre-migration snapshot instead).
+    pub down: Option<&'static str>,
+}
+
+impl Migration {
+    /// Hex SHA-256 over the version, name and up source.
+    pub fn checksum(&self) -> String {
+        let source = match &self.up {
+            Step::Sql(sql) => *sql,
+            Step::Code { fingerprint, .. } => fingerprint,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(self.version.to_le_bytes());
+        hasher.update(self.name.as_bytes());
+        hasher.update(source.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+fn seed_system_card(pool: &Pool<Sqlite>) -> MigrationFuture<'_> {
+    Box::pin(crate::db::seed_system_card(pool))
+}
+
+fn correctness_v2_backfill(pool: &Pool<Sqlite>) -> MigrationFuture<'_> {
+    Box::pin(crate::db::run_v2_backfill(pool))
+}
+
+fn assignment_status_backfill(pool: &Pool<Sqlite>) -> MigrationFuture<'_> {
+    Box::pin(crate::db::run_assignment_status_backfill(pool))
+}
+
+/// Boards walked before 0011 have no timeline. Queue a full replay of each
+/// rather than walking them all during boot; the job workers write the
+/// events.
+fn board_status_events_backfill(pool: &Pool<Sqlite>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let boards: Vec<(String, String, i32)> = sqlx::query_as(
+            "SELECT user_id, deal_subfolder, deal_number FROM board_status",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::Migration(e.to_string()))?;
+        let jobs = boards
+            .into_iter()
+            .map(|(user_id, deal_subfolder, deal_number)| crate::jobs::Job::Board {
+                user_id,
+                deal_subfolder,
+                deal_number,
+                full_replay: true,
+            });
+        let queued = crate::jobs::enqueue_all(pool, jobs)
+            .await
+            .map_err(|e| DbError::Migration(e.to_string()))?;
+        tracing::info!("board_status_events backfill: queued {} board replays", queued);
+        Ok(())
+    })
+}
+
+/// Every migration, in order. Versions must be strictly increasing.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        up: Step::Sql(include_str!("../migrations/0001_baseline.sql")),
+        down: None,
+    },
+    Migration {
+        version: 2,
+        name: "browse_views",
+        up: Step::Sql(include_str!("../migrations/0002_browse_views.sql")),
+        down: Some(include_str!("../migrations/0002_browse_views.down.sql")),
+    },
+    Migration {
+        version: 3,
+        name: "seed_system_card",
+        up: Step::Code {
+            fingerprint: "seed 2/1 Intermediate as public system card, link to all users as primary",
+            run: seed_system_card,
+        },
+        down: None,
+    },
+    Migration {
+        version: 4,
+        name: "correctness_v2_backfill",
+        up: Step::Code {
+            fingerprint: "schema_meta:correctness_v2_backfill; recompute_board_history + recompute_student_summary",
+            run: correctness_v2_backfill,
+        },
+        down: None,
+    },
+    Migration {
+        version: 5,
+        name: "assignment_board_status_backfill",
+        up: Step::Code {
+            fingerprint: "schema_meta:assignment_board_status_backfill; recompute_assignment_boards",
+            run: assignment_status_backfill,
+        },
+        down: None,
+    },
+    Migration {
+        version: 6,
+        name: "teacher_credentials",
+        up: Step::Sql(include_str!("../migrations/0006_teacher_credentials.sql")),
+        down: Some(include_str!("../migrations/0006_teacher_credentials.down.sql")),
+    },
+    Migration {
+        version: 7,
+        name: "recompute_jobs",
+        up: Step::Sql(include_str!("../migrations/0007_recompute_jobs.sql")),
+        down: Some(include_str!("../migrations/0007_recompute_jobs.down.sql")),
+    },
+    Migration {
+        version: 8,
+        name: "board_fold_state",
+        up: Step::Sql(include_str!("../migrations/0008_board_fold_state.sql")),
+        down: Some(include_str!("../migrations/0008_board_fold_state.down.sql")),
+    },
+    Migration {
+        version: 9,
+        name: "rules_profiles",
+        up: Step::Sql(include_str!("../migrations/0009_rules_profiles.sql")),
+        down: Some(include_str!("../migrations/0009_rules_profiles.down.sql")),
+    },
+    Migration {
+        version: 10,
+        name: "lesson_catalog",
+        up: Step::Sql(include_str!("../migrations/0010_lesson_catalog.sql")),
+        down: Some(include_str!("../migrations/0010_lesson_catalog.down.sql")),
+    },
+    Migration {
+        version: 11,
+        name: "board_status_events",
+        up: Step::Sql(include_str!("../migrations/0011_board_status_events.sql")),
+        down: Some(include_str!("../migrations/0011_board_status_events.down.sql")),
+    },
+    Migration {
+        version: 12,
+        name: "board_status_events_backfill",
+        up: Step::Code {
+            fingerprint: "enqueue full-replay Board jobs for every board_status row",
+            run: board_status_events_backfill,
+        },
+        // The queued replays only rebuild derived rows; nothing to undo.
+        down: Some(""),
+    },
+    Migration {
+        version: 13,
+        name: "observation_schema_version",
+        up: Step::Sql(include_str!("../migrations/0013_observation_schema_version.sql")),
+        down: Some(include_str!("../migrations/0013_observation_schema_version.down.sql")),
+    },
+    Migration {
+        version: 14,
+        name: "observation_cipher_format",
+        up: Step::Sql(include_str!("../migrations/0014_observation_cipher_format.sql")),
+        down: Some(include_str!("../migrations/0014_observation_cipher_format.down.sql")),
+    },
+    Migration {
+        version: 15,
+        name: "key_rotations",
+        up: Step::Sql(include_str!("../migrations/0015_key_rotations.sql")),
+        down: Some(include_str!("../migrations/0015_key_rotations.down.sql")),
+    },
+    Migration {
+        version: 16,
+        name: "client_diagnostics",
+        up: Step::Sql(include_str!("../migrations/0016_client_diagnostics.sql")),
+        down: Some(include_str!("../migrations/0016_client_diagnostics.down.sql")),
+    },
+    Migration {
+        version: 17,
+        name: "problem_reports",
+        up: Step::Sql(include_str!("../migrations/0017_problem_reports.sql")),
+        down: Some(include_str!("../migrations/0017_problem_reports.down.sql")),
+    },
+    Migration {
+        version: 18,
+        name: "report_triage",
+        up: Step::Sql(include_str!("../migrations/0018_report_triage.sql")),
+        down: Some(include_str!("../migrations/0018_report_triage.down.sql")),
+    },
+    Migration {
+        version: 19,
+        name: "classroom_staff",
+        up: Step::Sql(include_str!("../migrations/0019_classroom_staff.sql")),
+        down: Some(include_str!("../migrations/0019_classroom_staff.down.sql")),
+    },
+    Migration {
+        version: 20,
+        name: "join_code_lifecycle",
+        up: Step::Sql(include_str!("../migrations/0020_join_code_lifecycle.sql")),
+        down: Some(include_str!("../migrations/0020_join_code_lifecycle.down.sql")),
+    },
+    Migration {
+        version: 21,
+        name: "classroom_invitations",
+        up: Step::Sql(include_str!("../migrations/0021_classroom_invitations.sql")),
+        down: Some(include_str!("../migrations/0021_classroom_invitations.down.sql")),
+    },
+    Migration {
+        version: 22,
+        name: "invitation_tokens",
+        up: Step::Sql(include_str!("../migrations/0022_invitation_tokens.sql")),
+        down: Some(include_str!("../migrations/0022_invitation_tokens.down.sql")),
+    },
+    Migration {
+        version: 23,
+        name: "email_verified",
+        up: Step::Sql(include_str!("../migrations/0023_email_verified.sql")),
+        down: Some(include_str!("../migrations/0023_email_verified.down.sql")),
+    },
+    Migration {
+        version: 24,
+        name: "handoff_keeper",
+        up: Step::Sql(include_str!("../migrations/0024_handoff_keeper.sql")),
+        down: Some(include_str!("../migrations/0024_handoff_keeper.down.sql")),
+    },
+];
+
+/// Columns the pre-migration `run_migrations` added with `ALTER TABLE` over
+/// time. An old database may be missing any of them; they're folded into
+/// the baseline CREATEs for fresh databases.
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("viewers", "recovery_encrypted_private_key", "TEXT"),
+    ("users", "recovery_encrypted_key", "TEXT"),
+    ("users", "role", "TEXT NOT NULL DEFAULT 'student'"),
+    ("users", "teacher_terms_accepted_at", "TEXT"),
+    ("users", "attention_cleared_at", "TEXT"),
+    ("users", "activity_cleared_at", "TEXT"),
+    ("users", "name_corrected_at", "TEXT"),
+    ("users", "host_code", "TEXT"),
+    ("users", "invite_code", "TEXT"),
+    ("recovery_tokens", "recovery_code_hash", "TEXT"),
+    ("observations", "board_result", "TEXT"),
+    ("observations", "status", "TEXT"),
+    ("observations", "wilderness", "TEXT"),
+    ("observations", "exercise_id", "TEXT"),
+    ("observations", "assignment_id", "TEXT"),
+    ("observations", "jungle", "INTEGER NOT NULL DEFAULT 0"),
+    ("observations", "time_taken_ms", "INTEGER"),
+    ("board_status", "wilderness", "TEXT NOT NULL DEFAULT 'Tame'"),
+    ("board_status", "last_error_date", "TEXT"),
+    ("board_status", "star_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("board_status", "max_stars", "INTEGER NOT NULL DEFAULT 0"),
+    ("board_status", "last_star_update", "TEXT"),
+    ("board_status", "wild_achievement", "TEXT"),
+    ("exercises", "deleted_at", "TEXT"),
+];
+
+fn migration_err(e: impl std::fmt::Display) -> DbError {
+    DbError::Migration(e.to_string())
+}
+
+async fn table_exists(pool: &Pool<Sqlite>, table: &str) -> Result<bool, DbError> {
+    sqlx::query_scalar("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(table)
+        .fetch_one(pool)
+        .await
+        .map_err(migration_err)
+}
+
+/// Bring a pre-migration database up to the baseline's column set. No-op
+/// for fresh databases and for ones that already track migrations.
+async fn adopt_legacy_db(pool: &Pool<Sqlite>) -> Result<(), DbError> {
+    if table_exists(pool, "schema_migrations").await? || !table_exists(pool, "users").await? {
+        return Ok(());
+    }
+    tracing::info!("Adopting pre-migration database into schema_migrations");
+    for (table, column, column_def) in LEGACY_COLUMNS {
+        if !table_exists(pool, table).await? {
+            continue; // the baseline will create it with every column
+        }
+        let exists: bool = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?",
+            table
+        ))
+        .bind(column)
+        .fetch_one(pool)
+        .await
+        .map_err(migration_err)?;
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, column_def
+            ))
+            .execute(pool)
+            .await
+            .map_err(migration_err)?;
+            tracing::info!("Added column {}.{}", table, column);
+        }
+    }
+    Ok(())
+}
+
+async fn ensure_migrations_table(pool: &Pool<Sqlite>) -> Result<(), DbError> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version      INTEGER PRIMARY KEY,
+            name         TEXT NOT NULL,
+            checksum     TEXT NOT NULL,
+            applied_at   TEXT NOT NULL,
+            execution_ms INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(migration_err)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: String,
+}
+
+async fn applied_migrations(pool: &Pool<Sqlite>) -> Result<Vec<AppliedMigration>, DbError> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(vec![]);
+    }
+    sqlx::query_as(
+        "SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(migration_err)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationState {
+    Applied {
+        applied_at: String,
+    },
+    Pending,
+    /// Recorded checksum differs from the source compiled into this binary.
+    ChecksumMismatch {
+        recorded: String,
+    },
+    /// Recorded in the database but unknown to this binary (a newer build
+    /// ran against this file).
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub state: MigrationState,
+}
+
+/// Compare the registry against `schema_migrations`. Read-only.
+pub async fn status(pool: &Pool<Sqlite>) -> Result<Vec<MigrationStatus>, DbError> {
+    let applied = applied_migrations(pool).await?;
+    let mut out: Vec<MigrationStatus> = MIGRATIONS
+        .iter()
+        .map(|m| {
+            let checksum = m.checksum();
+            let state = match applied.iter().find(|a| a.version == m.version) {
+                None => MigrationState::Pending,
+                Some(a) if a.checksum != checksum => MigrationState::ChecksumMismatch {
+                    recorded: a.checksum.clone(),
+                },
+                Some(a) => MigrationState::Applied {
+                    applied_at: a.applied_at.clone(),
+                },
+            };
+            MigrationStatus {
+                version: m.version,
+                name: m.name.to_string(),
+                checksum,
+                state,
+            }
+        })
+        .collect();
+    for a in applied {
+        if !MIGRATIONS.iter().any(|m| m.version == a.version) {
+            out.push(MigrationStatus {
+                version: a.version,
+                name: a.name,
+                checksum: a.checksum,
+                state: MigrationState::Unknown,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Refuse to run against a database whose history doesn't match this
+/// binary.
+fn verify(statuses: &[MigrationStatus]) -> Result<(), DbError> {
+    for s in statuses {
+        match &s.state {
+            MigrationState::ChecksumMismatch { recorded } => {
+                return Err(DbError::Migration(format!(
+                    "migration {} ({}) was applied with checksum {} but this build has {}; \
+                     shipped migrations must not be edited",
+                    s.version, s.name, recorded, s.checksum
+                )));
+            }
+            MigrationState::Unknown => {
+                return Err(DbError::Migration(format!(
+                    "database has migration {} ({}) which this build doesn't know; \
+                     refusing to run an older binary against a newer schema",
+                    s.version, s.name
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+async fn apply(pool: &Pool<Sqlite>, migration: &Migration) -> Result<(), DbError> {
+    let started = std::time::Instant::now();
+    tracing::info!(
+        "Applying migration {} ({})",
+        migration.version,
+        migration.name
+    );
+
+    let record = |tx_ms: i64| {
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, name, checksum, applied_at, execution_ms) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(tx_ms)
+    };
+
+    match &migration.up {
+        Step::Sql(sql) => {
+            let mut tx = pool.begin().await.map_err(migration_err)?;
+            sqlx::raw_sql(sql).execute(&mut *tx).await.map_err(|e| {
+                DbError::Migration(format!("{} ({}): {}", migration.version, migration.name, e))
+            })?;
+            record(started.elapsed().as_millis() as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(migration_err)?;
+            tx.commit().await.map_err(migration_err)?;
+        }
+        Step::Code { run, .. } => {
+            run(pool).await?;
+            record(started.elapsed().as_millis() as i64)
+                .execute(pool)
+                .await
+                .map_err(migration_err)?;
+        }
+    }
+
+    tracing::info!(
+        "Migration {} ({}) applied in {:.2}s",
+        migration.version,
+        migration.name,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// The versions that [`migrate`] would apply, in order.
+pub fn pending(statuses: &[MigrationStatus]) -> Vec<i64> {
+    statuses
+        .iter()
+        .filter(|s| s.state == MigrationState::Pending)
+        .map(|s| s.version)
+        .collect()
+}
+
+/// Apply every pending migration. When `snapshot` is given and there is
+/// something to apply to an existing database, the file is first copied
+/// there with `VACUUM INTO`. Returns the number applied.
+pub async fn migrate(pool: &Pool<Sqlite>, snapshot: Option<PathBuf>) -> Result<usize, DbError> {
+    adopt_legacy_db(pool).await?;
+    let statuses = status(pool).await?;
+    verify(&statuses)?;
+
+    let todo = pending(&statuses);
+    if todo.is_empty() {
+        tracing::debug!("Schema up to date");
+        return Ok(0);
+    }
+
+    if let Some(path) = snapshot {
+        if table_exists(pool, "users").await? {
+            write_snapshot(pool, &path).await?;
+        }
+    }
+
+    ensure_migrations_table(pool).await?;
+    for migration in MIGRATIONS.iter().filter(|m| todo.contains(&m.version)) {
+        apply(pool, migration).await?;
+    }
+    tracing::info!("Database migrations completed successfully");
+    Ok(todo.len())
+}
+
+/// Undo applied migrations newer than `target`, newest first, using their
+/// `down` SQL. Stops before touching anything if one of them has no down.
+pub async fn rollback_to(pool: &Pool<Sqlite>, target: i64) -> Result<Vec<i64>, DbError> {
+    let statuses = status(pool).await?;
+    verify(&statuses)?;
+
+    let mut to_undo: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .filter(|m| {
+            m.version > target
+                && statuses.iter().any(|s| {
+                    s.version == m.version && matches!(s.state, MigrationState::Applied { .. })
+                })
+        })
+        .collect();
+    to_undo.sort_by_key(|m| std::cmp::Reverse(m.version));
+
+    if let Some(m) = to_undo.iter().find(|m| m.down.is_none()) {
+        return Err(DbError::Migration(format!(
+            "migration {} ({}) has no down migration; restore the pre-migration snapshot instead",
+            m.version, m.name
+        )));
+    }
+
+    let mut undone = Vec::new();
+    for m in to_undo {
+        let mut tx = pool.begin().await.map_err(migration_err)?;
+        sqlx::raw_sql(m.down.unwrap_or_default())
+            .execute(&mut *tx)
+            .await
+            .map_err(migration_err)?;
+        sqlx::query("DELETE FROM schema_migrations WHERE version = ?")
+            .bind(m.version)
+            .execute(&mut *tx)
+            .await
+            .map_err(migration_err)?;
+        tx.commit().await.map_err(migration_err)?;
+        tracing::info!("Rolled back migration {} ({})", m.version, m.name);
+        undone.push(m.version);
+    }
+    Ok(undone)
+}
+
+/// Where to put the pre-migration copy of a file database, or `None` for
+/// in-memory databases: `<db file>.pre-v<latest>-<UTC timestamp>.bak`.
+pub fn snapshot_path(database_url: &str) -> Option<PathBuf> {
+    let path = database_url
+        .trim_start_matches("sqlite://")
+        .trim_start_matches("sqlite:")
+        .split('?')
+        .next()
+        .unwrap_or("");
+    if path.is_empty() || path.contains(":memory:") {
+        return None;
+    }
+    let latest = MIGRATIONS.last().map(|m| m.version).unwrap_or(0);
+    Some(PathBuf::from(format!(
+        "{}.pre-v{}-{}.bak",
+        path,
+        latest,
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    )))
+}
+
+async fn write_snapshot(pool: &Pool<Sqlite>, path: &std::path::Path) -> Result<(), DbError> {
+    let target = path.to_string_lossy().replace('\'', "''");
+    sqlx::query(&format!("VACUUM INTO '{}'", target))
+        .execute(pool)
+        .await
+        .map_err(|e| DbError::Migration(format!("pre-migration snapshot failed: {}", e)))?;
+    tracing::info!("Wrote pre-migration snapshot to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn memory_pool() -> Pool<Sqlite> {
+        // One connection: every connection to :memory: is its own database.
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn versions_strictly_increase() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(
+                pair[0].version < pair[1].version,
+                "{} then {}",
+                pair[0].name,
+                pair[1].name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fresh_database_applies_everything_once() {
+        let pool = memory_pool().await;
+        assert_eq!(migrate(&pool, None).await.unwrap(), MIGRATIONS.len());
+        assert_eq!(migrate(&pool, None).await.unwrap(), 0);
+        assert!(pending(&status(&pool).await.unwrap()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn edited_migration_is_refused() {
+        let pool = memory_pool().await;
+        migrate(&pool, None).await.unwrap();
+        sqlx::query("UPDATE schema_migrations SET checksum = 'tampered' WHERE version = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            migrate(&pool, None).await,
+            Err(DbError::Migration(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn legacy_database_is_adopted() {
+        let pool = memory_pool().await;
+        // An old file: users without the later columns, no schema_migrations.
+        sqlx::query(
+            "CREATE TABLE users (id TEXT PRIMARY KEY, first_name TEXT NOT NULL, last_name TEXT NOT NULL, \
+             email TEXT NOT NULL UNIQUE, classroom TEXT, data_consent INTEGER NOT NULL DEFAULT 1, \
+             created_at TEXT NOT NULL, updated_at TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        migrate(&pool, None).await.unwrap();
+        let has_role: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('users') WHERE name = 'role'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(has_role);
+    }
+
+    #[tokio::test]
+    async fn rollback_stops_at_irreversible_migrations() {
+        let pool = memory_pool().await;
+        migrate(&pool, None).await.unwrap();
+        let latest = MIGRATIONS.last().unwrap().version;
+        assert_eq!(rollback_to(&pool, latest - 1).await.unwrap(), vec![latest]);
+        assert_eq!(pending(&status(&pool).await.unwrap()), vec![latest]);
+        assert!(rollback_to(&pool, 0).await.is_err());
+    }
+}
@@ -1,26 +1,11 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
 
-/// Assignment stored in the database
-#[derive(Debug, Clone, FromRow, Serialize)]
-pub struct Assignment {
-    pub id: String,
-    pub exercise_id: String,
-    pub classroom_id: Option<String>,
-    pub student_id: Option<String>,
-    pub assigned_by: String,
-    pub assigned_at: String,
-    pub due_at: Option<String>,
-    pub sort_order: Option<i32>,
-}
-
-/// Request to create an assignment
+/// Request to create an assignment (assigned_by is the caller)
 #[derive(Debug, Deserialize)]
 pub struct CreateAssignmentRequest {
     pub exercise_id: String,
     pub classroom_id: Option<String>,
     pub student_id: Option<String>,
-    pub assigned_by: String,
     pub due_at: Option<String>,
     pub sort_order: Option<i32>,
 }
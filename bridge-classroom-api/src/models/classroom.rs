@@ -1,44 +1,82 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-/// Classroom stored in the database
-#[derive(Debug, Clone, FromRow, Serialize)]
-pub struct Classroom {
-    pub id: String,
+use crate::policy::StaffRole;
+
+/// Request to create a classroom (the caller becomes its teacher)
+#[derive(Debug, Deserialize)]
+pub struct CreateClassroomRequest {
     pub name: String,
     pub description: Option<String>,
-    pub teacher_id: String,
-    pub join_code: String,
-    pub created_at: String,
+    #[serde(flatten)]
+    pub join_settings: JoinSettings,
 }
 
-/// Classroom member stored in the database
-#[derive(Debug, Clone, FromRow, Serialize)]
-pub struct ClassroomMember {
-    pub classroom_id: String,
-    pub student_id: String,
-    pub joined_at: String,
+/// Who may join with the classroom's join code. The defaults (no expiry,
+/// no cap, no approval) are how every classroom worked before.
+#[derive(Debug, Clone, Default, FromRow, Deserialize, Serialize)]
+pub struct JoinSettings {
+    /// RFC 3339; the join code stops working after this. None never expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_code_expires_at: Option<String>,
+    /// Most members the classroom takes. None is unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_members: Option<i64>,
+    /// Joining creates a join request a teacher must approve.
+    #[serde(default)]
+    pub requires_approval: bool,
 }
 
-/// Request to create a classroom
-#[derive(Debug, Deserialize)]
-pub struct CreateClassroomRequest {
-    pub teacher_id: String,
-    pub name: String,
-    pub description: Option<String>,
+/// Request to replace the join code (the old one stops working at once)
+#[derive(Debug, Default, Deserialize)]
+pub struct RegenerateJoinCodeRequest {
+    /// Expiry for the new code; None never expires.
+    #[serde(default)]
+    pub join_code_expires_at: Option<String>,
+}
+
+/// Response after changing the join code or its settings
+#[derive(Debug, Serialize)]
+pub struct JoinCodeResponse {
+    pub success: bool,
+    pub join_code: String,
+    #[serde(flatten)]
+    pub join_settings: JoinSettings,
 }
 
-/// Request to join a classroom
+/// Request to join a classroom (the caller joins)
 #[derive(Debug, Deserialize)]
 pub struct JoinClassroomRequest {
-    pub student_id: String,
+    /// Sharing-grant payload for the owner's viewer (`teacher_viewer_id`).
     pub encrypted_grant_payload: String,
+    /// Payloads for the other staff viewers in [`JoinInfo::staff_viewers`],
+    /// keyed by viewer id.
+    #[serde(default)]
+    pub staff_grant_payloads: HashMap<String, String>,
+    /// Token from a roster invitation link for this classroom; it stands in
+    /// for a teacher's approval.
+    #[serde(default)]
+    pub invitation_token: Option<String>,
 }
 
-/// Request to leave a classroom
+/// Request to share the caller's key with a classroom's staff
 #[derive(Debug, Deserialize)]
-pub struct LeaveClassroomRequest {
-    pub student_id: String,
+pub struct ShareWithStaffRequest {
+    /// Sharing-grant payloads keyed by viewer id, for viewers listed in
+    /// [`UnsharedClassroom::viewers`].
+    pub grant_payloads: HashMap<String, String>,
+}
+
+/// A classroom the student belongs to whose staff include viewers the
+/// student has never shared their key with: they joined at registration
+/// through a roster invitation, or the staff were added after they joined
+#[derive(Debug, Serialize)]
+pub struct UnsharedClassroom {
+    pub classroom_id: String,
+    pub classroom_name: String,
+    pub viewers: Vec<StaffViewer>,
 }
 
 /// Classroom info with member count (for listings)
@@ -52,6 +90,10 @@ pub struct ClassroomInfo {
     pub join_code: String,
     pub created_at: String,
     pub member_count: i64,
+    /// The listed teacher's role on this classroom's staff.
+    pub role: StaffRole,
+    #[serde(flatten)]
+    pub join_settings: JoinSettings,
 }
 
 /// Classroom detail with member roster
@@ -64,7 +106,10 @@ pub struct ClassroomDetail {
     pub teacher_id: String,
     pub join_code: String,
     pub created_at: String,
+    #[serde(flatten)]
+    pub join_settings: JoinSettings,
     pub members: Vec<MemberInfo>,
+    pub staff: Vec<StaffInfo>,
 }
 
 /// Member info for roster display
@@ -77,6 +122,82 @@ pub struct MemberInfo {
     pub joined_at: String,
 }
 
+/// A student waiting for a teacher to approve their join
+#[derive(Debug, Clone, Serialize)]
+pub struct JoinRequestInfo {
+    pub student_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub requested_at: String,
+}
+
+/// Response containing a classroom's pending join requests
+#[derive(Debug, Serialize)]
+pub struct JoinRequestListResponse {
+    pub success: bool,
+    pub requests: Vec<JoinRequestInfo>,
+}
+
+/// A roster invitation and how far it has got. `status` is one of
+/// `pending` (not emailed yet), `sent`, `failed`, `accepted` or `revoked`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct InvitationInfo {
+    pub id: String,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub status: String,
+    pub created_at: String,
+    pub sent_at: Option<String>,
+    pub accepted_at: Option<String>,
+    pub accepted_user_id: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// A roster line that was skipped, by 1-based line number
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterRowError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// Response to a roster upload: the invitations it created or re-sent, and
+/// the lines it couldn't use
+#[derive(Debug, Serialize)]
+pub struct RosterImportResponse {
+    pub success: bool,
+    pub invitations: Vec<InvitationInfo>,
+    pub errors: Vec<RosterRowError>,
+}
+
+/// Response containing a classroom's roster invitations
+#[derive(Debug, Serialize)]
+pub struct InvitationListResponse {
+    pub success: bool,
+    pub invitations: Vec<InvitationInfo>,
+}
+
+/// Staff member info for the classroom's staff list
+#[derive(Debug, Clone, Serialize)]
+pub struct StaffInfo {
+    pub user_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub role: StaffRole,
+    pub added_at: String,
+}
+
+/// A co-teacher or assistant the joining student also shares their key with
+#[derive(Debug, Serialize)]
+pub struct StaffViewer {
+    pub name: String,
+    pub role: StaffRole,
+    pub viewer_id: String,
+    pub public_key: String,
+}
+
 /// Public join info (no auth required)
 #[derive(Debug, Serialize)]
 pub struct JoinInfo {
@@ -88,6 +209,10 @@ pub struct JoinInfo {
     pub teacher_viewer_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub teacher_public_key: Option<String>,
+    /// Staff other than the owner who have a viewer key.
+    pub staff_viewers: Vec<StaffViewer>,
+    /// Joining only files a request until a teacher approves it.
+    pub requires_approval: bool,
 }
 
 /// Response after creating a classroom
@@ -111,12 +236,27 @@ pub struct ClassroomDetailResponse {
     pub classroom: ClassroomDetail,
 }
 
+/// Request to add a staff member or change their role
+#[derive(Debug, Deserialize)]
+pub struct SetStaffRequest {
+    pub role: StaffRole,
+}
+
+/// Response containing a classroom's staff
+#[derive(Debug, Serialize)]
+pub struct StaffListResponse {
+    pub success: bool,
+    pub staff: Vec<StaffInfo>,
+}
+
 /// Response after joining a classroom
 #[derive(Debug, Serialize)]
 pub struct JoinClassroomResponse {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub classroom_id: Option<String>,
+    /// The join is waiting for a teacher's approval.
+    pub pending: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
@@ -129,12 +269,35 @@ pub struct ClassroomActionResponse {
     pub error: Option<String>,
 }
 
-/// Helper to generate a join code in BRG-XXXX format
+/// Join-code alphabet: uppercase alphanumerics minus 0/O, 1/I/L, since
+/// codes are read aloud and typed in.
+const JOIN_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// Helper to generate a join code in BRG-XXXX-XXXX format (31^8 values, so
+/// neither collisions nor guessing are practical). Callers still retry on
+/// the UNIQUE constraint.
 pub fn generate_join_code() -> String {
     use ring::rand::{SecureRandom, SystemRandom};
     let rng = SystemRandom::new();
-    let mut buf = [0u8; 2];
-    rng.fill(&mut buf).expect("Failed to generate random bytes");
-    let num = u16::from_le_bytes(buf) % 10000;
-    format!("BRG-{:04}", num)
+    // Bytes at or past the last whole multiple of the alphabet are redrawn,
+    // so every character is equally likely.
+    let limit = (256 / JOIN_CODE_ALPHABET.len() * JOIN_CODE_ALPHABET.len()) as u8;
+    let mut chars = String::with_capacity(8);
+    let mut buf = [0u8; 16];
+    while chars.len() < 8 {
+        rng.fill(&mut buf).expect("Failed to generate random bytes");
+        chars.extend(
+            buf.iter()
+                .filter(|b| **b < limit)
+                .take(8 - chars.len())
+                .map(|b| JOIN_CODE_ALPHABET[*b as usize % JOIN_CODE_ALPHABET.len()] as char),
+        );
+    }
+    format!("BRG-{}-{}", &chars[..4], &chars[4..])
+}
+
+/// Join codes are matched case-insensitively and ignoring surrounding
+/// whitespace. Older BRG-NNNN codes are digits, so they are unaffected.
+pub fn normalize_join_code(code: &str) -> String {
+    code.trim().to_uppercase()
 }
@@ -16,17 +16,6 @@ pub struct ConventionCard {
     pub updated_at: String,
 }
 
-/// User-card link stored in the database
-#[derive(Debug, Clone, FromRow, Serialize)]
-pub struct UserConventionCard {
-    pub id: String,
-    pub user_id: String,
-    pub card_id: String,
-    pub is_primary: bool,
-    pub label: Option<String>,
-    pub linked_at: String,
-}
-
 /// Request to create a new convention card
 #[derive(Debug, Deserialize)]
 pub struct CreateConventionCardRequest {
@@ -34,9 +23,6 @@ pub struct CreateConventionCardRequest {
     pub description: Option<String>,
     pub card_data: serde_json::Value, // Accept JSON object
     pub visibility: Option<String>,   // private, shared, public
-    /// User performing the create. Required. Public-visibility cards
-    /// further require this user's role to be 'admin'.
-    pub acting_user_id: String,
 }
 
 /// Response after creating a convention card
@@ -46,11 +32,10 @@ pub struct CreateConventionCardResponse {
     pub card_id: String,
 }
 
-/// Request to update an existing convention card. All fields except
-/// `acting_user_id` are optional — only the provided ones are written.
+/// Request to update an existing convention card. All fields are
+/// optional — only the provided ones are written.
 #[derive(Debug, Deserialize)]
 pub struct UpdateConventionCardRequest {
-    pub acting_user_id: String,
     pub name: Option<String>,
     pub description: Option<String>,
     pub card_data: Option<serde_json::Value>,
@@ -69,11 +54,6 @@ pub struct LinkCardRequest {
     pub card_id: String,
     pub is_primary: Option<bool>,
     pub label: Option<String>,
-    /// User performing the link. Required when linking a private card —
-    /// must be the card's owner or an admin. Omittable when linking a
-    /// public card.
-    #[serde(default)]
-    pub acting_user_id: Option<String>,
 }
 
 /// Response after linking a card
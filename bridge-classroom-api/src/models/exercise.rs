@@ -1,18 +1,6 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-/// Exercise stored in the database
-#[derive(Debug, Clone, FromRow, Serialize)]
-pub struct Exercise {
-    pub id: String,
-    pub name: String,
-    pub description: Option<String>,
-    pub created_by: Option<String>,
-    pub curriculum_path: Option<String>,
-    pub visibility: String,
-    pub created_at: String,
-}
-
 /// Exercise board junction row
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct ExerciseBoard {
@@ -29,7 +17,6 @@ pub struct ExerciseBoard {
 pub struct CreateExerciseRequest {
     pub name: String,
     pub description: Option<String>,
-    pub created_by: Option<String>,
     pub curriculum_path: Option<String>,
     pub visibility: Option<String>,
     pub boards: Vec<BoardEntry>,
@@ -51,16 +38,6 @@ pub struct UpdateExerciseRequest {
     pub description: Option<String>,
     pub visibility: Option<String>,
     pub boards: Option<Vec<BoardEntry>>,
-    /// Caller's user_id. When the exercise has a `created_by` owner,
-    /// the actor must match (or be unset on the row for legacy
-    /// open-edit behaviour). Issue #15.
-    pub actor_user_id: Option<String>,
-}
-
-/// Query parameters for the DELETE endpoint (issue #15 soft-delete).
-#[derive(Debug, Deserialize)]
-pub struct DeleteExerciseQuery {
-    pub actor_user_id: Option<String>,
 }
 
 /// Query parameters for listing exercises
@@ -17,10 +17,9 @@ pub struct SharingGrant {
     pub revoked_at: Option<String>,
 }
 
-/// Request to create a sharing grant
+/// Request to create a sharing grant (grantor is the session caller)
 #[derive(Debug, Deserialize)]
 pub struct CreateGrantRequest {
-    pub grantor_id: String,
     pub grantee_id: String,
     pub encrypted_payload: String,
     pub expires_at: Option<String>,
@@ -64,12 +63,12 @@ pub struct RevokeGrantResponse {
 
 impl SharingGrant {
     /// Create a new grant from a request
-    pub fn from_request(req: &CreateGrantRequest) -> Self {
+    pub fn from_request(grantor_id: &str, req: &CreateGrantRequest) -> Self {
         let now = Utc::now().to_rfc3339();
 
         SharingGrant {
             id: uuid::Uuid::new_v4().to_string(),
-            grantor_id: req.grantor_id.clone(),
+            grantor_id: grantor_id.to_string(),
             grantee_id: req.grantee_id.clone(),
             encrypted_payload: req.encrypted_payload.clone(),
             granted_at: now,
@@ -1,6 +1,11 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::obs_crypto::{CIPHER_FORMAT_AAD, CIPHER_FORMAT_LEGACY};
+use crate::observation_schema;
+
 /// Observation stored in the database
 /// encrypted_data is AES-256-GCM encrypted with the student's secret key
 /// Viewers get the key through sharing grants, not per-observation key blobs
@@ -29,6 +34,11 @@ pub struct Observation {
     /// Total time spent on the board, ms. Lifted from the encrypted
     /// blob so the analytics path doesn't need decryption (issue #7).
     pub time_taken_ms: Option<i64>,
+    /// Version of the encrypted payload (`observation_schema.rs`); None
+    /// for rows from clients that predate it, which wrote version 1.
+    pub schema_version: Option<i64>,
+    /// How `encrypted_data` was sealed: `obs_crypto::CIPHER_FORMAT_*`.
+    pub cipher_format: i64,
 }
 
 /// Metadata-only observation (for dashboard queries)
@@ -96,12 +106,130 @@ pub struct ObservationMetadataInput {
     /// Issue #7.
     #[serde(default)]
     pub time_taken_ms: Option<i64>,
+    /// Version of the encrypted payload's schema. Clients that don't
+    /// send it wrote version 1.
+    #[serde(default)]
+    pub schema_version: Option<i64>,
+    /// `obs_crypto::CIPHER_FORMAT_AAD` when the client sealed the payload
+    /// with this metadata as associated data; legacy when absent.
+    #[serde(default)]
+    pub cipher_format: Option<i64>,
+}
+
+impl EncryptedObservation {
+    /// Check the cleartext metadata the server files and derives from, so
+    /// a malformed row is turned away before it reaches the board walk.
+    pub fn validate(&self) -> Result<(), String> {
+        let m = &self.metadata;
+        if m.observation_id.trim().is_empty() || m.observation_id.len() > 128 {
+            return Err("observation_id must be 1-128 characters".to_string());
+        }
+        if m.timestamp.parse::<chrono::DateTime<chrono::Utc>>().is_err() {
+            return Err(format!("timestamp {:?} is not RFC 3339", m.timestamp));
+        }
+        if m.skill_path.trim().is_empty() {
+            return Err("skill_path is required".to_string());
+        }
+        if self.encrypted_data.is_empty() || self.iv.is_empty() {
+            return Err("encrypted_data and iv are required".to_string());
+        }
+        match (&m.deal_subfolder, m.deal_number) {
+            (Some(subfolder), Some(n)) if !subfolder.is_empty() && n > 0 => {}
+            (None, None) => {}
+            _ => {
+                return Err(
+                    "deal_subfolder and a positive deal_number go together".to_string(),
+                )
+            }
+        }
+        if let Some(result) = m.board_result.as_deref() {
+            if !matches!(result, "" | "correct" | "corrected" | "failed") {
+                return Err(format!("board_result {:?} is not recognised", result));
+            }
+        }
+        if m.time_taken_ms.is_some_and(|ms| ms < 0) {
+            return Err("time_taken_ms must not be negative".to_string());
+        }
+        if let Some(version) = m.schema_version {
+            if !observation_schema::is_known_version(version) {
+                return Err(format!("schema_version {} is not supported", version));
+            }
+        }
+        if let Some(format) = m.cipher_format {
+            if !matches!(format, CIPHER_FORMAT_LEGACY | CIPHER_FORMAT_AAD) {
+                return Err(format!("cipher_format {} is not supported", format));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Request to submit observations
 #[derive(Debug, Deserialize)]
 pub struct SubmitObservationsRequest {
     pub observations: Vec<EncryptedObservation>,
+    /// Store the whole batch or none of it. By default each observation
+    /// stands alone and the valid ones are kept.
+    #[serde(default)]
+    pub all_or_nothing: bool,
+}
+
+/// What happened to one submitted observation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmitOutcome {
+    /// Inserted as a new row.
+    Stored,
+    /// Replaced an earlier submission with the same id.
+    Updated,
+    Rejected,
+}
+
+/// Why an observation was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectCode {
+    /// Metadata failed validation; resending it unchanged won't help.
+    InvalidMetadata,
+    /// `user_id` isn't the session's user.
+    UserMismatch,
+    /// The id is already used by another user's observation.
+    IdConflict,
+    /// The same id appears earlier in this batch.
+    DuplicateInBatch,
+    /// The database refused the row; safe to retry.
+    StorageError,
+    /// Valid, but not stored because another observation failed an
+    /// `all_or_nothing` batch; resend with the batch.
+    BatchRejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ObservationResult {
+    pub observation_id: String,
+    pub outcome: SubmitOutcome,
+    pub code: Option<RejectCode>,
+    pub message: Option<String>,
+}
+
+impl ObservationResult {
+    pub fn accepted(observation_id: &str, outcome: SubmitOutcome) -> Self {
+        Self {
+            observation_id: observation_id.to_string(),
+            outcome,
+            code: None,
+            message: None,
+        }
+    }
+
+    pub fn rejected(observation_id: &str, code: RejectCode, message: impl Into<String>) -> Self {
+        Self {
+            observation_id: observation_id.to_string(),
+            outcome: SubmitOutcome::Rejected,
+            code: Some(code),
+            message: Some(message.into()),
+        }
+    }
 }
 
 /// Response after submitting observations
@@ -109,7 +237,12 @@ pub struct SubmitObservationsRequest {
 pub struct SubmitObservationsResponse {
     pub received: usize,
     pub stored: usize,
+    /// Messages for the rejected observations (see `results`).
     pub errors: Vec<String>,
+    /// One entry per submitted observation, in request order.
+    pub results: Vec<ObservationResult>,
+    /// False when an `all_or_nothing` batch was rolled back.
+    pub committed: bool,
 }
 
 /// Query parameters for fetching observations
@@ -123,6 +256,68 @@ pub struct ObservationQuery {
     pub to: Option<String>,
     pub limit: Option<i32>,
     pub offset: Option<i32>,
+    /// `next_cursor` from the previous page; replaces `offset`.
+    pub cursor: Option<String>,
+}
+
+/// Keyset position in the newest-first `(timestamp, id)` order the
+/// observation listings use. Travels as an opaque URL-safe token so the
+/// encoding can change without breaking clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObservationCursor {
+    pub timestamp: String,
+    pub id: String,
+}
+
+impl ObservationCursor {
+    /// The cursor that resumes after `obs`.
+    pub fn after(timestamp: &str, id: &str) -> Self {
+        Self {
+            timestamp: timestamp.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| "Invalid cursor".to_string())
+    }
+}
+
+/// Output format for `GET /api/observations/export`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One full `Observation` (ciphertext included) per line.
+    #[default]
+    Ndjson,
+    /// The cleartext metadata columns only.
+    Csv,
+}
+
+/// Query parameters for the streaming export: the `ObservationQuery`
+/// filters, the format, and an optional keyset cursor to resume after the
+/// last `(timestamp, id)` a previous export wrote.
+#[derive(Debug, Deserialize)]
+pub struct ObservationExportQuery {
+    pub user_id: Option<String>,
+    pub classroom: Option<String>,
+    pub skill_path: Option<String>,
+    pub correct: Option<bool>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+    pub after_timestamp: Option<String>,
+    pub after_id: Option<String>,
 }
 
 impl Default for ObservationQuery {
@@ -136,6 +331,7 @@ impl Default for ObservationQuery {
             to: None,
             limit: Some(100),
             offset: Some(0),
+            cursor: None,
         }
     }
 }
@@ -147,6 +343,8 @@ pub struct ObservationsResponse {
     pub total: i64,
     pub limit: i32,
     pub offset: i32,
+    /// Pass as `cursor` to fetch the next page; `None` on the last page.
+    pub next_cursor: Option<String>,
 }
 
 /// Response containing only metadata
@@ -154,6 +352,8 @@ pub struct ObservationsResponse {
 pub struct ObservationsMetadataResponse {
     pub observations: Vec<ObservationMetadata>,
     pub total: i64,
+    /// Set when the request paged (`limit` or `cursor`) and more rows follow.
+    pub next_cursor: Option<String>,
 }
 
 impl Observation {
@@ -183,6 +383,8 @@ impl Observation {
             assignment_id: enc.metadata.assignment_id,
             jungle: enc.metadata.jungle,
             time_taken_ms: enc.metadata.time_taken_ms,
+            schema_version: enc.metadata.schema_version,
+            cipher_format: enc.metadata.cipher_format.unwrap_or(CIPHER_FORMAT_LEGACY),
         }
     }
 }
@@ -17,6 +17,9 @@ pub struct User {
     pub role: String,
     pub teacher_terms_accepted_at: Option<String>,
     pub name_corrected_at: Option<String>,
+    /// When a key rotation last completed; devices still holding the old
+    /// key must recover the new one by email.
+    pub key_rotated_at: Option<String>,
 }
 
 /// Request to create or update a user
@@ -32,6 +35,9 @@ pub struct CreateUserRequest {
     pub admin_grant: Option<CreateGrantPayload>,
     /// Optional: secret key for email recovery (server encrypts before storing)
     pub secret_key: Option<String>,
+    /// Optional: the token from a roster invitation link; a new account
+    /// registered with it joins the inviting classroom (routes/roster.rs)
+    pub invitation_token: Option<String>,
 }
 
 /// Payload for creating a sharing grant (embedded in user registration)
@@ -49,6 +55,11 @@ pub struct CreateUserResponse {
     /// True if this email was already registered (user should use recovery flow)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub existing_user: Option<bool>,
+    /// Session token for the registered user (see session.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_expires_at: Option<i64>,
 }
 
 /// User info for teacher dashboard
@@ -63,6 +74,8 @@ pub struct UserInfo {
     pub created_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name_corrected_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_rotated_at: Option<String>,
 }
 
 /// Response containing list of users
@@ -71,10 +84,9 @@ pub struct UsersListResponse {
     pub users: Vec<UserInfo>,
 }
 
-/// Request to upgrade a user's role
+/// Request to upgrade the caller's role
 #[derive(Debug, Deserialize)]
 pub struct RoleUpgradeRequest {
-    pub user_id: String,
     pub action: String,
 }
 
@@ -85,6 +97,9 @@ pub struct RoleUpgradeResponse {
     pub role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Re-minted session carrying the new role
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>,
 }
 
 impl User {
@@ -105,6 +120,7 @@ impl User {
             role: "student".to_string(),
             teacher_terms_accepted_at: None,
             name_corrected_at: None,
+            key_rotated_at: None,
         }
     }
 }
@@ -120,6 +136,7 @@ impl From<User> for UserInfo {
             role: user.role,
             created_at: user.created_at,
             name_corrected_at: user.name_corrected_at,
+            key_rotated_at: user.key_rotated_at,
         }
     }
 }
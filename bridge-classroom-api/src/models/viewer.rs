@@ -11,8 +11,6 @@ pub struct Viewer {
     pub public_key: String,
     pub role: String,
     pub created_at: String,
-    #[serde(skip_serializing)]
-    pub recovery_encrypted_private_key: Option<String>,
 }
 
 /// Request to create a new viewer
@@ -61,7 +59,6 @@ impl Viewer {
             public_key: req.public_key.clone(),
             role: req.role.clone().unwrap_or_else(|| "teacher".to_string()),
             created_at: now,
-            recovery_encrypted_private_key: None,
         }
     }
 }
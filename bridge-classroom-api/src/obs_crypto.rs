@@ -3,14 +3,57 @@
 //! as a **separate** field, and the GCM tag **appended** to the ciphertext.
 //!
 //! This is deliberately distinct from `recovery::encrypt_for_recovery`, which
-//! derives its key from a secret (SHA-256) and **prepends** the nonce. Used by
-//! the account-merge flow to re-encrypt one user's observations under another
-//! user's key, server-side, so the keeper's browser can still decrypt them.
+//! derives its key from a secret (see `recovery_keys`) and **prepends** the
+//! nonce. Used by the account-merge flow to re-encrypt one user's
+//! observations under another user's key, server-side, so the keeper's
+//! browser can still decrypt them.
+//!
+//! Rows come in two cipher formats (`observations.cipher_format`). Format 1,
+//! the legacy one, seals the payload alone, so a row's cleartext columns can
+//! be swapped with another's without anyone noticing. Format 2 also
+//! authenticates the columns the server files by as AES-GCM associated data
+//! (see [`ObservationAad`]); a client opts in per observation.
 
 use ring::aead::{self, LessSafeKey, UnboundKey, Nonce, AES_256_GCM, NONCE_LEN};
 use ring::rand::{SecureRandom, SystemRandom};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
+/// Payload sealed on its own.
+pub const CIPHER_FORMAT_LEGACY: i64 = 1;
+/// Payload sealed with [`ObservationAad`] as associated data.
+pub const CIPHER_FORMAT_AAD: i64 = 2;
+
+/// The cleartext columns a format-2 ciphertext is bound to.
+#[derive(Debug, Clone, Copy)]
+pub struct ObservationAad<'a> {
+    pub observation_id: &'a str,
+    pub user_id: &'a str,
+    pub deal_subfolder: Option<&'a str>,
+    pub deal_number: Option<i32>,
+    pub correct: bool,
+}
+
+impl ObservationAad<'_> {
+    /// The associated data for `format`: empty for legacy rows, otherwise
+    /// the JSON array the frontend's `observationAad()` builds with
+    /// `JSON.stringify`, byte for byte.
+    pub fn for_format(&self, format: i64) -> Result<Vec<u8>, String> {
+        match format {
+            CIPHER_FORMAT_LEGACY => Ok(Vec::new()),
+            CIPHER_FORMAT_AAD => Ok(serde_json::to_vec(&serde_json::json!([
+                "bridge-classroom/observation/v2",
+                self.observation_id,
+                self.user_id,
+                self.deal_subfolder,
+                self.deal_number,
+                self.correct,
+            ]))
+            .expect("AAD serializes")),
+            other => Err(format!("Unknown cipher format {}", other)),
+        }
+    }
+}
+
 fn key_from_b64(raw_key_b64: &str) -> Result<LessSafeKey, String> {
     let key_bytes = BASE64
         .decode(raw_key_b64)
@@ -23,6 +66,15 @@ fn key_from_b64(raw_key_b64: &str) -> Result<LessSafeKey, String> {
 /// Encrypt `plaintext` with a raw base64 AES-256 key, producing the frontend's
 /// format: `(encrypted_data = base64(ciphertext||tag), iv = base64(nonce))`.
 pub fn encrypt_observation(plaintext: &[u8], raw_key_b64: &str) -> Result<(String, String), String> {
+    encrypt_observation_with_aad(plaintext, raw_key_b64, &[])
+}
+
+/// [`encrypt_observation`], authenticating `aad` alongside the payload.
+pub fn encrypt_observation_with_aad(
+    plaintext: &[u8],
+    raw_key_b64: &str,
+    aad: &[u8],
+) -> Result<(String, String), String> {
     let key = key_from_b64(raw_key_b64)?;
 
     let rng = SystemRandom::new();
@@ -31,7 +83,7 @@ pub fn encrypt_observation(plaintext: &[u8], raw_key_b64: &str) -> Result<(Strin
     let nonce = Nonce::assume_unique_for_key(nonce_bytes);
 
     let mut in_out = plaintext.to_vec();
-    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out)
+    key.seal_in_place_append_tag(nonce, aead::Aad::from(aad), &mut in_out)
         .map_err(|e| format!("Encryption failed: {:?}", e))?;
 
     Ok((BASE64.encode(&in_out), BASE64.encode(nonce_bytes)))
@@ -42,6 +94,17 @@ pub fn decrypt_observation(
     encrypted_data_b64: &str,
     iv_b64: &str,
     raw_key_b64: &str,
+) -> Result<Vec<u8>, String> {
+    decrypt_observation_with_aad(encrypted_data_b64, iv_b64, raw_key_b64, &[])
+}
+
+/// [`decrypt_observation`] for a ciphertext sealed with `aad`. Fails, like a
+/// wrong key, when `aad` isn't what it was sealed with.
+pub fn decrypt_observation_with_aad(
+    encrypted_data_b64: &str,
+    iv_b64: &str,
+    raw_key_b64: &str,
+    aad: &[u8],
 ) -> Result<Vec<u8>, String> {
     let key = key_from_b64(raw_key_b64)?;
 
@@ -58,7 +121,7 @@ pub fn decrypt_observation(
         .decode(encrypted_data_b64)
         .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
     let plaintext = key
-        .open_in_place(nonce, aead::Aad::empty(), &mut in_out)
+        .open_in_place(nonce, aead::Aad::from(aad), &mut in_out)
         .map_err(|e| format!("Decryption failed: {:?}", e))?;
 
     Ok(plaintext.to_vec())
@@ -103,4 +166,35 @@ mod tests {
         let out = decrypt_observation(&enc2, &iv2, &k_keeper).unwrap();
         assert_eq!(&out, plaintext);
     }
+
+    #[test]
+    fn aad_binds_the_cleartext_columns() {
+        let key = fresh_key();
+        let meta = ObservationAad {
+            observation_id: "obs-1",
+            user_id: "u1",
+            deal_subfolder: Some("Stayman"),
+            deal_number: Some(3),
+            correct: true,
+        };
+        let aad = meta.for_format(CIPHER_FORMAT_AAD).unwrap();
+        assert_eq!(
+            aad,
+            br#"["bridge-classroom/observation/v2","obs-1","u1","Stayman",3,true]"#
+        );
+        assert!(meta.for_format(CIPHER_FORMAT_LEGACY).unwrap().is_empty());
+
+        let (enc, iv) = encrypt_observation_with_aad(b"secret", &key, &aad).unwrap();
+        assert_eq!(
+            decrypt_observation_with_aad(&enc, &iv, &key, &aad).unwrap(),
+            b"secret"
+        );
+        let swapped = ObservationAad {
+            correct: false,
+            ..meta
+        };
+        let swapped = swapped.for_format(CIPHER_FORMAT_AAD).unwrap();
+        assert!(decrypt_observation_with_aad(&enc, &iv, &key, &swapped).is_err());
+        assert!(decrypt_observation(&enc, &iv, &key).is_err());
+    }
 }
@@ -0,0 +1,446 @@
+//! Versioned shape of the encrypted observation payload.
+//!
+//! The server never needs the plaintext to file an observation, but the
+//! admin decrypt projection and the account merge do read it. This module
+//! gives each payload version a Rust type, checks what the frontend's
+//! `src/utils/observationSchema.js` writes, and upgrades older versions to
+//! the current one so callers only ever handle [`ObservationPayload`].
+//!
+//! - **Version 1**: everything written before payloads carried
+//!   `schema_version`. Old clients left sections out, so every field is
+//!   optional and the upgrade fills the gaps with the defaults the decrypt
+//!   projection has always used.
+//! - **Version 2**: adds `schema_version`; the sections the frontend's
+//!   `validateObservation` requires must be present.
+
+use serde::Deserialize;
+
+/// The version the frontend currently writes.
+pub const CURRENT_SCHEMA_VERSION: i64 = 2;
+
+/// Whether `version` is one this server can read.
+pub fn is_known_version(version: i64) -> bool {
+    (1..=CURRENT_SCHEMA_VERSION).contains(&version)
+}
+
+/// One seat's cards: the `"SAKQ HJ..."` string most deals use, or the
+/// per-suit arrays some older lesson files produced.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Hand {
+    Text(String),
+    Suits {
+        #[serde(default)]
+        spades: Vec<String>,
+        #[serde(default)]
+        hearts: Vec<String>,
+        #[serde(default)]
+        diamonds: Vec<String>,
+        #[serde(default)]
+        clubs: Vec<String>,
+    },
+}
+
+impl Default for Hand {
+    fn default() -> Self {
+        Hand::Text(String::new())
+    }
+}
+
+impl Hand {
+    /// The hand as a `"SQT H7 ..."` string, skipping void suits.
+    pub fn to_text(&self) -> String {
+        match self {
+            Hand::Text(s) => s.clone(),
+            Hand::Suits {
+                spades,
+                hearts,
+                diamonds,
+                clubs,
+            } => [("S", spades), ("H", hearts), ("D", diamonds), ("C", clubs)]
+                .iter()
+                .filter(|(_, cards)| !cards.is_empty())
+                .map(|(symbol, cards)| format!("{}{}", symbol, cards.concat()))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Hands {
+    #[serde(default)]
+    pub north: Hand,
+    #[serde(default)]
+    pub east: Hand,
+    #[serde(default)]
+    pub south: Hand,
+    #[serde(default)]
+    pub west: Hand,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct DealContext {
+    pub subfolder: Option<String>,
+    pub filename: Option<String>,
+    pub deal_number: Option<i64>,
+    pub kind: Option<String>,
+    pub dealer: Option<String>,
+    pub vulnerability: Option<String>,
+    pub student_seat: Option<String>,
+    pub hands: Option<Hands>,
+    pub full_auction: Option<String>,
+    pub contract: Option<String>,
+    pub declarer: Option<String>,
+    pub lead: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BidPrompt {
+    /// 0-based prompt within the deal; -1 for a board-level observation.
+    pub prompt_index: Option<i64>,
+    pub total_prompts: Option<i64>,
+    pub auction_so_far: Option<Vec<String>>,
+    pub expected_bid: Option<String>,
+    pub student_hand: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BidResult {
+    pub student_bid: Option<String>,
+    pub correct: Option<bool>,
+    pub attempt_number: Option<i64>,
+    pub time_taken_ms: Option<i64>,
+}
+
+/// One entry of a board-level observation's `prompts[]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PromptAttempt {
+    pub correct: Option<bool>,
+    pub time_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct AssignmentTag {
+    pub id: Option<String>,
+}
+
+/// Version 1: no `schema_version`, every section optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ObservationV1 {
+    pub observation_id: Option<String>,
+    pub timestamp: Option<String>,
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub skill_path: Option<String>,
+    pub board_result: Option<String>,
+    pub deal: Option<DealContext>,
+    pub bid_prompt: Option<BidPrompt>,
+    pub result: Option<BidResult>,
+    pub prompts: Option<Vec<PromptAttempt>>,
+    pub assignment: Option<AssignmentTag>,
+    pub exercise_id: Option<String>,
+    pub jungle: Option<bool>,
+}
+
+/// Version 2, the current payload.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ObservationV2 {
+    pub schema_version: i64,
+    pub observation_id: String,
+    pub timestamp: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub skill_path: String,
+    pub board_result: Option<String>,
+    pub deal: DealContext,
+    pub bid_prompt: BidPrompt,
+    pub result: BidResult,
+    #[serde(default)]
+    pub prompts: Vec<PromptAttempt>,
+    pub assignment: Option<AssignmentTag>,
+    pub exercise_id: Option<String>,
+    #[serde(default)]
+    pub jungle: bool,
+}
+
+/// The payload every reader works with.
+pub type ObservationPayload = ObservationV2;
+
+impl ObservationV2 {
+    /// The checks the frontend's `validateObservation` makes before it
+    /// encrypts, repeated here so a payload that skipped them is caught.
+    pub fn validate(&self) -> Result<(), String> {
+        let required = [
+            ("observation_id", self.observation_id.as_str()),
+            ("timestamp", self.timestamp.as_str()),
+            ("user_id", self.user_id.as_str()),
+            ("session_id", self.session_id.as_str()),
+            ("skill_path", self.skill_path.as_str()),
+        ];
+        if let Some((field, _)) = required.iter().find(|(_, v)| v.is_empty()) {
+            return Err(format!("{} is empty", field));
+        }
+        if self.deal.dealer.as_deref().unwrap_or("").is_empty() {
+            return Err("deal.dealer is required".to_string());
+        }
+        if self.deal.student_seat.as_deref().unwrap_or("").is_empty() {
+            return Err("deal.student_seat is required".to_string());
+        }
+        if self.deal.hands.is_none() {
+            return Err("deal.hands is required".to_string());
+        }
+        let prompt_index = self
+            .bid_prompt
+            .prompt_index
+            .ok_or("bid_prompt.prompt_index is required")?;
+        if prompt_index != -1
+            && self
+                .bid_prompt
+                .expected_bid
+                .as_deref()
+                .unwrap_or("")
+                .is_empty()
+        {
+            return Err(
+                "bid_prompt.expected_bid is required for a prompt-level observation".to_string(),
+            );
+        }
+        if self.result.correct.is_none() {
+            return Err("result.correct is required".to_string());
+        }
+        Ok(())
+    }
+
+    /// 0 when the payload didn't say (version 1 only).
+    pub fn prompt_index(&self) -> i64 {
+        self.bid_prompt.prompt_index.unwrap_or(0)
+    }
+
+    /// The student's hand, from the seat named in the deal (South when
+    /// it's missing or unrecognised).
+    pub fn student_hand(&self) -> String {
+        let Some(hands) = &self.deal.hands else {
+            return String::new();
+        };
+        let seat = self.deal.student_seat.as_deref().unwrap_or("S");
+        let hand = match seat.to_uppercase().as_str() {
+            "N" | "NORTH" => &hands.north,
+            "E" | "EAST" => &hands.east,
+            "W" | "WEST" => &hands.west,
+            _ => &hands.south,
+        };
+        hand.to_text()
+    }
+
+    /// The bids before this prompt, space-separated.
+    pub fn auction_so_far(&self) -> String {
+        self.bid_prompt
+            .auction_so_far
+            .as_deref()
+            .unwrap_or_default()
+            .join(" ")
+    }
+
+    /// Whether any prompt of a board-level observation was missed.
+    pub fn had_wrong_prompt(&self) -> bool {
+        self.prompts.iter().any(|p| p.correct == Some(false))
+    }
+
+    /// The board result the payload implies, given the observation's
+    /// cleartext `correct`: a board-level observation that was right in the
+    /// end but missed a prompt on the way is `corrected`.
+    pub fn inferred_board_result(&self, correct: bool) -> &'static str {
+        match (
+            correct,
+            self.prompt_index() == -1 && self.had_wrong_prompt(),
+        ) {
+            (false, _) => "failed",
+            (true, true) => "corrected",
+            (true, false) => "correct",
+        }
+    }
+}
+
+/// Upgrade a version 1 payload, filling what it left out.
+pub fn upgrade_v1(v1: ObservationV1) -> ObservationV2 {
+    ObservationV2 {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        observation_id: v1.observation_id.unwrap_or_default(),
+        timestamp: v1.timestamp.unwrap_or_default(),
+        user_id: v1.user_id.unwrap_or_default(),
+        session_id: v1.session_id.unwrap_or_default(),
+        skill_path: v1.skill_path.unwrap_or_default(),
+        board_result: v1.board_result,
+        deal: v1.deal.unwrap_or_default(),
+        bid_prompt: v1.bid_prompt.unwrap_or_default(),
+        result: v1.result.unwrap_or_default(),
+        prompts: v1.prompts.unwrap_or_default(),
+        assignment: v1.assignment,
+        exercise_id: v1.exercise_id,
+        jungle: v1.jungle.unwrap_or(false),
+    }
+}
+
+/// A decrypted payload, upgraded to the current version.
+#[derive(Debug, Clone)]
+pub struct ParsedObservation {
+    /// The version the payload was written as.
+    pub source_version: i64,
+    pub payload: ObservationPayload,
+}
+
+/// Parse decrypted plaintext as whichever version it declares, validate it,
+/// and upgrade it to [`ObservationPayload`].
+pub fn parse_payload(plaintext: &[u8]) -> Result<ParsedObservation, String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(plaintext).map_err(|e| format!("payload is not JSON: {}", e))?;
+    let source_version = match value.get("schema_version") {
+        None | Some(serde_json::Value::Null) => 1,
+        Some(v) => v
+            .as_i64()
+            .ok_or_else(|| format!("schema_version {} is not an integer", v))?,
+    };
+    let payload = match source_version {
+        1 => upgrade_v1(
+            serde_json::from_value(value).map_err(|e| format!("invalid v1 payload: {}", e))?,
+        ),
+        2 => {
+            let v2: ObservationV2 =
+                serde_json::from_value(value).map_err(|e| format!("invalid v2 payload: {}", e))?;
+            v2.validate()
+                .map_err(|e| format!("invalid v2 payload: {}", e))?;
+            v2
+        }
+        other => return Err(format!("unsupported schema_version {}", other)),
+    };
+    Ok(ParsedObservation {
+        source_version,
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_payload() -> serde_json::Value {
+        serde_json::json!({
+            "schema_version": 2,
+            "observation_id": "obs-1",
+            "timestamp": "2026-01-01T09:00:00Z",
+            "user_id": "u1",
+            "session_id": "s1",
+            "skill_path": "bidding/stayman",
+            "board_result": "corrected",
+            "deal": {
+                "subfolder": "Stayman",
+                "deal_number": 3,
+                "dealer": "N",
+                "student_seat": "E",
+                "hands": { "north": "SAK", "east": "SQT H7", "south": "", "west": "" },
+                "full_auction": "1NT P 2C P"
+            },
+            "bid_prompt": { "prompt_index": -1, "total_prompts": 2, "auction_so_far": [] },
+            "result": { "student_bid": "2C", "correct": true, "attempt_number": 1 },
+            "prompts": [{ "correct": false, "time_ms": 900 }, { "correct": true, "time_ms": 400 }],
+            "assignment": null,
+            "exercise_id": null,
+            "jungle": false
+        })
+    }
+
+    #[test]
+    fn legacy_payloads_upgrade_and_current_ones_are_validated() {
+        // Version 1: no schema_version, suit-array hands, sections missing.
+        let v1 = br#"{
+            "session_id": "s0",
+            "deal": { "student_seat": "n", "hands": { "north": { "spades": ["Q", "T"], "hearts": [], "clubs": ["2"] } } },
+            "bid_prompt": { "prompt_index": 1, "auction_so_far": ["1C", "P"], "expected_bid": "1S" }
+        }"#;
+        let parsed = parse_payload(v1).unwrap();
+        assert_eq!(parsed.source_version, 1);
+        let p = &parsed.payload;
+        assert_eq!(p.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(p.student_hand(), "SQT C2");
+        assert_eq!(p.auction_so_far(), "1C P");
+        assert_eq!(p.result, BidResult::default());
+        assert_eq!(p.inferred_board_result(false), "failed");
+
+        let v2 = v2_payload();
+        let parsed = parse_payload(v2.to_string().as_bytes()).unwrap();
+        assert_eq!(parsed.source_version, 2);
+        assert_eq!(parsed.payload.student_hand(), "SQT H7");
+        assert!(parsed.payload.had_wrong_prompt());
+        assert_eq!(parsed.payload.inferred_board_result(true), "corrected");
+
+        let mut missing = v2_payload();
+        missing["deal"].as_object_mut().unwrap().remove("hands");
+        assert!(parse_payload(missing.to_string().as_bytes())
+            .unwrap_err()
+            .contains("deal.hands"));
+        let mut future = v2_payload();
+        future["schema_version"] = 3.into();
+        assert!(parse_payload(future.to_string().as_bytes())
+            .unwrap_err()
+            .contains("unsupported"));
+        assert!(parse_payload(b"[1, 2]").is_err());
+    }
+
+    #[test]
+    fn malformed_payloads_are_refused_with_the_reason() {
+        assert!(!is_known_version(0));
+        assert!(is_known_version(1));
+        assert!(is_known_version(CURRENT_SCHEMA_VERSION));
+        assert!(!is_known_version(CURRENT_SCHEMA_VERSION + 1));
+
+        let refused =
+            |payload: serde_json::Value| parse_payload(payload.to_string().as_bytes()).unwrap_err();
+        let with = |pointer: &str, value: serde_json::Value| {
+            let mut payload = v2_payload();
+            *payload.pointer_mut(pointer).unwrap() = value;
+            payload
+        };
+
+        assert!(parse_payload(b"not json").unwrap_err().contains("not JSON"));
+        assert!(refused(with("/schema_version", 0.into())).contains("unsupported"));
+        assert!(refused(with("/schema_version", "2".into())).contains("not an integer"));
+        assert!(refused(with("/schema_version", 2.5.into())).contains("not an integer"));
+        assert!(refused(with("/session_id", serde_json::Value::Null)).contains("invalid v2"));
+        assert!(refused(with("/observation_id", "".into())).contains("observation_id is empty"));
+        assert!(refused(with("/deal/dealer", "".into())).contains("deal.dealer"));
+        assert!(
+            refused(with("/result/correct", serde_json::Value::Null)).contains("result.correct")
+        );
+        assert!(
+            refused(with("/bid_prompt/prompt_index", serde_json::Value::Null))
+                .contains("prompt_index")
+        );
+
+        // Only a prompt-level observation (index 0 and up) needs an expected bid.
+        let prompt_level = with("/bid_prompt/prompt_index", 0.into());
+        assert!(refused(prompt_level.clone()).contains("expected_bid"));
+        let mut answered = prompt_level;
+        answered["bid_prompt"]["expected_bid"] = "2C".into();
+        assert_eq!(
+            parse_payload(answered.to_string().as_bytes())
+                .unwrap()
+                .payload
+                .prompt_index(),
+            0
+        );
+
+        // An explicit null reads as version 1, whose sections must still
+        // have the right shape when present.
+        let legacy = with("/schema_version", serde_json::Value::Null);
+        assert_eq!(
+            parse_payload(legacy.to_string().as_bytes())
+                .unwrap()
+                .source_version,
+            1
+        );
+        let mut legacy = serde_json::json!({ "schema_version": null });
+        legacy["deal"] = "Stayman 3".into();
+        assert!(refused(legacy).contains("invalid v1"));
+    }
+}
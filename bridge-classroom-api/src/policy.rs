@@ -0,0 +1,506 @@
+//! Central authorization policy.
+//!
+//! Two layers, both built on the session caller ([`AuthUser`]):
+//!
+//! - **Roles** are declared in the handler signature. A handler that takes
+//!   [`RequireAdmin`] or [`RequireTeacher`] instead of a plain `AuthUser`
+//!   is rejected with 401 (no/invalid session) or 403 (role too low) before
+//!   its body runs. Every `/api/admin/*` route takes `RequireAdmin`.
+//! - **Ownership** is checked with [`authorize`] once the handler knows
+//!   which row it is acting on, by naming the [`Relation`] the caller must
+//!   hold to it. Admins hold every relation.
+//!
+//! Roles come from `users.role`, promoted to admin when the user's email
+//! also has an admin row in `viewers` and the user has proved they own
+//! that address (see [`resolve_role`]), and are frozen into the session
+//! token at mint time.
+//!
+//! Classrooms add a per-classroom [`StaffRole`] on top (`classroom_staff`),
+//! checked with [`Relation::ClassroomStaff`] and a [`ClassroomPermission`].
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+use crate::session::AuthUser;
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Student,
+    Teacher,
+    Admin,
+}
+
+impl Role {
+    /// Unknown role strings are treated as the least-privileged role.
+    pub fn parse(role: &str) -> Role {
+        match role {
+            "admin" => Role::Admin,
+            "teacher" => Role::Teacher,
+            _ => Role::Student,
+        }
+    }
+}
+
+/// A user's role on one classroom's staff (`classroom_staff.role`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StaffRole {
+    /// `classrooms.teacher_id`; exactly one per classroom.
+    Owner,
+    CoTeacher,
+    Assistant,
+}
+
+/// What a classroom's staff may do, by [`StaffRole`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassroomPermission {
+    /// See the roster, the dashboard and members' progress.
+    ViewProgress,
+    CreateAssignments,
+    /// Remove members and change classroom settings (rules profile).
+    ManageClassroom,
+    /// Add, change and remove co-teachers and assistants.
+    ManageStaff,
+    DeleteClassroom,
+}
+
+impl StaffRole {
+    /// Unknown role strings are no role at all.
+    pub fn parse(role: &str) -> Option<StaffRole> {
+        match role {
+            "owner" => Some(StaffRole::Owner),
+            "co_teacher" => Some(StaffRole::CoTeacher),
+            "assistant" => Some(StaffRole::Assistant),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StaffRole::Owner => "owner",
+            StaffRole::CoTeacher => "co_teacher",
+            StaffRole::Assistant => "assistant",
+        }
+    }
+
+    pub fn can(self, permission: ClassroomPermission) -> bool {
+        use ClassroomPermission::*;
+        match permission {
+            ViewProgress | CreateAssignments => true,
+            ManageClassroom => self != StaffRole::Assistant,
+            ManageStaff | DeleteClassroom => self == StaffRole::Owner,
+        }
+    }
+}
+
+/// A relation the caller must hold to the row a handler is acting on.
+#[derive(Debug, Clone, Copy)]
+pub enum Relation<'a> {
+    /// The caller *is* this user.
+    SelfUser(&'a str),
+    /// The caller is the row's owner (`created_by`, `teacher_id`,
+    /// `owner_user_id`, ...) as already loaded by the handler. `None`
+    /// (legacy rows with no owner) is owned by nobody but admins.
+    Owner(Option<&'a str>),
+    /// The caller is on this classroom's staff in a role that has the
+    /// permission.
+    ClassroomStaff(&'a str, ClassroomPermission),
+    /// The caller is the grantee of an active sharing grant from this user.
+    GrantGrantee(&'a str),
+    /// The caller may read this user's progress: the user themselves, the
+    /// staff of a classroom they belong to, or an active grantee.
+    ProgressViewer(&'a str),
+}
+
+fn forbidden(msg: &str) -> (StatusCode, String) {
+    (StatusCode::FORBIDDEN, msg.to_string())
+}
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// [`Relation::Owner`] without the `async`, for checks inside pure helpers.
+pub fn owns(caller: &AuthUser, owner: Option<&str>) -> bool {
+    caller.is_admin() || owner == Some(caller.user_id.as_str())
+}
+
+/// Require the caller to hold `relation`; 403 otherwise.
+pub async fn authorize(
+    db: &Pool<Sqlite>,
+    caller: &AuthUser,
+    relation: Relation<'_>,
+) -> Result<(), (StatusCode, String)> {
+    if holds(db, caller, relation).await? {
+        Ok(())
+    } else {
+        Err(forbidden(match relation {
+            Relation::SelfUser(_) => "Not your account",
+            Relation::Owner(_) => "Not the owner",
+            Relation::ClassroomStaff(_, ClassroomPermission::ViewProgress) => "Not your classroom",
+            Relation::ClassroomStaff(..) => "Your classroom role doesn't allow this",
+            Relation::GrantGrantee(_) | Relation::ProgressViewer(_) => {
+                "Not allowed to view this user"
+            }
+        }))
+    }
+}
+
+/// Whether the caller holds `relation` (admins always do).
+pub async fn holds(
+    db: &Pool<Sqlite>,
+    caller: &AuthUser,
+    relation: Relation<'_>,
+) -> Result<bool, (StatusCode, String)> {
+    if caller.is_admin() {
+        return Ok(true);
+    }
+    match relation {
+        Relation::SelfUser(user_id) => Ok(caller.user_id == user_id),
+        Relation::Owner(owner) => Ok(owns(caller, owner)),
+        Relation::ClassroomStaff(classroom_id, permission) => {
+            Ok(staff_role(db, classroom_id, &caller.user_id)
+                .await?
+                .is_some_and(|role| role.can(permission)))
+        }
+        Relation::GrantGrantee(grantor_id) => is_grantee(db, &caller.user_id, grantor_id).await,
+        Relation::ProgressViewer(user_id) => {
+            if caller.user_id == user_id {
+                return Ok(true);
+            }
+            if !caller.is_teacher() {
+                return Ok(false);
+            }
+            let teaches: bool = sqlx::query_scalar(
+                r#"SELECT COUNT(*) > 0
+                   FROM classroom_members m JOIN classroom_staff s ON s.classroom_id = m.classroom_id
+                   WHERE m.student_id = ? AND s.user_id = ?"#,
+            )
+            .bind(user_id)
+            .bind(&caller.user_id)
+            .fetch_one(db)
+            .await
+            .map_err(db_err)?;
+            if teaches {
+                return Ok(true);
+            }
+            is_grantee(db, &caller.user_id, user_id).await
+        }
+    }
+}
+
+/// The user's role on the classroom's staff, if any.
+pub async fn staff_role(
+    db: &Pool<Sqlite>,
+    classroom_id: &str,
+    user_id: &str,
+) -> Result<Option<StaffRole>, (StatusCode, String)> {
+    let role: Option<String> = sqlx::query_scalar(
+        "SELECT role FROM classroom_staff WHERE classroom_id = ? AND user_id = ?",
+    )
+    .bind(classroom_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(db_err)?;
+    Ok(role.as_deref().and_then(StaffRole::parse))
+}
+
+/// Grants name a viewer, not a user; viewers and users are the same person
+/// when the emails match (the same join `get_user` uses for the viewer key).
+async fn is_grantee(
+    db: &Pool<Sqlite>,
+    user_id: &str,
+    grantor_id: &str,
+) -> Result<bool, (StatusCode, String)> {
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query_scalar(
+        r#"SELECT COUNT(*) > 0
+           FROM sharing_grants g
+           JOIN viewers v ON v.id = g.grantee_id
+           JOIN users u ON u.email = v.email COLLATE NOCASE
+           WHERE g.grantor_id = ? AND u.id = ?
+             AND g.revoked = 0
+             AND (g.expires_at IS NULL OR g.expires_at > ?)"#,
+    )
+    .bind(grantor_id)
+    .bind(user_id)
+    .bind(&now)
+    .fetch_one(db)
+    .await
+    .map_err(db_err)
+}
+
+/// Whether an admin viewer row exists for the user's email.
+pub async fn is_admin_viewer(db: &Pool<Sqlite>, user_id: &str) -> bool {
+    sqlx::query_scalar(
+        r#"SELECT COUNT(*) > 0 FROM viewers v JOIN users u ON u.email = v.email COLLATE NOCASE
+           WHERE u.id = ? AND v.role = 'admin'"#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+    .unwrap_or(false)
+}
+
+/// Record that the user just proved they read mail at their address (a
+/// recovery or password-reset link). Until then [`resolve_role`] won't
+/// promote them, since registration takes any email unchecked.
+pub async fn mark_email_verified(
+    db: &Pool<Sqlite>,
+    user_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE users SET email_verified_at = ? WHERE id = ?")
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(user_id)
+        .execute(db)
+        .await
+        .map_err(db_err)?;
+    Ok(())
+}
+
+/// The role to put in a freshly minted session: `users.role`, promoted to
+/// admin when an admin viewer row exists for the same email and the email
+/// is verified.
+pub async fn resolve_role(db: &Pool<Sqlite>, user_id: &str, user_role: &str) -> String {
+    if Role::parse(user_role) == Role::Admin {
+        return user_role.to_string();
+    }
+    let verified: bool = sqlx::query_scalar(
+        "SELECT email_verified_at IS NOT NULL FROM users WHERE id = ?",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or(false);
+    if verified && is_admin_viewer(db, user_id).await {
+        "admin".to_string()
+    } else {
+        user_role.to_string()
+    }
+}
+
+async fn require_role(
+    parts: &mut Parts,
+    state: &AppState,
+    min: Role,
+) -> Result<AuthUser, (StatusCode, String)> {
+    let caller = AuthUser::from_request_parts(parts, state).await?;
+    if caller.role() < min {
+        return Err(forbidden(match min {
+            Role::Admin => "Admins only",
+            _ => "Teachers only",
+        }));
+    }
+    Ok(caller)
+}
+
+/// Caller with the admin role.
+#[derive(Debug, Clone)]
+pub struct RequireAdmin(pub AuthUser);
+
+/// Caller with the teacher (or admin) role.
+#[derive(Debug, Clone)]
+pub struct RequireTeacher(pub AuthUser);
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequireAdmin {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, Role::Admin)
+            .await
+            .map(RequireAdmin)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequireTeacher {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, Role::Teacher)
+            .await
+            .map(RequireTeacher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::session::{mint_token, SessionClaims};
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+        routing::{get, post},
+        Router,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tower::ServiceExt;
+
+    fn state() -> AppState {
+        AppState {
+            db: SqlitePoolOptions::new()
+                .connect_lazy("sqlite::memory:")
+                .unwrap(),
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        }
+    }
+
+    fn token(state: &AppState, role: &str) -> String {
+        let now = chrono::Utc::now().timestamp();
+        let claims = SessionClaims {
+            sub: format!("{role}-1"),
+            role: role.to_string(),
+            iat: now,
+            exp: now + 3600,
+        };
+        mint_token(&claims, &state.config.session_secret)
+    }
+
+    fn admin_router(state: AppState) -> Router {
+        Router::new()
+            .route("/api/admin/stats", get(crate::routes::admin_stats))
+            .route(
+                "/api/admin/merge-accounts",
+                post(crate::routes::merge_accounts),
+            )
+            .route(
+                "/api/admin/decrypt-observations",
+                post(crate::routes::admin_decrypt_observations),
+            )
+            .with_state(state)
+    }
+
+    async fn status_for(
+        state: &AppState,
+        method: Method,
+        uri: &str,
+        role: Option<&str>,
+    ) -> StatusCode {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            // The shared API key must not be enough on its own.
+            .header("x-api-key", &state.config.api_key);
+        if let Some(role) = role {
+            req = req.header("authorization", format!("Bearer {}", token(state, role)));
+        }
+        let body = Body::from(r#"{"merge_user_id":"a","keeper_user_id":"b"}"#);
+        admin_router(state.clone())
+            .oneshot(req.body(body).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_routes_reject_everyone_but_admins() {
+        let state = state();
+        let routes = [
+            (Method::GET, "/api/admin/stats"),
+            (Method::POST, "/api/admin/merge-accounts"),
+            (Method::POST, "/api/admin/decrypt-observations"),
+        ];
+        for (method, uri) in routes {
+            let expect = [
+                (None, StatusCode::UNAUTHORIZED),
+                (Some("student"), StatusCode::FORBIDDEN),
+                (Some("teacher"), StatusCode::FORBIDDEN),
+                (Some("bogus"), StatusCode::FORBIDDEN),
+            ];
+            for (role, status) in expect {
+                assert_eq!(
+                    status_for(&state, method.clone(), uri, role).await,
+                    status,
+                    "{method} {uri} as {role:?}"
+                );
+            }
+            let admin = status_for(&state, method.clone(), uri, Some("admin")).await;
+            assert!(
+                admin != StatusCode::UNAUTHORIZED && admin != StatusCode::FORBIDDEN,
+                "{method} {uri} as admin got {admin}"
+            );
+        }
+    }
+
+    #[test]
+    fn staff_role_permissions() {
+        use ClassroomPermission::*;
+        for role in [StaffRole::Owner, StaffRole::CoTeacher, StaffRole::Assistant] {
+            assert!(role.can(ViewProgress) && role.can(CreateAssignments));
+            assert_eq!(StaffRole::parse(role.as_str()), Some(role));
+        }
+        assert!(StaffRole::CoTeacher.can(ManageClassroom));
+        assert!(!StaffRole::Assistant.can(ManageClassroom));
+        for role in [StaffRole::CoTeacher, StaffRole::Assistant] {
+            assert!(!role.can(ManageStaff) && !role.can(DeleteClassroom));
+        }
+        assert!(StaffRole::Owner.can(DeleteClassroom));
+        assert_eq!(StaffRole::parse("teacher"), None);
+    }
+
+    #[test]
+    fn roles_are_ordered_and_unknown_is_student() {
+        assert!(Role::Student < Role::Teacher && Role::Teacher < Role::Admin);
+        assert_eq!(Role::parse("viewer"), Role::Student);
+        assert_eq!(Role::parse("admin"), Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn pure_relations_and_admin_bypass() {
+        let db = state().db;
+        let student = AuthUser {
+            user_id: "s1".into(),
+            role: "student".into(),
+        };
+        let admin = AuthUser {
+            user_id: "a1".into(),
+            role: "admin".into(),
+        };
+
+        assert!(holds(&db, &student, Relation::SelfUser("s1"))
+            .await
+            .unwrap());
+        assert!(!holds(&db, &student, Relation::SelfUser("s2"))
+            .await
+            .unwrap());
+        assert!(holds(&db, &student, Relation::Owner(Some("s1")))
+            .await
+            .unwrap());
+        assert!(!holds(&db, &student, Relation::Owner(None)).await.unwrap());
+        // A student never views someone else's progress, without touching the DB.
+        assert!(!holds(&db, &student, Relation::ProgressViewer("s2"))
+            .await
+            .unwrap());
+
+        for relation in [
+            Relation::SelfUser("s1"),
+            Relation::Owner(None),
+            Relation::ClassroomStaff("c1", ClassroomPermission::DeleteClassroom),
+            Relation::GrantGrantee("s1"),
+            Relation::ProgressViewer("s1"),
+        ] {
+            assert!(holds(&db, &admin, relation).await.unwrap());
+        }
+    }
+}
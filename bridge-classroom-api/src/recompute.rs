@@ -0,0 +1,100 @@
+//! Rebuild every derived rollup for one user.
+//!
+//! `board_status`, `assignment_board_status` and `student_summary` are all
+//! caches over `observations` (see `documentation/CORRECTNESS_AND_MASTERY.md`
+//! §14). This module re-derives all three for a user in dependency order:
+//! board walks first, then assignment projections, then the summary that
+//! reads from them. Used by the admin CLI's `recompute` command.
+
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::routes::board_status::{recompute_assignment_boards, recompute_board_history};
+use crate::student_summary::recompute_student_summary;
+
+/// What a recompute touched. Failures are logged and counted, not fatal,
+/// matching the backfills: one bad board shouldn't block the rest.
+#[derive(Debug, Default, Serialize)]
+pub struct RecomputeReport {
+    pub boards: usize,
+    pub assignments: usize,
+    pub failures: usize,
+}
+
+/// Recompute every board, assignment rollup and the summary for `user_id`.
+/// Boards that only exist in `board_status` (observations since deleted)
+/// are included so they fall back to `not_attempted`.
+pub async fn recompute_user(pool: &Pool<Sqlite>, user_id: &str) -> Result<RecomputeReport, String> {
+    let boards: Vec<(String, i32)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT deal_subfolder, deal_number
+        FROM observations
+        WHERE user_id = ? AND deal_subfolder IS NOT NULL AND deal_number IS NOT NULL
+        UNION
+        SELECT deal_subfolder, deal_number FROM board_status WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Board lookup failed: {}", e))?;
+
+    let assignments: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT assignment_id FROM observations
+        WHERE user_id = ? AND assignment_id IS NOT NULL
+        UNION
+        SELECT DISTINCT assignment_id FROM assignment_board_status WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Assignment lookup failed: {}", e))?;
+
+    let mut report = RecomputeReport {
+        boards: boards.len(),
+        assignments: assignments.len(),
+        failures: 0,
+    };
+
+    for (subfolder, deal_number) in &boards {
+        if let Err(e) = recompute_board_history(pool, user_id, subfolder, *deal_number).await {
+            tracing::error!(
+                "recompute board {}/{} for {}: {}",
+                subfolder,
+                deal_number,
+                user_id,
+                e
+            );
+            report.failures += 1;
+        }
+    }
+    for assignment_id in &assignments {
+        if let Err(e) = recompute_assignment_boards(pool, user_id, assignment_id).await {
+            tracing::error!(
+                "recompute assignment {} for {}: {}",
+                assignment_id,
+                user_id,
+                e
+            );
+            report.failures += 1;
+        }
+    }
+    if let Err(e) = recompute_student_summary(pool, user_id).await {
+        tracing::error!("recompute summary for {}: {}", user_id, e);
+        report.failures += 1;
+    }
+
+    Ok(report)
+}
+
+/// Every user id, for `recompute --all`.
+pub async fn all_user_ids(pool: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+    sqlx::query_scalar("SELECT id FROM users ORDER BY id")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("User lookup failed: {}", e))
+}
@@ -0,0 +1,287 @@
+//! Server-side wrapping of recovery blobs (`users.recovery_encrypted_key`,
+//! `viewers.recovery_encrypted_private_key`, the keys held by an unfinished
+//! `key_rotations` row) under `RECOVERY_SECRET`.
+//!
+//! Blobs come in two formats:
+//!
+//! - **Legacy**: `base64(nonce || ciphertext)`, keyed by a single SHA-256 of
+//!   the secret. Carries nothing that says which secret sealed it.
+//! - **v2**: `rk2:<key id>:base64(nonce || ciphertext)`, keyed by HKDF-SHA256
+//!   of the secret. The key id is derived from the secret too, so it names
+//!   the secret without revealing it, and the `rk2:<key id>` prefix is the
+//!   AES-GCM associated data so it can't be swapped.
+//!
+//! A [`RecoveryKeyring`] holds the current secret, which seals everything
+//! new, plus any previous secrets that are still accepted for opening. That
+//! lets `RECOVERY_SECRET` be changed without a flag day: set the new one,
+//! move the old one to `RECOVERY_SECRET_PREVIOUS`, re-wrap every blob
+//! (`bridge-classroom-admin rewrap-recovery`), then drop the old one.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use ring::aead::{self, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{KeyType, Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+
+/// Prefix of a v2 blob.
+const V2_PREFIX: &str = "rk2";
+const HKDF_SALT: &[u8] = b"bridge-classroom/recovery";
+const HKDF_INFO_KEY: &[u8] = b"aes-256-gcm key";
+const HKDF_INFO_KEY_ID: &[u8] = b"key id";
+/// Bytes of the derived key id (hex-encoded in the blob).
+const KEY_ID_LEN: usize = 6;
+
+/// Output length for an HKDF expansion.
+struct Len(usize);
+
+impl KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone)]
+struct Secret {
+    id: String,
+    key: [u8; 32],
+    legacy_key: [u8; 32],
+}
+
+impl Secret {
+    fn derive(secret: &str) -> Secret {
+        let prk = Salt::new(HKDF_SHA256, HKDF_SALT).extract(secret.as_bytes());
+        let mut key = [0u8; 32];
+        let mut id = [0u8; KEY_ID_LEN];
+        prk.expand(&[HKDF_INFO_KEY], Len(key.len()))
+            .and_then(|okm| okm.fill(&mut key))
+            .expect("HKDF output length is valid");
+        prk.expand(&[HKDF_INFO_KEY_ID], Len(id.len()))
+            .and_then(|okm| okm.fill(&mut id))
+            .expect("HKDF output length is valid");
+        Secret {
+            id: id.iter().map(|b| format!("{:02x}", b)).collect(),
+            key,
+            legacy_key: Sha256::digest(secret.as_bytes()).into(),
+        }
+    }
+}
+
+/// What a blob says about the secret that sealed it, without opening it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobKeyId {
+    /// A pre-v2 blob: any configured secret might open it.
+    Legacy,
+    /// A v2 blob sealed by the secret with this key id.
+    V2(String),
+}
+
+impl BlobKeyId {
+    pub fn of(blob: &str) -> BlobKeyId {
+        match split_v2(blob) {
+            Some((id, _)) => BlobKeyId::V2(id.to_string()),
+            None => BlobKeyId::Legacy,
+        }
+    }
+}
+
+/// `rk2:<id>:<body>` → `(id, body)`. Base64 never contains `:`, so a
+/// legacy blob can't be mistaken for one.
+fn split_v2(blob: &str) -> Option<(&str, &str)> {
+    let rest = blob.strip_prefix(V2_PREFIX)?.strip_prefix(':')?;
+    rest.split_once(':')
+}
+
+/// The current recovery secret plus any previous ones still accepted.
+#[derive(Clone)]
+pub struct RecoveryKeyring {
+    /// `secrets[0]` is current.
+    secrets: Vec<Secret>,
+}
+
+impl fmt::Debug for RecoveryKeyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoveryKeyring")
+            .field("key_ids", &self.key_ids())
+            .finish()
+    }
+}
+
+impl RecoveryKeyring {
+    /// A keyring sealing with `current` and accepting nothing else.
+    pub fn new(current: &str) -> RecoveryKeyring {
+        RecoveryKeyring {
+            secrets: vec![Secret::derive(current)],
+        }
+    }
+
+    /// Also accept `previous` secrets for opening. Duplicates of a secret
+    /// already on the ring are ignored.
+    pub fn with_previous<'a>(
+        mut self,
+        previous: impl IntoIterator<Item = &'a str>,
+    ) -> RecoveryKeyring {
+        for secret in previous {
+            let derived = Secret::derive(secret);
+            if !self.secrets.iter().any(|s| s.id == derived.id) {
+                self.secrets.push(derived);
+            }
+        }
+        self
+    }
+
+    /// Key id of the current secret.
+    pub fn current_key_id(&self) -> &str {
+        &self.secrets[0].id
+    }
+
+    /// Key ids of every accepted secret, current first.
+    pub fn key_ids(&self) -> Vec<&str> {
+        self.secrets.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    /// Whether `blob` is already a v2 blob under the current secret.
+    pub fn is_current(&self, blob: &str) -> bool {
+        BlobKeyId::of(blob) == BlobKeyId::V2(self.current_key_id().to_string())
+    }
+
+    /// Seal `plaintext` under the current secret as a v2 blob.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let secret = &self.secrets[0];
+        let aad = format!("{}:{}", V2_PREFIX, secret.id);
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce_bytes)
+            .map_err(|_| "Failed to generate nonce")?;
+        let mut in_out = plaintext.as_bytes().to_vec();
+        aes_key(&secret.key)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                aead::Aad::from(aad.as_bytes()),
+                &mut in_out,
+            )
+            .map_err(|e| format!("Encryption failed: {:?}", e))?;
+
+        let mut combined = nonce_bytes.to_vec();
+        combined.extend_from_slice(&in_out);
+        Ok(format!("{}:{}", aad, BASE64.encode(&combined)))
+    }
+
+    /// Open a v2 blob with the secret it names, or a legacy blob with
+    /// whichever accepted secret opens it.
+    pub fn decrypt(&self, blob: &str) -> Result<String, String> {
+        if let Some((id, body)) = split_v2(blob) {
+            let secret = self
+                .secrets
+                .iter()
+                .find(|s| s.id == id)
+                .ok_or_else(|| format!("Recovery key {} is not configured", id))?;
+            let aad = format!("{}:{}", V2_PREFIX, id);
+            return open(&secret.key, body, aad.as_bytes());
+        }
+        let mut last_err = String::new();
+        for secret in &self.secrets {
+            match open(&secret.legacy_key, blob, &[]) {
+                Ok(plaintext) => return Ok(plaintext),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+}
+
+fn aes_key(key_bytes: &[u8; 32]) -> Result<LessSafeKey, String> {
+    let unbound = UnboundKey::new(&AES_256_GCM, key_bytes)
+        .map_err(|e| format!("Failed to create key: {:?}", e))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+fn open(key_bytes: &[u8; 32], body_b64: &str, aad: &[u8]) -> Result<String, String> {
+    let combined = BASE64
+        .decode(body_b64)
+        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+    if combined.len() < NONCE_LEN {
+        return Err("Invalid encrypted data".to_string());
+    }
+    let (nonce_bytes, ciphertext) = combined.split_at(NONCE_LEN);
+    let nonce =
+        Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| "Invalid nonce length")?;
+
+    let mut in_out = ciphertext.to_vec();
+    let decrypted = aes_key(key_bytes)?
+        .open_in_place(nonce, aead::Aad::from(aad), &mut in_out)
+        .map_err(|e| format!("Decryption failed: {:?}", e))?;
+    String::from_utf8(decrypted.to_vec()).map_err(|e| format!("Invalid UTF-8: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A blob as the pre-v2 `encrypt_for_recovery` wrote it.
+    fn legacy_blob(plaintext: &str, secret: &str) -> String {
+        let key_bytes: [u8; 32] = Sha256::digest(secret.as_bytes()).into();
+        let nonce_bytes = [7u8; NONCE_LEN];
+        let mut in_out = plaintext.as_bytes().to_vec();
+        aes_key(&key_bytes)
+            .unwrap()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                aead::Aad::empty(),
+                &mut in_out,
+            )
+            .unwrap();
+        let mut combined = nonce_bytes.to_vec();
+        combined.extend_from_slice(&in_out);
+        BASE64.encode(&combined)
+    }
+
+    #[test]
+    fn v2_blobs_name_their_secret() {
+        let ring = RecoveryKeyring::new("current");
+        let blob = ring.encrypt("student-key").unwrap();
+        assert!(blob.starts_with(&format!("rk2:{}:", ring.current_key_id())));
+        assert!(ring.is_current(&blob));
+        assert_eq!(ring.decrypt(&blob).unwrap(), "student-key");
+
+        // Key ids are stable per secret and distinct across secrets.
+        assert_eq!(
+            RecoveryKeyring::new("current").current_key_id(),
+            ring.current_key_id()
+        );
+        assert_ne!(
+            RecoveryKeyring::new("other").current_key_id(),
+            ring.current_key_id()
+        );
+
+        // The prefix is authenticated: relabelling the blob breaks it.
+        let both = RecoveryKeyring::new("other").with_previous(["current"]);
+        let relabelled = blob.replace(ring.current_key_id(), both.current_key_id());
+        assert!(both.decrypt(&relabelled).is_err());
+
+        // A secret that isn't on the ring is reported by id.
+        let err = RecoveryKeyring::new("other").decrypt(&blob).unwrap_err();
+        assert!(err.contains(ring.current_key_id()));
+    }
+
+    #[test]
+    fn previous_secrets_open_old_blobs() {
+        let legacy = legacy_blob("legacy-key", "old");
+        let old_v2 = RecoveryKeyring::new("old").encrypt("v2-key").unwrap();
+
+        let ring = RecoveryKeyring::new("new").with_previous(["old", "new"]);
+        assert_eq!(ring.key_ids().len(), 2);
+        assert_eq!(ring.decrypt(&legacy).unwrap(), "legacy-key");
+        assert_eq!(ring.decrypt(&old_v2).unwrap(), "v2-key");
+        assert!(!ring.is_current(&legacy));
+        assert!(!ring.is_current(&old_v2));
+        assert_eq!(BlobKeyId::of(&legacy), BlobKeyId::Legacy);
+
+        // Once "old" is dropped, neither opens.
+        let ring = RecoveryKeyring::new("new");
+        assert!(ring.decrypt(&legacy).is_err());
+        assert!(ring.decrypt(&old_v2).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Where "Report a Problem" submissions are delivered once they are queued.
+//!
+//! Reports are stored in `problem_reports` first (see routes/reports.rs);
+//! a worker then hands each one to the configured [`ReportSink`]:
+//!
+//! - **github**: a `classroom-feedback` issue in `GITHUB_ISSUES_REPO`
+//!   (`GITHUB_ISSUES_TOKEN`).
+//! - **email**: a message to `REPORT_EMAIL_TO`, over the configured email
+//!   transport.
+//! - **none**: nothing is delivered; reports wait in the queue for curators
+//!   (and are delivered if a sink is configured later).
+//!
+//! `REPORT_SINK` picks one explicitly; otherwise GitHub is used when a token
+//! is set, and none when it isn't.
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::email::{templates::escape_html, Email, Mailer};
+use crate::routes::ReportRequest;
+
+const FEEDBACK_LABEL: &str = "classroom-feedback";
+
+/// What a sink returns for a delivered report. GitHub fills in the issue;
+/// email has nothing to link to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FiledReport {
+    pub issue_url: Option<String>,
+    pub issue_number: Option<i64>,
+}
+
+/// Something that can deliver a queued report.
+pub trait ReportSink: Send + Sync {
+    /// Short sink name, stored with each delivered report.
+    fn name(&self) -> &'static str;
+
+    fn file<'a>(&'a self, report: &'a ReportRequest) -> BoxFuture<'a, Result<FiledReport, String>>;
+}
+
+/// Which sink to build, as configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportSinkKind {
+    Github,
+    Email { to: String },
+    None,
+}
+
+impl ReportSinkKind {
+    /// Pick a sink from the environment; see the module docs.
+    pub fn from_env(github_token: Option<&str>) -> Result<ReportSinkKind, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        match var("REPORT_SINK").as_deref() {
+            Some("github") if github_token.is_some() => Ok(ReportSinkKind::Github),
+            Some("github") => {
+                Err("GITHUB_ISSUES_TOKEN is required for REPORT_SINK=github".to_string())
+            }
+            Some("email") => Ok(ReportSinkKind::Email {
+                to: var("REPORT_EMAIL_TO")
+                    .ok_or("REPORT_EMAIL_TO is required for REPORT_SINK=email")?,
+            }),
+            Some("none") => Ok(ReportSinkKind::None),
+            Some(other) => Err(format!(
+                "Unknown REPORT_SINK '{}' (expected github, email or none)",
+                other
+            )),
+            None if github_token.is_some() => Ok(ReportSinkKind::Github),
+            None => Ok(ReportSinkKind::None),
+        }
+    }
+
+    /// Build the configured sink. Ok(None) for `none`.
+    pub fn sink(&self, config: &Config) -> Result<Option<Box<dyn ReportSink>>, String> {
+        Ok(match self {
+            ReportSinkKind::Github => Some(Box::new(GitHubSink {
+                token: config
+                    .github_issues_token
+                    .clone()
+                    .ok_or("GITHUB_ISSUES_TOKEN not configured")?,
+                repo: config.github_issues_repo.clone(),
+                client: Client::new(),
+            })),
+            ReportSinkKind::Email { to } => Some(Box::new(EmailSink {
+                mailer: config
+                    .mailer
+                    .clone()
+                    .ok_or("REPORT_SINK=email but no email transport is configured")?,
+                to: to.clone(),
+            })),
+            ReportSinkKind::None => None,
+        })
+    }
+}
+
+/// Files a `classroom-feedback` issue in the content repo. The token lives
+/// only in server config and is never returned or logged.
+pub struct GitHubSink {
+    token: String,
+    repo: String,
+    client: Client,
+}
+
+/// Body for the GitHub "create an issue" call.
+#[derive(Debug, Serialize)]
+struct GitHubIssueRequest {
+    title: String,
+    body: String,
+    labels: Vec<String>,
+}
+
+/// The slice of GitHub's create-issue response we care about.
+#[derive(Debug, Deserialize)]
+struct GitHubIssueResponse {
+    html_url: String,
+    number: i64,
+}
+
+impl ReportSink for GitHubSink {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn file<'a>(&'a self, report: &'a ReportRequest) -> BoxFuture<'a, Result<FiledReport, String>> {
+        async move {
+            let issue_req = GitHubIssueRequest {
+                title: report_title(report),
+                body: build_issue_body(report),
+                labels: vec![FEEDBACK_LABEL.to_string()],
+            };
+            let url = format!("https://api.github.com/repos/{}/issues", self.repo);
+            let response = self
+                .client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", "2022-11-28")
+                // GitHub requires a User-Agent on every REST request.
+                .header("User-Agent", "bridge-classroom-report")
+                .json(&issue_req)
+                .send()
+                .await
+                .map_err(|e| format!("GitHub issue request failed: {}", e))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let error_text = response.text().await.unwrap_or_default();
+                return Err(format!(
+                    "GitHub issue API error: {} - {}",
+                    status, error_text
+                ));
+            }
+
+            let issue: GitHubIssueResponse = response
+                .json()
+                .await
+                .map_err(|e| format!("Could not parse GitHub issue response: {}", e))?;
+            Ok(FiledReport {
+                issue_url: Some(issue.html_url),
+                issue_number: Some(issue.number),
+            })
+        }
+        .boxed()
+    }
+}
+
+/// Mails each report to a fixed address (a curator or a shared inbox).
+pub struct EmailSink {
+    mailer: Mailer,
+    to: String,
+}
+
+impl ReportSink for EmailSink {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn file<'a>(&'a self, report: &'a ReportRequest) -> BoxFuture<'a, Result<FiledReport, String>> {
+        async move {
+            let body = build_issue_body(report);
+            let email = Email {
+                to: self.to.clone(),
+                subject: report_title(report),
+                html: format!(
+                    "<pre style=\"white-space: pre-wrap; font-family: inherit;\">{}</pre>",
+                    escape_html(&body)
+                ),
+                text: body,
+            };
+            self.mailer.send(&email).await?;
+            Ok(FiledReport::default())
+        }
+        .boxed()
+    }
+}
+
+/// "Classroom report: <scenario> · Deal <n>"
+pub fn report_title(req: &ReportRequest) -> String {
+    let scenario = req
+        .scenario
+        .as_deref()
+        .or(req.lesson_id.as_deref())
+        .unwrap_or("Unknown scenario");
+    let deal_label = req
+        .display_number
+        .map(|n| n.to_string())
+        .or_else(|| req.board_tag.clone())
+        .unwrap_or_else(|| "?".to_string());
+    format!("Classroom report: {} · Deal {}", scenario, deal_label)
+}
+
+/// Render the captured state into a readable Markdown body: the learner's note
+/// first, then a context block, then the deal PBN as a code block.
+pub fn build_issue_body(req: &ReportRequest) -> String {
+    let note = req.note.trim();
+    let mut ctx: Vec<String> = Vec::new();
+
+    let tier = req.reporter_tier.as_deref().unwrap_or("learner");
+    ctx.push(format!("- **Reporter:** {}", tier));
+
+    if let Some(scenario) = req.scenario.as_deref().or(req.lesson_id.as_deref()) {
+        let pretty = req
+            .lesson_name
+            .as_deref()
+            .filter(|n| !n.is_empty() && *n != scenario)
+            .map(|n| format!(" ({})", n))
+            .unwrap_or_default();
+        ctx.push(format!("- **Scenario:** {}{}", scenario, pretty));
+    }
+    if let Some(collection) = &req.collection {
+        ctx.push(format!("- **Collection:** {}", collection));
+    }
+
+    // Deal identity: display number + the two board tags that pin it down.
+    let mut deal_bits: Vec<String> = Vec::new();
+    if let Some(n) = req.display_number {
+        deal_bits.push(format!("display #{}", n));
+    }
+    if let Some(b) = &req.board_tag {
+        deal_bits.push(format!("[Board] {}", b));
+    }
+    if let Some(ob) = &req.original_board {
+        deal_bits.push(format!("[OriginalBoard] {}", ob));
+    }
+    if !deal_bits.is_empty() {
+        ctx.push(format!("- **Deal:** {}", deal_bits.join(" · ")));
+    }
+
+    if let Some(seat) = &req.student_seat {
+        ctx.push(format!("- **Student seat:** {}", seat));
+    }
+    if let Some(contract) = &req.contract {
+        if !contract.is_empty() {
+            ctx.push(format!("- **Contract:** {}", contract));
+        }
+    }
+    if !req.auction.is_empty() {
+        ctx.push(format!("- **Auction so far:** {}", req.auction.join(" – ")));
+    }
+    if let Some(idx) = req.step_index {
+        let prompt = req
+            .prompt
+            .as_deref()
+            .map(|p| format!(" — “{}”", truncate(p, 280)))
+            .unwrap_or_default();
+        ctx.push(format!("- **Step:** index {}{}", idx, prompt));
+    }
+    if let Some(src) = &req.source_url {
+        ctx.push(format!("- **Source:** {}", src));
+    }
+    if let Some(sc) = &req.source_commit {
+        ctx.push(format!("- **Source commit:** {}", sc));
+    }
+    if req.app_version.is_some() || req.app_commit.is_some() {
+        let v = req.app_version.as_deref().unwrap_or("?");
+        let c = req
+            .app_commit
+            .as_deref()
+            .map(|c| format!(" (commit {})", c))
+            .unwrap_or_default();
+        ctx.push(format!("- **App:** v{}{}", v, c));
+    }
+
+    let deal_block = req
+        .deal_pbn
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("\n\n**Deal (PBN):**\n```\n{}\n```", p))
+        .unwrap_or_default();
+
+    format!(
+        "{note}\n\n---\n\n{context}{deal}\n\n_Filed automatically from the Bridge Classroom “Report a Problem” button._",
+        note = note,
+        context = ctx.join("\n"),
+        deal = deal_block,
+    )
+}
+
+/// Truncate a string to at most `max` chars, appending an ellipsis when cut.
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max).collect();
+    out.push('…');
+    out
+}
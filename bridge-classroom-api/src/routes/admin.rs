@@ -1,30 +1,19 @@
 use axum::{
-    extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    extract::State,
+    http::StatusCode,
     Json,
 };
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
-use ring::aead::{self, LessSafeKey, UnboundKey, AES_256_GCM, Nonce, NONCE_LEN};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
+use crate::jobs::{queue_stats, QueueStats};
+use crate::obs_crypto::{decrypt_observation_with_aad, ObservationAad};
+use crate::observation_schema::parse_payload;
+use crate::policy::RequireAdmin;
 use crate::AppState;
 use super::recovery::decrypt_for_recovery;
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 // ---- Request / Response types ----
 
-#[derive(Debug, Deserialize)]
-pub struct AdminQuery {
-    pub admin_id: Option<String>,
-}
-
 #[derive(Debug, Serialize)]
 pub struct AdminStatsResponse {
     pub success: bool,
@@ -114,13 +103,8 @@ struct MaxTimestampRow {
 /// GET /api/admin/stats
 pub async fn admin_stats(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    Query(_query): Query<AdminQuery>,
+    _admin: RequireAdmin,
 ) -> Result<Json<AdminStatsResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let now = chrono::Utc::now();
     let seven_days_ago = (now - chrono::Duration::days(7)).to_rfc3339();
     // "Today" = local (Pacific) midnight, converted to UTC for comparison with stored timestamps
@@ -290,12 +274,8 @@ pub async fn admin_stats(
 /// GET /api/admin/health
 pub async fn admin_health(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
 ) -> Result<Json<AdminHealthResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     // Uptime
     let uptime_seconds = state.started_at.elapsed().as_secs();
 
@@ -328,6 +308,32 @@ pub async fn admin_health(
     }))
 }
 
+// ---- Recompute job queue ----
+
+#[derive(Debug, Serialize)]
+pub struct JobQueueResponse {
+    pub success: bool,
+    pub workers: usize,
+    pub queue: QueueStats,
+}
+
+/// GET /api/admin/jobs
+/// Recompute queue depth and the jobs that exhausted their retries.
+pub async fn admin_job_queue(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+) -> Result<Json<JobQueueResponse>, (StatusCode, String)> {
+    let queue = queue_stats(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(JobQueueResponse {
+        success: true,
+        workers: state.config.recompute_workers,
+        queue,
+    }))
+}
+
 // ---- Observation decryption ----
 
 #[derive(Debug, Serialize)]
@@ -357,55 +363,42 @@ struct ObservationEncryptedRow {
     encrypted_data: String,
     iv: String,
     board_result: Option<String>,
+    schema_version: Option<i64>,
+    cipher_format: i64,
 }
 
-/// Decrypt an observation's encrypted_data using the student's AES key.
+/// Decrypt an observation's encrypted_data using the student's AES key,
+/// checking the row's cleartext columns when its cipher format binds them.
 fn decrypt_observation_data(
-    encrypted_data: &str,
-    iv: &str,
+    obs: &ObservationEncryptedRow,
     aes_key_base64: &str,
 ) -> Result<String, String> {
-    let key_bytes = BASE64
-        .decode(aes_key_base64)
-        .map_err(|e| format!("Bad AES key base64: {}", e))?;
-    let iv_bytes = BASE64
-        .decode(iv)
-        .map_err(|e| format!("Bad IV base64: {}", e))?;
-    let ciphertext = BASE64
-        .decode(encrypted_data)
-        .map_err(|e| format!("Bad ciphertext base64: {}", e))?;
-
-    if iv_bytes.len() != NONCE_LEN {
-        return Err(format!("IV length {} != expected {}", iv_bytes.len(), NONCE_LEN));
+    let aad = ObservationAad {
+        observation_id: &obs.id,
+        user_id: &obs.user_id,
+        deal_subfolder: obs.deal_subfolder.as_deref(),
+        deal_number: obs.deal_number,
+        correct: obs.correct,
     }
-
-    let nonce_array: [u8; NONCE_LEN] = iv_bytes
-        .try_into()
-        .map_err(|_| "Invalid nonce length")?;
-    let nonce = Nonce::assume_unique_for_key(nonce_array);
-
-    let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
-        .map_err(|e| format!("Failed to create AES key: {:?}", e))?;
-    let key = LessSafeKey::new(unbound_key);
-
-    let mut in_out = ciphertext;
-    let plaintext = key
-        .open_in_place(nonce, aead::Aad::empty(), &mut in_out)
-        .map_err(|e| format!("Decryption failed: {:?}", e))?;
-
-    String::from_utf8(plaintext.to_vec()).map_err(|e| format!("Invalid UTF-8: {}", e))
+    .for_format(obs.cipher_format)?;
+    let plaintext =
+        decrypt_observation_with_aad(&obs.encrypted_data, &obs.iv, aes_key_base64, &aad)?;
+    String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8: {}", e))
 }
 
 /// POST /api/admin/decrypt-observations
 /// Decrypts all observations using RECOVERY_SECRET and populates observations_decrypted table.
 pub async fn admin_decrypt_observations(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
 ) -> Result<Json<DecryptObservationsResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    decrypt_all_observations(&state).await.map(Json)
+}
 
+/// The rebuild behind `admin_decrypt_observations`, shared with the admin CLI.
+pub async fn decrypt_all_observations(
+    state: &AppState,
+) -> Result<DecryptObservationsResponse, (StatusCode, String)> {
     let recovery_secret = state
         .config
         .recovery_secret
@@ -440,6 +433,7 @@ pub async fn admin_decrypt_observations(
             auction_so_far TEXT,
             skill_path TEXT,
             session_id TEXT,
+            schema_version INTEGER,
             decrypted_json TEXT,
             created_at TEXT DEFAULT (datetime('now'))
         )
@@ -485,7 +479,8 @@ pub async fn admin_decrypt_observations(
         let observations: Vec<ObservationEncryptedRow> = sqlx::query_as(
             r#"
             SELECT id, user_id, timestamp, skill_path, correct,
-                   deal_subfolder, deal_number, encrypted_data, iv, board_result
+                   deal_subfolder, deal_number, encrypted_data, iv, board_result,
+                   schema_version, cipher_format
             FROM observations
             WHERE user_id = ?
             ORDER BY timestamp ASC
@@ -497,132 +492,26 @@ pub async fn admin_decrypt_observations(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
         for obs in &observations {
-            match decrypt_observation_data(&obs.encrypted_data, &obs.iv, &aes_key) {
+            match decrypt_observation_data(obs, &aes_key) {
                 Ok(json_str) => {
-                    // Parse the decrypted JSON
-                    let json: serde_json::Value = match serde_json::from_str(&json_str) {
-                        Ok(v) => v,
+                    // Read it as the version it declares, upgraded to the current one.
+                    let parsed = match parse_payload(json_str.as_bytes()) {
+                        Ok(p) => p,
                         Err(e) => {
-                            tracing::warn!("Failed to parse decrypted JSON for obs {}: {}", obs.id, e);
+                            tracing::warn!("Unreadable payload for obs {}: {}", obs.id, e);
                             total_errors += 1;
                             continue;
                         }
                     };
-
-                    // Extract fields
-                    let prompt_index = json
-                        .get("bid_prompt")
-                        .and_then(|bp| bp.get("prompt_index"))
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(0) as i32;
-
-                    let session_id = json.get("session_id").and_then(|v| v.as_str()).unwrap_or("");
-
-                    let student_bid = json
-                        .get("result")
-                        .and_then(|r| r.get("student_bid"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-
-                    let expected_bid = json
-                        .get("bid_prompt")
-                        .and_then(|bp| bp.get("expected_bid"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-
-                    // Extract student's hand cards
-                    let student_seat_raw = json
-                        .get("deal")
-                        .and_then(|d| d.get("student_seat"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("S");
-                    // Map seat letter to full name for JSON lookup
-                    let seat_upper = student_seat_raw.to_uppercase();
-                    let student_seat: &str = match seat_upper.as_str() {
-                        "N" => "north",
-                        "E" => "east",
-                        "S" => "south",
-                        "W" => "west",
-                        _ => "south",
-                    };
-                    let student_hand_value = json
-                        .get("deal")
-                        .and_then(|d| d.get("hands"))
-                        .and_then(|h| h.get(student_seat));
-                    let student_hand = match student_hand_value {
-                        Some(serde_json::Value::String(s)) => s.clone(),
-                        Some(serde_json::Value::Object(obj)) => {
-                            // Object format: {"spades":["Q","T"],"hearts":["7"],...}
-                            let mut parts = Vec::new();
-                            for (suit, symbol) in [("spades", "S"), ("hearts", "H"), ("diamonds", "D"), ("clubs", "C")] {
-                                if let Some(cards) = obj.get(suit).and_then(|v| v.as_array()) {
-                                    let card_str: Vec<&str> = cards
-                                        .iter()
-                                        .filter_map(|c| c.as_str())
-                                        .collect();
-                                    if !card_str.is_empty() {
-                                        parts.push(format!("{}{}", symbol, card_str.join("")));
-                                    }
-                                }
-                            }
-                            parts.join(" ")
-                        }
-                        _ => String::new(),
-                    };
-
-                    // Extract auction info
-                    let full_auction = json
-                        .get("deal")
-                        .and_then(|d| d.get("full_auction"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    let auction_so_far = json
-                        .get("bid_prompt")
-                        .and_then(|bp| bp.get("auction_so_far"))
-                        .and_then(|v| v.as_array())
-                        .map(|arr| {
-                            arr.iter()
-                                .filter_map(|v| v.as_str())
-                                .collect::<Vec<_>>()
-                                .join(" ")
-                        })
-                        .unwrap_or_default();
-
-                    let board_result_payload = json
-                        .get("board_result")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-
-                    // Analyze prompts array for had_wrong_prompt
-                    let prompts = json.get("prompts").and_then(|v| v.as_array());
-                    let prompt_count = prompts.map(|a| a.len()).unwrap_or(0) as i32;
-                    let had_wrong_prompt = prompts
-                        .map(|arr| {
-                            arr.iter().any(|p| {
-                                p.get("correct").and_then(|v| v.as_bool()) == Some(false)
-                            })
-                        })
-                        .unwrap_or(false);
-
-                    // Infer board_result
-                    let inferred_board_result = if prompt_index == -1 {
-                        // Board-level: use prompts array to detect corrections
-                        if !obs.correct {
-                            Some("failed".to_string())
-                        } else if had_wrong_prompt {
-                            Some("corrected".to_string())
-                        } else {
-                            Some("correct".to_string())
-                        }
-                    } else {
-                        // Prompt-level: infer directly from correct field
-                        if obs.correct {
-                            Some("correct".to_string())
-                        } else {
-                            Some("failed".to_string())
-                        }
-                    };
+                    if obs.schema_version.is_some_and(|v| v != parsed.source_version) {
+                        tracing::warn!(
+                            "Obs {} metadata says schema_version {:?} but the payload is version {}",
+                            obs.id, obs.schema_version, parsed.source_version
+                        );
+                        total_errors += 1;
+                        continue;
+                    }
+                    let payload = &parsed.payload;
 
                     // Insert into decrypted table
                     if let Err(e) = sqlx::query(
@@ -632,29 +521,31 @@ pub async fn admin_decrypt_observations(
                              prompt_index, correct, board_result_metadata, board_result_payload,
                              inferred_board_result, had_wrong_prompt, prompt_count,
                              student_bid, expected_bid, student_hand, full_auction,
-                             auction_so_far, skill_path, session_id, decrypted_json)
-                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                             auction_so_far, skill_path, session_id, schema_version,
+                             decrypted_json)
+                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                         "#,
                     )
                     .bind(&obs.id)
                     .bind(&obs.user_id)
                     .bind(&obs.timestamp)
                     .bind(&obs.deal_subfolder)
-                    .bind(&obs.deal_number)
-                    .bind(prompt_index)
+                    .bind(obs.deal_number)
+                    .bind(payload.prompt_index())
                     .bind(obs.correct)
                     .bind(&obs.board_result)
-                    .bind(&board_result_payload)
-                    .bind(&inferred_board_result)
-                    .bind(had_wrong_prompt as i32)
-                    .bind(prompt_count)
-                    .bind(student_bid)
-                    .bind(expected_bid)
-                    .bind(&student_hand)
-                    .bind(&full_auction)
-                    .bind(&auction_so_far)
+                    .bind(&payload.board_result)
+                    .bind(payload.inferred_board_result(obs.correct))
+                    .bind(payload.had_wrong_prompt() as i32)
+                    .bind(payload.prompts.len() as i64)
+                    .bind(payload.result.student_bid.as_deref().unwrap_or(""))
+                    .bind(payload.bid_prompt.expected_bid.as_deref().unwrap_or(""))
+                    .bind(payload.student_hand())
+                    .bind(payload.deal.full_auction.as_deref().unwrap_or(""))
+                    .bind(payload.auction_so_far())
                     .bind(&obs.skill_path)
-                    .bind(session_id)
+                    .bind(&payload.session_id)
+                    .bind(parsed.source_version)
                     .bind(&json_str)
                     .execute(&state.db)
                     .await
@@ -681,13 +572,13 @@ pub async fn admin_decrypt_observations(
         users_processed, total_decrypted, users_skipped, total_errors
     );
 
-    Ok(Json(DecryptObservationsResponse {
+    Ok(DecryptObservationsResponse {
         success: true,
         users_processed,
         observations_decrypted: total_decrypted,
         users_skipped,
         errors: total_errors,
-    }))
+    })
 }
 
 /// Get disk usage using df command (macOS/Linux compatible)
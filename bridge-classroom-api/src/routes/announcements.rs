@@ -1,19 +1,14 @@
 use axum::{
     extract::State,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
 
+use crate::policy::RequireAdmin;
 use crate::AppState;
 
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 // ---- Types ----
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -72,25 +67,33 @@ pub async fn get_active_announcement(
     }))
 }
 
-/// POST /api/admin/announcement — requires API key
+/// POST /api/admin/announcement — admins only
 pub async fn set_announcement(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    RequireAdmin(admin): RequireAdmin,
     Json(body): Json<SetAnnouncementRequest>,
 ) -> Result<Json<AnnouncementResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let row = publish_announcement(&state.db, &admin.user_id, &body).await?;
+
+    Ok(Json(AnnouncementResponse {
+        success: true,
+        announcement: Some(row),
+    }))
+}
 
+/// Replace the active announcement. Shared by `set_announcement` and the
+/// admin CLI; `created_by` is the admin's user id (or an operator label).
+pub async fn publish_announcement(
+    db: &Pool<Sqlite>,
+    created_by: &str,
+    body: &SetAnnouncementRequest,
+) -> Result<AnnouncementRow, (StatusCode, String)> {
     if body.message.trim().is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Message cannot be empty".to_string()));
     }
 
     // Deactivate all existing announcements
-    sqlx::query("UPDATE announcements SET active = 0")
-        .execute(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    deactivate_announcements(db).await?;
 
     // Insert new announcement
     let id = uuid::Uuid::new_v4().to_string();
@@ -99,45 +102,43 @@ pub async fn set_announcement(
     sqlx::query(
         r#"
         INSERT INTO announcements (id, message, type, created_by, created_at, expires_at, active)
-        VALUES (?, ?, ?, 'admin', ?, ?, 1)
+        VALUES (?, ?, ?, ?, ?, ?, 1)
         "#,
     )
     .bind(&id)
     .bind(body.message.trim())
     .bind(&body.announcement_type)
+    .bind(created_by)
     .bind(&now)
     .bind(&body.expires_at)
-    .execute(&state.db)
+    .execute(db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let row: AnnouncementRow = sqlx::query_as(
+    sqlx::query_as(
         "SELECT id, message, type, created_by, created_at, expires_at, active FROM announcements WHERE id = ?",
     )
     .bind(&id)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
 
-    Ok(Json(AnnouncementResponse {
-        success: true,
-        announcement: Some(row),
-    }))
+/// Turn off every announcement.
+pub async fn deactivate_announcements(db: &Pool<Sqlite>) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE announcements SET active = 0")
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(())
 }
 
-/// DELETE /api/admin/announcement — requires API key
+/// DELETE /api/admin/announcement — admins only
 pub async fn clear_announcement(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    sqlx::query("UPDATE announcements SET active = 0")
-        .execute(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    deactivate_announcements(&state.db).await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
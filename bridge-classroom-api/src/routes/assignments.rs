@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 
@@ -9,16 +9,10 @@ use crate::models::{
     AssignmentListResponse, AssignmentQuery, CreateAssignmentRequest, CreateAssignmentResponse,
     StudentAssignmentProgress,
 };
+use crate::policy::{authorize, ClassroomPermission, Relation, RequireTeacher};
+use crate::session::AuthUser;
 use crate::AppState;
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 // ---- Helper structs for queries ----
 
 #[derive(sqlx::FromRow)]
@@ -30,6 +24,7 @@ struct AssignmentRow {
     assigned_by: String,
     assigned_at: String,
     due_at: Option<String>,
+    #[allow(dead_code)]
     sort_order: Option<i32>,
     exercise_name: String,
     classroom_name: Option<String>,
@@ -47,13 +42,9 @@ struct StudentMemberRow {
 /// POST /api/assignments — Create an assignment
 pub async fn create_assignment(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    RequireTeacher(caller): RequireTeacher,
     Json(req): Json<CreateAssignmentRequest>,
 ) -> Result<Json<CreateAssignmentResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     // Validate: exactly one of classroom_id or student_id must be set
     match (&req.classroom_id, &req.student_id) {
         (Some(_), Some(_)) => {
@@ -71,25 +62,19 @@ pub async fn create_assignment(
         _ => {}
     }
 
-    // Verify teacher role
-    let role: Option<String> =
-        sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
-            .bind(&req.assigned_by)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    match role.as_deref() {
-        Some("teacher") | Some("admin") => {}
-        Some(_) => {
-            return Err((
-                StatusCode::FORBIDDEN,
-                "Only teachers can create assignments".to_string(),
-            ));
-        }
-        None => {
-            return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    // A classroom assignment must target a classroom the caller staffs;
+    // a direct one, a student whose progress they can see.
+    match (&req.classroom_id, &req.student_id) {
+        (Some(cid), _) => {
+            authorize(
+                &state.db,
+                &caller,
+                Relation::ClassroomStaff(cid, ClassroomPermission::CreateAssignments),
+            )
+            .await?
         }
+        (_, Some(sid)) => authorize(&state.db, &caller, Relation::ProgressViewer(sid)).await?,
+        _ => {}
     }
 
     // Verify exercise exists
@@ -135,7 +120,7 @@ pub async fn create_assignment(
     .bind(&req.exercise_id)
     .bind(&req.classroom_id)
     .bind(&req.student_id)
-    .bind(&req.assigned_by)
+    .bind(&caller.user_id)
     .bind(&now)
     .bind(&req.due_at)
     .bind(req.sort_order)
@@ -187,7 +172,7 @@ pub async fn create_assignment(
             classroom_name,
             student_id: req.student_id,
             student_name,
-            assigned_by: req.assigned_by,
+            assigned_by: caller.user_id,
             assigned_at: now,
             due_at: req.due_at,
             total_boards: board_count,
@@ -209,25 +194,29 @@ pub async fn create_assignment(
 /// - classroom_id: list assignments for this classroom
 pub async fn list_assignments(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<AssignmentQuery>,
 ) -> Result<Json<AssignmentListResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     // Student view: get assignments for this student (direct + via classroom membership)
     if let Some(ref student_id) = query.student_id {
+        authorize(&state.db, &caller, Relation::ProgressViewer(student_id)).await?;
         return list_student_assignments(&state, student_id).await;
     }
 
     // Teacher view: get assignments created by this teacher
     if let Some(ref assigned_by) = query.assigned_by {
+        authorize(&state.db, &caller, Relation::SelfUser(assigned_by)).await?;
         return list_teacher_assignments(&state, assigned_by).await;
     }
 
     // Classroom view: get assignments for a specific classroom
     if let Some(ref classroom_id) = query.classroom_id {
+        authorize(
+            &state.db,
+            &caller,
+            Relation::ClassroomStaff(classroom_id, ClassroomPermission::ViewProgress),
+        )
+        .await?;
         return list_classroom_assignments(&state, classroom_id).await;
     }
 
@@ -623,13 +612,9 @@ async fn compute_student_progress(
 /// GET /api/assignments/:id — Get assignment detail with per-student progress
 pub async fn get_assignment(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _caller: AuthUser,
     Path(assignment_id): Path<String>,
 ) -> Result<Json<AssignmentDetailResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let row = sqlx::query_as::<_, AssignmentRow>(
         r#"
         SELECT a.id, a.exercise_id, a.classroom_id, a.student_id, a.assigned_by,
@@ -827,15 +812,14 @@ pub async fn get_assignment(
 /// DELETE /api/assignments/:id — Delete assignment (teacher only)
 pub async fn delete_assignment(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(assignment_id): Path<String>,
 ) -> Result<Json<AssignmentActionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let result = sqlx::query("DELETE FROM assignments WHERE id = ?")
+    // Only the assigning teacher (or an admin) may delete.
+    let result = sqlx::query("DELETE FROM assignments WHERE id = ? AND (assigned_by = ? OR ?)")
         .bind(&assignment_id)
+        .bind(&caller.user_id)
+        .bind(caller.is_admin())
         .execute(&state.db)
         .await
         .map_err(|e| {
@@ -5,11 +5,38 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{
+    credentials,
+    email::templates,
+    models::User,
+    policy::{self, RequireTeacher, Role},
+    routes::recovery::{
+        decrypt_for_recovery, ClientIp, frontend_base_url,
+        generate_recovery_token, hash_token, rate_limit_allow,
+    },
+    session, AppState,
+};
+
+/// Throttle reset-link SENDS per email and per client IP, same limits as
+/// recovery requests (§S4).
+const RESET_MAX_PER_EMAIL: u32 = 3;
+const RESET_EMAIL_WINDOW_SECS: u64 = 900; // 15 minutes
+const RESET_MAX_PER_IP: u32 = 10;
+const RESET_IP_WINDOW_SECS: u64 = 3600; // 1 hour
+
+static RESET_EMAIL_LIMITER: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+static RESET_IP_LIMITER: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
 
 /// Request body for teacher authentication
 #[derive(Debug, Deserialize)]
 pub struct TeacherAuthRequest {
+    pub email: String,
     pub password: String,
 }
 
@@ -18,8 +45,117 @@ pub struct TeacherAuthRequest {
 pub struct TeacherAuthResponse {
     pub success: bool,
     pub message: String,
+    pub session_token: String,
+    pub expires_at: i64,
+    pub user_id: String,
+    pub role: String,
+}
+
+/// Request body for setting or changing a teacher password
+#[derive(Debug, Deserialize)]
+pub struct SetTeacherPasswordRequest {
+    /// Required when a password is already set
+    pub current_password: Option<String>,
+    pub new_password: String,
+}
+
+/// Request body for emailing a password-reset link
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+/// Request body for completing a password reset from the emailed link
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetClaim {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Generic success response for the credential-management endpoints
+#[derive(Debug, Serialize)]
+pub struct CredentialActionResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct CredentialRow {
+    user_id: String,
+    role: String,
+    password_hash: String,
+    locked_until: Option<String>,
+}
+
+/// Request body for opening a per-user session
+#[derive(Debug, Deserialize)]
+pub struct SessionProofRequest {
+    pub user_id: String,
+    /// The user's observation secret key — proves possession of the account
+    pub secret_key: String,
+}
+
+/// A freshly minted session token
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub success: bool,
+    pub session_token: String,
+    pub expires_at: i64,
+    pub user_id: String,
+    pub role: String,
+}
+
+impl SessionResponse {
+    pub fn issue(state: &AppState, user_id: &str, role: &str) -> Self {
+        let (session_token, expires_at) = session::issue(&state.config, user_id, role);
+        SessionResponse {
+            success: true,
+            session_token,
+            expires_at,
+            user_id: user_id.to_string(),
+            role: role.to_string(),
+        }
+    }
+}
+
+/// Check `secret_key` against the user's recovery-encrypted copy.
+///
+/// Accounts registered before recovery existed have no copy on file, so
+/// there is nothing to check against: that is a 401 pointing at email
+/// recovery. Enrolling whatever key was presented would let anyone who
+/// knows a user id take the account. A recovery claim proves the email
+/// and returns a session, after which the device's sync enrolls its key.
+pub async fn verify_user_secret(
+    state: &AppState,
+    user: &User,
+    secret_key: &str,
+) -> Result<bool, (StatusCode, String)> {
+    let Some(recovery_secret) = state.config.recovery_secret.as_ref() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Sessions require RECOVERY_SECRET to be configured".to_string(),
+        ));
+    };
+
+    match &user.recovery_encrypted_key {
+        Some(encrypted) => {
+            let stored = decrypt_for_recovery(encrypted, recovery_secret).map_err(|e| {
+                tracing::error!("Failed to decrypt recovery key for {}: {}", user.id, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to verify key".to_string())
+            })?;
+            Ok(session::secrets_match(&stored, secret_key))
+        }
+        None => {
+            tracing::warn!("Session proof for {}, which has no key on file", user.id);
+            Err((StatusCode::UNAUTHORIZED, NO_KEY_ON_FILE.to_string()))
+        }
+    }
 }
 
+/// The 401 for an account with no key on file (see [`verify_user_secret`]).
+pub const NO_KEY_ON_FILE: &str =
+    "No key is on file for this account; recover it by email to sign in";
+
 /// Validate API key from request headers
 fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
     if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
@@ -29,7 +165,11 @@ fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
 }
 
 /// POST /api/auth/teacher
-/// Authenticate teacher with password
+/// Authenticate a teacher with their own email + password and open a session.
+///
+/// Unknown email, missing credential and wrong password all get the same
+/// 401. After `MAX_FAILED_ATTEMPTS` failures the credential locks for
+/// `LOCKOUT_MINUTES` (429), even for the right password.
 pub async fn authenticate_teacher(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -40,13 +180,434 @@ pub async fn authenticate_teacher(
         return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
     }
 
-    // Check password
-    if req.password == state.config.teacher_password {
-        Ok(Json(TeacherAuthResponse {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid email or password".to_string());
+
+    let row = sqlx::query_as::<_, CredentialRow>(
+        r#"
+        SELECT u.id AS user_id, u.role, c.password_hash, c.locked_until
+        FROM users u JOIN teacher_credentials c ON c.user_id = u.id
+        WHERE u.email = ? COLLATE NOCASE
+        "#,
+    )
+    .bind(req.email.trim())
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(row) = row else {
+        // Take as long as a wrong password would, so the timing doesn't
+        // tell which emails have a teacher account.
+        credentials::verify_dummy_password(&req.password);
+        return Err(invalid());
+    };
+
+    let now = chrono::Utc::now();
+    if credentials::is_locked(row.locked_until.as_deref(), now) {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many failed attempts. Try again later or reset your password.".to_string(),
+        ));
+    }
+
+    if !credentials::verify_password(&req.password, &row.password_hash) {
+        let failed: i64 = sqlx::query_scalar(
+            "UPDATE teacher_credentials SET failed_attempts = failed_attempts + 1 WHERE user_id = ? RETURNING failed_attempts",
+        )
+        .bind(&row.user_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        if let Some(until) = credentials::lock_after_failure(failed, now) {
+            tracing::warn!("Teacher credential for {} locked after {} failures", row.user_id, failed);
+            sqlx::query(
+                "UPDATE teacher_credentials SET failed_attempts = 0, locked_until = ? WHERE user_id = ?",
+            )
+            .bind(until.to_rfc3339())
+            .bind(&row.user_id)
+            .execute(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        }
+        return Err(invalid());
+    }
+
+    // A credential outlives a demotion; only current teachers get in.
+    let role = policy::resolve_role(&state.db, &row.user_id, &row.role).await;
+    if Role::parse(&role) < Role::Teacher {
+        return Err(invalid());
+    }
+
+    sqlx::query(
+        "UPDATE teacher_credentials SET failed_attempts = 0, locked_until = NULL WHERE user_id = ?",
+    )
+    .bind(&row.user_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (session_token, expires_at) = session::issue(&state.config, &row.user_id, &role);
+    Ok(Json(TeacherAuthResponse {
+        success: true,
+        message: "Authentication successful".to_string(),
+        session_token,
+        expires_at,
+        user_id: row.user_id,
+        role,
+    }))
+}
+
+/// Store a fresh hash for `user_id` and clear any lockout.
+async fn store_password(
+    state: &AppState,
+    user_id: &str,
+    password: &str,
+) -> Result<(), (StatusCode, String)> {
+    credentials::validate_new_password(password).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let hash = credentials::hash_password(password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    sqlx::query(
+        r#"
+        INSERT INTO teacher_credentials (user_id, password_hash, failed_attempts, locked_until, updated_at)
+        VALUES (?, ?, 0, NULL, ?)
+        ON CONFLICT(user_id) DO UPDATE SET
+            password_hash = excluded.password_hash,
+            failed_attempts = 0,
+            locked_until = NULL,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(&hash)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(())
+}
+
+/// POST /api/auth/teacher/password
+/// Set (first time) or change the caller's teacher password. Changing an
+/// existing password requires the current one.
+pub async fn set_teacher_password(
+    State(state): State<AppState>,
+    RequireTeacher(caller): RequireTeacher,
+    Json(req): Json<SetTeacherPasswordRequest>,
+) -> Result<Json<CredentialActionResponse>, (StatusCode, String)> {
+    let existing: Option<String> =
+        sqlx::query_scalar("SELECT password_hash FROM teacher_credentials WHERE user_id = ?")
+            .bind(&caller.user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Some(hash) = existing {
+        let current = req.current_password.as_deref().unwrap_or("");
+        if !credentials::verify_password(current, &hash) {
+            return Err((StatusCode::UNAUTHORIZED, "Current password is incorrect".to_string()));
+        }
+    }
+
+    store_password(&state, &caller.user_id, &req.new_password).await?;
+    tracing::info!("Teacher password set for {}", caller.user_id);
+
+    Ok(Json(CredentialActionResponse {
+        success: true,
+        message: "Password updated".to_string(),
+    }))
+}
+
+/// POST /api/auth/teacher/reset-request
+/// Email a one-time password-reset link. Always answers the same way so
+/// the endpoint can't be used to find teacher addresses.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Json(req): Json<PasswordResetRequest>,
+) -> Result<Json<CredentialActionResponse>, (StatusCode, String)> {
+    let reply = || {
+        Ok(Json(CredentialActionResponse {
             success: true,
-            message: "Authentication successful".to_string(),
+            message: "If that address belongs to a teacher account, a reset link is on its way."
+                .to_string(),
         }))
-    } else {
-        Err((StatusCode::UNAUTHORIZED, "Invalid password".to_string()))
+    };
+
+    let email_key = req.email.trim().to_lowercase();
+    if email_key.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Email is required".to_string()));
+    }
+
+    let user = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT id, first_name, role FROM users WHERE email = ? COLLATE NOCASE",
+    )
+    .bind(&email_key)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some((user_id, first_name, role)) = user else {
+        return reply();
+    };
+    // An admin viewer who hasn't verified their email yet can still be
+    // sent a link: it goes to that address, and using it verifies it.
+    if Role::parse(&policy::resolve_role(&state.db, &user_id, &role).await) < Role::Teacher
+        && !policy::is_admin_viewer(&state.db, &user_id).await
+    {
+        return reply();
+    }
+
+    if !rate_limit_allow(&RESET_EMAIL_LIMITER, &email_key, RESET_MAX_PER_EMAIL, RESET_EMAIL_WINDOW_SECS)
+        || !rate_limit_allow(&RESET_IP_LIMITER, &ip, RESET_MAX_PER_IP, RESET_IP_WINDOW_SECS)
+    {
+        tracing::warn!("Password reset throttled (email={}, ip={})", email_key, ip);
+        return reply();
+    }
+
+    let token = generate_recovery_token();
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::hours(credentials::RESET_TOKEN_HOURS);
+
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = ?")
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    sqlx::query(
+        r#"
+        INSERT INTO password_reset_tokens (id, user_id, token_hash, created_at, expires_at, used)
+        VALUES (?, ?, ?, ?, ?, 0)
+        "#,
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(&user_id)
+    .bind(hash_token(&token))
+    .bind(now.to_rfc3339())
+    .bind(expires_at.to_rfc3339())
+    .execute(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let reset_url = format!("{}?reset_password={}", frontend_base_url(&state, &headers), token);
+    let email = templates::teacher_password_reset(
+        &email_key,
+        &first_name,
+        &reset_url,
+        credentials::RESET_TOKEN_HOURS,
+    );
+    match state.config.mailer.as_ref() {
+        Some(mailer) => {
+            if let Err(e) = mailer.send(&email).await {
+                tracing::error!("Failed to send password reset email for {}: {}", user_id, e);
+            }
+        }
+        None => {
+            // Same break-glass as recovery: no email service, print the link.
+            tracing::warn!("No email transport configured - logging password reset link instead");
+            println!("\n{}", "=".repeat(70));
+            println!("TEACHER PASSWORD RESET LINK (email not configured)");
+            println!("User: {} ({})", first_name, email_key);
+            println!("Link: {}", reset_url);
+            println!("Expires: {}", expires_at.to_rfc3339());
+            println!("{}\n", "=".repeat(70));
+        }
+    }
+
+    reply()
+}
+
+/// POST /api/auth/teacher/reset
+/// Complete a reset from the emailed link: burn the token, store the new
+/// password and clear any lockout.
+pub async fn reset_teacher_password(
+    State(state): State<AppState>,
+    Json(req): Json<PasswordResetClaim>,
+) -> Result<Json<CredentialActionResponse>, (StatusCode, String)> {
+    credentials::validate_new_password(&req.new_password)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let row: Option<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT id, user_id FROM password_reset_tokens
+        WHERE token_hash = ? AND used = 0 AND expires_at > ?
+        "#,
+    )
+    .bind(hash_token(&req.token))
+    .bind(&now)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some((token_id, user_id)) = row else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired reset link".to_string()));
+    };
+
+    // Claim the token atomically so a replayed link can't race the first use.
+    let marked = sqlx::query("UPDATE password_reset_tokens SET used = 1 WHERE id = ? AND used = 0")
+        .bind(&token_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if marked.rows_affected() == 0 {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired reset link".to_string()));
+    }
+
+    store_password(&state, &user_id, &req.new_password).await?;
+    // The link was emailed to the account's address.
+    policy::mark_email_verified(&state.db, &user_id).await?;
+    tracing::info!("Teacher password reset for {}", user_id);
+
+    Ok(Json(CredentialActionResponse {
+        success: true,
+        message: "Password reset. You can now sign in.".to_string(),
+    }))
+}
+
+/// POST /api/sessions
+/// Trade proof of the user's secret key for a signed session token
+pub async fn create_session(
+    State(state): State<AppState>,
+    Json(req): Json<SessionProofRequest>,
+) -> Result<Json<SessionResponse>, (StatusCode, String)> {
+    if req.user_id.is_empty() || req.secret_key.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "user_id and secret_key are required".to_string(),
+        ));
+    }
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(&req.user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to query user {}: {}", req.user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+
+    // Same response for unknown user and wrong key — don't confirm ids.
+    let Some(user) = user else {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+    };
+    if !verify_user_secret(&state, &user, &req.secret_key).await? {
+        tracing::warn!("Session proof failed for user {}", user.id);
+        return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+    }
+
+    let role = policy::resolve_role(&state.db, &user.id, &user.role).await;
+    Ok(Json(SessionResponse::issue(&state, &user.id, &role)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::email::testing::{mail_dir, sent_emails};
+    use crate::email::EmailTransport;
+    use std::sync::Arc;
+
+    const EMAIL: &str = "ann.teacher@example.com";
+
+    async fn sign_in(
+        state: &AppState,
+        password: &str,
+    ) -> Result<Json<TeacherAuthResponse>, (StatusCode, String)> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", state.config.api_key.parse().unwrap());
+        let req = TeacherAuthRequest {
+            email: EMAIL.to_uppercase(),
+            password: password.to_string(),
+        };
+        authenticate_teacher(State(state.clone()), headers, Json(req)).await
+    }
+
+    #[tokio::test]
+    async fn repeated_failures_lock_the_credential_until_a_reset() {
+        let dir = mail_dir();
+        let mut config = Config::for_tests();
+        config.mailer = Some(
+            EmailTransport::Dir { path: dir.clone() }
+                .mailer(&config.from_email)
+                .unwrap(),
+        );
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+             VALUES ('t1', 'Ann', 'Lee', ?, 'teacher', '', '')",
+        )
+        .bind(EMAIL)
+        .execute(&state.db)
+        .await
+        .unwrap();
+        store_password(&state, "t1", "correct horse battery")
+            .await
+            .unwrap();
+
+        let Json(ok) = sign_in(&state, "correct horse battery").await.unwrap();
+        assert_eq!((ok.user_id.as_str(), ok.role.as_str()), ("t1", "teacher"));
+        for _ in 0..credentials::MAX_FAILED_ATTEMPTS {
+            let err = sign_in(&state, "wrong password!").await.unwrap_err();
+            assert_eq!(err.0, StatusCode::UNAUTHORIZED);
+        }
+        // Locked: even the right password is turned away.
+        let err = sign_in(&state, "correct horse battery").await.unwrap_err();
+        assert_eq!(err.0, StatusCode::TOO_MANY_REQUESTS);
+
+        // An unknown address gets the same answer, but only the teacher is
+        // emailed.
+        for email in [EMAIL, "nobody@example.com"] {
+            let Json(reply) = request_password_reset(
+                State(state.clone()),
+                ClientIp("10.0.0.1".to_string()),
+                HeaderMap::new(),
+                Json(PasswordResetRequest {
+                    email: email.to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+            assert!(reply.success);
+        }
+        let sent = sent_emails(&dir);
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].headers.contains(&format!("To: {}", EMAIL)));
+        let token = sent[0]
+            .text
+            .split("reset_password=")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .to_string();
+
+        let reset = |token: String, new_password: &str| {
+            reset_teacher_password(
+                State(state.clone()),
+                Json(PasswordResetClaim {
+                    token,
+                    new_password: new_password.to_string(),
+                }),
+            )
+        };
+        assert_eq!(
+            reset(token.clone(), "short").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert!(reset(token.clone(), "a brand new password").await.unwrap().0.success);
+        // The link works once.
+        assert_eq!(
+            reset(token, "another new password").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+
+        // The reset lifted the lock and replaced the password.
+        assert_eq!(
+            sign_in(&state, "correct horse battery").await.unwrap_err().0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert!(sign_in(&state, "a brand new password").await.is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
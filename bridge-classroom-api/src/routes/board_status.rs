@@ -1,17 +1,18 @@
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Sqlite};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::{BTreeMap, HashMap};
 
+use crate::policy::{authorize, Relation};
+use crate::rules::{self, RulesParams};
+use crate::session::AuthUser;
 use crate::AppState;
 
-const COOLDOWN_SECS: i64 = 3600; // 1 hour
-const ACHIEVEMENT_SPACING_DAYS: i64 = 6;
-
 // ---- Models ----
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -48,23 +49,13 @@ fn parse_timestamp(ts: &str) -> Option<DateTime<Utc>> {
 
 // ---- API Handler ----
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 /// GET /api/board-status?user_id=X&deal_subfolder=Y
 pub async fn get_board_status(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<BoardStatusQuery>,
 ) -> Result<Json<BoardStatusResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::ProgressViewer(&query.user_id)).await?;
 
     // FromRow on BoardStatusEntry means we can pull all the v2 columns
     // directly. The legacy `achievement` column is intentionally not
@@ -131,12 +122,10 @@ pub struct AssignmentStatusResponse {
 /// source for the assignment progress bar (no client-side observation query).
 pub async fn get_assignment_status(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<AssignmentStatusQuery>,
 ) -> Result<Json<AssignmentStatusResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::ProgressViewer(&query.user_id)).await?;
 
     let entries: Vec<AssignmentStatusEntry> = sqlx::query_as(
         r#"
@@ -171,15 +160,16 @@ pub async fn get_assignment_status(
 ///   3. `assignment_id` set → resolve to the underlying exercise, then 25% rule
 ///   4. None of the above → `Tame`
 ///
-/// The 25% rule: if fewer than 25% of the exercise's boards share the
-/// `deal_subfolder` of the board being recorded, wilderness is Wild.
-/// Otherwise Tame.
+/// The 25% rule: if fewer than 25% (the profile's `wild_share_below`) of
+/// the exercise's boards share the `deal_subfolder` of the board being
+/// recorded, wilderness is Wild. Otherwise Tame.
 pub async fn derive_wilderness(
     pool: &Pool<Sqlite>,
     deal_subfolder: Option<&str>,
     exercise_id: Option<&str>,
     assignment_id: Option<&str>,
     jungle: bool,
+    rules: &RulesParams,
 ) -> String {
     if jungle {
         return "Wild".to_string();
@@ -229,7 +219,7 @@ pub async fn derive_wilderness(
     match row {
         Ok((total, same_lesson)) if total > 0 => {
             let ratio = same_lesson as f64 / total as f64;
-            if ratio < 0.25 { "Wild".to_string() } else { "Tame".to_string() }
+            if ratio < rules.wild_share_below { "Wild".to_string() } else { "Tame".to_string() }
         }
         // Empty or missing exercise — default safe, treat as Tame.
         _ => "Tame".to_string(),
@@ -244,7 +234,9 @@ pub async fn derive_wilderness(
 // §7 (paws), §11 (wilderness). Replaces the old `recompute_board_status`
 // / `compute_achievement` pair with a single full-history walk that
 // produces both the per-observation `status` / `wilderness` fields and
-// the final `board_status` row in one pass.
+// the final `board_status` row in one pass. The walk's running state is
+// persisted with the row so the recompute workers can fold newly appended
+// observations in without replaying (`fold_board_observations`).
 //
 // The old `recompute_board_status` and its helpers are left untouched
 // for now; they're still wired to `observations.rs::submit_observations`
@@ -260,25 +252,290 @@ struct ObservationFullRow {
     wilderness: Option<String>,
 }
 
+/// Running state of the §5–§7 walk over one board's history. It is
+/// persisted on `board_status` after every walk, so an appended
+/// observation can be folded in without replaying the board
+/// (`fold_board_observations`).
+#[derive(Debug, Clone, Default)]
+struct BoardWalker {
+    last_error_date: Option<DateTime<Utc>>,
+    star_count: i64,
+    max_stars: i64,
+    last_star_update: Option<DateTime<Utc>>,
+    wild_achievement: Option<String>,
+}
+
+impl BoardWalker {
+    /// Advance the walk by one observation and return its §5 status.
+    /// `is_cold` is only consulted for a Wild clean_correct that could
+    /// still earn Fresh.
+    fn step(
+        &mut self,
+        rules: &RulesParams,
+        obs: &ObservationFullRow,
+        obs_ts: Option<DateTime<Utc>>,
+        wilderness: &str,
+        is_cold: impl FnOnce() -> bool,
+    ) -> &'static str {
+        let is_tame = wilderness == "Tame";
+
+        // Derive this observation's status (§5).
+        let obs_status: &str =
+            derive_obs_status_v2(obs, obs_ts, &mut self.last_error_date, rules.cooldown_secs);
+
+        // Star transitions (§6.2/§6.3). Tame failed/corrected reset the
+        // track; any clean_correct (Tame or Wild) can advance it.
+        if is_tame && (obs_status == "failed" || obs_status == "corrected") {
+            self.star_count = 0;
+            self.last_star_update = None;
+        } else if obs_status == "clean_correct" {
+            match (self.last_star_update, obs_ts) {
+                (None, Some(t)) => {
+                    self.last_star_update = Some(t);
+                    // star_count stays at 0 — track initiated, no star yet.
+                }
+                (Some(lsu), Some(cur)) if (cur - lsu).num_days() >= rules.achievement_spacing_days => {
+                    self.star_count += 1;
+                    self.last_star_update = Some(cur);
+                    if self.star_count > self.max_stars {
+                        self.max_stars = self.star_count;
+                    }
+                }
+                _ => {} // timestamps unparseable; skip
+            }
+        }
+        // close_correct and wild failed/corrected: no star change.
+
+        // Wild achievement transitions (§7.2).
+        if obs_status == "clean_correct"
+            && !is_tame
+            && self.wild_achievement.as_deref() != Some("Fresh")
+        {
+            if is_cold() {
+                self.wild_achievement = Some("Fresh".to_string());
+            } else if self.wild_achievement.is_none() {
+                self.wild_achievement = Some("Recent".to_string());
+            }
+        }
+
+        obs_status
+    }
+}
+
+/// How far a walk got: the number of observations folded, the last one in
+/// (timestamp, id) order, and the latest parseable timestamp among them.
+/// The next fold resumes from here.
+struct FoldMark<'a> {
+    folded_count: i64,
+    last_observation_id: Option<&'a str>,
+    last_observation_at: Option<&'a str>,
+    latest_observed_at: Option<DateTime<Utc>>,
+}
+
+fn observation_wilderness(obs: &ObservationFullRow) -> String {
+    obs.wilderness.clone()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "Tame".to_string())
+}
+
+/// A `board_status_events` row: one transition a walk step made.
+#[derive(Debug, Clone, PartialEq)]
+struct BoardEvent {
+    observation_id: String,
+    occurred_at: String,
+    kind: &'static str,
+    from_value: Option<String>,
+    to_value: Option<String>,
+}
+
+/// The timeline events for one walker step: `status_changed` when the §5
+/// status differs from the previous observation's, `star_earned` (§6.3),
+/// `track_reset` when a Tame failure clears an initiated star track
+/// (§6.2), and `paw_earned` (§7.2).
+fn step_events(
+    obs: &ObservationFullRow,
+    prev_status: &str,
+    status: &str,
+    before: &BoardWalker,
+    after: &BoardWalker,
+) -> Vec<BoardEvent> {
+    let event = |kind, from_value: Option<String>, to_value: Option<String>| BoardEvent {
+        observation_id: obs.id.clone(),
+        occurred_at: obs.timestamp.clone(),
+        kind,
+        from_value,
+        to_value,
+    };
+    let mut events = Vec::new();
+    if status != prev_status {
+        events.push(event(
+            "status_changed",
+            Some(prev_status.to_string()),
+            Some(status.to_string()),
+        ));
+    }
+    if after.star_count > before.star_count {
+        events.push(event(
+            "star_earned",
+            Some(before.star_count.to_string()),
+            Some(after.star_count.to_string()),
+        ));
+    }
+    if before.last_star_update.is_some() && after.last_star_update.is_none() {
+        events.push(event(
+            "track_reset",
+            Some(before.star_count.to_string()),
+            Some("0".to_string()),
+        ));
+    }
+    if after.wild_achievement != before.wild_achievement {
+        events.push(event(
+            "paw_earned",
+            before.wild_achievement.clone(),
+            after.wild_achievement.clone(),
+        ));
+    }
+    events
+}
+
+/// Append `events` to the board's timeline. A full replay (`replay`)
+/// first supersedes the live events it no longer produces; events it
+/// reproduces stay as they are, so an unchanged replay writes nothing.
+#[allow(clippy::too_many_arguments)]
+async fn record_board_events(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    deal_subfolder: &str,
+    deal_number: i32,
+    rules_profile_id: &str,
+    events: &[BoardEvent],
+    replay: bool,
+) -> Result<(), String> {
+    let now = Utc::now().to_rfc3339();
+    if replay {
+        type LiveEvent = (i64, String, String, Option<String>, Option<String>, String);
+        let live: Vec<LiveEvent> = sqlx::query_as(
+            r#"
+            SELECT id, observation_id, kind, from_value, to_value, rules_profile_id
+            FROM board_status_events
+            WHERE user_id = ? AND deal_subfolder = ? AND deal_number = ?
+              AND superseded_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(deal_subfolder)
+        .bind(deal_number)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Board event fetch failed: {}", e))?;
+        for (id, observation_id, kind, from_value, to_value, profile_id) in live {
+            let reproduced = profile_id == rules_profile_id
+                && events.iter().any(|e| {
+                    e.observation_id == observation_id
+                        && e.kind == kind
+                        && e.from_value == from_value
+                        && e.to_value == to_value
+                });
+            if !reproduced {
+                sqlx::query("UPDATE board_status_events SET superseded_at = ? WHERE id = ?")
+                    .bind(&now)
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| format!("Board event supersede failed: {}", e))?;
+            }
+        }
+    }
+
+    // The live-row unique index turns a reproduced event into a no-op.
+    for event in events {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO board_status_events (
+                user_id, deal_subfolder, deal_number, observation_id, occurred_at,
+                kind, from_value, to_value, rules_profile_id, recorded_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(deal_subfolder)
+        .bind(deal_number)
+        .bind(&event.observation_id)
+        .bind(&event.occurred_at)
+        .bind(event.kind)
+        .bind(&event.from_value)
+        .bind(&event.to_value)
+        .bind(rules_profile_id)
+        .bind(&now)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Board event insert failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// The outcome of walking one board's history: each observation's
+/// (status, wilderness) in walk order, the timeline events, and the
+/// walker's final state.
+struct BoardWalk {
+    steps: Vec<(&'static str, String)>,
+    events: Vec<BoardEvent>,
+    walker: BoardWalker,
+    latest_observed_at: Option<DateTime<Utc>>,
+}
+
+/// Walk `observations` (already in (timestamp, id) order) under `rules`.
+/// Pure: the full recompute writes the result, the what-if replay doesn't.
+fn walk_board(observations: &[ObservationFullRow], rules: &RulesParams) -> BoardWalk {
+    let mut walk = BoardWalk {
+        steps: Vec::with_capacity(observations.len()),
+        events: Vec::new(),
+        walker: BoardWalker::default(),
+        latest_observed_at: None,
+    };
+    let mut prev_status = "not_attempted";
+    for (i, obs) in observations.iter().enumerate() {
+        let obs_ts = parse_timestamp(&obs.timestamp);
+        let wilderness = observation_wilderness(obs);
+        let before = walk.walker.clone();
+        let obs_status = walk.walker.step(rules, obs, obs_ts, &wilderness, || {
+            is_board_cold(observations, i, obs_ts, rules.achievement_spacing_days)
+        });
+        walk.events
+            .extend(step_events(obs, prev_status, obs_status, &before, &walk.walker));
+        prev_status = obs_status;
+        walk.latest_observed_at = walk.latest_observed_at.max(obs_ts);
+        walk.steps.push((obs_status, wilderness));
+    }
+    walk
+}
+
 /// Walk every observation for (user, board) in chronological order and
 /// recompute both the per-observation `status` / `wilderness` fields
-/// and the final `board_status` row.
+/// and the final `board_status` row, under the user's rules profile.
+/// The walk's transitions are appended to `board_status_events`,
+/// superseding earlier events the replay no longer produces.
 ///
 /// Idempotent: running it again on unchanged input produces unchanged
-/// output. Used by both the v2 backfill and (eventually) the runtime
-/// observation-insert path.
+/// output. Used by the v2 backfill, admin recomputes, and as the fallback
+/// whenever `fold_board_observations` can't take the incremental path.
 pub async fn recompute_board_history(
     pool: &Pool<Sqlite>,
     user_id: &str,
     deal_subfolder: &str,
     deal_number: i32,
 ) -> Result<(), String> {
+    let profile = rules::profile_for_user(pool, user_id).await?;
+
+    // (timestamp, id) so ties walk in a stable order — the incremental
+    // fold resumes from the same position.
     let observations: Vec<ObservationFullRow> = sqlx::query_as(
         r#"
         SELECT id, timestamp, correct, board_result, wilderness
         FROM observations
         WHERE user_id = ? AND deal_subfolder = ? AND deal_number = ?
-        ORDER BY timestamp ASC
+        ORDER BY timestamp ASC, id ASC
         "#,
     )
     .bind(user_id)
@@ -288,115 +545,374 @@ pub async fn recompute_board_history(
     .await
     .map_err(|e| format!("Observation fetch failed: {}", e))?;
 
-    if observations.is_empty() {
-        upsert_board_status_v2(
-            pool, user_id, deal_subfolder, deal_number,
-            "not_attempted", "Tame",
-            None, 0, 0, None, None, None,
+    let walk = walk_board(&observations, &profile.params);
+
+    // §C8: apply the per-observation status/wilderness rewrites, and the
+    // board_status row derived from them, in a single transaction.
+    // Previously each row was UPDATEd independently against the pool, so an
+    // error partway through left observations.status inconsistent with the
+    // derived history until the next sync happened to fix it.
+    let mut tx = pool.begin().await.map_err(|e| format!("begin recompute tx failed: {}", e))?;
+
+    for (obs, (obs_status, wilderness)) in observations.iter().zip(&walk.steps) {
+        sqlx::query(
+            r#"UPDATE observations SET status = ?, wilderness = ? WHERE id = ?"#,
         )
-        .await?;
-        return Ok(());
+        .bind(*obs_status)
+        .bind(wilderness)
+        .bind(&obs.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Observation update failed: {}", e))?;
     }
+    record_board_events(
+        &mut tx,
+        user_id,
+        deal_subfolder,
+        deal_number,
+        &profile.id,
+        &walk.events,
+        true,
+    )
+    .await?;
 
-    // Running state across the walk.
-    let mut last_error_date: Option<DateTime<Utc>> = None;
-    let mut star_count: i64 = 0;
-    let mut max_stars: i64 = 0;
-    let mut last_star_update: Option<DateTime<Utc>> = None;
-    let mut wild_achievement: Option<String> = None;
+    // The final observation's values feed the board_status row; an empty
+    // board falls back to not_attempted.
+    let (final_status, final_wilderness) = walk
+        .steps
+        .last()
+        .map(|(status, wilderness)| (*status, wilderness.as_str()))
+        .unwrap_or(("not_attempted", "Tame"));
+    let last = observations.last();
+    let mark = FoldMark {
+        folded_count: observations.len() as i64,
+        last_observation_id: last.map(|o| o.id.as_str()),
+        last_observation_at: last.map(|o| o.timestamp.as_str()),
+        latest_observed_at: walk.latest_observed_at,
+    };
+    upsert_board_status_v2(
+        &mut tx,
+        user_id,
+        deal_subfolder,
+        deal_number,
+        final_status,
+        final_wilderness,
+        &walk.walker,
+        &mark,
+        &profile.id,
+    )
+    .await?;
 
-    // Capture the final-observation values for the board_status upsert.
-    let mut final_status = String::from("not_attempted");
-    let mut final_wilderness = String::from("Tame");
-    let mut final_timestamp = String::new();
+    tx.commit().await.map_err(|e| format!("commit recompute tx failed: {}", e))
+}
 
-    // §C8: apply the per-observation status/wilderness rewrites in a single
-    // transaction. Previously each row was UPDATEd independently against the
-    // pool, so an error partway through left observations.status inconsistent
-    // with the derived history until the next sync happened to fix it.
-    let mut tx = pool.begin().await.map_err(|e| format!("begin recompute tx failed: {}", e))?;
+/// A board's derived state as a replay left it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoardOutcome {
+    pub status: String,
+    pub wilderness: String,
+    pub star_count: i64,
+    pub max_stars: i64,
+    pub wild_achievement: Option<String>,
+}
 
-    for (i, obs) in observations.iter().enumerate() {
-        let obs_ts = parse_timestamp(&obs.timestamp);
-        let wilderness = obs.wilderness.clone()
-            .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "Tame".to_string());
-        let is_tame = wilderness == "Tame";
+#[derive(Debug, sqlx::FromRow)]
+struct ReplayRow {
+    id: String,
+    timestamp: String,
+    correct: bool,
+    board_result: Option<String>,
+    deal_subfolder: String,
+    deal_number: i32,
+    exercise_id: Option<String>,
+    assignment_id: Option<String>,
+    jungle: bool,
+}
 
-        // Derive this observation's status (§5).
-        let obs_status: &str = derive_obs_status_v2(obs, obs_ts, &mut last_error_date);
+/// Replay every board `user_id` has observations on under `rules`, writing
+/// nothing. Wilderness is re-derived from each observation's context under
+/// the same rules rather than read back from the frozen column, so the
+/// result is what the history would have produced had `rules` been active
+/// all along. Backs the rules-profile what-if endpoint.
+pub async fn replay_user_boards(
+    pool: &Pool<Sqlite>,
+    user_id: &str,
+    rules: &RulesParams,
+) -> Result<BTreeMap<(String, i32), BoardOutcome>, String> {
+    let rows: Vec<ReplayRow> = sqlx::query_as(
+        r#"
+        SELECT id, timestamp, correct, board_result, deal_subfolder, deal_number,
+               exercise_id, assignment_id, jungle
+        FROM observations
+        WHERE user_id = ? AND deal_subfolder IS NOT NULL AND deal_number IS NOT NULL
+        ORDER BY deal_subfolder, deal_number, timestamp ASC, id ASC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Observation fetch failed: {}", e))?;
 
-        // Star transitions (§6.2/§6.3). Tame failed/corrected reset the
-        // track; any clean_correct (Tame or Wild) can advance it.
-        if is_tame && (obs_status == "failed" || obs_status == "corrected") {
-            star_count = 0;
-            last_star_update = None;
-        } else if obs_status == "clean_correct" {
-            match (last_star_update, obs_ts) {
-                (None, Some(t)) => {
-                    last_star_update = Some(t);
-                    // star_count stays at 0 — track initiated, no star yet.
-                }
-                (Some(lsu), Some(cur)) => {
-                    if (cur - lsu).num_days() >= ACHIEVEMENT_SPACING_DAYS {
-                        star_count += 1;
-                        last_star_update = Some(cur);
-                        if star_count > max_stars {
-                            max_stars = star_count;
-                        }
-                    }
-                }
-                _ => {} // timestamps unparseable; skip
+    // Observations share a handful of contexts; derive each once.
+    type Context = (String, Option<String>, Option<String>, bool);
+    let mut wilderness_by_context: HashMap<Context, String> = HashMap::new();
+    let mut boards: BTreeMap<(String, i32), Vec<ObservationFullRow>> = BTreeMap::new();
+    for row in rows {
+        let context = (
+            row.deal_subfolder.clone(),
+            row.exercise_id,
+            row.assignment_id,
+            row.jungle,
+        );
+        let wilderness = match wilderness_by_context.get(&context) {
+            Some(w) => w.clone(),
+            None => {
+                let w = derive_wilderness(
+                    pool,
+                    Some(&context.0),
+                    context.1.as_deref(),
+                    context.2.as_deref(),
+                    context.3,
+                    rules,
+                )
+                .await;
+                wilderness_by_context.insert(context, w.clone());
+                w
             }
-        }
-        // close_correct and wild failed/corrected: no star change.
+        };
+        boards
+            .entry((row.deal_subfolder, row.deal_number))
+            .or_default()
+            .push(ObservationFullRow {
+                id: row.id,
+                timestamp: row.timestamp,
+                correct: row.correct,
+                board_result: row.board_result,
+                wilderness: Some(wilderness),
+            });
+    }
 
-        // Wild achievement transitions (§7.2).
-        if obs_status == "clean_correct"
-            && !is_tame
-            && wild_achievement.as_deref() != Some("Fresh")
-        {
-            let cold = is_board_cold(&observations, i, obs_ts);
-            if cold {
-                wild_achievement = Some("Fresh".to_string());
-            } else if wild_achievement.is_none() {
-                wild_achievement = Some("Recent".to_string());
+    Ok(boards
+        .into_iter()
+        .map(|(board, observations)| {
+            let walk = walk_board(&observations, rules);
+            let (status, wilderness) = walk.steps.last().cloned().expect("non-empty board");
+            let outcome = BoardOutcome {
+                status: status.to_string(),
+                wilderness,
+                star_count: walk.walker.star_count,
+                max_stars: walk.walker.max_stars,
+                wild_achievement: walk.walker.wild_achievement,
+            };
+            (board, outcome)
+        })
+        .collect())
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PersistedWalk {
+    status: String,
+    last_error_date: Option<String>,
+    star_count: i64,
+    max_stars: i64,
+    last_star_update: Option<String>,
+    wild_achievement: Option<String>,
+    last_observation_at: Option<String>,
+    folded_count: Option<i64>,
+    last_observation_id: Option<String>,
+    latest_observed_at: Option<String>,
+    rules_profile_id: String,
+}
+
+/// Parse a persisted optional timestamp; `Err` if present but unreadable.
+fn parse_persisted(ts: &Option<String>) -> Result<Option<DateTime<Utc>>, ()> {
+    match ts {
+        None => Ok(None),
+        Some(t) => parse_timestamp(t).map(Some).ok_or(()),
+    }
+}
+
+/// Fold observations appended since the last walk into `board_status`,
+/// touching only the new rows: O(new) rather than O(history).
+///
+/// Falls back to `recompute_board_history` whenever the shortcut can't be
+/// proven equivalent to a full replay: no persisted walk yet, the number
+/// of observations before the resume point changed (out-of-order insert
+/// or deletion), or a new observation isn't strictly later than
+/// everything already folded (so the §7 cold-board check would need the
+/// history), or the user's rules profile changed since the last walk.
+/// Observations edited in place by an upsert aren't detectable here — the
+/// caller must ask for a full replay for those.
+pub async fn fold_board_observations(
+    pool: &Pool<Sqlite>,
+    user_id: &str,
+    deal_subfolder: &str,
+    deal_number: i32,
+) -> Result<(), String> {
+    let persisted: Option<PersistedWalk> = sqlx::query_as(
+        r#"
+        SELECT status, last_error_date, star_count, max_stars, last_star_update,
+               wild_achievement, last_observation_at, folded_count,
+               last_observation_id, latest_observed_at,
+               COALESCE(rules_profile_id, ?) AS rules_profile_id
+        FROM board_status
+        WHERE user_id = ? AND deal_subfolder = ? AND deal_number = ?
+        "#,
+    )
+    .bind(rules::DEFAULT_PROFILE_ID)
+    .bind(user_id)
+    .bind(deal_subfolder)
+    .bind(deal_number)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("board_status fetch failed: {}", e))?;
+
+    let full_replay = || recompute_board_history(pool, user_id, deal_subfolder, deal_number);
+
+    let Some(p) = persisted else {
+        return full_replay().await;
+    };
+    let profile = rules::profile_for_user(pool, user_id).await?;
+    if p.rules_profile_id != profile.id {
+        return full_replay().await;
+    }
+    let (Some(folded_count), Some(last_id), Some(last_at)) =
+        (p.folded_count, p.last_observation_id.as_deref(), p.last_observation_at.as_deref())
+    else {
+        return full_replay().await;
+    };
+    let (Ok(last_error_date), Ok(last_star_update), Ok(mut latest)) = (
+        parse_persisted(&p.last_error_date),
+        parse_persisted(&p.last_star_update),
+        parse_persisted(&p.latest_observed_at),
+    ) else {
+        return full_replay().await;
+    };
+
+    // Everything up to the resume point must be exactly what was folded.
+    let folded_now: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM observations
+        WHERE user_id = ? AND deal_subfolder = ? AND deal_number = ?
+          AND (timestamp < ? OR (timestamp = ? AND id <= ?))
+        "#,
+    )
+    .bind(user_id)
+    .bind(deal_subfolder)
+    .bind(deal_number)
+    .bind(last_at)
+    .bind(last_at)
+    .bind(last_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("Observation count failed: {}", e))?;
+    if folded_now != folded_count {
+        return full_replay().await;
+    }
+
+    let appended: Vec<ObservationFullRow> = sqlx::query_as(
+        r#"
+        SELECT id, timestamp, correct, board_result, wilderness
+        FROM observations
+        WHERE user_id = ? AND deal_subfolder = ? AND deal_number = ?
+          AND (timestamp > ? OR (timestamp = ? AND id > ?))
+        ORDER BY timestamp ASC, id ASC
+        "#,
+    )
+    .bind(user_id)
+    .bind(deal_subfolder)
+    .bind(deal_number)
+    .bind(last_at)
+    .bind(last_at)
+    .bind(last_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Observation fetch failed: {}", e))?;
+    if appended.is_empty() {
+        return Ok(());
+    }
+
+    // Each appended observation must be strictly later than everything
+    // before it; then the nearest earlier observation is `latest`, and the
+    // cold-board check needs nothing else.
+    let mut timestamps = Vec::with_capacity(appended.len());
+    let mut check = latest;
+    for obs in &appended {
+        match parse_timestamp(&obs.timestamp) {
+            Some(t) if check.is_none_or(|l| t > l) => {
+                timestamps.push(t);
+                check = Some(t);
             }
+            _ => return full_replay().await,
         }
+    }
 
-        // Write the per-observation fields.
-        sqlx::query(
-            r#"UPDATE observations SET status = ?, wilderness = ? WHERE id = ?"#,
-        )
-        .bind(obs_status)
-        .bind(&wilderness)
-        .bind(&obs.id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| format!("Observation update failed: {}", e))?;
+    let mut walker = BoardWalker {
+        last_error_date,
+        star_count: p.star_count,
+        max_stars: p.max_stars,
+        last_star_update,
+        wild_achievement: p.wild_achievement,
+    };
+    let mut final_status = "not_attempted";
+    let mut final_wilderness = String::from("Tame");
+    let mut prev_status = p.status;
+    let mut events = Vec::new();
 
-        final_status = obs_status.to_string();
+    let mut tx = pool.begin().await.map_err(|e| format!("begin fold tx failed: {}", e))?;
+    for (obs, ts) in appended.iter().zip(timestamps) {
+        let wilderness = observation_wilderness(obs);
+        let window_start = ts - chrono::Duration::days(profile.params.achievement_spacing_days);
+        let cold = latest.is_none_or(|l| l < window_start);
+        let before = walker.clone();
+        let obs_status = walker.step(&profile.params, obs, Some(ts), &wilderness, || cold);
+        events.extend(step_events(obs, &prev_status, obs_status, &before, &walker));
+        prev_status = obs_status.to_string();
+        latest = Some(ts);
+
+        sqlx::query(r#"UPDATE observations SET status = ?, wilderness = ? WHERE id = ?"#)
+            .bind(obs_status)
+            .bind(&wilderness)
+            .bind(&obs.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Observation update failed: {}", e))?;
+
+        final_status = obs_status;
         final_wilderness = wilderness;
-        final_timestamp = obs.timestamp.clone();
     }
+    record_board_events(
+        &mut tx,
+        user_id,
+        deal_subfolder,
+        deal_number,
+        &profile.id,
+        &events,
+        false,
+    )
+    .await?;
 
-    tx.commit().await.map_err(|e| format!("commit recompute tx failed: {}", e))?;
-
+    let last = appended.last().expect("non-empty");
+    let mark = FoldMark {
+        folded_count: folded_count + appended.len() as i64,
+        last_observation_id: Some(&last.id),
+        last_observation_at: Some(&last.timestamp),
+        latest_observed_at: latest,
+    };
     upsert_board_status_v2(
-        pool,
+        &mut tx,
         user_id,
         deal_subfolder,
         deal_number,
-        &final_status,
+        final_status,
         &final_wilderness,
-        last_error_date.map(|t| t.to_rfc3339()),
-        star_count,
-        max_stars,
-        last_star_update.map(|t| t.to_rfc3339()),
-        wild_achievement,
-        Some(&final_timestamp),
+        &walker,
+        &mark,
+        &profile.id,
     )
-    .await
+    .await?;
+
+    tx.commit().await.map_err(|e| format!("commit fold tx failed: {}", e))
 }
 
 fn effective_board_result_v2(obs: &ObservationFullRow) -> String {
@@ -421,6 +937,7 @@ fn derive_obs_status_v2(
     obs: &ObservationFullRow,
     obs_ts: Option<DateTime<Utc>>,
     last_error_date: &mut Option<DateTime<Utc>>,
+    cooldown_secs: i64,
 ) -> &'static str {
     let effective = effective_board_result_v2(obs);
     match effective.as_str() {
@@ -434,7 +951,7 @@ fn derive_obs_status_v2(
         }
         "correct" => {
             let within_cooldown = match (*last_error_date, obs_ts) {
-                (Some(led), Some(cur)) => (cur - led).num_seconds() < COOLDOWN_SECS,
+                (Some(led), Some(cur)) => (cur - led).num_seconds() < cooldown_secs,
                 _ => false,
             };
             if within_cooldown { "close_correct" } else { "clean_correct" }
@@ -479,6 +996,7 @@ pub async fn recompute_assignment_boards(
         Some(e) => e,
         None => return Ok(()), // orphaned assignment_id — nothing to roll up
     };
+    let cooldown_secs = rules::profile_for_user(pool, user_id).await?.params.cooldown_secs;
 
     let boards: Vec<(String, i32)> = sqlx::query_as(
         "SELECT deal_subfolder, deal_number FROM exercise_boards WHERE exercise_id = ?",
@@ -512,7 +1030,7 @@ pub async fn recompute_assignment_boards(
         let mut last_observation_at: Option<String> = None;
         for obs in &observations {
             let obs_ts = parse_timestamp(&obs.timestamp);
-            final_status = derive_obs_status_v2(obs, obs_ts, &mut last_error_date);
+            final_status = derive_obs_status_v2(obs, obs_ts, &mut last_error_date, cooldown_secs);
             last_observation_at = Some(obs.timestamp.clone());
         }
 
@@ -573,12 +1091,13 @@ fn is_board_cold(
     observations: &[ObservationFullRow],
     current_index: usize,
     current_ts: Option<DateTime<Utc>>,
+    spacing_days: i64,
 ) -> bool {
     let cur = match current_ts {
         Some(t) => t,
         None => return true,
     };
-    let window_start = cur - chrono::Duration::days(ACHIEVEMENT_SPACING_DAYS);
+    let window_start = cur - chrono::Duration::days(spacing_days);
     for (i, other) in observations.iter().enumerate() {
         if i == current_index {
             continue;
@@ -594,18 +1113,15 @@ fn is_board_cold(
 
 #[allow(clippy::too_many_arguments)]
 async fn upsert_board_status_v2(
-    pool: &Pool<Sqlite>,
+    conn: &mut SqliteConnection,
     user_id: &str,
     deal_subfolder: &str,
     deal_number: i32,
     status: &str,
     wilderness: &str,
-    last_error_date: Option<String>,
-    star_count: i64,
-    max_stars: i64,
-    last_star_update: Option<String>,
-    wild_achievement: Option<String>,
-    last_observation_at: Option<&str>,
+    walker: &BoardWalker,
+    mark: &FoldMark<'_>,
+    rules_profile_id: &str,
 ) -> Result<(), String> {
     let now = Utc::now().to_rfc3339();
     // The legacy `achievement` column is no longer used; we always
@@ -616,9 +1132,10 @@ async fn upsert_board_status_v2(
             user_id, deal_subfolder, deal_number,
             status, wilderness, last_error_date,
             star_count, max_stars, last_star_update, wild_achievement,
-            last_observation_at, updated_at, achievement
+            last_observation_at, updated_at, achievement,
+            folded_count, last_observation_id, latest_observed_at, rules_profile_id
         )
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'none')
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'none', ?, ?, ?, ?)
         ON CONFLICT(user_id, deal_subfolder, deal_number) DO UPDATE SET
             status              = excluded.status,
             wilderness          = excluded.wilderness,
@@ -628,7 +1145,11 @@ async fn upsert_board_status_v2(
             last_star_update    = excluded.last_star_update,
             wild_achievement    = excluded.wild_achievement,
             last_observation_at = excluded.last_observation_at,
-            updated_at          = excluded.updated_at
+            updated_at          = excluded.updated_at,
+            folded_count        = excluded.folded_count,
+            last_observation_id = excluded.last_observation_id,
+            latest_observed_at  = excluded.latest_observed_at,
+            rules_profile_id    = excluded.rules_profile_id
         "#,
     )
     .bind(user_id)
@@ -636,16 +1157,344 @@ async fn upsert_board_status_v2(
     .bind(deal_number)
     .bind(status)
     .bind(wilderness)
-    .bind(&last_error_date)
-    .bind(star_count)
-    .bind(max_stars)
-    .bind(&last_star_update)
-    .bind(&wild_achievement)
-    .bind(last_observation_at)
+    .bind(walker.last_error_date.map(|t| t.to_rfc3339()))
+    .bind(walker.star_count)
+    .bind(walker.max_stars)
+    .bind(walker.last_star_update.map(|t| t.to_rfc3339()))
+    .bind(&walker.wild_achievement)
+    .bind(mark.last_observation_at)
     .bind(&now)
-    .execute(pool)
+    .bind(mark.folded_count)
+    .bind(mark.last_observation_id)
+    .bind(mark.latest_observed_at.map(|t| t.to_rfc3339()))
+    .bind(rules_profile_id)
+    .execute(&mut *conn)
     .await
     .map_err(|e| format!("Upsert failed: {}", e))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use proptest::prelude::*;
+
+    const USER: &str = "u1";
+    const SUBFOLDER: &str = "Stayman";
+
+    #[derive(Debug, Clone)]
+    struct GenObs {
+        gap_minutes: i64,
+        correct: bool,
+        board_result: Option<&'static str>,
+        wild: bool,
+    }
+
+    fn gen_obs() -> impl Strategy<Value = GenObs> {
+        (
+            // Ties, same-session retries, next-day and across-the-spacing gaps.
+            prop_oneof![Just(0i64), 1i64..120, 1_200i64..4_320, 7_200i64..12_960],
+            any::<bool>(),
+            prop::option::of(prop::sample::select(vec!["correct", "failed", "corrected"])),
+            prop::bool::weighted(0.3),
+        )
+            .prop_map(|(gap_minutes, correct, board_result, wild)| GenObs {
+                gap_minutes,
+                correct,
+                board_result,
+                wild,
+            })
+    }
+
+    /// Observations in chronological order, the order they arrive in (a
+    /// permutation, so offline syncs can land out of order), and batch sizes.
+    fn gen_history() -> impl Strategy<Value = (Vec<GenObs>, Vec<usize>, Vec<usize>)> {
+        prop::collection::vec(gen_obs(), 1..24).prop_flat_map(|obs| {
+            let n = obs.len();
+            let arrival: Vec<usize> = (0..n).collect();
+            let in_order = Just(arrival.clone());
+            (
+                Just(obs),
+                prop_oneof![3 => in_order, 1 => Just(arrival).prop_shuffle()],
+                prop::collection::vec(1usize..5, n),
+            )
+        })
+    }
+
+    async fn insert_user(pool: &Pool<Sqlite>) {
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+             VALUES (?, 'Test', 'Student', 't@example.com', '2026-01-01', '2026-01-01')",
+        )
+        .bind(USER)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_obs(pool: &Pool<Sqlite>, id: &str, ts: &str, o: &GenObs) {
+        sqlx::query(
+            r#"
+            INSERT INTO observations (
+                id, user_id, timestamp, skill_path, correct, deal_subfolder,
+                deal_number, encrypted_data, iv, created_at, board_result, wilderness
+            )
+            VALUES (?, ?, ?, 'bidding', ?, ?, 1, '', '', ?, ?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(USER)
+        .bind(ts)
+        .bind(o.correct)
+        .bind(SUBFOLDER)
+        .bind(ts)
+        .bind(o.board_result)
+        .bind(if o.wild { "Wild" } else { "Tame" })
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    type Snapshot = (
+        Vec<(String, Option<String>, Option<String>)>,
+        Vec<String>,
+        Vec<String>,
+    );
+
+    /// Everything the walk writes, minus `updated_at` and event bookkeeping.
+    async fn snapshot(pool: &Pool<Sqlite>) -> Snapshot {
+        let observations = sqlx::query_as(
+            "SELECT id, status, wilderness FROM observations ORDER BY id",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let board: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT json_array(status, wilderness, last_error_date, star_count, max_stars,
+                              last_star_update, wild_achievement, last_observation_at,
+                              folded_count, last_observation_id, latest_observed_at)
+            FROM board_status
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let events: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT json_array(observation_id, occurred_at, kind, from_value, to_value)
+            FROM board_status_events
+            WHERE superseded_at IS NULL
+            ORDER BY observation_id, kind
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        (observations, board, events)
+    }
+
+    async fn fold_matches_full_replay(obs: Vec<GenObs>, arrival: Vec<usize>, batches: Vec<usize>) {
+        let full = crate::db::test_pool().await;
+        let folded = crate::db::test_pool().await;
+        insert_user(&full).await;
+        insert_user(&folded).await;
+
+        let base = Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap();
+        let mut at = base;
+        let timestamps: Vec<String> = obs
+            .iter()
+            .map(|o| {
+                at += chrono::Duration::minutes(o.gap_minutes);
+                at.to_rfc3339()
+            })
+            .collect();
+
+        let mut pending = arrival.into_iter().enumerate().peekable();
+        for size in batches {
+            if pending.peek().is_none() {
+                break;
+            }
+            for (seq, i) in pending.by_ref().take(size) {
+                // ids follow arrival, not chronology, so (timestamp, id)
+                // tie-breaks aren't trivially aligned.
+                let id = format!("obs-{:02}", seq);
+                insert_obs(&full, &id, &timestamps[i], &obs[i]).await;
+                insert_obs(&folded, &id, &timestamps[i], &obs[i]).await;
+            }
+            recompute_board_history(&full, USER, SUBFOLDER, 1).await.unwrap();
+            fold_board_observations(&folded, USER, SUBFOLDER, 1).await.unwrap();
+            assert_eq!(snapshot(&full).await, snapshot(&folded).await);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn incremental_fold_matches_full_replay((obs, arrival, batches) in gen_history()) {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(fold_matches_full_replay(obs, arrival, batches));
+        }
+    }
+
+    #[tokio::test]
+    async fn fold_only_touches_appended_observations() {
+        let pool = crate::db::test_pool().await;
+        insert_user(&pool).await;
+        let clean = GenObs {
+            gap_minutes: 0,
+            correct: true,
+            board_result: Some("correct"),
+            wild: false,
+        };
+        insert_obs(&pool, "a", "2026-01-05T09:00:00+00:00", &clean).await;
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+
+        // Scribble over the folded row: a fold must leave it alone.
+        sqlx::query("UPDATE observations SET status = 'sentinel' WHERE id = 'a'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        insert_obs(&pool, "b", "2026-01-12T09:00:00+00:00", &clean).await;
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+
+        let (observations, _, _) = snapshot(&pool).await;
+        assert_eq!(observations[0].1.as_deref(), Some("sentinel"));
+        assert_eq!(observations[1].1.as_deref(), Some("clean_correct"));
+        let (stars, folded): (i64, i64) = sqlx::query_as(
+            "SELECT star_count, folded_count FROM board_status WHERE user_id = ?",
+        )
+        .bind(USER)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!((stars, folded), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn profile_change_is_replayed_and_what_if_writes_nothing() {
+        let pool = crate::db::test_pool().await;
+        insert_user(&pool).await;
+        let clean = GenObs {
+            gap_minutes: 0,
+            correct: true,
+            board_result: Some("correct"),
+            wild: false,
+        };
+        insert_obs(&pool, "a", "2026-01-05T09:00:00+00:00", &clean).await;
+        insert_obs(&pool, "b", "2026-01-12T09:00:00+00:00", &clean).await;
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+
+        let strict = RulesParams {
+            achievement_spacing_days: 10,
+            ..RulesParams::default()
+        };
+        let profile = rules::create_profile(&pool, "strict", &strict, USER).await.unwrap();
+        sqlx::query(
+            "INSERT INTO classrooms (id, name, teacher_id, join_code, created_at, rules_profile_id) \
+             VALUES ('c1', 'Tuesday', ?, 'JOIN01', '', ?)",
+        )
+        .bind(USER)
+        .bind(&profile.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO classroom_members VALUES ('c1', ?, '')")
+            .bind(USER)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let board_row = || async {
+            sqlx::query_as::<_, (i64, Option<String>)>(
+                "SELECT star_count, rules_profile_id FROM board_status WHERE user_id = ?",
+            )
+            .bind(USER)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        };
+
+        let replayed = replay_user_boards(&pool, USER, &strict).await.unwrap();
+        assert_eq!(replayed[&(SUBFOLDER.to_string(), 1)].star_count, 0);
+        assert_eq!(board_row().await, (1, Some("default".to_string())));
+
+        // No new observations, but the profile moved: the fold replays.
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+        assert_eq!(board_row().await, (0, Some(profile.id)));
+    }
+
+    #[tokio::test]
+    async fn replay_supersedes_events_it_no_longer_produces() {
+        let pool = crate::db::test_pool().await;
+        insert_user(&pool).await;
+        let clean = GenObs {
+            gap_minutes: 0,
+            correct: true,
+            board_result: Some("correct"),
+            wild: false,
+        };
+        let failed = GenObs {
+            correct: false,
+            board_result: Some("failed"),
+            ..clean.clone()
+        };
+        insert_obs(&pool, "a", "2026-01-05T09:00:00+00:00", &clean).await;
+        insert_obs(&pool, "b", "2026-01-12T09:00:00+00:00", &clean).await;
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+
+        let live = || async {
+            sqlx::query_as::<_, (String, String, Option<String>)>(
+                "SELECT observation_id, kind, to_value FROM board_status_events \
+                 WHERE superseded_at IS NULL ORDER BY occurred_at, kind",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+        };
+        let event = |obs: &str, kind: &str, to: &str| {
+            (obs.to_string(), kind.to_string(), Some(to.to_string()))
+        };
+        assert_eq!(
+            live().await,
+            vec![
+                event("a", "status_changed", "clean_correct"),
+                event("b", "star_earned", "1"),
+            ]
+        );
+
+        // A failure arriving late, between the two: the star never happens.
+        insert_obs(&pool, "c", "2026-01-08T09:00:00+00:00", &failed).await;
+        fold_board_observations(&pool, USER, SUBFOLDER, 1).await.unwrap();
+        assert_eq!(
+            live().await,
+            vec![
+                event("a", "status_changed", "clean_correct"),
+                event("c", "status_changed", "failed"),
+                event("c", "track_reset", "0"),
+                event("b", "status_changed", "clean_correct"),
+            ]
+        );
+        let superseded: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM board_status_events WHERE superseded_at IS NOT NULL",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(superseded, 1);
+
+        // Replaying unchanged history appends nothing.
+        recompute_board_history(&pool, USER, SUBFOLDER, 1).await.unwrap();
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM board_status_events")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(total, 5);
+    }
+}
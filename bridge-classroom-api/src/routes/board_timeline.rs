@@ -0,0 +1,318 @@
+//! Board-status timeline.
+//!
+//! `board_status` only holds each board's latest state; the walk in
+//! `board_status.rs` also appends every transition it makes to
+//! `board_status_events`. This endpoint reads that log back for one
+//! student, optionally narrowed to a lesson or a single board, with
+//! running totals on each event so a progress chart can plot it directly.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::policy::{authorize, Relation};
+use crate::session::AuthUser;
+use crate::AppState;
+
+const DEFAULT_LIMIT: usize = 1000;
+const MAX_LIMIT: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct BoardTimelineQuery {
+    pub user_id: String,
+    /// One lesson's boards...
+    pub deal_subfolder: Option<String>,
+    /// ...or one board of it.
+    pub deal_number: Option<i32>,
+    /// Only events at or after this timestamp (totals still count
+    /// everything before it).
+    pub since: Option<String>,
+    /// Events to return (default 1000, at most 5000), oldest first.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct EventRow {
+    deal_subfolder: String,
+    deal_number: i32,
+    observation_id: String,
+    occurred_at: String,
+    kind: String,
+    from_value: Option<String>,
+    to_value: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoardTimelineEvent {
+    pub deal_subfolder: String,
+    pub deal_number: i32,
+    pub observation_id: String,
+    pub occurred_at: String,
+    /// `status_changed`, `star_earned`, `track_reset` or `paw_earned`.
+    pub kind: String,
+    pub from_value: Option<String>,
+    pub to_value: Option<String>,
+    /// Sum of current star counts across the boards in scope, after this
+    /// event.
+    pub total_stars: i64,
+    /// Boards in scope with a Fresh paw, after this event.
+    pub fresh_paws: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoardTimelineResponse {
+    pub user_id: String,
+    pub events: Vec<BoardTimelineEvent>,
+    /// More events matched than `limit` allowed.
+    pub truncated: bool,
+}
+
+/// Running totals over the events of one scope, board by board.
+#[derive(Default)]
+struct Totals {
+    stars: HashMap<(String, i32), i64>,
+    fresh: HashMap<(String, i32), bool>,
+    total_stars: i64,
+    fresh_paws: i64,
+}
+
+impl Totals {
+    fn apply(&mut self, row: &EventRow) {
+        let board = (row.deal_subfolder.clone(), row.deal_number);
+        match row.kind.as_str() {
+            "star_earned" | "track_reset" => {
+                let count = row
+                    .to_value
+                    .as_deref()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                let previous = self.stars.insert(board, count).unwrap_or(0);
+                self.total_stars += count - previous;
+            }
+            "paw_earned" => {
+                let fresh = row.to_value.as_deref() == Some("Fresh");
+                let previous = self.fresh.insert(board, fresh).unwrap_or(false);
+                self.fresh_paws += fresh as i64 - previous as i64;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn build_timeline(
+    rows: Vec<EventRow>,
+    since: Option<&str>,
+    limit: usize,
+) -> (Vec<BoardTimelineEvent>, bool) {
+    let mut totals = Totals::default();
+    let mut events = Vec::new();
+    let mut truncated = false;
+    for row in rows {
+        totals.apply(&row);
+        if since.is_some_and(|since| row.occurred_at.as_str() < since) {
+            continue;
+        }
+        if events.len() == limit {
+            truncated = true;
+            break;
+        }
+        events.push(BoardTimelineEvent {
+            deal_subfolder: row.deal_subfolder,
+            deal_number: row.deal_number,
+            observation_id: row.observation_id,
+            occurred_at: row.occurred_at,
+            kind: row.kind,
+            from_value: row.from_value,
+            to_value: row.to_value,
+            total_stars: totals.total_stars,
+            fresh_paws: totals.fresh_paws,
+        });
+    }
+    (events, truncated)
+}
+
+/// GET /api/board-status/timeline?user_id=X[&deal_subfolder=Y[&deal_number=N]][&since=][&limit=]
+pub async fn get_board_timeline(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Query(query): Query<BoardTimelineQuery>,
+) -> Result<Json<BoardTimelineResponse>, (StatusCode, String)> {
+    authorize(&state.db, &caller, Relation::ProgressViewer(&query.user_id)).await?;
+    if query.deal_number.is_some() && query.deal_subfolder.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "deal_number requires deal_subfolder".to_string(),
+        ));
+    }
+
+    // Totals need the whole history in scope, so `since` is applied after.
+    let rows: Vec<EventRow> = sqlx::query_as(
+        r#"
+        SELECT deal_subfolder, deal_number, observation_id, occurred_at,
+               kind, from_value, to_value
+        FROM board_status_events
+        WHERE user_id = ?1 AND superseded_at IS NULL
+          AND (?2 IS NULL OR deal_subfolder = ?2)
+          AND (?3 IS NULL OR deal_number = ?3)
+        ORDER BY occurred_at ASC, id ASC
+        "#,
+    )
+    .bind(&query.user_id)
+    .bind(&query.deal_subfolder)
+    .bind(query.deal_number)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (events, truncated) = build_timeline(rows, query.since.as_deref(), limit);
+
+    Ok(Json(BoardTimelineResponse {
+        user_id: query.user_id,
+        events,
+        truncated,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn event(deal: i32, at: &str, kind: &str, to: &str) -> EventRow {
+        EventRow {
+            deal_subfolder: "Stayman".to_string(),
+            deal_number: deal,
+            observation_id: format!("obs-{}-{}", deal, at),
+            occurred_at: at.to_string(),
+            kind: kind.to_string(),
+            from_value: None,
+            to_value: Some(to.to_string()),
+        }
+    }
+
+    fn history() -> Vec<EventRow> {
+        vec![
+            event(1, "2026-01-01T09:00:00Z", "star_earned", "1"),
+            event(2, "2026-01-02T09:00:00Z", "paw_earned", "Fresh"),
+            event(1, "2026-01-08T09:00:00Z", "star_earned", "2"),
+            event(1, "2026-01-09T09:00:00Z", "track_reset", "0"),
+        ]
+    }
+
+    #[test]
+    fn since_skips_events_but_not_their_totals_and_limit_truncates() {
+        let (events, truncated) = build_timeline(history(), Some("2026-01-08T09:00:00Z"), 10);
+        assert!(!truncated);
+        let totals: Vec<(i64, i64)> = events
+            .iter()
+            .map(|e| (e.total_stars, e.fresh_paws))
+            .collect();
+        assert_eq!(totals, vec![(2, 1), (0, 1)]);
+
+        // Exactly `limit` events is not truncation; one more is.
+        let (events, truncated) = build_timeline(history(), None, 4);
+        assert_eq!((events.len(), truncated), (4, false));
+        let (events, truncated) = build_timeline(history(), None, 3);
+        assert_eq!((events.len(), truncated), (3, true));
+
+        // A malformed star count is treated as zero.
+        let (events, _) = build_timeline(
+            vec![event(1, "2026-01-01T09:00:00Z", "star_earned", "x")],
+            None,
+            10,
+        );
+        assert_eq!(events[0].total_stars, 0);
+    }
+
+    #[tokio::test]
+    async fn timeline_requests_are_checked() {
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for id in ["u1", "u2"] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+                 VALUES (?, 'Test', 'Student', ?, '', '')",
+            )
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        for row in history() {
+            sqlx::query(
+                "INSERT INTO board_status_events (user_id, deal_subfolder, deal_number, \
+                 observation_id, occurred_at, kind, to_value, rules_profile_id, recorded_at) \
+                 VALUES ('u1', ?, ?, ?, ?, ?, ?, 'default-v1', '')",
+            )
+            .bind(&row.deal_subfolder)
+            .bind(row.deal_number)
+            .bind(&row.observation_id)
+            .bind(&row.occurred_at)
+            .bind(&row.kind)
+            .bind(&row.to_value)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        let query = |user_id: &str| BoardTimelineQuery {
+            user_id: user_id.to_string(),
+            deal_subfolder: None,
+            deal_number: None,
+            since: None,
+            limit: None,
+        };
+        let as_student = |user_id: &str| AuthUser {
+            user_id: user_id.to_string(),
+            role: "student".to_string(),
+        };
+        let fetch = |caller: AuthUser, query: BoardTimelineQuery| {
+            get_board_timeline(State(state.clone()), caller, Query(query))
+        };
+
+        let err = fetch(as_student("u2"), query("u1")).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+        let board_only = BoardTimelineQuery {
+            deal_number: Some(1),
+            ..query("u1")
+        };
+        let err = fetch(as_student("u1"), board_only).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        // One board: the other board's paw is out of scope.
+        let board = BoardTimelineQuery {
+            deal_subfolder: Some("Stayman".to_string()),
+            deal_number: Some(1),
+            limit: Some(0),
+            ..query("u1")
+        };
+        let Json(timeline) = fetch(as_student("u1"), board).await.unwrap();
+        assert_eq!((timeline.events.len(), timeline.truncated), (1, true));
+        let Json(timeline) = fetch(
+            as_student("u1"),
+            BoardTimelineQuery {
+                deal_subfolder: Some("Stayman".to_string()),
+                deal_number: Some(1),
+                ..query("u1")
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(timeline.events.len(), 3);
+        assert!(timeline.events.iter().all(|e| e.fresh_paws == 0));
+
+        // Nobody's history is empty rather than an error.
+        let Json(timeline) = fetch(as_student("u2"), query("u2")).await.unwrap();
+        assert!(timeline.events.is_empty() && !timeline.truncated);
+    }
+}
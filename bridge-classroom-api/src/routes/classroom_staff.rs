@@ -0,0 +1,616 @@
+//! Classroom staff: the owner plus any co-teachers and assistants.
+//!
+//! Every classroom has one `owner` row in `classroom_staff` (the same user
+//! as `classrooms.teacher_id`). The owner adds and removes the others; what
+//! each role may do is [`StaffRole::can`]. Staff need a teacher account.
+//!
+//! Students share their key with every staff viewer when they join (see
+//! `join_classroom`). A member who hasn't shared with some staff viewer yet
+//! (staff added after they joined, or a member made at registration by a
+//! roster invitation) has the classroom listed in `unshared_classrooms` on
+//! `GET /api/users/:id`; their client then shares through
+//! [`share_with_staff`].
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{Pool, Sqlite};
+
+use super::classrooms::{ensure_classroom, upsert_grant};
+use crate::models::{
+    ClassroomActionResponse, SetStaffRequest, ShareWithStaffRequest, StaffInfo,
+    StaffListResponse, StaffViewer, UnsharedClassroom,
+};
+use crate::policy::{authorize, staff_role, ClassroomPermission, Relation, Role, StaffRole};
+use crate::session::AuthUser;
+use crate::AppState;
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+#[derive(sqlx::FromRow)]
+struct StaffRow {
+    user_id: String,
+    first_name: String,
+    last_name: String,
+    email: String,
+    role: String,
+    added_at: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct UnsharedViewerRow {
+    classroom_id: String,
+    classroom_name: String,
+    first_name: String,
+    last_name: String,
+    role: String,
+    viewer_id: String,
+    public_key: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct StaffViewerRow {
+    first_name: String,
+    last_name: String,
+    role: String,
+    viewer_id: String,
+    public_key: String,
+}
+
+fn parse_role(role: &str) -> StaffRole {
+    StaffRole::parse(role).unwrap_or(StaffRole::Assistant)
+}
+
+/// The classroom's staff, owner first.
+pub(crate) async fn classroom_staff(
+    db: &Pool<Sqlite>,
+    classroom_id: &str,
+) -> Result<Vec<StaffInfo>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, StaffRow>(
+        r#"
+        SELECT s.user_id, u.first_name, u.last_name, u.email, s.role, s.added_at
+        FROM classroom_staff s
+        JOIN users u ON u.id = s.user_id
+        WHERE s.classroom_id = ?
+        ORDER BY s.role != 'owner', s.added_at
+        "#,
+    )
+    .bind(classroom_id)
+    .fetch_all(db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| StaffInfo {
+            user_id: r.user_id,
+            first_name: r.first_name,
+            last_name: r.last_name,
+            email: r.email,
+            role: parse_role(&r.role),
+            added_at: r.added_at,
+        })
+        .collect())
+}
+
+/// Viewer keys of the staff other than the owner: who a joining student
+/// shares with besides `teacher_viewer_id`. Staff without a viewer record
+/// can't be shared with yet and are left out.
+pub(crate) async fn staff_viewers(
+    db: &Pool<Sqlite>,
+    classroom_id: &str,
+) -> Result<Vec<StaffViewer>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, StaffViewerRow>(
+        r#"
+        SELECT u.first_name, u.last_name, s.role, v.id as viewer_id, v.public_key
+        FROM classroom_staff s
+        JOIN users u ON u.id = s.user_id
+        JOIN viewers v ON v.email = u.email
+        WHERE s.classroom_id = ? AND s.role != 'owner'
+        ORDER BY s.added_at
+        "#,
+    )
+    .bind(classroom_id)
+    .fetch_all(db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| StaffViewer {
+            name: format!("{} {}", r.first_name, r.last_name),
+            role: parse_role(&r.role),
+            viewer_id: r.viewer_id,
+            public_key: r.public_key,
+        })
+        .collect())
+}
+
+/// The student's classrooms with staff viewers (owner included) they have
+/// never shared their key with. A grant the student revoked counts as
+/// shared: it isn't offered again.
+pub(crate) async fn unshared_classrooms(
+    db: &Pool<Sqlite>,
+    student_id: &str,
+) -> Result<Vec<UnsharedClassroom>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, UnsharedViewerRow>(
+        r#"
+        SELECT m.classroom_id, c.name AS classroom_name, u.first_name, u.last_name, s.role,
+               v.id AS viewer_id, v.public_key
+        FROM classroom_members m
+        JOIN classrooms c ON c.id = m.classroom_id
+        JOIN classroom_staff s ON s.classroom_id = m.classroom_id
+        JOIN users u ON u.id = s.user_id
+        JOIN viewers v ON v.email = u.email
+        WHERE m.student_id = ?1
+          AND NOT EXISTS (
+              SELECT 1 FROM sharing_grants g WHERE g.grantor_id = ?1 AND g.grantee_id = v.id)
+        ORDER BY m.joined_at, m.classroom_id, s.role != 'owner', s.added_at
+        "#,
+    )
+    .bind(student_id)
+    .fetch_all(db)
+    .await
+    .map_err(db_err)?;
+
+    let mut classrooms: Vec<UnsharedClassroom> = Vec::new();
+    for r in rows {
+        let viewer = StaffViewer {
+            name: format!("{} {}", r.first_name, r.last_name),
+            role: parse_role(&r.role),
+            viewer_id: r.viewer_id,
+            public_key: r.public_key,
+        };
+        match classrooms.last_mut() {
+            Some(c) if c.classroom_id == r.classroom_id => c.viewers.push(viewer),
+            _ => classrooms.push(UnsharedClassroom {
+                classroom_id: r.classroom_id,
+                classroom_name: r.classroom_name,
+                viewers: vec![viewer],
+            }),
+        }
+    }
+    Ok(classrooms)
+}
+
+/// POST /api/classrooms/:id/grants — a member shares their key with the
+/// classroom's staff. Each payload must name one of the classroom's staff
+/// viewers (owner included); existing grants are renewed.
+pub async fn share_with_staff(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+    Json(req): Json<ShareWithStaffRequest>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    ensure_classroom(&state, &classroom_id).await?;
+    let member: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM classroom_members WHERE classroom_id = ? AND student_id = ?",
+    )
+    .bind(&classroom_id)
+    .bind(&caller.user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+    if !member {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Not a member of this classroom".to_string(),
+        ));
+    }
+    if req.grant_payloads.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "grant_payloads is empty".to_string(),
+        ));
+    }
+
+    let viewer_ids: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT v.id FROM classroom_staff s
+        JOIN users u ON u.id = s.user_id
+        JOIN viewers v ON v.email = u.email
+        WHERE s.classroom_id = ?
+        "#,
+    )
+    .bind(&classroom_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+    if req.grant_payloads.keys().any(|id| !viewer_ids.contains(id)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "grant_payloads names a viewer who isn't on this classroom's staff".to_string(),
+        ));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    for (viewer_id, payload) in &req.grant_payloads {
+        upsert_grant(&mut tx, &caller.user_id, viewer_id, payload, &now).await?;
+    }
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!(
+        "Student {} shared their key with {} staff viewer(s) of classroom {}",
+        caller.user_id,
+        req.grant_payloads.len(),
+        classroom_id
+    );
+
+    Ok(Json(ClassroomActionResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+/// GET /api/classrooms/:id/staff — the classroom's staff (any staff member)
+pub async fn list_staff(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+) -> Result<Json<StaffListResponse>, (StatusCode, String)> {
+    ensure_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ViewProgress),
+    )
+    .await?;
+
+    Ok(Json(StaffListResponse {
+        success: true,
+        staff: classroom_staff(&state.db, &classroom_id).await?,
+    }))
+}
+
+/// PUT /api/classrooms/:id/staff/:user_id — add a co-teacher or assistant,
+/// or change their role (owner only)
+///
+/// Existing members haven't shared with someone just added; each does so
+/// from their own client the next time it syncs (see the module docs), so
+/// a new staff member sees a student's progress only after that.
+pub async fn set_staff(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, user_id)): Path<(String, String)>,
+    Json(req): Json<SetStaffRequest>,
+) -> Result<Json<StaffListResponse>, (StatusCode, String)> {
+    ensure_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageStaff),
+    )
+    .await?;
+
+    if req.role == StaffRole::Owner {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A classroom has exactly one owner".to_string(),
+        ));
+    }
+    let current = staff_role(&state.db, &classroom_id, &user_id).await?;
+    if current == Some(StaffRole::Owner) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "The owner's role can't be changed".to_string(),
+        ));
+    }
+
+    let user_role: Option<String> = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+        .bind(&user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(db_err)?;
+    let Some(user_role) = user_role else {
+        return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    };
+    if Role::parse(&user_role) < Role::Teacher {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Classroom staff need a teacher account".to_string(),
+        ));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query(
+        r#"
+        INSERT INTO classroom_staff (classroom_id, user_id, role, added_by, added_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(classroom_id, user_id) DO UPDATE SET role = excluded.role
+        "#,
+    )
+    .bind(&classroom_id)
+    .bind(&user_id)
+    .bind(req.role.as_str())
+    .bind(&caller.user_id)
+    .bind(&now)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    tracing::info!(
+        "{} is now {} of classroom {}",
+        user_id,
+        req.role.as_str(),
+        classroom_id
+    );
+
+    Ok(Json(StaffListResponse {
+        success: true,
+        staff: classroom_staff(&state.db, &classroom_id).await?,
+    }))
+}
+
+/// DELETE /api/classrooms/:id/staff/:user_id — remove a co-teacher or
+/// assistant (the owner, or staff removing themselves)
+///
+/// Like removing a member, this does not revoke students' sharing grants.
+pub async fn remove_staff(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, user_id)): Path<(String, String)>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    ensure_classroom(&state, &classroom_id).await?;
+    if caller.user_id != user_id {
+        authorize(
+            &state.db,
+            &caller,
+            Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageStaff),
+        )
+        .await?;
+    }
+
+    match staff_role(&state.db, &classroom_id, &user_id).await? {
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "Not on this classroom's staff".to_string(),
+        )),
+        Some(StaffRole::Owner) => Err((
+            StatusCode::BAD_REQUEST,
+            "The owner can't be removed; delete the classroom instead".to_string(),
+        )),
+        Some(_) => {
+            sqlx::query("DELETE FROM classroom_staff WHERE classroom_id = ? AND user_id = ?")
+                .bind(&classroom_id)
+                .bind(&user_id)
+                .execute(&state.db)
+                .await
+                .map_err(db_err)?;
+            tracing::info!(
+                "Removed {} from the staff of classroom {}",
+                user_id,
+                classroom_id
+            );
+            Ok(Json(ClassroomActionResponse {
+                success: true,
+                error: None,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::{CreateClassroomRequest, JoinClassroomRequest};
+    use crate::policy::{holds, RequireTeacher};
+    use crate::routes::{
+        create_classroom, delete_classroom, get_join_info, join_classroom, list_classrooms,
+        ClassroomQuery,
+    };
+    use axum::extract::Query;
+    use crate::routes::recovery::ClientIp;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn user(id: &str, role: &str) -> AuthUser {
+        AuthUser {
+            user_id: id.into(),
+            role: role.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn assistants_see_progress_and_share_keys_but_cannot_delete() {
+        let db = crate::db::test_pool().await;
+        let state = AppState {
+            db: db.clone(),
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for (id, role) in [("owner", "teacher"), ("ta", "teacher"), ("kid", "student")] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+                 VALUES (?, ?, 'X', ?, ?, '', '')",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(role)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        for id in ["owner", "ta"] {
+            sqlx::query(
+                "INSERT INTO viewers (id, name, email, public_key, created_at) VALUES (?, ?, ?, ?, '')",
+            )
+            .bind(format!("v-{id}"))
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(format!("pk-{id}"))
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let (owner, ta, kid) = (
+            user("owner", "teacher"),
+            user("ta", "teacher"),
+            user("kid", "student"),
+        );
+
+        let created = create_classroom(
+            State(state.clone()),
+            RequireTeacher(owner.clone()),
+            Json(CreateClassroomRequest {
+                name: "Tuesday".into(),
+                description: None,
+                join_settings: Default::default(),
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+        .classroom;
+        let id = created.id.clone();
+
+        // Only staff with ManageStaff may add staff, and students can't be staff.
+        let add = |caller: AuthUser, who: &str| {
+            set_staff(
+                State(state.clone()),
+                caller,
+                Path((id.clone(), who.to_string())),
+                Json(SetStaffRequest {
+                    role: StaffRole::Assistant,
+                }),
+            )
+        };
+        assert_eq!(
+            add(ta.clone(), "ta").await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            add(owner.clone(), "kid").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        let staff = add(owner.clone(), "ta").await.unwrap().0.staff;
+        let roles: Vec<_> = staff.iter().map(|s| (s.user_id.as_str(), s.role)).collect();
+        assert_eq!(
+            roles,
+            [("owner", StaffRole::Owner), ("ta", StaffRole::Assistant)]
+        );
+
+        // The join info offers the assistant's key; joining shares with both.
+        let info = get_join_info(
+            State(state.clone()),
+            ClientIp("10.0.0.3".to_string()),
+            Path(created.join_code.clone()),
+        )
+        .await
+        .unwrap()
+        .0;
+        assert_eq!(info.teacher_viewer_id.as_deref(), Some("v-owner"));
+        assert_eq!(info.staff_viewers.len(), 1);
+        assert_eq!(info.staff_viewers[0].viewer_id, "v-ta");
+
+        let join = |payloads: &[(&str, &str)]| {
+            join_classroom(
+                State(state.clone()),
+                ClientIp("10.0.0.3".to_string()),
+                kid.clone(),
+                Path(created.join_code.clone()),
+                Json(JoinClassroomRequest {
+                    encrypted_grant_payload: "for-owner".into(),
+                    staff_grant_payloads: payloads
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect::<HashMap<_, _>>(),
+                    invitation_token: None,
+                }),
+            )
+        };
+        assert_eq!(
+            join(&[("v-stranger", "x")]).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert!(join(&[("v-ta", "for-ta")]).await.unwrap().0.error.is_none());
+        let grants: Vec<(String, String)> = sqlx::query_as(
+            "SELECT grantee_id, encrypted_payload FROM sharing_grants WHERE grantor_id = 'kid' ORDER BY grantee_id",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(
+            grants,
+            [
+                ("v-owner".to_string(), "for-owner".to_string()),
+                ("v-ta".to_string(), "for-ta".to_string())
+            ]
+        );
+
+        // The assistant sees the classroom and the student's progress, and
+        // may assign work, but can't delete the classroom.
+        let listed = list_classrooms(
+            State(state.clone()),
+            ta.clone(),
+            Query(ClassroomQuery { teacher_id: None }),
+        )
+        .await
+        .unwrap()
+        .0
+        .classrooms;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].role, StaffRole::Assistant);
+        assert!(holds(&db, &ta, Relation::ProgressViewer("kid"))
+            .await
+            .unwrap());
+        assert!(holds(
+            &db,
+            &ta,
+            Relation::ClassroomStaff(&id, ClassroomPermission::CreateAssignments)
+        )
+        .await
+        .unwrap());
+        let delete =
+            |caller: AuthUser| delete_classroom(State(state.clone()), caller, Path(id.clone()));
+        assert_eq!(
+            delete(ta.clone()).await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+
+        // The owner can't be removed; the assistant can leave.
+        let remove = |caller: AuthUser, who: &str| {
+            remove_staff(
+                State(state.clone()),
+                caller,
+                Path((id.clone(), who.to_string())),
+            )
+        };
+        assert_eq!(
+            remove(owner.clone(), "owner").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert!(remove(ta.clone(), "ta").await.unwrap().0.success);
+        assert_eq!(
+            remove(owner.clone(), "ta").await.unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        assert!(!holds(
+            &db,
+            &ta,
+            Relation::ClassroomStaff(&id, ClassroomPermission::ViewProgress)
+        )
+        .await
+        .unwrap());
+
+        assert!(delete(owner.clone()).await.unwrap().0.success);
+        let left: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM classrooms) + (SELECT COUNT(*) FROM classroom_members) \
+             + (SELECT COUNT(*) FROM classroom_staff)",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(left, 0);
+    }
+}
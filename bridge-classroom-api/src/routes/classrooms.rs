@@ -1,31 +1,49 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::Deserialize;
+use sqlx::{Sqlite, Transaction};
 
 use crate::models::{
-    generate_join_code, ClassroomActionResponse, ClassroomDetail, ClassroomDetailResponse,
-    ClassroomInfo, ClassroomListResponse, CreateClassroomRequest, CreateClassroomResponse,
-    JoinClassroomRequest, JoinClassroomResponse, JoinInfo, LeaveClassroomRequest, MemberInfo,
+    generate_join_code, normalize_join_code, ClassroomActionResponse, ClassroomDetail,
+    ClassroomDetailResponse, ClassroomInfo, ClassroomListResponse, CreateClassroomRequest,
+    CreateClassroomResponse, JoinClassroomRequest, JoinClassroomResponse, JoinInfo, JoinSettings,
+    MemberInfo, StaffViewer,
 };
+use super::classroom_staff::{classroom_staff, staff_viewers};
+use super::recovery::{rate_limit_allow, rate_limit_exhausted, ClientIp};
+use super::roster::holds_invitation;
+use super::rules_profiles::{requeue_changed_profiles, snapshot_profiles};
+use crate::policy::{authorize, ClassroomPermission, Relation, RequireTeacher, StaffRole};
+use crate::session::AuthUser;
 use crate::AppState;
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 /// Query parameters for classroom listing
 #[derive(Debug, Deserialize)]
 pub struct ClassroomQuery {
+    /// Admins only; everyone else lists their own classrooms.
     pub teacher_id: Option<String>,
 }
 
+/// Failed join-code lookups (unknown or expired) allowed per client IP in
+/// the window. Past that, every lookup from the IP is refused until the
+/// window ends, so scanning for valid codes gets nowhere. The global cap
+/// bounds a scan spread over many addresses.
+const JOIN_LOOKUP_MISSES_PER_IP: u32 = 20;
+const JOIN_LOOKUP_MISSES_GLOBAL: u32 = 500;
+const JOIN_LOOKUP_WINDOW_SECS: u64 = 900; // 15 minutes
+/// Limiter key counting every miss, whatever the address.
+const JOIN_LOOKUP_GLOBAL_KEY: &str = "*";
+
+static JOIN_LOOKUP_LIMITER: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
 // ---- Helper structs for joined queries ----
 
 #[derive(sqlx::FromRow)]
@@ -37,6 +55,9 @@ struct ClassroomWithCount {
     join_code: String,
     created_at: String,
     member_count: i64,
+    role: String,
+    #[sqlx(flatten)]
+    join_settings: JoinSettings,
 }
 
 #[derive(sqlx::FromRow)]
@@ -59,51 +80,32 @@ struct JoinInfoRow {
 }
 
 #[derive(sqlx::FromRow)]
-struct ClassroomRow {
-    id: String,
-    name: String,
+pub(crate) struct ClassroomRow {
+    pub(crate) id: String,
+    pub(crate) name: String,
     description: Option<String>,
-    teacher_id: String,
-    join_code: String,
+    pub(crate) teacher_id: String,
+    pub(crate) join_code: String,
     created_at: String,
+    #[sqlx(flatten)]
+    pub(crate) join_settings: JoinSettings,
 }
 
+const CLASSROOM_COLUMNS: &str = "id, name, description, teacher_id, join_code, created_at, \
+     join_code_expires_at, max_members, requires_approval";
+
 // ---- Endpoints ----
 
 /// POST /api/classrooms — Create a new classroom
 pub async fn create_classroom(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    RequireTeacher(caller): RequireTeacher,
     Json(req): Json<CreateClassroomRequest>,
 ) -> Result<Json<CreateClassroomResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     if req.name.trim().is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Classroom name is required".to_string()));
     }
-
-    // Verify teacher exists and has teacher role
-    let role: Option<String> =
-        sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
-            .bind(&req.teacher_id)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    match role.as_deref() {
-        Some("teacher") | Some("admin") => {}
-        Some(_) => {
-            return Err((
-                StatusCode::FORBIDDEN,
-                "Only teachers can create classrooms".to_string(),
-            ));
-        }
-        None => {
-            return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
-        }
-    }
+    let join_settings = validate_join_settings(req.join_settings)?;
 
     // Generate unique join code with retry
     let id = uuid::Uuid::new_v4().to_string();
@@ -111,23 +113,44 @@ pub async fn create_classroom(
     let mut join_code = generate_join_code();
 
     for attempt in 0..5 {
+        let mut tx = state.db.begin().await.map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction failed: {}", e))
+        })?;
         let result = sqlx::query(
             r#"
-            INSERT INTO classrooms (id, name, description, teacher_id, join_code, created_at)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO classrooms (id, name, description, teacher_id, join_code, created_at,
+                                    join_code_expires_at, max_members, requires_approval)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&id)
         .bind(req.name.trim())
         .bind(&req.description)
-        .bind(&req.teacher_id)
+        .bind(&caller.user_id)
         .bind(&join_code)
         .bind(&now)
-        .execute(&state.db)
+        .bind(&join_settings.join_code_expires_at)
+        .bind(join_settings.max_members)
+        .bind(join_settings.requires_approval)
+        .execute(&mut *tx)
         .await;
 
         match result {
             Ok(_) => {
+                // The creator is the owner on the staff list too.
+                sqlx::query(
+                    "INSERT INTO classroom_staff (classroom_id, user_id, role, added_by, added_at) VALUES (?, ?, 'owner', ?, ?)",
+                )
+                .bind(&id)
+                .bind(&caller.user_id)
+                .bind(&caller.user_id)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                tx.commit().await.map_err(|e| {
+                    (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit failed: {}", e))
+                })?;
                 tracing::info!("Classroom created: {} ({})", req.name.trim(), join_code);
                 return Ok(Json(CreateClassroomResponse {
                     success: true,
@@ -135,17 +158,17 @@ pub async fn create_classroom(
                         id,
                         name: req.name.trim().to_string(),
                         description: req.description,
-                        teacher_id: req.teacher_id,
+                        teacher_id: caller.user_id,
                         join_code,
                         created_at: now,
                         member_count: 0,
+                        role: StaffRole::Owner,
+                        join_settings,
                     },
                 }));
             }
             Err(e) => {
-                // Check if this is a unique constraint violation on join_code
-                let err_str = e.to_string();
-                if err_str.contains("UNIQUE") && err_str.contains("join_code") && attempt < 4 {
+                if is_join_code_collision(&e) && attempt < 4 {
                     join_code = generate_join_code();
                     continue;
                 }
@@ -161,27 +184,26 @@ pub async fn create_classroom(
     ))
 }
 
-/// GET /api/classrooms — List teacher's classrooms with member counts
+/// GET /api/classrooms — List the classrooms the teacher staffs, with member
+/// counts and their role in each
 pub async fn list_classrooms(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<ClassroomQuery>,
 ) -> Result<Json<ClassroomListResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let teacher_id = query.teacher_id.ok_or_else(|| {
-        (StatusCode::BAD_REQUEST, "teacher_id is required".to_string())
-    })?;
+    let teacher_id = match query.teacher_id {
+        Some(t) if caller.is_admin() => t,
+        _ => caller.user_id,
+    };
 
     let rows = sqlx::query_as::<_, ClassroomWithCount>(
         r#"
         SELECT c.id, c.name, c.description, c.teacher_id, c.join_code, c.created_at,
-               COUNT(cm.student_id) as member_count
+               c.join_code_expires_at, c.max_members, c.requires_approval,
+               COUNT(cm.student_id) as member_count, s.role
         FROM classrooms c
+        JOIN classroom_staff s ON s.classroom_id = c.id AND s.user_id = ?
         LEFT JOIN classroom_members cm ON cm.classroom_id = c.id
-        WHERE c.teacher_id = ?
         GROUP BY c.id
         ORDER BY c.created_at DESC
         "#,
@@ -204,6 +226,8 @@ pub async fn list_classrooms(
             join_code: r.join_code,
             created_at: r.created_at,
             member_count: r.member_count,
+            role: StaffRole::parse(&r.role).unwrap_or(StaffRole::Assistant),
+            join_settings: r.join_settings,
         })
         .collect();
 
@@ -216,21 +240,18 @@ pub async fn list_classrooms(
 /// GET /api/classrooms/:id — Get classroom detail with member roster
 pub async fn get_classroom(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(classroom_id): Path<String>,
 ) -> Result<Json<ClassroomDetailResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let classroom = load_classroom(&state, &classroom_id).await?;
 
-    let classroom = sqlx::query_as::<_, ClassroomRow>(
-        "SELECT id, name, description, teacher_id, join_code, created_at FROM classrooms WHERE id = ?",
+    // The roster carries emails; only the classroom's staff see it.
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ViewProgress),
     )
-    .bind(&classroom_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or_else(|| (StatusCode::NOT_FOUND, "Classroom not found".to_string()))?;
+    .await?;
 
     let members = sqlx::query_as::<_, MemberRow>(
         r#"
@@ -245,6 +266,7 @@ pub async fn get_classroom(
     .fetch_all(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let staff = classroom_staff(&state.db, &classroom_id).await?;
 
     Ok(Json(ClassroomDetailResponse {
         success: true,
@@ -255,6 +277,7 @@ pub async fn get_classroom(
             teacher_id: classroom.teacher_id,
             join_code: classroom.join_code,
             created_at: classroom.created_at,
+            join_settings: classroom.join_settings,
             members: members
                 .into_iter()
                 .map(|m| MemberInfo {
@@ -265,6 +288,7 @@ pub async fn get_classroom(
                     joined_at: m.joined_at,
                 })
                 .collect(),
+            staff,
         },
     }))
 }
@@ -272,9 +296,12 @@ pub async fn get_classroom(
 /// GET /api/join/:join_code — Public endpoint for join page info
 pub async fn get_join_info(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Path(join_code): Path<String>,
 ) -> Result<Json<JoinInfo>, (StatusCode, String)> {
-    // No API key required — this is a public endpoint
+    // No API key required — this is a public endpoint, so lookups are
+    // rate limited (see classroom_for_code).
+    let classroom = classroom_for_code(&state, &ip, &join_code).await?;
 
     let row = sqlx::query_as::<_, JoinInfoRow>(
         r#"
@@ -284,10 +311,10 @@ pub async fn get_join_info(
         FROM classrooms c
         JOIN users u ON u.id = c.teacher_id
         LEFT JOIN viewers v ON v.email = u.email
-        WHERE c.join_code = ?
+        WHERE c.id = ?
         "#,
     )
-    .bind(&join_code)
+    .bind(&classroom.id)
     .fetch_optional(&state.db)
     .await
     .map_err(|e| {
@@ -296,158 +323,523 @@ pub async fn get_join_info(
     })?
     .ok_or_else(|| (StatusCode::NOT_FOUND, "Classroom not found".to_string()))?;
 
+    let staff_viewers = staff_viewers(&state.db, &classroom.id).await?;
+
     Ok(Json(JoinInfo {
         classroom_name: row.classroom_name,
         classroom_description: row.classroom_description,
         teacher_name: format!("{} {}", row.teacher_first_name, row.teacher_last_name),
         teacher_viewer_id: row.viewer_id,
         teacher_public_key: row.public_key,
+        staff_viewers,
+        requires_approval: classroom.join_settings.requires_approval,
     }))
 }
 
-/// POST /api/join/:join_code — Join a classroom (creates membership + sharing grant)
+/// POST /api/join/:join_code — Join a classroom (creates membership + sharing
+/// grants for the owner and the other staff viewers)
+///
+/// When the classroom requires approval this files a join request instead
+/// (`pending: true`); the grants are created when a teacher approves it.
+/// A roster invitation token for the caller stands in for that approval.
+/// A full classroom is 409 and an expired code 410.
+///
+/// A student who is already a member can post again to renew their grants;
+/// nothing else changes. (Clients share with staff they've missed through
+/// `POST /api/classrooms/:id/grants` instead.)
 pub async fn join_classroom(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    ClientIp(ip): ClientIp,
+    caller: AuthUser,
     Path(join_code): Path<String>,
     Json(req): Json<JoinClassroomRequest>,
 ) -> Result<Json<JoinClassroomResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let classroom = classroom_for_code(&state, &ip, &join_code).await?;
 
-    if req.student_id.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "student_id is required".to_string()));
+    // Staff payloads may only name this classroom's staff viewers.
+    let staff_viewers = staff_viewers(&state.db, &classroom.id).await?;
+    let staff_grants: Vec<(&StaffViewer, &String)> = staff_viewers
+        .iter()
+        .filter_map(|v| req.staff_grant_payloads.get(&v.viewer_id).map(|p| (v, p)))
+        .collect();
+    if staff_grants.len() != req.staff_grant_payloads.len() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "staff_grant_payloads names a viewer who isn't on this classroom's staff".to_string(),
+        ));
     }
 
-    // Look up classroom
-    let classroom = sqlx::query_as::<_, ClassroomRow>(
-        "SELECT id, name, description, teacher_id, join_code, created_at FROM classrooms WHERE join_code = ?",
-    )
-    .bind(&join_code)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or_else(|| (StatusCode::NOT_FOUND, "Classroom not found".to_string()))?;
-
     // Check if already a member
     let existing: Option<String> = sqlx::query_scalar(
         "SELECT student_id FROM classroom_members WHERE classroom_id = ? AND student_id = ?",
     )
     .bind(&classroom.id)
-    .bind(&req.student_id)
+    .bind(&caller.user_id)
     .fetch_optional(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let now = chrono::Utc::now().to_rfc3339();
+
     if existing.is_some() {
+        let owner_viewer = owner_viewer_id(&state, &classroom)
+            .await?
+            .filter(|_| !req.encrypted_grant_payload.is_empty());
+        let mut tx = state.db.begin().await.map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction failed: {}", e))
+        })?;
+        if let Some(viewer_id) = &owner_viewer {
+            let payload = &req.encrypted_grant_payload;
+            upsert_grant(&mut tx, &caller.user_id, viewer_id, payload, &now).await?;
+        }
+        for (viewer, payload) in &staff_grants {
+            upsert_grant(&mut tx, &caller.user_id, &viewer.viewer_id, payload, &now).await?;
+        }
+        tx.commit().await.map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit failed: {}", e))
+        })?;
         return Ok(Json(JoinClassroomResponse {
             success: true,
             classroom_id: Some(classroom.id),
+            pending: false,
             error: Some("Already a member of this classroom".to_string()),
         }));
     }
 
-    // Look up teacher's viewer record for sharing grant
-    let teacher_viewer_id: Option<String> = sqlx::query_scalar(
-        r#"
-        SELECT v.id FROM viewers v
-        JOIN users u ON v.email = u.email
-        WHERE u.id = ?
-        "#,
+    let invited = match req.invitation_token.as_deref() {
+        Some(token) => holds_invitation(&state.db, &classroom.id, &caller.user_id, token).await?,
+        None => false,
+    };
+    if classroom.join_settings.requires_approval && !invited {
+        // A full classroom takes no requests either (approving would 409).
+        ensure_capacity(&state, &classroom).await?;
+        let staff_payloads = serde_json::to_string(&req.staff_grant_payloads)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        sqlx::query(
+            r#"
+            INSERT INTO classroom_join_requests
+                (classroom_id, student_id, encrypted_grant_payload, staff_grant_payloads, requested_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(classroom_id, student_id) DO UPDATE SET
+                encrypted_grant_payload = excluded.encrypted_grant_payload,
+                staff_grant_payloads = excluded.staff_grant_payloads
+            "#,
+        )
+        .bind(&classroom.id)
+        .bind(&caller.user_id)
+        .bind(&req.encrypted_grant_payload)
+        .bind(&staff_payloads)
+        .bind(&now)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        tracing::info!(
+            "Student {} asked to join classroom {} ({})",
+            caller.user_id,
+            classroom.name,
+            classroom.id
+        );
+        return Ok(Json(JoinClassroomResponse {
+            success: true,
+            classroom_id: Some(classroom.id),
+            pending: true,
+            error: None,
+        }));
+    }
+
+    let staff_grants: Vec<(&str, &str)> = staff_grants
+        .iter()
+        .map(|(v, p)| (v.viewer_id.as_str(), p.as_str()))
+        .collect();
+    admit_member(
+        &state,
+        &classroom,
+        &caller.user_id,
+        &req.encrypted_grant_payload,
+        &staff_grants,
     )
-    .bind(&classroom.teacher_id)
+    .await?;
+
+    tracing::info!(
+        "Student {} joined classroom {} ({})",
+        caller.user_id,
+        classroom.name,
+        classroom.id
+    );
+
+    Ok(Json(JoinClassroomResponse {
+        success: true,
+        classroom_id: Some(classroom.id),
+        pending: false,
+        error: None,
+    }))
+}
+
+/// The classroom a join code opens: 404 for an unknown code, 410 for an
+/// expired one. Both count as misses against the caller's IP and the
+/// global cap. An IP with too many misses, or any caller once the global
+/// cap is reached, is refused outright (429) for the window.
+pub(crate) async fn classroom_for_code(
+    state: &AppState,
+    ip: &str,
+    join_code: &str,
+) -> Result<ClassroomRow, (StatusCode, String)> {
+    let exhausted = |key: &str, max: u32| {
+        rate_limit_exhausted(&JOIN_LOOKUP_LIMITER, key, max, JOIN_LOOKUP_WINDOW_SECS)
+    };
+    if exhausted(ip, JOIN_LOOKUP_MISSES_PER_IP)
+        || exhausted(JOIN_LOOKUP_GLOBAL_KEY, JOIN_LOOKUP_MISSES_GLOBAL)
+    {
+        tracing::warn!("Join code lookups throttled (ip={})", ip);
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many join code attempts. Please wait a few minutes and try again.".to_string(),
+        ));
+    }
+
+    let classroom = sqlx::query_as::<_, ClassroomRow>(&format!(
+        "SELECT {} FROM classrooms WHERE join_code = ?",
+        CLASSROOM_COLUMNS
+    ))
+    .bind(normalize_join_code(join_code))
     .fetch_optional(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let miss = |status: StatusCode, msg: &str| {
+        for (key, max) in [
+            (ip, JOIN_LOOKUP_MISSES_PER_IP),
+            (JOIN_LOOKUP_GLOBAL_KEY, JOIN_LOOKUP_MISSES_GLOBAL),
+        ] {
+            rate_limit_allow(&JOIN_LOOKUP_LIMITER, key, max, JOIN_LOOKUP_WINDOW_SECS);
+        }
+        Err((status, msg.to_string()))
+    };
+    match classroom {
+        None => miss(StatusCode::NOT_FOUND, "Classroom not found"),
+        Some(c) if join_code_expired(&c.join_settings) => {
+            miss(StatusCode::GONE, "This join code has expired")
+        }
+        Some(c) => Ok(c),
+    }
+}
+
+fn join_code_expired(settings: &JoinSettings) -> bool {
+    settings.join_code_expires_at.as_deref().is_some_and(|t| {
+        chrono::DateTime::parse_from_rfc3339(t).map_or(true, |t| t <= chrono::Utc::now())
+    })
+}
+
+/// 409 when the classroom already has `max_members` members. Only an early
+/// answer for join requests: [`admit_member`] re-checks as it inserts.
+pub(crate) async fn ensure_capacity(
+    state: &AppState,
+    classroom: &ClassroomRow,
+) -> Result<(), (StatusCode, String)> {
+    let Some(max) = classroom.join_settings.max_members else {
+        return Ok(());
+    };
+    let members: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM classroom_members WHERE classroom_id = ?")
+            .bind(&classroom.id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if members >= max {
+        return Err((StatusCode::CONFLICT, "This classroom is full".to_string()));
+    }
+    Ok(())
+}
+
+/// Make the student a member, with a sharing grant to the owner's viewer
+/// (when they have one) and to each `(viewer_id, payload)` in
+/// `staff_grants`. Drops any pending join request and accepts any roster
+/// invitation for the student's email.
+///
+/// The member count is checked by the INSERT itself, so two students
+/// taking the last seat at once can't both get it: the loser gets 409 and
+/// nothing is written.
+pub(crate) async fn admit_member(
+    state: &AppState,
+    classroom: &ClassroomRow,
+    student_id: &str,
+    owner_grant_payload: &str,
+    staff_grants: &[(&str, &str)],
+) -> Result<(), (StatusCode, String)> {
+    let teacher_viewer_id = owner_viewer_id(state, classroom).await?;
+
     let now = chrono::Utc::now().to_rfc3339();
+    // Joining can change which rules profile governs the student.
+    let profiles_before = snapshot_profiles(&state.db, [student_id.to_string()]).await?;
 
-    // Use a transaction to create membership and sharing grant atomically
+    // Use a transaction to create membership and sharing grants atomically
     let mut tx = state.db.begin().await.map_err(|e| {
         (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction failed: {}", e))
     })?;
 
-    // Insert classroom membership
-    sqlx::query(
-        "INSERT INTO classroom_members (classroom_id, student_id, joined_at) VALUES (?, ?, ?)",
+    // Insert classroom membership, if there's still room
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO classroom_members (classroom_id, student_id, joined_at)
+        SELECT c.id, ?, ? FROM classrooms c
+        WHERE c.id = ?
+          AND (c.max_members IS NULL
+               OR (SELECT COUNT(*) FROM classroom_members WHERE classroom_id = c.id)
+                  < c.max_members)
+        "#,
     )
-    .bind(&classroom.id)
-    .bind(&req.student_id)
+    .bind(student_id)
     .bind(&now)
+    .bind(&classroom.id)
     .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Failed to insert classroom member: {}", e);
         (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
+    if inserted.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "This classroom is full".to_string()));
+    }
 
     // Create sharing grant if teacher has a viewer record
     if let Some(viewer_id) = &teacher_viewer_id {
-        let grant_id = uuid::Uuid::new_v4().to_string();
-        sqlx::query(
-            r#"
-            INSERT INTO sharing_grants (id, grantor_id, grantee_id, encrypted_payload, granted_at, expires_at, revoked, revoked_at)
-            VALUES (?, ?, ?, ?, ?, NULL, 0, NULL)
-            ON CONFLICT(grantor_id, grantee_id) DO UPDATE SET
-                encrypted_payload = excluded.encrypted_payload,
-                granted_at = excluded.granted_at,
-                revoked = 0,
-                revoked_at = NULL
-            "#,
-        )
-        .bind(&grant_id)
-        .bind(&req.student_id)
-        .bind(viewer_id)
-        .bind(&req.encrypted_grant_payload)
-        .bind(&now)
+        upsert_grant(&mut tx, student_id, viewer_id, owner_grant_payload, &now).await?;
+    }
+    for (viewer_id, payload) in staff_grants {
+        upsert_grant(&mut tx, student_id, viewer_id, payload, &now).await?;
+    }
+
+    sqlx::query("DELETE FROM classroom_join_requests WHERE classroom_id = ? AND student_id = ?")
+        .bind(&classroom.id)
+        .bind(student_id)
         .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to create sharing grant: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
-        })?;
-    }
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query(
+        r#"
+        UPDATE classroom_invitations
+        SET status = 'accepted', accepted_at = ?, accepted_user_id = ?
+        WHERE classroom_id = ? AND status IN ('pending', 'sent', 'failed')
+          AND email = (SELECT email FROM users WHERE id = ?)
+        "#,
+    )
+    .bind(&now)
+    .bind(student_id)
+    .bind(&classroom.id)
+    .bind(student_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     tx.commit().await.map_err(|e| {
         (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit failed: {}", e))
     })?;
 
-    tracing::info!(
-        "Student {} joined classroom {} ({})",
-        req.student_id,
-        classroom.name,
-        join_code
-    );
+    requeue_changed_profiles(&state.db, profiles_before).await;
+    Ok(())
+}
 
-    Ok(Json(JoinClassroomResponse {
-        success: true,
-        classroom_id: Some(classroom.id),
-        error: None,
-    }))
+/// The classroom owner's viewer record, which a member's main sharing grant
+/// goes to. `None` until the owner has one.
+async fn owner_viewer_id(
+    state: &AppState,
+    classroom: &ClassroomRow,
+) -> Result<Option<String>, (StatusCode, String)> {
+    sqlx::query_scalar(
+        r#"
+        SELECT v.id FROM viewers v
+        JOIN users u ON v.email = u.email
+        WHERE u.id = ?
+        "#,
+    )
+    .bind(&classroom.teacher_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
-/// DELETE /api/classrooms/:id/members/:uid — Remove a member (teacher only)
-pub async fn remove_member(
+/// Create (or renew) the student's sharing grant to a viewer.
+pub(crate) async fn upsert_grant(
+    tx: &mut Transaction<'_, Sqlite>,
+    grantor_id: &str,
+    viewer_id: &str,
+    encrypted_payload: &str,
+    now: &str,
+) -> Result<(), (StatusCode, String)> {
+    let grant_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO sharing_grants (id, grantor_id, grantee_id, encrypted_payload, granted_at, expires_at, revoked, revoked_at)
+        VALUES (?, ?, ?, ?, ?, NULL, 0, NULL)
+        ON CONFLICT(grantor_id, grantee_id) DO UPDATE SET
+            encrypted_payload = excluded.encrypted_payload,
+            granted_at = excluded.granted_at,
+            revoked = 0,
+            revoked_at = NULL
+        "#,
+    )
+    .bind(&grant_id)
+    .bind(grantor_id)
+    .bind(viewer_id)
+    .bind(encrypted_payload)
+    .bind(now)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to create sharing grant: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+    Ok(())
+}
+
+/// DELETE /api/classrooms/:id — Delete a classroom (owner only)
+///
+/// Members, staff, roster invitations and the classroom's assignments go
+/// with it; table
+/// sessions opened for it are kept, detached. Like removing a member, this
+/// does not revoke anyone's sharing grants.
+pub async fn delete_classroom(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    Path((classroom_id, student_id)): Path<(String, String)>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
 ) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    ensure_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::DeleteClassroom),
+    )
+    .await?;
 
-    // Verify the classroom exists
-    let teacher_id: Option<String> =
-        sqlx::query_scalar("SELECT teacher_id FROM classrooms WHERE id = ?")
+    let members: Vec<String> =
+        sqlx::query_scalar("SELECT student_id FROM classroom_members WHERE classroom_id = ?")
             .bind(&classroom_id)
-            .fetch_optional(&state.db)
+            .fetch_all(&state.db)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Leaving the classroom can change which rules profile governs a member.
+    let profiles_before = snapshot_profiles(&state.db, members).await?;
+
+    let mut tx = state.db.begin().await.map_err(|e| {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction failed: {}", e))
+    })?;
+    for sql in [
+        "DELETE FROM assignment_board_status WHERE assignment_id IN (SELECT id FROM assignments WHERE classroom_id = ?)",
+        "DELETE FROM assignments WHERE classroom_id = ?",
+        "UPDATE table_sessions SET classroom_id = NULL WHERE classroom_id = ?",
+        "DELETE FROM classroom_members WHERE classroom_id = ?",
+        "DELETE FROM classroom_join_requests WHERE classroom_id = ?",
+        "DELETE FROM classroom_invitations WHERE classroom_id = ?",
+        "DELETE FROM classroom_staff WHERE classroom_id = ?",
+        "DELETE FROM classrooms WHERE id = ?",
+    ] {
+        sqlx::query(sql)
+            .bind(&classroom_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to delete classroom {}: {}", classroom_id, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            })?;
+    }
+    tx.commit().await.map_err(|e| {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit failed: {}", e))
+    })?;
+
+    requeue_changed_profiles(&state.db, profiles_before).await;
+    tracing::info!("Classroom {} deleted by {}", classroom_id, caller.user_id);
+
+    Ok(Json(ClassroomActionResponse {
+        success: true,
+        error: None,
+    }))
+}
 
-    if teacher_id.is_none() {
-        return Err((StatusCode::NOT_FOUND, "Classroom not found".to_string()));
+/// Check join settings from a request and normalize the expiry to UTC.
+pub(crate) fn validate_join_settings(
+    settings: JoinSettings,
+) -> Result<JoinSettings, (StatusCode, String)> {
+    let join_code_expires_at = match settings.join_code_expires_at.as_deref() {
+        None => None,
+        Some(t) => Some(
+            chrono::DateTime::parse_from_rfc3339(t)
+                .map_err(|_| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        "join_code_expires_at must be an RFC 3339 timestamp".to_string(),
+                    )
+                })?
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339(),
+        ),
+    };
+    if settings.max_members.is_some_and(|m| m < 1) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "max_members must be at least 1".to_string(),
+        ));
     }
+    Ok(JoinSettings {
+        join_code_expires_at,
+        ..settings
+    })
+}
+
+/// Whether an insert/update failed only because the new join code is taken.
+pub(crate) fn is_join_code_collision(e: &sqlx::Error) -> bool {
+    let err_str = e.to_string();
+    err_str.contains("UNIQUE") && err_str.contains("join_code")
+}
+
+/// The classroom, or 404.
+pub(crate) async fn load_classroom(
+    state: &AppState,
+    classroom_id: &str,
+) -> Result<ClassroomRow, (StatusCode, String)> {
+    sqlx::query_as::<_, ClassroomRow>(&format!(
+        "SELECT {} FROM classrooms WHERE id = ?",
+        CLASSROOM_COLUMNS
+    ))
+    .bind(classroom_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Classroom not found".to_string()))
+}
+
+/// 404 unless the classroom exists.
+pub(crate) async fn ensure_classroom(
+    state: &AppState,
+    classroom_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM classrooms WHERE id = ?")
+        .bind(classroom_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if exists {
+        Ok(())
+    } else {
+        Err((StatusCode::NOT_FOUND, "Classroom not found".to_string()))
+    }
+}
+
+/// DELETE /api/classrooms/:id/members/:uid — Remove a member (owner or co-teacher)
+pub async fn remove_member(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, student_id)): Path<(String, String)>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    ensure_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageClassroom),
+    )
+    .await?;
+    let profiles_before = snapshot_profiles(&state.db, [student_id.clone()]).await?;
 
     // Delete membership (does NOT revoke sharing grant per spec)
     let result = sqlx::query(
@@ -466,6 +858,7 @@ pub async fn remove_member(
         }));
     }
 
+    requeue_changed_profiles(&state.db, profiles_before).await;
     tracing::info!("Removed student {} from classroom {}", student_id, classroom_id);
 
     Ok(Json(ClassroomActionResponse {
@@ -474,34 +867,41 @@ pub async fn remove_member(
     }))
 }
 
-/// POST /api/classrooms/:id/members/leave — Student leaves a classroom
+/// POST /api/classrooms/:id/members/leave — Student leaves a classroom (or
+/// withdraws a pending join request)
 pub async fn leave_classroom(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(classroom_id): Path<String>,
-    Json(req): Json<LeaveClassroomRequest>,
 ) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
+    let profiles_before = snapshot_profiles(&state.db, [caller.user_id.clone()]).await?;
     let result = sqlx::query(
         "DELETE FROM classroom_members WHERE classroom_id = ? AND student_id = ?",
     )
     .bind(&classroom_id)
-    .bind(&req.student_id)
+    .bind(&caller.user_id)
     .execute(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if result.rows_affected() == 0 {
+        let withdrawn = sqlx::query(
+            "DELETE FROM classroom_join_requests WHERE classroom_id = ? AND student_id = ?",
+        )
+        .bind(&classroom_id)
+        .bind(&caller.user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
         return Ok(Json(ClassroomActionResponse {
-            success: false,
-            error: Some("Not a member of this classroom".to_string()),
+            success: withdrawn.rows_affected() > 0,
+            error: (withdrawn.rows_affected() == 0)
+                .then(|| "Not a member of this classroom".to_string()),
         }));
     }
 
-    tracing::info!("Student {} left classroom {}", req.student_id, classroom_id);
+    requeue_changed_profiles(&state.db, profiles_before).await;
+    tracing::info!("Student {} left classroom {}", caller.user_id, classroom_id);
 
     Ok(Json(ClassroomActionResponse {
         success: true,
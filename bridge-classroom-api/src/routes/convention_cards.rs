@@ -4,7 +4,6 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
-use sqlx::SqlitePool;
 
 use crate::{
     models::{
@@ -13,6 +12,8 @@ use crate::{
         UpdateConventionCardRequest, UpdateConventionCardResponse, UserCardInfo,
         UserCardsResponse,
     },
+    policy::{self, Relation},
+    session::AuthUser,
     AppState,
 };
 
@@ -23,66 +24,25 @@ pub struct CardQuery {
     pub owner_id: Option<String>,
 }
 
-/// Query parameters for single-card read (optional viewer for authz)
-#[derive(Debug, Deserialize)]
-pub struct CardReadQuery {
-    /// Caller's user id. Omit for an anonymous read (then only public
-    /// cards are visible).
-    pub viewer_id: Option<String>,
-}
-
-/// Query parameters for delete + unlink (the body would have been
-/// cleaner, but DELETE bodies are awkward in HTTP).
-#[derive(Debug, Deserialize)]
-pub struct ActingUserQuery {
-    pub acting_user_id: Option<String>,
-}
-
 // ─────────────────────────────────────────────────────────────────
 // Authorization helpers
 // ─────────────────────────────────────────────────────────────────
 
-/// Fetch the role for a user id. Returns None when the user doesn't
-/// exist (treat as anonymous).
-async fn fetch_caller_role(db: &SqlitePool, user_id: &str) -> Option<String> {
-    sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = ?")
-        .bind(user_id)
-        .fetch_optional(db)
-        .await
-        .ok()
-        .flatten()
-}
-
-fn is_admin(role: &Option<String>) -> bool {
-    role.as_deref() == Some("admin")
-}
-
-/// Read-side check: who can see this card?
-fn can_read(card: &ConventionCard, viewer_id: Option<&str>, viewer_role: &Option<String>) -> bool {
+/// Read-side check: who can see this card? `caller` is None for an
+/// anonymous read (then only public cards are visible).
+fn can_read(card: &ConventionCard, caller: Option<&AuthUser>) -> bool {
     if card.visibility == "public" {
         return true;
     }
-    if is_admin(viewer_role) {
-        return true;
-    }
-    match (viewer_id, &card.owner_id) {
-        (Some(v), Some(o)) => v == o,
-        _ => false,
-    }
+    caller.is_some_and(|c| policy::owns(c, card.owner_id.as_deref()))
 }
 
 /// Write-side check: who can update / delete this card?
-fn can_write(card: &ConventionCard, viewer_id: Option<&str>, viewer_role: &Option<String>) -> bool {
-    if is_admin(viewer_role) {
-        return true;
-    }
+fn can_write(card: &ConventionCard, caller: &AuthUser) -> bool {
     if card.visibility == "public" {
-        return false; // only admin can change public cards
-    }
-    match (viewer_id, &card.owner_id) {
-        (Some(v), Some(o)) => v == o,
-        _ => false,
+        return caller.is_admin(); // only admin can change public cards
     }
+    policy::owns(caller, card.owner_id.as_deref())
 }
 
 /// GET /api/cards
@@ -120,14 +80,14 @@ pub async fn list_cards(
     Ok(Json(infos))
 }
 
-/// GET /api/cards/:card_id?viewer_id=...
+/// GET /api/cards/:card_id
 /// Get a specific convention card with full data. Anonymous callers
-/// (no viewer_id) can read public cards; private cards require the
+/// (no session) can read public cards; private cards require the
 /// owner or an admin.
 pub async fn get_card(
     State(state): State<AppState>,
+    caller: Option<AuthUser>,
     Path(card_id): Path<String>,
-    Query(query): Query<CardReadQuery>,
 ) -> Result<Json<ConventionCardFull>, (StatusCode, String)> {
     let card: Option<ConventionCard> =
         sqlx::query_as::<_, ConventionCard>("SELECT * FROM convention_cards WHERE id = ?")
@@ -138,11 +98,7 @@ pub async fn get_card(
 
     let card = card.ok_or((StatusCode::NOT_FOUND, "Card not found".to_string()))?;
 
-    let viewer_role = match &query.viewer_id {
-        Some(id) => fetch_caller_role(&state.db, id).await,
-        None => None,
-    };
-    if !can_read(&card, query.viewer_id.as_deref(), &viewer_role) {
+    if !can_read(&card, caller.as_ref()) {
         return Err((StatusCode::FORBIDDEN, "Not allowed to read this card".to_string()));
     }
 
@@ -162,43 +118,28 @@ fn c_to_full(card: &ConventionCard) -> Result<ConventionCardFull, String> {
 }
 
 /// POST /api/cards
-/// Create a new convention card. Body must include `acting_user_id`;
-/// the new card is owned by that user. Public-visibility cards
-/// further require the caller to be an admin.
+/// Create a new convention card owned by the caller. Public-visibility
+/// cards further require the caller to be an admin.
 pub async fn create_card(
     State(state): State<AppState>,
+    caller: AuthUser,
     Json(req): Json<CreateConventionCardRequest>,
 ) -> Result<Json<CreateConventionCardResponse>, (StatusCode, String)> {
     if req.name.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
     }
-    if req.acting_user_id.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "acting_user_id is required".to_string(),
-        ));
-    }
-
-    let role = fetch_caller_role(&state.db, &req.acting_user_id).await;
-    if role.is_none() {
-        return Err((
-            StatusCode::FORBIDDEN,
-            "acting_user_id does not match a known user".to_string(),
-        ));
-    }
-
     let visibility = req
         .visibility
         .clone()
         .unwrap_or_else(|| "private".to_string());
-    if visibility == "public" && !is_admin(&role) {
+    if visibility == "public" && !caller.is_admin() {
         return Err((
             StatusCode::FORBIDDEN,
             "Only admins can create public cards".to_string(),
         ));
     }
 
-    let card = ConventionCard::from_request(&req, Some(req.acting_user_id.clone()));
+    let card = ConventionCard::from_request(&req, Some(caller.user_id.clone()));
 
     sqlx::query(
         r#"
@@ -223,7 +164,7 @@ pub async fn create_card(
         "Created convention card: {} ({}) owner={} visibility={}",
         card.name,
         card.id,
-        req.acting_user_id,
+        caller.user_id,
         card.visibility
     );
 
@@ -234,21 +175,14 @@ pub async fn create_card(
 }
 
 /// PUT /api/cards/:card_id
-/// Update an existing convention card. Body must include
-/// `acting_user_id`. Admins can update any card; non-admins can only
-/// update private cards they own.
+/// Update an existing convention card. Admins can update any card;
+/// non-admins can only update private cards they own.
 pub async fn update_card(
     State(state): State<AppState>,
+    caller: AuthUser,
     Path(card_id): Path<String>,
     Json(req): Json<UpdateConventionCardRequest>,
 ) -> Result<Json<UpdateConventionCardResponse>, (StatusCode, String)> {
-    if req.acting_user_id.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "acting_user_id is required".to_string(),
-        ));
-    }
-
     let card: Option<ConventionCard> =
         sqlx::query_as::<_, ConventionCard>("SELECT * FROM convention_cards WHERE id = ?")
             .bind(&card_id)
@@ -257,14 +191,13 @@ pub async fn update_card(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let card = card.ok_or((StatusCode::NOT_FOUND, "Card not found".to_string()))?;
 
-    let role = fetch_caller_role(&state.db, &req.acting_user_id).await;
-    if !can_write(&card, Some(&req.acting_user_id), &role) {
+    if !can_write(&card, &caller) {
         return Err((StatusCode::FORBIDDEN, "Not allowed to edit this card".to_string()));
     }
 
     // Visibility changes (public ↔ private) require admin.
     if let Some(new_vis) = &req.visibility {
-        if new_vis != &card.visibility && !is_admin(&role) {
+        if new_vis != &card.visibility && !caller.is_admin() {
             return Err((
                 StatusCode::FORBIDDEN,
                 "Only admins can change card visibility".to_string(),
@@ -301,28 +234,20 @@ pub async fn update_card(
     tracing::info!(
         "Updated convention card {} by user {}",
         card_id,
-        req.acting_user_id
+        caller.user_id
     );
 
     Ok(Json(UpdateConventionCardResponse { success: true }))
 }
 
-/// DELETE /api/cards/:card_id?acting_user_id=...
+/// DELETE /api/cards/:card_id
 /// Delete a convention card. Admin: any card. Non-admin: own private
 /// cards only. Cascade-deletes user_convention_cards rows.
 pub async fn delete_card(
     State(state): State<AppState>,
+    caller: AuthUser,
     Path(card_id): Path<String>,
-    Query(query): Query<ActingUserQuery>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let acting_user_id = query.acting_user_id.unwrap_or_default();
-    if acting_user_id.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "acting_user_id is required".to_string(),
-        ));
-    }
-
     let card: Option<ConventionCard> =
         sqlx::query_as::<_, ConventionCard>("SELECT * FROM convention_cards WHERE id = ?")
             .bind(&card_id)
@@ -331,8 +256,7 @@ pub async fn delete_card(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let card = card.ok_or((StatusCode::NOT_FOUND, "Card not found".to_string()))?;
 
-    let role = fetch_caller_role(&state.db, &acting_user_id).await;
-    if !can_write(&card, Some(&acting_user_id), &role) {
+    if !can_write(&card, &caller) {
         return Err((StatusCode::FORBIDDEN, "Not allowed to delete this card".to_string()));
     }
 
@@ -351,7 +275,7 @@ pub async fn delete_card(
     tracing::info!(
         "Deleted convention card {} by user {}",
         card_id,
-        acting_user_id
+        caller.user_id
     );
 
     Ok(Json(serde_json::json!({ "success": true })))
@@ -394,14 +318,17 @@ pub async fn get_user_cards(
 }
 
 /// POST /api/users/:user_id/cards
-/// Link a card to a user. Linking a private card requires the
-/// `acting_user_id` body field, which must be either the card's owner
-/// or an admin. Linking a public card is open to anyone.
+/// Link a card to a user. The caller must be that user or an admin;
+/// linking a private card further requires the caller to be able to
+/// read it (owner or admin).
 pub async fn link_card_to_user(
     State(state): State<AppState>,
+    caller: AuthUser,
     Path(user_id): Path<String>,
     Json(req): Json<LinkCardRequest>,
 ) -> Result<Json<LinkCardResponse>, (StatusCode, String)> {
+    policy::authorize(&state.db, &caller, Relation::SelfUser(&user_id)).await?;
+
     let card: Option<ConventionCard> =
         sqlx::query_as::<_, ConventionCard>("SELECT * FROM convention_cards WHERE id = ?")
             .bind(&req.card_id)
@@ -410,20 +337,8 @@ pub async fn link_card_to_user(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let card = card.ok_or((StatusCode::NOT_FOUND, "Card not found".to_string()))?;
 
-    // Private cards: require authz from acting_user_id
-    if card.visibility != "public" {
-        let acting = req
-            .acting_user_id
-            .as_deref()
-            .filter(|s| !s.is_empty())
-            .ok_or((
-                StatusCode::FORBIDDEN,
-                "acting_user_id is required to link a private card".to_string(),
-            ))?;
-        let role = fetch_caller_role(&state.db, acting).await;
-        if !can_read(&card, Some(acting), &role) {
-            return Err((StatusCode::FORBIDDEN, "Not allowed to link this card".to_string()));
-        }
+    if !can_read(&card, Some(&caller)) {
+        return Err((StatusCode::FORBIDDEN, "Not allowed to link this card".to_string()));
     }
 
     let link_id = uuid::Uuid::new_v4().to_string();
@@ -466,30 +381,15 @@ pub async fn link_card_to_user(
     }))
 }
 
-/// DELETE /api/users/:user_id/cards/:card_id?acting_user_id=...
+/// DELETE /api/users/:user_id/cards/:card_id
 /// Unlink a card from a user. Allowed when the caller is the same
 /// user OR an admin.
 pub async fn unlink_card_from_user(
     State(state): State<AppState>,
+    caller: AuthUser,
     Path((user_id, card_id)): Path<(String, String)>,
-    Query(query): Query<ActingUserQuery>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let acting_user_id = query.acting_user_id.unwrap_or_default();
-    if acting_user_id.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "acting_user_id is required".to_string(),
-        ));
-    }
-    if acting_user_id != user_id {
-        let role = fetch_caller_role(&state.db, &acting_user_id).await;
-        if !is_admin(&role) {
-            return Err((
-                StatusCode::FORBIDDEN,
-                "Only the user themselves or an admin can unlink a card".to_string(),
-            ));
-        }
-    }
+    policy::authorize(&state.db, &caller, Relation::SelfUser(&user_id)).await?;
 
     sqlx::query("DELETE FROM user_convention_cards WHERE user_id = ? AND card_id = ?")
         .bind(&user_id)
@@ -1,9 +1,42 @@
-use axum::{extract::{Query, State}, http::StatusCode, Json};
-use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
 use tracing::warn;
 
+use super::recovery::{rate_limit_allow, ClientIp};
+use crate::policy::RequireAdmin;
 use crate::AppState;
 
+/// Per-IP cap on stored reports. Past it, reports are still logged but not
+/// stored, so one broken client in a retry loop can't fill the table.
+const MAX_PER_IP: u32 = 120;
+const IP_WINDOW_SECS: u64 = 600; // 10 minutes
+
+/// Reports of one fingerprint stored in full per window; after that only
+/// one in SAMPLE_EVERY is stored, with that weight.
+const FULL_PER_FINGERPRINT: u32 = 20;
+const SAMPLE_EVERY: u32 = 10;
+const FINGERPRINT_WINDOW_SECS: u64 = 3600;
+
+/// Events read from one POST; the client batches at most 20.
+const MAX_BATCH: usize = 50;
+/// Longest stored field; longer values are cut.
+const MAX_FIELD_LEN: usize = 1000;
+
+static IP_LIMITER: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+static FINGERPRINT_COUNTS: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Debug, Deserialize)]
 pub struct DiagnosticEvent {
     pub event: String,
@@ -28,13 +61,162 @@ pub struct DiagnosticQuery {
     pub timestamp: Option<String>,
 }
 
+/// Replace the parts of an error message that vary between occurrences of
+/// the same problem: digit runs become `#`, ids (hex runs, long
+/// alphanumeric tokens) become `<id>`, and whitespace is collapsed.
+pub fn normalize_error(error: &str) -> String {
+    let mut out = String::with_capacity(error.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if word.is_empty() {
+            return;
+        }
+        let has_digit = word.chars().any(|c| c.is_ascii_digit());
+        if word.chars().all(|c| c.is_ascii_digit()) {
+            out.push('#');
+        } else if has_digit
+            && (word.len() >= 12
+                || (word.len() >= 4 && word.chars().all(|c| c.is_ascii_hexdigit())))
+        {
+            out.push_str("<id>");
+        } else if has_digit {
+            // "3000ms" -> "#ms"
+            for c in word.chars() {
+                if !c.is_ascii_digit() {
+                    out.push(c);
+                } else if !out.ends_with('#') {
+                    out.push('#');
+                }
+            }
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for c in error.trim().chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        flush(&mut word, &mut out);
+        if c.is_whitespace() {
+            if !out.ends_with(' ') {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+/// Groups reports of the same problem: event, context and normalised error.
+pub fn fingerprint(event: &str, context: &str, error: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(event.as_bytes());
+    hasher.update([0]);
+    hasher.update(context.as_bytes());
+    hasher.update([0]);
+    hasher.update(normalize_error(error.unwrap_or("")).as_bytes());
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Browser family from a user agent. Order matters: Edge and Opera also
+/// claim Chrome, and Chrome also claims Safari.
+pub fn browser_family(user_agent: Option<&str>) -> &'static str {
+    let Some(ua) = user_agent.filter(|ua| !ua.trim().is_empty()) else {
+        return "unknown";
+    };
+    if ua.contains("Edg/") || ua.contains("EdgiOS") {
+        "Edge"
+    } else if ua.contains("OPR/") {
+        "Opera"
+    } else if ua.contains("Firefox/") || ua.contains("FxiOS") {
+        "Firefox"
+    } else if ua.contains("Chrome/") || ua.contains("CriOS") {
+        "Chrome"
+    } else if ua.contains("Safari/") {
+        "Safari"
+    } else {
+        "other"
+    }
+}
+
+/// How many reports a stored row for `fingerprint` stands for, or None to
+/// drop this one (sampled out).
+fn sample_weight(fingerprint: &str) -> Option<i64> {
+    let now = Instant::now();
+    let mut map = match FINGERPRINT_COUNTS.lock() {
+        Ok(m) => m,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    map.retain(|_, (start, _)| now.duration_since(*start).as_secs() < FINGERPRINT_WINDOW_SECS);
+    let entry = map.entry(fingerprint.to_string()).or_insert((now, 0));
+    entry.1 += 1;
+    let seen = entry.1;
+    if seen <= FULL_PER_FINGERPRINT {
+        Some(1)
+    } else if (seen - FULL_PER_FINGERPRINT).is_multiple_of(SAMPLE_EVERY) {
+        Some(SAMPLE_EVERY as i64)
+    } else {
+        None
+    }
+}
+
+fn truncate(s: &str) -> String {
+    s.chars().take(MAX_FIELD_LEN).collect()
+}
+
+/// Store one report, subject to the per-IP limit and sampling. Returns
+/// whether a row was written.
+async fn record(
+    db: &Pool<Sqlite>,
+    ip: &str,
+    event: &str,
+    context: &str,
+    error: Option<&str>,
+    user_agent: Option<&str>,
+    client_timestamp: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    if !rate_limit_allow(&IP_LIMITER, ip, MAX_PER_IP, IP_WINDOW_SECS) {
+        return Ok(false);
+    }
+    let error = error.filter(|e| !e.is_empty());
+    let fingerprint = fingerprint(event, context, error);
+    let Some(weight) = sample_weight(&fingerprint) else {
+        return Ok(false);
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO client_diagnostics
+            (fingerprint, event, context, error, browser, user_agent, client_timestamp, weight, received_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&fingerprint)
+    .bind(truncate(event))
+    .bind(truncate(context))
+    .bind(error.map(truncate))
+    .bind(browser_family(user_agent))
+    .bind(user_agent.map(truncate))
+    .bind(client_timestamp.map(truncate))
+    .bind(weight)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(db)
+    .await?;
+    Ok(true)
+}
+
 /// POST /api/diagnostics — log client-side errors (batch)
 /// No auth required so errors can be logged even when registration fails
 pub async fn log_diagnostics(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Json(body): Json<DiagnosticPayload>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    for event in &body.events {
+    let mut stored = 0;
+    for event in body.events.iter().take(MAX_BATCH) {
         warn!(
             event = %event.event,
             context = %event.context,
@@ -43,15 +225,34 @@ pub async fn log_diagnostics(
             timestamp = event.timestamp.as_deref().unwrap_or("unknown"),
             "CLIENT DIAGNOSTIC"
         );
+        match record(
+            &state.db,
+            &ip,
+            &event.event,
+            &event.context,
+            event.error.as_deref(),
+            event.user_agent.as_deref(),
+            event.timestamp.as_deref(),
+        )
+        .await
+        {
+            Ok(true) => stored += 1,
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to store client diagnostic: {}", e),
+        }
     }
 
-    Ok(Json(serde_json::json!({ "success": true })))
+    Ok(Json(
+        serde_json::json!({ "success": true, "stored": stored }),
+    ))
 }
 
 /// GET /api/diagnostics — log a single client-side error via query params
 /// No CORS preflight needed (simple GET request), so this works even when
 /// POST requests are blocked by CORS/preflight issues.
 pub async fn log_diagnostic_get(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Query(params): Query<DiagnosticQuery>,
 ) -> Json<serde_json::Value> {
     warn!(
@@ -63,5 +264,289 @@ pub async fn log_diagnostic_get(
         "CLIENT DIAGNOSTIC (GET)"
     );
 
+    // `browser` carries the full user agent (see src/utils/diagnostics.js).
+    if let Err(e) = record(
+        &state.db,
+        &ip,
+        &params.event,
+        params.context.as_deref().unwrap_or(""),
+        params.error.as_deref(),
+        params.browser.as_deref(),
+        params.timestamp.as_deref().filter(|t| !t.is_empty()),
+    )
+    .await
+    {
+        tracing::error!("Failed to store client diagnostic: {}", e);
+    }
+
     Json(serde_json::json!({ "ok": true }))
 }
+
+/// Delete reports received more than `retention_days` ago.
+pub async fn prune_diagnostics(db: &Pool<Sqlite>, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days)).to_rfc3339();
+    let result = sqlx::query("DELETE FROM client_diagnostics WHERE received_at < ?")
+        .bind(cutoff)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Prune old reports now and then hourly, for as long as the server runs.
+pub fn spawn_diagnostics_pruner(db: Pool<Sqlite>, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match prune_diagnostics(&db, retention_days).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Pruned {} client diagnostics", n),
+                Err(e) => tracing::error!("Pruning client diagnostics failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Query params for GET /api/admin/diagnostics
+#[derive(Debug, Deserialize)]
+pub struct AdminDiagnosticsQuery {
+    /// Only reports received at or after this RFC 3339 time.
+    pub since: Option<String>,
+    pub event: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One fingerprint's reports.
+#[derive(Debug, Serialize)]
+pub struct DiagnosticGroup {
+    pub fingerprint: String,
+    pub event: String,
+    pub context: String,
+    /// The most recent error text for this fingerprint.
+    pub error: Option<String>,
+    /// Estimated report count (sampled rows count for their weight).
+    pub count: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub browsers: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminDiagnosticsResponse {
+    pub success: bool,
+    pub groups: Vec<DiagnosticGroup>,
+}
+
+#[derive(sqlx::FromRow)]
+struct GroupRow {
+    fingerprint: String,
+    event: String,
+    context: String,
+    error: Option<String>,
+    count: i64,
+    first_seen: String,
+    last_seen: String,
+}
+
+/// Reports grouped by fingerprint, most recently seen first.
+pub async fn diagnostic_groups(
+    db: &Pool<Sqlite>,
+    since: Option<&str>,
+    event: Option<&str>,
+    limit: i64,
+) -> Result<Vec<DiagnosticGroup>, sqlx::Error> {
+    let rows: Vec<GroupRow> = sqlx::query_as(
+        r#"
+        SELECT d.fingerprint,
+               MAX(d.event) AS event,
+               MAX(d.context) AS context,
+               (SELECT l.error FROM client_diagnostics l
+                 WHERE l.fingerprint = d.fingerprint
+                 ORDER BY l.received_at DESC, l.id DESC LIMIT 1) AS error,
+               SUM(d.weight) AS count,
+               MIN(d.received_at) AS first_seen,
+               MAX(d.received_at) AS last_seen
+        FROM client_diagnostics d
+        WHERE (? IS NULL OR d.received_at >= ?)
+          AND (? IS NULL OR d.event = ?)
+        GROUP BY d.fingerprint
+        ORDER BY last_seen DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(since)
+    .bind(since)
+    .bind(event)
+    .bind(event)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let mut groups = Vec::with_capacity(rows.len());
+    for row in rows {
+        let browsers: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT browser, SUM(weight) FROM client_diagnostics
+            WHERE fingerprint = ? AND (? IS NULL OR received_at >= ?)
+            GROUP BY browser
+            "#,
+        )
+        .bind(&row.fingerprint)
+        .bind(since)
+        .bind(since)
+        .fetch_all(db)
+        .await?;
+        groups.push(DiagnosticGroup {
+            fingerprint: row.fingerprint,
+            event: row.event,
+            context: row.context,
+            error: row.error,
+            count: row.count,
+            first_seen: row.first_seen,
+            last_seen: row.last_seen,
+            browsers: browsers.into_iter().collect(),
+        });
+    }
+    Ok(groups)
+}
+
+/// GET /api/admin/diagnostics
+/// Stored client diagnostics grouped by fingerprint.
+pub async fn admin_diagnostics(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+    Query(query): Query<AdminDiagnosticsQuery>,
+) -> Result<Json<AdminDiagnosticsResponse>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    let groups = diagnostic_groups(
+        &state.db,
+        query.since.as_deref(),
+        query.event.as_deref(),
+        limit,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+    })?;
+    Ok(Json(AdminDiagnosticsResponse {
+        success: true,
+        groups,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_differing_only_in_ids_share_a_fingerprint() {
+        assert_eq!(
+            normalize_error("TypeError:  failed to fetch /api/users/3f2a9c1e-77b0-4d2e-9a11-0c5e2f7d8b41 (status 503)"),
+            "TypeError: failed to fetch /api/users/<id>-<id>-<id>-<id>-<id> (status #)"
+        );
+        let a = fingerprint(
+            "sync_failed",
+            "flush",
+            Some("timeout after 3000ms on req 8f3ab2c1d9"),
+        );
+        let b = fingerprint(
+            "sync_failed",
+            "flush",
+            Some("timeout after 5000ms on req 11aa22bb33"),
+        );
+        let c = fingerprint(
+            "sync_failed",
+            "other",
+            Some("timeout after 5000ms on req 11aa22bb33"),
+        );
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let chrome = "Mozilla/5.0 (Macintosh) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36";
+        let edge =
+            "Mozilla/5.0 (Windows NT 10.0) AppleWebKit/537.36 Chrome/120.0 Safari/537.36 Edg/120.0";
+        let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0) AppleWebKit/605.1.15 Version/17.0 Mobile/15E148 Safari/604.1";
+        assert_eq!(browser_family(Some(chrome)), "Chrome");
+        assert_eq!(browser_family(Some(edge)), "Edge");
+        assert_eq!(browser_family(Some(safari)), "Safari");
+        assert_eq!(browser_family(None), "unknown");
+    }
+
+    #[tokio::test]
+    async fn reports_are_grouped_sampled_and_pruned() {
+        let db = crate::db::test_pool().await;
+        let chrome = Some("Mozilla/5.0 Chrome/120.0 Safari/537.36");
+        let firefox = Some("Mozilla/5.0 Gecko/20100101 Firefox/121.0");
+
+        for i in 0..25 {
+            let error = format!("request {} timed out", i);
+            let ua = if i % 5 == 0 { firefox } else { chrome };
+            // Spread over IPs so only sampling, not the rate limit, applies.
+            let ip = format!("test-group-{}", i % 3);
+            record(&db, &ip, "grouped_event", "flush", Some(&error), ua, None)
+                .await
+                .unwrap();
+        }
+        record(
+            &db,
+            "test-group-0",
+            "other_event",
+            "boot",
+            None,
+            chrome,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let groups = diagnostic_groups(&db, None, None, 10).await.unwrap();
+        assert_eq!(groups.len(), 2);
+        let grouped = groups.iter().find(|g| g.event == "grouped_event").unwrap();
+        // 20 stored in full, then 1 of the next 5 would need 10: none yet.
+        assert_eq!(grouped.count, 20);
+        assert_eq!(grouped.browsers["Firefox"], 4);
+        assert_eq!(grouped.browsers["Chrome"], 16);
+        assert_eq!(grouped.error.as_deref(), Some("request 19 timed out"));
+
+        // The 30th report is the first sampled one and stands for ten.
+        for i in 25..30 {
+            record(
+                &db,
+                "test-group-1",
+                "grouped_event",
+                "flush",
+                Some(&format!("request {} timed out", i)),
+                chrome,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let groups = diagnostic_groups(&db, None, Some("grouped_event"), 10)
+            .await
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].count, 30);
+
+        // The per-IP limit stops storing, not logging.
+        for _ in 0..MAX_PER_IP {
+            rate_limit_allow(&IP_LIMITER, "test-flood", MAX_PER_IP, IP_WINDOW_SECS);
+        }
+        assert!(!record(&db, "test-flood", "flood", "x", None, chrome, None)
+            .await
+            .unwrap());
+
+        sqlx::query("UPDATE client_diagnostics SET received_at = '2000-01-01T00:00:00+00:00' WHERE event = 'other_event'")
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(prune_diagnostics(&db, 30).await.unwrap(), 1);
+        assert_eq!(
+            diagnostic_groups(&db, None, None, 10).await.unwrap().len(),
+            1
+        );
+    }
+}
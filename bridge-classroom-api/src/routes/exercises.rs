@@ -1,31 +1,33 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 
 use crate::models::{
-    CreateExerciseRequest, CreateExerciseResponse, DeleteExerciseQuery, ExerciseActionResponse,
+    CreateExerciseRequest, CreateExerciseResponse, ExerciseActionResponse,
     ExerciseAssignmentRef, ExerciseBoard, ExerciseDetail, ExerciseDetailResponse, ExerciseInfo,
     ExerciseListResponse, ExerciseQuery, UpdateExerciseRequest,
 };
+use crate::policy;
+use crate::session::AuthUser;
 use crate::AppState;
 
-/// Verify the actor owns the exercise. Returns Err if a `created_by` is
-/// set on the row and the actor does not match. When `created_by IS
-/// NULL` (legacy exercises with no recorded owner) anyone with the API
-/// key may edit — keeps pre-#15 data usable.
+/// Verify the caller owns the exercise (or is an admin). Returns Err if a
+/// `created_by` is set on the row and the caller does not match. When
+/// `created_by IS NULL` (legacy exercises with no recorded owner) any
+/// signed-in user may edit — keeps pre-#15 data usable.
 fn check_owner(
     created_by: Option<&str>,
-    actor_user_id: Option<&str>,
+    caller: &AuthUser,
 ) -> Result<(), (StatusCode, String)> {
-    match (created_by, actor_user_id) {
-        (Some(owner), Some(actor)) if owner == actor => Ok(()),
-        (Some(_), _) => Err((
+    if created_by.is_none() || policy::owns(caller, created_by) {
+        Ok(())
+    } else {
+        Err((
             StatusCode::FORBIDDEN,
             "Only the exercise's creator can modify or delete it".to_string(),
-        )),
-        (None, _) => Ok(()),
+        ))
     }
 }
 
@@ -91,13 +93,6 @@ async fn check_creation_quota(
     Ok(())
 }
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
 
 // ---- Helper structs for joined queries ----
 
@@ -161,8 +156,7 @@ async fn fetch_assignments_for_exercises(
     if exercise_ids.is_empty() {
         return Ok(Vec::new());
     }
-    let placeholders = std::iter::repeat("?")
-        .take(exercise_ids.len())
+    let placeholders = std::iter::repeat_n("?", exercise_ids.len())
         .collect::<Vec<_>>()
         .join(",");
     // `users` stores names as (first_name, last_name) — no `name`
@@ -218,13 +212,9 @@ fn group_assignments_by_exercise(
 /// POST /api/exercises — Create an exercise with boards
 pub async fn create_exercise(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<CreateExerciseRequest>,
 ) -> Result<Json<CreateExerciseResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     if req.name.trim().is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Exercise name is required".to_string()));
     }
@@ -236,7 +226,7 @@ pub async fn create_exercise(
         ));
     }
 
-    check_creation_quota(&state.db, req.created_by.as_deref()).await?;
+    check_creation_quota(&state.db, Some(&caller.user_id)).await?;
 
     let id = uuid::Uuid::new_v4().to_string();
     let now = chrono::Utc::now().to_rfc3339();
@@ -257,7 +247,7 @@ pub async fn create_exercise(
     .bind(&id)
     .bind(req.name.trim())
     .bind(&req.description)
-    .bind(&req.created_by)
+    .bind(&caller.user_id)
     .bind(&req.curriculum_path)
     .bind(visibility)
     .bind(&now)
@@ -304,7 +294,7 @@ pub async fn create_exercise(
             id,
             name: req.name.trim().to_string(),
             description: req.description,
-            created_by: req.created_by,
+            created_by: Some(caller.user_id),
             curriculum_path: req.curriculum_path,
             visibility: visibility.to_string(),
             created_at: now,
@@ -321,13 +311,9 @@ pub async fn create_exercise(
 /// GET /api/exercises — List exercises with board counts
 pub async fn list_exercises(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _caller: AuthUser,
     Query(query): Query<ExerciseQuery>,
 ) -> Result<Json<ExerciseListResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     // Soft-deleted rows are excluded — they're tombstones kept only so
     // observations' `exercise_id` references remain resolvable for
     // history views.
@@ -406,13 +392,9 @@ pub async fn list_exercises(
 /// GET /api/exercises/:id — Get exercise detail with board list
 pub async fn get_exercise(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _caller: AuthUser,
     Path(exercise_id): Path<String>,
 ) -> Result<Json<ExerciseDetailResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let exercise = sqlx::query_as::<_, ExerciseRow>(
         "SELECT id, name, description, created_by, curriculum_path, visibility, created_at FROM exercises WHERE id = ? AND deleted_at IS NULL",
     )
@@ -454,14 +436,10 @@ pub async fn get_exercise(
 /// PUT /api/exercises/:id — Update exercise (owner only)
 pub async fn update_exercise(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(exercise_id): Path<String>,
     Json(req): Json<UpdateExerciseRequest>,
 ) -> Result<Json<ExerciseActionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     // Verify exercise exists and the actor owns it (issue #15).
     let existing = sqlx::query_as::<_, ExerciseRow>(
         "SELECT id, name, description, created_by, curriculum_path, visibility, created_at FROM exercises WHERE id = ? AND deleted_at IS NULL",
@@ -472,7 +450,7 @@ pub async fn update_exercise(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
     .ok_or_else(|| (StatusCode::NOT_FOUND, "Exercise not found".to_string()))?;
 
-    check_owner(existing.created_by.as_deref(), req.actor_user_id.as_deref())?;
+    check_owner(existing.created_by.as_deref(), &caller)?;
 
     let mut tx = state.db.begin().await.map_err(|e| {
         (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
@@ -560,14 +538,9 @@ pub async fn update_exercise(
 /// treat the deleted exercise as unavailable.
 pub async fn delete_exercise(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(exercise_id): Path<String>,
-    Query(query): Query<DeleteExerciseQuery>,
 ) -> Result<Json<ExerciseActionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let existing = sqlx::query_as::<_, ExerciseRow>(
         "SELECT id, name, description, created_by, curriculum_path, visibility, created_at FROM exercises WHERE id = ? AND deleted_at IS NULL",
     )
@@ -577,7 +550,7 @@ pub async fn delete_exercise(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
     .ok_or_else(|| (StatusCode::NOT_FOUND, "Exercise not found".to_string()))?;
 
-    check_owner(existing.created_by.as_deref(), query.actor_user_id.as_deref())?;
+    check_owner(existing.created_by.as_deref(), &caller)?;
 
     let now = chrono::Utc::now().to_rfc3339();
     let result = sqlx::query("UPDATE exercises SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
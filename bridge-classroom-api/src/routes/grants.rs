@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::Deserialize;
@@ -9,17 +9,10 @@ use crate::models::{
     CreateGrantRequest, CreateGrantResponse, GrantInfo, GrantsListResponse, RevokeGrantResponse,
     SharingGrant,
 };
+use crate::policy::{authorize, Relation};
+use crate::session::AuthUser;
 use crate::AppState;
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(|k| k == expected_key)
-        .unwrap_or(false)
-}
-
 /// Query parameters for grant lookup
 #[derive(Debug, Deserialize)]
 pub struct GrantQuery {
@@ -27,17 +20,13 @@ pub struct GrantQuery {
     pub grantor_id: Option<String>,
 }
 
-/// Create a new sharing grant
+/// Create a new sharing grant from the caller to a viewer
 pub async fn create_grant(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<CreateGrantRequest>,
 ) -> Result<Json<CreateGrantResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let grant = SharingGrant::from_request(&req);
+    let grant = SharingGrant::from_request(&caller.user_id, &req);
 
     // Use upsert to handle re-granting (update encrypted_payload if grant exists)
     let result = sqlx::query(
@@ -79,8 +68,16 @@ pub async fn create_grant(
 /// Get grants - filtered by grantee_id or grantor_id
 pub async fn get_grants(
     State(state): State<AppState>,
+    caller: AuthUser,
     Query(query): Query<GrantQuery>,
 ) -> Result<Json<GrantsListResponse>, (StatusCode, String)> {
+    if query.grantee_id.is_some() && !caller.is_teacher() {
+        return Err((StatusCode::FORBIDDEN, "Only viewers can list received grants".to_string()));
+    }
+    if let Some(grantor_id) = &query.grantor_id {
+        authorize(&state.db, &caller, Relation::SelfUser(grantor_id)).await?;
+    }
+
     // Build query based on filters
     let grants: Vec<GrantInfo> = if let Some(grantee_id) = query.grantee_id {
         // Get grants for a viewer (includes grantor info)
@@ -169,15 +166,12 @@ pub async fn get_grants(
     Ok(Json(GrantsListResponse { grants }))
 }
 
-/// Revoke a sharing grant
+/// Revoke a sharing grant (only the grantor may revoke)
 pub async fn revoke_grant(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(grant_id): Path<String>,
 ) -> Result<Json<RevokeGrantResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
 
     let now = chrono::Utc::now().to_rfc3339();
 
@@ -185,11 +179,12 @@ pub async fn revoke_grant(
         r#"
         UPDATE sharing_grants
         SET revoked = 1, revoked_at = ?
-        WHERE id = ?
+        WHERE id = ? AND grantor_id = ?
         "#,
     )
     .bind(&now)
     .bind(&grant_id)
+    .bind(&caller.user_id)
     .execute(&state.db)
     .await;
 
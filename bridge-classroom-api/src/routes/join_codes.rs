@@ -0,0 +1,563 @@
+//! Join-code lifecycle: replacing a classroom's code, its join settings
+//! (expiry, capacity, approval) and the join requests approval creates.
+//!
+//! Looking a code up and joining with it live in classrooms.rs
+//! (`get_join_info`, `join_classroom`). All of these need the
+//! [`ClassroomPermission::ManageClassroom`] permission, except listing
+//! requests, which any staff member may do.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+
+use super::classroom_staff::staff_viewers;
+use super::classrooms::{
+    admit_member, is_join_code_collision, load_classroom, validate_join_settings,
+};
+use crate::models::{
+    generate_join_code, ClassroomActionResponse, JoinCodeResponse, JoinRequestInfo,
+    JoinRequestListResponse, JoinSettings, RegenerateJoinCodeRequest,
+};
+use crate::policy::{authorize, ClassroomPermission, Relation};
+use crate::session::AuthUser;
+use crate::AppState;
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+#[derive(sqlx::FromRow)]
+struct JoinRequestRow {
+    encrypted_grant_payload: String,
+    staff_grant_payloads: String,
+}
+
+async fn authorize_manage(
+    state: &AppState,
+    caller: &AuthUser,
+    classroom_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    authorize(
+        &state.db,
+        caller,
+        Relation::ClassroomStaff(classroom_id, ClassroomPermission::ManageClassroom),
+    )
+    .await
+}
+
+/// POST /api/classrooms/:id/join-code — replace the join code. The old code
+/// stops working at once; the new one gets the requested expiry.
+pub async fn regenerate_join_code(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+    Json(req): Json<RegenerateJoinCodeRequest>,
+) -> Result<Json<JoinCodeResponse>, (StatusCode, String)> {
+    let classroom = load_classroom(&state, &classroom_id).await?;
+    authorize_manage(&state, &caller, &classroom_id).await?;
+    let settings = validate_join_settings(JoinSettings {
+        join_code_expires_at: req.join_code_expires_at,
+        ..classroom.join_settings
+    })?;
+
+    for attempt in 0..5 {
+        let join_code = generate_join_code();
+        let result = sqlx::query(
+            "UPDATE classrooms SET join_code = ?, join_code_expires_at = ? WHERE id = ?",
+        )
+        .bind(&join_code)
+        .bind(&settings.join_code_expires_at)
+        .bind(&classroom_id)
+        .execute(&state.db)
+        .await;
+
+        match result {
+            Ok(_) => {
+                tracing::info!(
+                    "Join code for classroom {} replaced by {}",
+                    classroom_id,
+                    caller.user_id
+                );
+                return Ok(Json(JoinCodeResponse {
+                    success: true,
+                    join_code,
+                    join_settings: settings,
+                }));
+            }
+            Err(e) if is_join_code_collision(&e) && attempt < 4 => continue,
+            Err(e) => return Err(db_err(e)),
+        }
+    }
+
+    Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to generate unique join code after 5 attempts".to_string(),
+    ))
+}
+
+/// PUT /api/classrooms/:id/join-settings — replace the join settings.
+/// Omitted fields go back to their defaults (no expiry, no cap, no
+/// approval). Lowering the cap doesn't remove anyone, and turning approval
+/// off leaves existing requests for a teacher to decide.
+pub async fn update_join_settings(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+    Json(req): Json<JoinSettings>,
+) -> Result<Json<JoinCodeResponse>, (StatusCode, String)> {
+    let classroom = load_classroom(&state, &classroom_id).await?;
+    authorize_manage(&state, &caller, &classroom_id).await?;
+    let settings = validate_join_settings(req)?;
+
+    sqlx::query(
+        r#"
+        UPDATE classrooms
+        SET join_code_expires_at = ?, max_members = ?, requires_approval = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(&settings.join_code_expires_at)
+    .bind(settings.max_members)
+    .bind(settings.requires_approval)
+    .bind(&classroom_id)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(JoinCodeResponse {
+        success: true,
+        join_code: classroom.join_code,
+        join_settings: settings,
+    }))
+}
+
+/// GET /api/classrooms/:id/join-requests — students waiting for approval,
+/// oldest first
+pub async fn list_join_requests(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+) -> Result<Json<JoinRequestListResponse>, (StatusCode, String)> {
+    load_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ViewProgress),
+    )
+    .await?;
+
+    let rows: Vec<(String, String, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT r.student_id, u.first_name, u.last_name, u.email, r.requested_at
+        FROM classroom_join_requests r
+        JOIN users u ON u.id = r.student_id
+        WHERE r.classroom_id = ?
+        ORDER BY r.requested_at
+        "#,
+    )
+    .bind(&classroom_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(JoinRequestListResponse {
+        success: true,
+        requests: rows
+            .into_iter()
+            .map(
+                |(student_id, first_name, last_name, email, requested_at)| JoinRequestInfo {
+                    student_id,
+                    first_name,
+                    last_name,
+                    email,
+                    requested_at,
+                },
+            )
+            .collect(),
+    }))
+}
+
+/// POST /api/classrooms/:id/join-requests/:student_id/approve — make the
+/// student a member and create the sharing grants they sent. Payloads for
+/// staff who have since left are dropped. 409 when the classroom is full.
+pub async fn approve_join_request(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, student_id)): Path<(String, String)>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    let classroom = load_classroom(&state, &classroom_id).await?;
+    authorize_manage(&state, &caller, &classroom_id).await?;
+
+    let request: Option<JoinRequestRow> = sqlx::query_as(
+        r#"
+        SELECT encrypted_grant_payload, staff_grant_payloads
+        FROM classroom_join_requests
+        WHERE classroom_id = ? AND student_id = ?
+        "#,
+    )
+    .bind(&classroom_id)
+    .bind(&student_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_err)?;
+    let Some(request) = request else {
+        return Err((StatusCode::NOT_FOUND, "No pending join request".to_string()));
+    };
+    let payloads: HashMap<String, String> =
+        serde_json::from_str(&request.staff_grant_payloads).unwrap_or_default();
+    let viewers = staff_viewers(&state.db, &classroom_id).await?;
+    let staff_grants: Vec<(&str, &str)> = viewers
+        .iter()
+        .filter_map(|v| {
+            payloads
+                .get(&v.viewer_id)
+                .map(|p| (v.viewer_id.as_str(), p.as_str()))
+        })
+        .collect();
+
+    admit_member(
+        &state,
+        &classroom,
+        &student_id,
+        &request.encrypted_grant_payload,
+        &staff_grants,
+    )
+    .await?;
+
+    tracing::info!(
+        "{} approved student {} into classroom {}",
+        caller.user_id,
+        student_id,
+        classroom_id
+    );
+
+    Ok(Json(ClassroomActionResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+/// DELETE /api/classrooms/:id/join-requests/:student_id — decline a join
+/// request
+pub async fn decline_join_request(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, student_id)): Path<(String, String)>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    load_classroom(&state, &classroom_id).await?;
+    authorize_manage(&state, &caller, &classroom_id).await?;
+
+    let result = sqlx::query(
+        "DELETE FROM classroom_join_requests WHERE classroom_id = ? AND student_id = ?",
+    )
+    .bind(&classroom_id)
+    .bind(&student_id)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    if result.rows_affected() == 0 {
+        return Ok(Json(ClassroomActionResponse {
+            success: false,
+            error: Some("No pending join request".to_string()),
+        }));
+    }
+
+    tracing::info!(
+        "{} declined student {}'s request to join classroom {}",
+        caller.user_id,
+        student_id,
+        classroom_id
+    );
+
+    Ok(Json(ClassroomActionResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::{CreateClassroomRequest, JoinClassroomRequest};
+    use crate::policy::RequireTeacher;
+    use crate::routes::{create_classroom, get_join_info, join_classroom};
+    use crate::routes::recovery::ClientIp;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn user(id: &str, role: &str) -> AuthUser {
+        AuthUser {
+            user_id: id.into(),
+            role: role.into(),
+        }
+    }
+
+    fn from_ip(ip: &str) -> ClientIp {
+        ClientIp(ip.to_string())
+    }
+
+    #[tokio::test]
+    async fn codes_expire_rotate_cap_and_wait_for_approval() {
+        let db = crate::db::test_pool().await;
+        let state = AppState {
+            db: db.clone(),
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for (id, role) in [("t", "teacher"), ("s1", "student"), ("s2", "student")] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+                 VALUES (?, ?, 'X', ?, ?, '', '')",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(role)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let teacher = user("t", "teacher");
+        let created = create_classroom(
+            State(state.clone()),
+            RequireTeacher(teacher.clone()),
+            Json(CreateClassroomRequest {
+                name: "Tuesday".into(),
+                description: None,
+                join_settings: JoinSettings {
+                    join_code_expires_at: None,
+                    max_members: Some(1),
+                    requires_approval: true,
+                },
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+        .classroom;
+        let id = created.id.clone();
+        let code = created.join_code.clone();
+        assert!(code.starts_with("BRG-") && code.len() == 13, "{code}");
+
+        let ip = from_ip("10.0.0.1");
+        let join = |student: &str, code: &str| {
+            join_classroom(
+                State(state.clone()),
+                ip.clone(),
+                user(student, "student"),
+                Path(code.to_string()),
+                Json(JoinClassroomRequest {
+                    encrypted_grant_payload: "payload".into(),
+                    staff_grant_payloads: HashMap::new(),
+                    invitation_token: None,
+                }),
+            )
+        };
+
+        // Approval: joining (with a lower-case code) only files a request.
+        let joined = join("s1", &code.to_lowercase()).await.unwrap().0;
+        assert!(joined.pending);
+        let requests = list_join_requests(State(state.clone()), teacher.clone(), Path(id.clone()))
+            .await
+            .unwrap()
+            .0
+            .requests;
+        assert_eq!(requests.len(), 1);
+        let members = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM classroom_members")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+        };
+        assert_eq!(members().await, 0);
+        let approved = approve_join_request(
+            State(state.clone()),
+            teacher.clone(),
+            Path((id.clone(), "s1".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(approved.0.success);
+        assert_eq!(members().await, 1);
+
+        // Capacity: the classroom takes one member.
+        assert_eq!(join("s2", &code).await.unwrap_err().0, StatusCode::CONFLICT);
+
+        // Rotation: the old code is dead, the new one works.
+        let settings = update_join_settings(
+            State(state.clone()),
+            teacher.clone(),
+            Path(id.clone()),
+            Json(JoinSettings::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(settings.0.join_code, code);
+        let rotated = regenerate_join_code(
+            State(state.clone()),
+            teacher.clone(),
+            Path(id.clone()),
+            Json(RegenerateJoinCodeRequest::default()),
+        )
+        .await
+        .unwrap()
+        .0;
+        assert_ne!(rotated.join_code, code);
+        assert_eq!(
+            join("s2", &code).await.unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        let joined = join("s2", &rotated.join_code).await.unwrap().0;
+        assert!(!joined.pending && joined.error.is_none());
+
+        // Expiry.
+        let expired = regenerate_join_code(
+            State(state.clone()),
+            teacher.clone(),
+            Path(id.clone()),
+            Json(RegenerateJoinCodeRequest {
+                join_code_expires_at: Some("2020-01-01T00:00:00Z".into()),
+            }),
+        )
+        .await
+        .unwrap()
+        .0;
+        let info = get_join_info(
+            State(state.clone()),
+            ip.clone(),
+            Path(expired.join_code.clone()),
+        )
+        .await;
+        assert_eq!(info.unwrap_err().0, StatusCode::GONE);
+
+        // Scanning: once an IP has missed too often, even a good code is refused.
+        let valid = regenerate_join_code(
+            State(state.clone()),
+            teacher.clone(),
+            Path(id.clone()),
+            Json(RegenerateJoinCodeRequest::default()),
+        )
+        .await
+        .unwrap()
+        .0
+        .join_code;
+        let scanner = from_ip("10.9.9.9");
+        for n in 0..20 {
+            let miss = get_join_info(
+                State(state.clone()),
+                scanner.clone(),
+                Path(format!("BRG-{n:04}")),
+            )
+            .await;
+            assert_eq!(miss.unwrap_err().0, StatusCode::NOT_FOUND);
+        }
+        let refused = get_join_info(State(state.clone()), scanner, Path(valid.clone())).await;
+        assert_eq!(refused.unwrap_err().0, StatusCode::TOO_MANY_REQUESTS);
+        assert!(get_join_info(State(state.clone()), ip, Path(valid))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_one_approval_takes_the_last_seat() {
+        let db = crate::db::test_pool().await;
+        let state = AppState {
+            db: db.clone(),
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for (id, role) in [("t", "teacher"), ("s1", "student"), ("s2", "student")] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+                 VALUES (?, ?, 'X', ?, ?, '', '')",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(role)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let teacher = user("t", "teacher");
+        let created = create_classroom(
+            State(state.clone()),
+            RequireTeacher(teacher.clone()),
+            Json(CreateClassroomRequest {
+                name: "Tuesday".into(),
+                description: None,
+                join_settings: JoinSettings {
+                    join_code_expires_at: None,
+                    max_members: Some(1),
+                    requires_approval: true,
+                },
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+        .classroom;
+        let id = created.id.clone();
+        let ip = from_ip("10.0.0.2");
+        for student in ["s1", "s2"] {
+            let joined = join_classroom(
+                State(state.clone()),
+                ip.clone(),
+                user(student, "student"),
+                Path(created.join_code.clone()),
+                Json(JoinClassroomRequest {
+                    encrypted_grant_payload: "payload".into(),
+                    staff_grant_payloads: HashMap::new(),
+                    invitation_token: None,
+                }),
+            )
+            .await
+            .unwrap()
+            .0;
+            assert!(joined.pending);
+        }
+
+        // Both requests were filed while the classroom was empty; approving
+        // them at once still admits only one.
+        let approve = |student: &str| {
+            approve_join_request(
+                State(state.clone()),
+                teacher.clone(),
+                Path((id.clone(), student.to_string())),
+            )
+        };
+        let (first, second) = tokio::join!(approve("s1"), approve("s2"));
+        let refused: Vec<StatusCode> = [first, second]
+            .into_iter()
+            .filter_map(|r| r.err().map(|e| e.0))
+            .collect();
+        assert_eq!(refused, [StatusCode::CONFLICT]);
+        let (members, requests): (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM classroom_members), \
+                    (SELECT COUNT(*) FROM classroom_join_requests)",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        // The refused request stays for the teacher to decline or retry.
+        assert_eq!((members, requests), (1, 1));
+
+        // Someone who can't manage the classroom can't approve at all.
+        let outsider = approve_join_request(
+            State(state.clone()),
+            user("s1", "student"),
+            Path((id.clone(), "s2".to_string())),
+        )
+        .await;
+        assert_eq!(outsider.unwrap_err().0, StatusCode::FORBIDDEN);
+    }
+}
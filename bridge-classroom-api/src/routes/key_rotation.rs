@@ -0,0 +1,720 @@
+//! Student key rotation.
+//!
+//! A student who thinks their AES key has leaked (a lost device, a shared
+//! browser) generates a new one and posts it here. The server can already
+//! recover the old key (`recover_user_key`), so it re-encrypts every
+//! observation under the new key the way `merge_user_into` moves them
+//! between accounts, reading each one back before it is written.
+//!
+//! The work runs in the background off a `key_rotations` row, in batches by
+//! observation id with the cursor committed alongside each batch, so a
+//! restart resumes it (`resume_key_rotations`). A final transaction checks
+//! every row opens under the new key (catching observations submitted under
+//! the old one meanwhile), swaps `recovery_encrypted_key`, revokes the
+//! user's sharing grants (they wrap the old key; the client reissues them)
+//! and drops the merge handoffs still waiting to give the old key to
+//! merged-away devices.
+//!
+//! Nothing hands the new key to the user's other devices: the point of
+//! rotating is that the old key may be in the wrong hands, and anything
+//! wrapped under it would be too. Those devices fail their next key proof
+//! (`POST /api/sessions`) and sign back in through email recovery, which
+//! returns the new key.
+//!
+//! For the same reason a session is not enough to start a rotation: it only
+//! proves the old key. The caller also spends the code from a recovery
+//! email (`POST /api/recovery/request`). Resuming an unfinished rotation
+//! needs no code, since it needs the new key, which only the device that
+//! started it has.
+//!
+//! A rotation that fails stays `failed` with its keys kept, because some
+//! observations are already under the new key; posting the same key again
+//! resumes it.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use super::merge::{recover_user_key, ObsCipher, OBS_CIPHER_COLUMNS};
+use super::recovery::{decrypt_for_recovery, encrypt_for_recovery, redeem_recovery_code};
+use crate::obs_crypto::{
+    decrypt_observation_with_aad, encrypt_observation, encrypt_observation_with_aad,
+};
+use crate::policy::{authorize, Relation};
+use crate::session::AuthUser;
+use crate::AppState;
+
+/// Observations re-encrypted per transaction.
+const BATCH_SIZE: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct StartKeyRotationRequest {
+    pub user_id: String,
+    /// The new AES-256 key, base64 (as `generateSecretKey()` produces).
+    pub new_key: String,
+    /// The 6-digit code from a recovery email. Required unless this resumes
+    /// an unfinished rotation.
+    #[serde(default)]
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyRotationQuery {
+    pub user_id: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RotationRow {
+    id: i64,
+    user_id: String,
+    status: String,
+    old_key: Option<String>,
+    new_key: Option<String>,
+    total: i64,
+    reencrypted: i64,
+    cursor: Option<String>,
+    revoked_grantees: Option<String>,
+    error: Option<String>,
+    created_at: String,
+    completed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyRotationStatus {
+    pub id: i64,
+    pub user_id: String,
+    /// `running`, `failed` or `completed`.
+    pub status: String,
+    /// Observations the user had when the rotation started.
+    pub total: i64,
+    /// Observations checked or re-encrypted so far.
+    pub reencrypted: i64,
+    /// Viewers whose grants were revoked; reissue them under the new key.
+    pub revoked_grantees: Vec<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl From<RotationRow> for KeyRotationStatus {
+    fn from(row: RotationRow) -> Self {
+        KeyRotationStatus {
+            id: row.id,
+            user_id: row.user_id,
+            status: row.status,
+            total: row.total,
+            reencrypted: row.reencrypted,
+            revoked_grantees: row
+                .revoked_grantees
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            error: row.error,
+            created_at: row.created_at,
+            completed_at: row.completed_at,
+        }
+    }
+}
+
+fn err500<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+async fn fetch_rotation(
+    db: &sqlx::Pool<sqlx::Sqlite>,
+    id: i64,
+) -> Result<Option<RotationRow>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM key_rotations WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Record a rotation of `user_id` to `new_key`, or pick an unfinished one
+/// to the same key back up. Returns the rotation id and whether the caller
+/// should run it (false when it is already running).
+pub async fn begin_key_rotation(
+    state: &AppState,
+    user_id: &str,
+    new_key: &str,
+) -> Result<(i64, bool), (StatusCode, String)> {
+    let recovery_secret = state.config.recovery_secret.as_ref().ok_or((
+        StatusCode::BAD_REQUEST,
+        "RECOVERY_SECRET not configured".to_string(),
+    ))?;
+    encrypt_observation(b"", new_key)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("new_key: {}", e)))?;
+    let old_key = recover_user_key(state, user_id)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let unfinished: Option<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT id, new_key FROM key_rotations \
+         WHERE user_id = ? AND status IN ('running', 'failed')",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(err500)?;
+    if let Some((id, pending_key)) = unfinished {
+        let pending_key = pending_key
+            .and_then(|k| decrypt_for_recovery(&k, recovery_secret).ok())
+            .unwrap_or_default();
+        if pending_key != new_key {
+            return Err((
+                StatusCode::CONFLICT,
+                "An unfinished rotation to a different key must be resumed with that key"
+                    .to_string(),
+            ));
+        }
+        let resumed = sqlx::query(
+            "UPDATE key_rotations SET status = 'running', error = NULL, updated_at = ? \
+             WHERE id = ? AND status = 'failed'",
+        )
+        .bind(&now)
+        .bind(id)
+        .execute(&state.db)
+        .await
+        .map_err(err500)?;
+        return Ok((id, resumed.rows_affected() > 0));
+    }
+
+    if old_key == new_key {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "new_key is the current key".to_string(),
+        ));
+    }
+    let (total, old_key_stored): (i64, Option<String>) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM observations WHERE user_id = ?1), \
+                recovery_encrypted_key FROM users WHERE id = ?1",
+    )
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(err500)?;
+    let new_key_stored = encrypt_for_recovery(new_key, recovery_secret).map_err(err500)?;
+
+    let id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO key_rotations
+            (user_id, status, old_key, new_key, total, created_at, updated_at)
+        VALUES (?, 'running', ?, ?, ?, ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(old_key_stored)
+    .bind(new_key_stored)
+    .bind(total)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| match e {
+        // Another request started one between our check and insert.
+        sqlx::Error::Database(db) if db.is_unique_violation() => (
+            StatusCode::CONFLICT,
+            "A key rotation is already in progress".to_string(),
+        ),
+        e => err500(e),
+    })?;
+    tracing::info!(
+        "key rotation {} started for {} ({} observations)",
+        id,
+        user_id,
+        total
+    );
+    Ok((id, true))
+}
+
+/// Move one observation to `new_key`. Rows that already open under it
+/// (done by an earlier batch, or submitted by a client that switched) are
+/// left alone. Returns whether the row was rewritten.
+async fn reseal(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    obs: &ObsCipher,
+    old_key: &str,
+    new_key: &str,
+) -> Result<bool, String> {
+    let aad = obs.aad(user_id)?;
+    if decrypt_observation_with_aad(&obs.encrypted_data, &obs.iv, new_key, &aad).is_ok() {
+        return Ok(false);
+    }
+    let plaintext = decrypt_observation_with_aad(&obs.encrypted_data, &obs.iv, old_key, &aad)
+        .map_err(|e| format!("obs {} opens under neither key: {}", obs.id, e))?;
+    let (enc, iv) = encrypt_observation_with_aad(&plaintext, new_key, &aad)?;
+    let check = decrypt_observation_with_aad(&enc, &iv, new_key, &aad)?;
+    if check != plaintext {
+        return Err(format!("self-verify mismatch on obs {}", obs.id));
+    }
+    sqlx::query("UPDATE observations SET encrypted_data = ?, iv = ? WHERE id = ? AND user_id = ?")
+        .bind(&enc)
+        .bind(&iv)
+        .bind(&obs.id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+async fn rotate(state: &AppState, id: i64, batch_size: i64) -> Result<(), String> {
+    let recovery_secret = state
+        .config
+        .recovery_secret
+        .as_ref()
+        .ok_or("RECOVERY_SECRET not configured")?;
+    let Some(row) = fetch_rotation(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+    if row.status != "running" {
+        return Ok(());
+    }
+    let user_id = row.user_id.as_str();
+    let (Some(old_stored), Some(new_stored)) = (&row.old_key, &row.new_key) else {
+        return Err("rotation has no keys".to_string());
+    };
+    let old_key = decrypt_for_recovery(old_stored, recovery_secret)?;
+    let new_key = decrypt_for_recovery(new_stored, recovery_secret)?;
+    let select = format!(
+        "SELECT {} FROM observations WHERE user_id = ? AND id > ? ORDER BY id LIMIT ?",
+        OBS_CIPHER_COLUMNS
+    );
+
+    // Batches, each committed with the cursor that follows it.
+    let mut cursor = row.cursor.clone().unwrap_or_default();
+    loop {
+        let batch: Vec<ObsCipher> = sqlx::query_as(&select)
+            .bind(user_id)
+            .bind(&cursor)
+            .bind(batch_size)
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(last) = batch.last() else { break };
+        cursor = last.id.clone();
+
+        let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+        for obs in &batch {
+            reseal(&mut tx, user_id, obs, &old_key, &new_key).await?;
+        }
+        sqlx::query(
+            "UPDATE key_rotations SET cursor = ?, reencrypted = reencrypted + ?, updated_at = ? \
+             WHERE id = ?",
+        )
+        .bind(&cursor)
+        .bind(batch.len() as i64)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+
+    // Final pass: every row must open under the new key before it becomes
+    // the account's key.
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let all: Vec<ObsCipher> = sqlx::query_as(&format!(
+        "SELECT {} FROM observations WHERE user_id = ?",
+        OBS_CIPHER_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut stragglers = 0;
+    for obs in &all {
+        if reseal(&mut tx, user_id, obs, &old_key, &new_key).await? {
+            stragglers += 1;
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let claimed = sqlx::query(
+        r#"
+        UPDATE key_rotations
+        SET status = 'completed', old_key = NULL, new_key = NULL,
+            updated_at = ?1, completed_at = ?1
+        WHERE id = ?2 AND status = 'running'
+        "#,
+    )
+    .bind(&now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    if claimed.rows_affected() == 0 {
+        // Another run of the same rotation finished first.
+        return Ok(());
+    }
+    sqlx::query("UPDATE users SET recovery_encrypted_key = ?, key_rotated_at = ? WHERE id = ?")
+        .bind(new_stored)
+        .bind(&now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let revoked: Vec<String> = sqlx::query_scalar(
+        "UPDATE sharing_grants SET revoked = 1, revoked_at = ? \
+         WHERE grantor_id = ? AND revoked = 0 RETURNING grantee_id",
+    )
+    .bind(&now)
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    // Pending handoffs carry this account's identity, old key included.
+    // Dropping them sends merged-away devices through email recovery too.
+    let handoffs = sqlx::query("DELETE FROM account_handoff WHERE keeper_user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+    sqlx::query("UPDATE key_rotations SET revoked_grantees = ? WHERE id = ?")
+        .bind(serde_json::to_string(&revoked).map_err(|e| e.to_string())?)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    tracing::info!(
+        "key rotation {} completed for {}: {} observations, {} caught up at the end, \
+         {} grants revoked, {} handoffs dropped",
+        id,
+        user_id,
+        all.len(),
+        stragglers,
+        revoked.len(),
+        handoffs
+    );
+    Ok(())
+}
+
+/// Run rotation `id` to completion, or mark it `failed`.
+pub async fn run_key_rotation(state: &AppState, id: i64) {
+    if let Err(e) = rotate(state, id, BATCH_SIZE).await {
+        tracing::error!("key rotation {} failed: {}", id, e);
+        let _ = sqlx::query(
+            "UPDATE key_rotations SET status = 'failed', error = ?, updated_at = ? \
+             WHERE id = ? AND status = 'running'",
+        )
+        .bind(&e)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    }
+}
+
+fn spawn_rotation(state: &AppState, id: i64) {
+    let state = state.clone();
+    tokio::spawn(async move { run_key_rotation(&state, id).await });
+}
+
+/// Restart the rotations a previous process left running. Call once at
+/// startup.
+pub async fn resume_key_rotations(state: &AppState) -> Result<usize, sqlx::Error> {
+    let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM key_rotations WHERE status = 'running'")
+        .fetch_all(&state.db)
+        .await?;
+    for &id in &ids {
+        spawn_rotation(state, id);
+    }
+    if !ids.is_empty() {
+        tracing::info!("Resumed {} interrupted key rotations", ids.len());
+    }
+    Ok(ids.len())
+}
+
+/// POST /api/key-rotation { user_id, new_key, recovery_code }
+/// Start rotating the caller's key, or resume an unfinished rotation to the
+/// same key. Poll `GET /api/key-rotation` for progress.
+pub async fn start_key_rotation(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Json(req): Json<StartKeyRotationRequest>,
+) -> Result<Json<KeyRotationStatus>, (StatusCode, String)> {
+    authorize(&state.db, &caller, Relation::SelfUser(&req.user_id)).await?;
+    let unfinished: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM key_rotations \
+         WHERE user_id = ? AND status IN ('running', 'failed'))",
+    )
+    .bind(&req.user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(err500)?;
+    if !unfinished {
+        let code = req.recovery_code.as_deref().ok_or((
+            StatusCode::FORBIDDEN,
+            "recovery_code is required: request a recovery email first".to_string(),
+        ))?;
+        redeem_recovery_code(&state, &req.user_id, code).await?;
+    }
+    let (id, run) = begin_key_rotation(&state, &req.user_id, &req.new_key).await?;
+    let row = fetch_rotation(&state.db, id)
+        .await
+        .map_err(err500)?
+        .ok_or((StatusCode::NOT_FOUND, "Rotation not found".to_string()))?;
+    if run {
+        spawn_rotation(&state, id);
+    }
+    Ok(Json(row.into()))
+}
+
+/// GET /api/key-rotation?user_id=X
+/// The user's most recent rotation.
+pub async fn get_key_rotation(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Query(query): Query<KeyRotationQuery>,
+) -> Result<Json<KeyRotationStatus>, (StatusCode, String)> {
+    authorize(&state.db, &caller, Relation::SelfUser(&query.user_id)).await?;
+    let row: Option<RotationRow> =
+        sqlx::query_as("SELECT * FROM key_rotations WHERE user_id = ? ORDER BY id DESC LIMIT 1")
+            .bind(&query.user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(err500)?;
+    row.map(|r| Json(r.into()))
+        .ok_or((StatusCode::NOT_FOUND, "No key rotation".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::obs_crypto::ObservationAad;
+    use crate::routes::auth::verify_user_secret;
+    use crate::routes::recovery::hash_token;
+    use crate::recovery_keys::RecoveryKeyring;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    const SECRET: &str = "test-recovery-secret";
+
+    async fn insert_observation(state: &AppState, id: &str, key: &str, format: i64) {
+        let aad = ObservationAad {
+            observation_id: id,
+            user_id: "u1",
+            deal_subfolder: None,
+            deal_number: None,
+            correct: true,
+        }
+        .for_format(format)
+        .unwrap();
+        let (enc, iv) = encrypt_observation_with_aad(id.as_bytes(), key, &aad).unwrap();
+        sqlx::query(
+            "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+             encrypted_data, iv, created_at, cipher_format) \
+             VALUES (?, 'u1', '2026-01-01T00:00:00Z', 'bidding/stayman', 1, ?, ?, '', ?)",
+        )
+        .bind(id)
+        .bind(&enc)
+        .bind(&iv)
+        .bind(format)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotation_resumes_catches_stragglers_and_swaps_keys() {
+        let mut config = Config::for_tests();
+        config.recovery_secret = Some(RecoveryKeyring::new(SECRET));
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        let old_key = BASE64.encode([1u8; 32]);
+        let new_key = BASE64.encode([2u8; 32]);
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at, \
+             recovery_encrypted_key) VALUES ('u1', 'Test', 'Student', 'u1@example.com', '', '', ?)",
+        )
+        .bind(encrypt_for_recovery(&old_key, &RecoveryKeyring::new(SECRET)).unwrap())
+        .execute(&state.db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO viewers (id, name, email, public_key, created_at) \
+             VALUES ('v1', 'Teacher', 't@example.com', 'pk', '')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO sharing_grants (id, grantor_id, grantee_id, encrypted_payload, granted_at) \
+             VALUES ('g1', 'u1', 'v1', 'wrapped-old-key', '')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        // A merge onto u1 left a handoff of u1's identity, and one onto
+        // someone else is waiting too.
+        sqlx::query(
+            "INSERT INTO account_handoff \
+               (from_user_id, keeper_user_id, encrypted_payload, iv, created_at, expires_at) \
+             VALUES ('away1', 'u1', 'wrapped-old-key', 'iv', '', '9999'), \
+                    ('away2', 'u2', 'wrapped-other-key', 'iv', '', '9999')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        for id in ["a", "b", "c"] {
+            insert_observation(&state, id, &old_key, 1).await;
+        }
+        insert_observation(&state, "d", &old_key, 2).await;
+
+        assert_eq!(
+            begin_key_rotation(&state, "u1", &old_key)
+                .await
+                .unwrap_err()
+                .0,
+            StatusCode::BAD_REQUEST
+        );
+        let (id, run) = begin_key_rotation(&state, "u1", &new_key).await.unwrap();
+        assert!(run);
+        assert_eq!(
+            begin_key_rotation(&state, "u1", &BASE64.encode([3u8; 32]))
+                .await
+                .unwrap_err()
+                .0,
+            StatusCode::CONFLICT
+        );
+
+        // Pretend a batch ending at "b" already ran. "a" and "b" are still
+        // under the old key, standing in for observations submitted under
+        // it mid-rotation, so only the final pass can move them.
+        sqlx::query("UPDATE key_rotations SET cursor = 'b', reencrypted = 2 WHERE id = ?")
+            .bind(id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        rotate(&state, id, 1).await.unwrap();
+
+        let status: KeyRotationStatus =
+            fetch_rotation(&state.db, id).await.unwrap().unwrap().into();
+        assert_eq!(status.status, "completed");
+        assert_eq!((status.total, status.reencrypted), (4, 4));
+        assert_eq!(status.revoked_grantees, ["v1"]);
+
+        let rows: Vec<ObsCipher> = sqlx::query_as(&format!(
+            "SELECT {} FROM observations ORDER BY id",
+            OBS_CIPHER_COLUMNS
+        ))
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        for row in &rows {
+            let aad = row.aad("u1").unwrap();
+            let plaintext =
+                decrypt_observation_with_aad(&row.encrypted_data, &row.iv, &new_key, &aad).unwrap();
+            assert_eq!(plaintext, row.id.as_bytes());
+        }
+        assert_eq!(recover_user_key(&state, "u1").await.unwrap(), new_key);
+        let (keys_cleared, revoked): (bool, bool) = sqlx::query_as(
+            "SELECT (SELECT old_key IS NULL AND new_key IS NULL FROM key_rotations), \
+                    (SELECT revoked FROM sharing_grants WHERE id = 'g1')",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert!(keys_cleared && revoked);
+
+        // Nothing is wrapped under the old key, and it no longer proves
+        // the account: other devices have to recover by email.
+        let handoffs: Vec<String> = sqlx::query_scalar("SELECT from_user_id FROM account_handoff")
+            .fetch_all(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(handoffs, ["away2"]);
+        let user: crate::models::User = sqlx::query_as("SELECT * FROM users WHERE id = 'u1'")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert!(!verify_user_secret(&state, &user, &old_key).await.unwrap());
+        assert!(verify_user_secret(&state, &user, &new_key).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn starting_a_rotation_takes_an_emailed_code() {
+        let mut config = Config::for_tests();
+        config.recovery_secret = Some(RecoveryKeyring::new(SECRET));
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        let old_key = BASE64.encode([1u8; 32]);
+        let new_key = BASE64.encode([2u8; 32]);
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at, \
+             recovery_encrypted_key) VALUES ('u1', 'Test', 'Student', 'u1@example.com', '', '', ?)",
+        )
+        .bind(encrypt_for_recovery(&old_key, &RecoveryKeyring::new(SECRET)).unwrap())
+        .execute(&state.db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO recovery_tokens \
+               (id, user_id, token_hash, recovery_code_hash, created_at, expires_at, used) \
+             VALUES ('t1', 'u1', 'link', ?, '', '9999', 0)",
+        )
+        .bind(hash_token("123456"))
+        .execute(&state.db)
+        .await
+        .unwrap();
+        let caller = || AuthUser {
+            user_id: "u1".to_string(),
+            role: "student".to_string(),
+        };
+        let request = |code: Option<&str>| {
+            Json(StartKeyRotationRequest {
+                user_id: "u1".to_string(),
+                new_key: new_key.clone(),
+                recovery_code: code.map(str::to_string),
+            })
+        };
+
+        // The old key's session alone, or with a wrong code, starts nothing.
+        for code in [None, Some("654321")] {
+            let err = start_key_rotation(State(state.clone()), caller(), request(code))
+                .await
+                .unwrap_err();
+            assert_eq!(err.0, StatusCode::FORBIDDEN);
+        }
+        let rotations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM key_rotations")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(rotations, 0);
+
+        let Json(status) =
+            start_key_rotation(State(state.clone()), caller(), request(Some("123456")))
+                .await
+                .unwrap();
+        assert_eq!(status.user_id, "u1");
+        let (used, verified): (bool, bool) = sqlx::query_as(
+            "SELECT (SELECT used FROM recovery_tokens WHERE id = 't1'), \
+                    (SELECT email_verified_at IS NOT NULL FROM users WHERE id = 'u1')",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert!(used && verified);
+    }
+}
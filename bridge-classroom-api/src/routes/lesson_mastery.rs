@@ -2,25 +2,34 @@
 //!
 //! Implements `documentation/CORRECTNESS_AND_MASTERY.md` §13. The tier
 //! is derived on demand (not stored — see §13.3) from `board_status`
-//! and the global lesson catalog (boards seen across all users).
+//! and the lesson catalog: a lesson's declared boards when it has been
+//! imported (`lesson_catalog.rs`), otherwise the boards seen across all
+//! users.
 //!
-//! Returned tiers:
+//! Returned tiers (thresholds are the user's rules profile; defaults shown):
 //!   - "Exploring" — user has at least one observation in this lesson
 //!   - "Learning"  — ≥50% of the lesson's boards have max_stars ≥ 1
 //!   - "Retaining" — ≥80% of the lesson's boards have max_stars ≥ 2
 //!   - "Mastering" — ≥80% have (max_stars ≥ 2 OR wild_achievement='Fresh')
-//!                   AND ≥25% have wild_achievement='Fresh'
+//!     AND ≥25% have wild_achievement='Fresh'
 //!
 //! A lesson can only be in one tier; we return the highest the user
 //! qualifies for. Lessons the user has never touched are omitted.
 
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sqlx::{Pool, Sqlite};
+
+use crate::policy::{authorize, Relation};
+use crate::rules::{self, RulesParams};
+use crate::session::AuthUser;
 use crate::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -46,38 +55,185 @@ pub struct LessonMasteryEntry {
     /// Boards qualifying for the Mastering "deep" criterion
     /// (max_stars ≥ 2 OR wild_achievement = 'Fresh').
     pub deep: i64,
+    /// Whether `total_boards` is the imported catalog's declared count
+    /// rather than the seen-boards approximation.
+    pub catalogued: bool,
+    /// Boards a curator has put under review; left out of every count
+    /// above until the review is cleared.
+    pub boards_under_review: i64,
 }
 
 #[derive(Debug, Serialize)]
 pub struct LessonMasteryResponse {
     pub lessons: Vec<LessonMasteryEntry>,
+    /// The rules profile the tiers were decided under.
+    pub rules_profile: String,
 }
 
-#[derive(Debug, sqlx::FromRow)]
-struct LessonStatsRow {
-    deal_subfolder: String,
+#[derive(Debug, Default)]
+pub(crate) struct LessonStatsRow {
+    pub(crate) deal_subfolder: String,
     total_boards: i64,
     attempted_boards: i64,
     silver_or_better: i64,
     gold: i64,
     fresh_paw: i64,
     deep: i64,
+    pub(crate) catalogued: bool,
+    boards_under_review: i64,
+}
+
+impl LessonStatsRow {
+    fn add_board(&mut self, max_stars: i64, wild_achievement: Option<&str>) {
+        let fresh = wild_achievement == Some("Fresh");
+        self.attempted_boards += 1;
+        self.silver_or_better += (max_stars >= 1) as i64;
+        self.gold += (max_stars >= 2) as i64;
+        self.fresh_paw += fresh as i64;
+        self.deep += (max_stars >= 2 || fresh) as i64;
+    }
+}
+
+/// What counts as "the lesson's boards" for each lesson.
+pub(crate) struct LessonCatalog {
+    /// Imported lessons and their declared boards.
+    declared: HashMap<String, HashSet<i32>>,
+    /// Distinct boards seen per lesson across all users, for lessons
+    /// that haven't been imported.
+    seen: HashMap<String, i64>,
+    /// Boards under curator review (`boards_under_review`), per lesson.
+    /// They count for neither the user nor the lesson.
+    under_review: HashMap<String, HashSet<i32>>,
+}
+
+impl LessonCatalog {
+    pub(crate) async fn load(db: &Pool<Sqlite>) -> Result<Self, sqlx::Error> {
+        let declared_rows: Vec<(String, Option<i32>)> = sqlx::query_as(
+            r#"
+            SELECT l.deal_subfolder, lb.deal_number
+            FROM lessons l
+            LEFT JOIN lesson_boards lb ON lb.deal_subfolder = l.deal_subfolder
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+        let review_rows: Vec<(String, i32)> =
+            sqlx::query_as("SELECT deal_subfolder, deal_number FROM boards_under_review")
+                .fetch_all(db)
+                .await?;
+        let mut under_review: HashMap<String, HashSet<i32>> = HashMap::new();
+        for (subfolder, deal_number) in review_rows {
+            under_review.entry(subfolder).or_default().insert(deal_number);
+        }
+
+        let mut declared: HashMap<String, HashSet<i32>> = HashMap::new();
+        for (subfolder, deal_number) in declared_rows {
+            let boards = declared.entry(subfolder).or_default();
+            boards.extend(deal_number);
+        }
+        for (subfolder, boards) in declared.iter_mut() {
+            if let Some(reviewed) = under_review.get(subfolder) {
+                boards.retain(|b| !reviewed.contains(b));
+            }
+        }
+
+        let seen_rows: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT deal_subfolder, COUNT(DISTINCT deal_number)
+            FROM board_status bs
+            WHERE deal_subfolder IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM boards_under_review r
+                  WHERE r.deal_subfolder = bs.deal_subfolder
+                    AND r.deal_number = bs.deal_number
+              )
+            GROUP BY deal_subfolder
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self {
+            declared,
+            seen: seen_rows.into_iter().collect(),
+            under_review,
+        })
+    }
+
+    /// Tally per-lesson stats from a user's boards, given as (deal_subfolder,
+    /// deal_number, max_stars, wild_achievement). For a catalogued lesson,
+    /// boards it doesn't declare are ignored, and boards under review are
+    /// ignored everywhere.
+    pub(crate) fn tally<'a>(
+        &self,
+        boards: impl IntoIterator<Item = (&'a str, i32, i64, Option<&'a str>)>,
+    ) -> Vec<LessonStatsRow> {
+        let mut lessons: BTreeMap<&str, LessonStatsRow> = BTreeMap::new();
+        for (subfolder, deal_number, max_stars, wild_achievement) in boards {
+            let reviewed = self.under_review.get(subfolder);
+            if reviewed.is_some_and(|r| r.contains(&deal_number)) {
+                continue;
+            }
+            let boards_under_review = reviewed.map_or(0, |r| r.len() as i64);
+            let row = match self.declared.get(subfolder) {
+                Some(declared) if !declared.contains(&deal_number) => continue,
+                Some(declared) => lessons.entry(subfolder).or_insert_with(|| LessonStatsRow {
+                    deal_subfolder: subfolder.to_string(),
+                    total_boards: declared.len() as i64,
+                    catalogued: true,
+                    boards_under_review,
+                    ..Default::default()
+                }),
+                None => lessons.entry(subfolder).or_insert_with(|| LessonStatsRow {
+                    deal_subfolder: subfolder.to_string(),
+                    total_boards: self.seen.get(subfolder).copied().unwrap_or(0),
+                    boards_under_review,
+                    ..Default::default()
+                }),
+            };
+            row.add_board(max_stars, wild_achievement);
+        }
+        lessons
+            .into_values()
+            .map(|mut row| {
+                // A board this user has that no walk has recorded for the
+                // seen-boards count yet (e.g. a what-if replay).
+                row.total_boards = row.total_boards.max(row.attempted_boards);
+                row
+            })
+            .collect()
+    }
 }
 
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(|k| k == expected_key)
-        .unwrap_or(false)
+/// The user's per-lesson stats from `board_status`, ordered by lesson.
+pub(crate) async fn lesson_stats(
+    db: &Pool<Sqlite>,
+    catalog: &LessonCatalog,
+    user_id: &str,
+) -> Result<Vec<LessonStatsRow>, sqlx::Error> {
+    let boards: Vec<(String, i32, i64, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT deal_subfolder, deal_number, max_stars, wild_achievement
+        FROM board_status
+        WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok(catalog.tally(
+        boards
+            .iter()
+            .map(|(sub, deal, stars, wild)| (sub.as_str(), *deal, *stars, wild.as_deref())),
+    ))
 }
 
-/// Decide the tier for a lesson given the user's stats. Returns a
-/// lowercase-Title-Case string ("Exploring" / "Learning" / "Retaining"
-/// / "Mastering"). Callers should not invoke this for lessons with
-/// zero observations; that case is `None` and we filter it out before
-/// returning.
-fn decide_tier(row: &LessonStatsRow) -> &'static str {
+/// Decide the tier for a lesson given the user's stats and the rules
+/// profile's thresholds. Returns a lowercase-Title-Case string
+/// ("Exploring" / "Learning" / "Retaining" / "Mastering"). Callers should
+/// not invoke this for lessons with zero observations; that case is
+/// `None` and we filter it out before returning.
+pub(crate) fn decide_tier(row: &LessonStatsRow, rules: &RulesParams) -> &'static str {
     if row.total_boards == 0 || row.attempted_boards == 0 {
         return "Exploring"; // defensive — shouldn't be called in this case
     }
@@ -85,17 +241,17 @@ fn decide_tier(row: &LessonStatsRow) -> &'static str {
 
     let deep_frac = row.deep as f64 / total;
     let fresh_frac = row.fresh_paw as f64 / total;
-    if deep_frac >= 0.80 && fresh_frac >= 0.25 {
+    if deep_frac >= rules.mastering_deep_share && fresh_frac >= rules.mastering_fresh_share {
         return "Mastering";
     }
 
     let gold_frac = row.gold as f64 / total;
-    if gold_frac >= 0.80 {
+    if gold_frac >= rules.retaining_gold_share {
         return "Retaining";
     }
 
     let silver_or_better_frac = row.silver_or_better as f64 / total;
-    if silver_or_better_frac >= 0.50 {
+    if silver_or_better_frac >= rules.learning_silver_share {
         return "Learning";
     }
 
@@ -105,51 +261,32 @@ fn decide_tier(row: &LessonStatsRow) -> &'static str {
 /// GET /api/lesson-mastery?user_id=X
 ///
 /// Returns a tier per lesson the user has touched, ordered by lesson
-/// subfolder. The "boards in lesson" denominator is the count of
-/// distinct deal_numbers seen for that subfolder across ALL users
-/// (i.e. the lesson catalog grows lazily as boards are encountered).
+/// subfolder. The "boards in lesson" denominator is the lesson's declared
+/// boards when it's in the imported catalog; otherwise the count of
+/// distinct deal_numbers seen for that subfolder across ALL users (i.e.
+/// that lesson's catalog grows lazily as boards are encountered).
 pub async fn get_lesson_mastery(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<LessonMasteryQuery>,
 ) -> Result<Json<LessonMasteryResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::ProgressViewer(&query.user_id)).await?;
+    let profile = rules::profile_for_user(&state.db, &query.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
-    let rows: Vec<LessonStatsRow> = sqlx::query_as(
-        r#"
-        WITH lesson_totals AS (
-            SELECT deal_subfolder, COUNT(DISTINCT deal_number) AS total_boards
-            FROM board_status
-            WHERE deal_subfolder IS NOT NULL
-            GROUP BY deal_subfolder
-        )
-        SELECT
-            bs.deal_subfolder                                                         AS deal_subfolder,
-            lt.total_boards                                                           AS total_boards,
-            COUNT(*)                                                                  AS attempted_boards,
-            SUM(CASE WHEN bs.max_stars >= 1 THEN 1 ELSE 0 END)                        AS silver_or_better,
-            SUM(CASE WHEN bs.max_stars >= 2 THEN 1 ELSE 0 END)                        AS gold,
-            SUM(CASE WHEN bs.wild_achievement = 'Fresh' THEN 1 ELSE 0 END)            AS fresh_paw,
-            SUM(CASE WHEN bs.max_stars >= 2 OR bs.wild_achievement = 'Fresh' THEN 1 ELSE 0 END) AS deep
-        FROM board_status bs
-        JOIN lesson_totals lt ON lt.deal_subfolder = bs.deal_subfolder
-        WHERE bs.user_id = ?
-        GROUP BY bs.deal_subfolder, lt.total_boards
-        ORDER BY bs.deal_subfolder
-        "#,
-    )
-    .bind(&query.user_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let catalog = LessonCatalog::load(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let rows = lesson_stats(&state.db, &catalog, &query.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let lessons: Vec<LessonMasteryEntry> = rows
         .into_iter()
         .filter(|r| r.attempted_boards > 0)
         .map(|r| {
-            let tier = decide_tier(&r).to_string();
+            let tier = decide_tier(&r, &profile.params).to_string();
             LessonMasteryEntry {
                 deal_subfolder: r.deal_subfolder,
                 tier,
@@ -159,9 +296,14 @@ pub async fn get_lesson_mastery(
                 gold: r.gold,
                 fresh_paw: r.fresh_paw,
                 deep: r.deep,
+                catalogued: r.catalogued,
+                boards_under_review: r.boards_under_review,
             }
         })
         .collect();
 
-    Ok(Json(LessonMasteryResponse { lessons }))
+    Ok(Json(LessonMasteryResponse {
+        lessons,
+        rules_profile: profile.id,
+    }))
 }
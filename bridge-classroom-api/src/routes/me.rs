@@ -1,53 +1,36 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Json};
 
 use crate::{
     models::{RoleUpgradeRequest, RoleUpgradeResponse, User},
+    session::{self, AuthUser},
     AppState,
 };
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 /// PATCH /api/users/me
-/// Upgrade a user's role (e.g., student → teacher)
+/// Upgrade the caller's role (e.g., student → teacher)
 pub async fn upgrade_role(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<RoleUpgradeRequest>,
 ) -> Result<Json<RoleUpgradeResponse>, (StatusCode, String)> {
-    // Validate API key
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    if req.user_id.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "user_id is required".to_string()));
-    }
+    let user_id = caller.user_id;
 
     if req.action != "become_teacher" {
         return Ok(Json(RoleUpgradeResponse {
             success: false,
             role: String::new(),
             error: Some(format!("Unknown action: {}", req.action)),
+            session_token: None,
         }));
     }
 
     // Fetch the user
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-        .bind(&req.user_id)
+        .bind(&user_id)
         .fetch_optional(&state.db)
         .await
         .map_err(|e| {
-            tracing::error!("Failed to query user {}: {}", req.user_id, e);
+            tracing::error!("Failed to query user {}: {}", user_id, e);
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
         })?;
 
@@ -58,6 +41,7 @@ pub async fn upgrade_role(
                 success: false,
                 role: String::new(),
                 error: Some("User not found".to_string()),
+                session_token: None,
             }));
         }
     };
@@ -68,6 +52,7 @@ pub async fn upgrade_role(
             success: false,
             role: user.role,
             error: Some("Only students can upgrade to teacher".to_string()),
+            session_token: None,
         }));
     }
 
@@ -82,19 +67,24 @@ pub async fn upgrade_role(
     )
     .bind(&now)
     .bind(&now)
-    .bind(&req.user_id)
+    .bind(&user_id)
     .execute(&state.db)
     .await
     .map_err(|e| {
-        tracing::error!("Failed to upgrade user {} to teacher: {}", req.user_id, e);
+        tracing::error!("Failed to upgrade user {} to teacher: {}", user_id, e);
         (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
-    tracing::info!("User {} upgraded to teacher", req.user_id);
+    tracing::info!("User {} upgraded to teacher", user_id);
+
+    // The caller's current token still says "student"; hand back one
+    // carrying the new role.
+    let (session_token, _) = session::issue(&state.config, &user_id, "teacher");
 
     Ok(Json(RoleUpgradeResponse {
         success: true,
         role: "teacher".to_string(),
         error: None,
+        session_token: Some(session_token),
     }))
 }
@@ -6,27 +6,27 @@
 
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
 
-use crate::obs_crypto::{decrypt_observation, encrypt_observation};
+use crate::obs_crypto::{
+    decrypt_observation_with_aad, encrypt_observation, encrypt_observation_with_aad,
+    ObservationAad, CIPHER_FORMAT_AAD,
+};
+use crate::observation_schema::parse_payload;
+use crate::policy::RequireAdmin;
+use crate::recovery_keys::RecoveryKeyring;
 use crate::routes::board_status::{recompute_assignment_boards, recompute_board_history};
 use crate::routes::recovery::decrypt_for_recovery;
+use crate::session::AuthUser;
 use crate::student_summary::recompute_student_summary;
 use crate::AppState;
 
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(|k| k == expected_key)
-        .unwrap_or(false)
-}
-
 /// Recover a user's raw base64 AES key from their recovery_encrypted_key.
-async fn recover_user_key(state: &AppState, user_id: &str) -> Result<String, String> {
+pub(crate) async fn recover_user_key(state: &AppState, user_id: &str) -> Result<String, String> {
     let recovery_secret = state
         .config
         .recovery_secret
@@ -63,43 +63,82 @@ pub struct MergeDryRunResponse {
     pub total: i64,
     pub decrypted_ok: i64,
     pub json_valid: i64,
+    /// Payloads that parse as a known observation schema version; the
+    /// merge refuses to move anything unless all of them do.
+    pub schema_valid: i64,
     pub roundtrip_ok: i64,
+    /// Rows sealed with their cleartext metadata as associated data.
+    pub aad_bound: i64,
+    /// Ids of AAD-bound rows that no longer open under their own metadata:
+    /// a cleartext column was changed or swapped after they were sealed.
+    pub metadata_mismatch: Vec<String>,
     pub sample: Option<String>,
     pub error: Option<String>,
 }
 
 #[derive(sqlx::FromRow)]
-struct ObsCipher {
-    encrypted_data: String,
-    iv: String,
+pub(crate) struct ObsCipher {
+    pub id: String,
+    pub encrypted_data: String,
+    pub iv: String,
+    pub deal_subfolder: Option<String>,
+    pub deal_number: Option<i32>,
+    pub correct: bool,
+    pub cipher_format: i64,
+}
+
+pub(crate) const OBS_CIPHER_COLUMNS: &str =
+    "id, encrypted_data, iv, deal_subfolder, deal_number, correct, cipher_format";
+
+impl ObsCipher {
+    /// The associated data this row is sealed with while it belongs to
+    /// `user_id` (empty for legacy rows).
+    pub fn aad(&self, user_id: &str) -> Result<Vec<u8>, String> {
+        ObservationAad {
+            observation_id: &self.id,
+            user_id,
+            deal_subfolder: self.deal_subfolder.as_deref(),
+            deal_number: self.deal_number,
+            correct: self.correct,
+        }
+        .for_format(self.cipher_format)
+    }
 }
 
 /// GET /api/admin/merge-dryrun?user_id=X
 /// Recovers X's key, decrypts every observation with obs_crypto, checks the
-/// plaintext parses as JSON, and round-trips it under a throwaway key. Proves
+/// plaintext parses as JSON and as a known observation schema version, and
+/// round-trips it under a throwaway key. Proves
 /// the server can read frontend-encrypted data and re-encode it readably,
 /// WITHOUT mutating anything.
 pub async fn merge_dry_run(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
     Query(q): Query<MergeDryRunQuery>,
 ) -> Result<Json<MergeDryRunResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    verify_user_decryption(&state, &q.user_id).await.map(Json)
+}
 
-    let key = match recover_user_key(&state, &q.user_id).await {
+/// The dry-run check behind `merge_dry_run`, shared with the admin CLI.
+pub async fn verify_user_decryption(
+    state: &AppState,
+    user_id: &str,
+) -> Result<MergeDryRunResponse, (StatusCode, String)> {
+    let key = match recover_user_key(state, user_id).await {
         Ok(k) => k,
         Err(e) => {
-            return Ok(Json(MergeDryRunResponse {
+            return Ok(MergeDryRunResponse {
                 success: false,
                 total: 0,
                 decrypted_ok: 0,
                 json_valid: 0,
+                schema_valid: 0,
                 roundtrip_ok: 0,
+                aad_bound: 0,
+                metadata_mismatch: Vec::new(),
                 sample: None,
                 error: Some(e),
-            }))
+            })
         }
     };
 
@@ -112,23 +151,39 @@ pub async fn merge_dry_run(
         BASE64.encode(k)
     };
 
-    let rows: Vec<ObsCipher> =
-        sqlx::query_as("SELECT encrypted_data, iv FROM observations WHERE user_id = ?")
-            .bind(&q.user_id)
-            .fetch_all(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let rows: Vec<ObsCipher> = sqlx::query_as(&format!(
+        "SELECT {} FROM observations WHERE user_id = ?",
+        OBS_CIPHER_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let total = rows.len() as i64;
     let mut decrypted_ok = 0i64;
     let mut json_valid = 0i64;
+    let mut schema_valid = 0i64;
     let mut roundtrip_ok = 0i64;
+    let mut aad_bound = 0i64;
+    let mut metadata_mismatch = Vec::new();
     let mut sample: Option<String> = None;
 
     for r in &rows {
-        let plaintext = match decrypt_observation(&r.encrypted_data, &r.iv, &key) {
+        let Ok(aad) = r.aad(user_id) else { continue };
+        if r.cipher_format == CIPHER_FORMAT_AAD {
+            aad_bound += 1;
+        }
+        let plaintext = match decrypt_observation_with_aad(&r.encrypted_data, &r.iv, &key, &aad) {
             Ok(p) => p,
-            Err(_) => continue,
+            Err(_) => {
+                // The key opens the legacy rows, so an AAD row that won't
+                // open has had its metadata changed under it.
+                if r.cipher_format == CIPHER_FORMAT_AAD {
+                    metadata_mismatch.push(r.id.clone());
+                }
+                continue;
+            }
         };
         decrypted_ok += 1;
 
@@ -139,10 +194,13 @@ pub async fn merge_dry_run(
                 sample = Some(s.chars().take(160).collect());
             }
         }
+        if parse_payload(&plaintext).is_ok() {
+            schema_valid += 1;
+        }
 
         // Round-trip under the throwaway key, then read it back.
-        if let Ok((enc2, iv2)) = encrypt_observation(&plaintext, &throwaway) {
-            if let Ok(back) = decrypt_observation(&enc2, &iv2, &throwaway) {
+        if let Ok((enc2, iv2)) = encrypt_observation_with_aad(&plaintext, &throwaway, &aad) {
+            if let Ok(back) = decrypt_observation_with_aad(&enc2, &iv2, &throwaway, &aad) {
                 if back == plaintext {
                     roundtrip_ok += 1;
                 }
@@ -150,15 +208,21 @@ pub async fn merge_dry_run(
         }
     }
 
-    Ok(Json(MergeDryRunResponse {
-        success: total > 0 && decrypted_ok == total && roundtrip_ok == total,
+    Ok(MergeDryRunResponse {
+        success: total > 0
+            && decrypted_ok == total
+            && schema_valid == total
+            && roundtrip_ok == total,
         total,
         decrypted_ok,
         json_valid,
+        schema_valid,
         roundtrip_ok,
+        aad_bound,
+        metadata_mismatch,
         sample,
         error: None,
-    }))
+    })
 }
 
 // ---------------------------------------------------------------------------
@@ -189,10 +253,96 @@ struct HandoffIdentity {
     email: String,
     role: String,
     classroom: Option<String>,
-    secret_key: String, // the KEEPER's AES key
+    secret_key: String, // the AES key the device should switch to
     viewer_private_key: Option<String>,
 }
 
+/// Wrap `identity` under `wrap_key`, the key the receiving device holds.
+fn wrap_handoff(
+    identity: &HandoffIdentity,
+    wrap_key: &str,
+) -> Result<(String, String), String> {
+    let payload_json = serde_json::to_vec(identity).map_err(|e| e.to_string())?;
+    encrypt_observation(&payload_json, wrap_key)
+}
+
+/// Stage a wrapped handoff of `keeper_user_id` for the devices signed in as
+/// `from_user_id`, replacing any earlier one. Served for 30 days, or until
+/// the keeper rotates its key.
+async fn stage_handoff(
+    conn: &mut SqliteConnection,
+    from_user_id: &str,
+    keeper_user_id: &str,
+    encrypted_payload: &str,
+    iv: &str,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::days(30);
+    sqlx::query(
+        "INSERT INTO account_handoff \
+           (from_user_id, keeper_user_id, encrypted_payload, iv, created_at, expires_at, used) \
+         VALUES (?, ?, ?, ?, ?, ?, 0) \
+         ON CONFLICT(from_user_id) DO UPDATE SET \
+           keeper_user_id = excluded.keeper_user_id, \
+           encrypted_payload = excluded.encrypted_payload, iv = excluded.iv, \
+           created_at = excluded.created_at, expires_at = excluded.expires_at, used = 0",
+    )
+    .bind(from_user_id)
+    .bind(keeper_user_id)
+    .bind(encrypted_payload)
+    .bind(iv)
+    .bind(now.to_rfc3339())
+    .bind(expires_at.to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// `user_id`'s identity carrying `secret_key`, with their viewer private key
+/// recovered too if they're a teacher/admin. None if there's no such user.
+async fn handoff_identity(
+    state: &AppState,
+    user_id: &str,
+    secret_key: &str,
+    recovery_secret: &RecoveryKeyring,
+) -> Result<Option<HandoffIdentity>, sqlx::Error> {
+    let row: Option<(String, String, String, String, Option<String>, String)> =
+        sqlx::query_as("SELECT id, first_name, last_name, email, classroom, role FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await?;
+    let Some((id, first_name, last_name, email, classroom, role)) = row else {
+        return Ok(None);
+    };
+
+    let viewer_private_key = if role == "teacher" || role == "admin" {
+        let row: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT recovery_encrypted_private_key FROM viewers WHERE email = ?")
+                .bind(&email)
+                .fetch_optional(&state.db)
+                .await
+                .ok()
+                .flatten();
+        match row {
+            Some((Some(enc),)) => decrypt_for_recovery(&enc, recovery_secret).ok(),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(Some(HandoffIdentity {
+        id,
+        first_name,
+        last_name,
+        email,
+        role,
+        classroom,
+        secret_key: secret_key.to_string(),
+        viewer_private_key,
+    }))
+}
+
 fn err500<E: std::fmt::Display>(ctx: &str) -> impl Fn(E) -> (StatusCode, String) + '_ {
     move |e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}: {}", ctx, e))
 }
@@ -200,23 +350,29 @@ fn err500<E: std::fmt::Display>(ctx: &str) -> impl Fn(E) -> (StatusCode, String)
 /// POST /api/admin/merge-accounts  { merge_user_id, keeper_user_id }
 pub async fn merge_accounts(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
     Json(req): Json<MergeRequest>,
 ) -> Result<Json<MergeResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let away = req.merge_user_id;
-    let keeper = req.keeper_user_id;
+    merge_user_into(&state, &req.merge_user_id, &req.keeper_user_id)
+        .await
+        .map(Json)
+}
 
+/// The merge behind `merge_accounts`, shared with the admin CLI. Refusals
+/// (missing keys, unknown keeper) come back as `success: false`; anything
+/// that aborts the transaction is an `Err`.
+pub async fn merge_user_into(
+    state: &AppState,
+    away: &str,
+    keeper: &str,
+) -> Result<MergeResponse, (StatusCode, String)> {
     let bail = |msg: String| {
-        Ok(Json(MergeResponse {
+        Ok(MergeResponse {
             success: false,
             observations_moved: 0,
             keeper_email: None,
             error: Some(msg),
-        }))
+        })
     };
 
     if away == keeper {
@@ -229,65 +385,35 @@ pub async fn merge_accounts(
     };
 
     // Recover both AES keys server-side.
-    let k_away = match recover_user_key(&state, &away).await {
+    let k_away = match recover_user_key(state, away).await {
         Ok(k) => k,
         Err(e) => return bail(e),
     };
-    let k_keeper = match recover_user_key(&state, &keeper).await {
+    let k_keeper = match recover_user_key(state, keeper).await {
         Ok(k) => k,
         Err(e) => return bail(e),
     };
 
     // Keeper identity for the handoff payload.
-    let keeper_row: Option<(String, String, String, String, Option<String>, String)> =
-        sqlx::query_as("SELECT id, first_name, last_name, email, classroom, role FROM users WHERE id = ?")
-            .bind(&keeper)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(err500("keeper lookup"))?;
-    let (kid, kfirst, klast, kemail, kclassroom, krole) = match keeper_row {
-        Some(r) => r,
+    let identity = match handoff_identity(state, keeper, &k_keeper, &recovery_secret)
+        .await
+        .map_err(err500("keeper lookup"))?
+    {
+        Some(identity) => identity,
         None => return bail(format!("keeper account {} not found", keeper)),
     };
-
-    // Recover the keeper's viewer private key too, if they're a teacher/admin.
-    let viewer_private_key = if krole == "teacher" || krole == "admin" {
-        let row: Option<(Option<String>,)> =
-            sqlx::query_as("SELECT recovery_encrypted_private_key FROM viewers WHERE email = ?")
-                .bind(&kemail)
-                .fetch_optional(&state.db)
-                .await
-                .ok()
-                .flatten();
-        match row {
-            Some((Some(enc),)) => decrypt_for_recovery(&enc, &recovery_secret).ok(),
-            _ => None,
-        }
-    } else {
-        None
-    };
+    let kemail = identity.email.clone();
 
     // Build + wrap the handoff payload under the AWAY key (only that device unwraps).
-    let identity = HandoffIdentity {
-        id: kid.clone(),
-        first_name: kfirst,
-        last_name: klast,
-        email: kemail.clone(),
-        role: krole,
-        classroom: kclassroom,
-        secret_key: k_keeper.clone(),
-        viewer_private_key,
-    };
-    let payload_json = serde_json::to_vec(&identity).map_err(err500("serialize identity"))?;
     let (handoff_payload, handoff_iv) =
-        encrypt_observation(&payload_json, &k_away).map_err(err500("wrap handoff"))?;
+        wrap_handoff(&identity, &k_away).map_err(err500("wrap handoff"))?;
 
     // Which keeper boards/assignments to recompute after the move (from away's obs).
     let affected_boards: Vec<(String, i32)> = sqlx::query_as(
         "SELECT DISTINCT deal_subfolder, deal_number FROM observations \
          WHERE user_id = ? AND deal_subfolder IS NOT NULL AND deal_number IS NOT NULL",
     )
-    .bind(&away)
+    .bind(away)
     .fetch_all(&state.db)
     .await
     .map_err(err500("affected boards"))?;
@@ -295,30 +421,43 @@ pub async fn merge_accounts(
         "SELECT DISTINCT assignment_id FROM observations \
          WHERE user_id = ? AND assignment_id IS NOT NULL",
     )
-    .bind(&away)
+    .bind(away)
     .fetch_all(&state.db)
     .await
     .map_err(err500("affected assignments"))?;
 
     // Away's observations to re-encode.
-    let obs: Vec<(String, String, String)> =
-        sqlx::query_as("SELECT id, encrypted_data, iv FROM observations WHERE user_id = ?")
-            .bind(&away)
-            .fetch_all(&state.db)
-            .await
-            .map_err(err500("fetch observations"))?;
+    let obs: Vec<ObsCipher> = sqlx::query_as(&format!(
+        "SELECT {} FROM observations WHERE user_id = ?",
+        OBS_CIPHER_COLUMNS
+    ))
+    .bind(away)
+    .fetch_all(&state.db)
+    .await
+    .map_err(err500("fetch observations"))?;
     let observations_moved = obs.len() as i64;
 
     // ---- One atomic transaction: re-encode (self-verify), move, hand off, delete ----
     let mut tx = state.db.begin().await.map_err(err500("begin tx"))?;
 
-    for (id, enc, iv) in &obs {
-        let plaintext = decrypt_observation(enc, iv, &k_away)
+    for row in &obs {
+        let id = &row.id;
+        // AAD rows are bound to their owner, so they're resealed for the keeper.
+        let (away_aad, keeper_aad) = match (row.aad(away), row.aad(keeper)) {
+            (Ok(a), Ok(k)) => (a, k),
+            (Err(e), _) | (_, Err(e)) => return bail(format!("obs {}: {}", id, e)),
+        };
+        let plaintext = decrypt_observation_with_aad(&row.encrypted_data, &row.iv, &k_away, &away_aad)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("decrypt obs {}: {}", id, e)))?;
-        let (new_enc, new_iv) = encrypt_observation(&plaintext, &k_keeper)
+        // The keeper's browser will read this as an observation, so it has to
+        // be one. The bytes are re-encrypted as they are, not upgraded.
+        if let Err(e) = parse_payload(&plaintext) {
+            return bail(format!("obs {} payload: {} — merge aborted, no changes made", id, e));
+        }
+        let (new_enc, new_iv) = encrypt_observation_with_aad(&plaintext, &k_keeper, &keeper_aad)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("re-encrypt obs {}: {}", id, e)))?;
         // Self-verify: the keeper's browser must be able to read what we wrote.
-        let check = decrypt_observation(&new_enc, &new_iv, &k_keeper)
+        let check = decrypt_observation_with_aad(&new_enc, &new_iv, &k_keeper, &keeper_aad)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("verify obs {}: {}", id, e)))?;
         if check != plaintext {
             return Err((
@@ -329,7 +468,7 @@ pub async fn merge_accounts(
         sqlx::query("UPDATE observations SET encrypted_data = ?, iv = ?, user_id = ? WHERE id = ?")
             .bind(&new_enc)
             .bind(&new_iv)
-            .bind(&keeper)
+            .bind(keeper)
             .bind(id)
             .execute(&mut *tx)
             .await
@@ -340,17 +479,18 @@ pub async fn merge_accounts(
     for stmt in [
         "DELETE FROM observations_decrypted WHERE user_id = ?",
         "DELETE FROM board_status WHERE user_id = ?",
+        "DELETE FROM board_status_events WHERE user_id = ?",
         "DELETE FROM assignment_board_status WHERE user_id = ?",
         "DELETE FROM student_summary WHERE user_id = ?",
         "DELETE FROM recovery_tokens WHERE user_id = ?",
         "DELETE FROM user_convention_cards WHERE user_id = ?",
     ] {
-        sqlx::query(stmt).bind(&away).execute(&mut *tx).await.map_err(err500("cleanup"))?;
+        sqlx::query(stmt).bind(away).execute(&mut *tx).await.map_err(err500("cleanup"))?;
     }
     // Grants where away is either side are now meaningless (its data is the keeper's).
     sqlx::query("DELETE FROM sharing_grants WHERE grantor_id = ? OR grantee_id = ?")
-        .bind(&away)
-        .bind(&away)
+        .bind(away)
+        .bind(away)
         .execute(&mut *tx)
         .await
         .map_err(err500("cleanup grants"))?;
@@ -359,46 +499,32 @@ pub async fn merge_accounts(
         "INSERT OR IGNORE INTO classroom_members (classroom_id, student_id, joined_at) \
          SELECT classroom_id, ?, joined_at FROM classroom_members WHERE student_id = ?",
     )
-    .bind(&keeper)
-    .bind(&away)
+    .bind(keeper)
+    .bind(away)
     .execute(&mut *tx)
     .await
     .map_err(err500("move memberships"))?;
     sqlx::query("DELETE FROM classroom_members WHERE student_id = ?")
-        .bind(&away)
+        .bind(away)
         .execute(&mut *tx)
         .await
         .map_err(err500("drop memberships"))?;
     // Individual (student) assignments move to the keeper.
     sqlx::query("UPDATE assignments SET student_id = ? WHERE student_id = ?")
-        .bind(&keeper)
-        .bind(&away)
+        .bind(keeper)
+        .bind(away)
         .execute(&mut *tx)
         .await
         .map_err(err500("move assignments"))?;
 
     // Stage the handoff row (30-day window; safe at rest, wrapped under K_away).
-    let now = chrono::Utc::now();
-    let expires_at = now + chrono::Duration::days(30);
-    sqlx::query(
-        "INSERT INTO account_handoff (from_user_id, encrypted_payload, iv, created_at, expires_at, used) \
-         VALUES (?, ?, ?, ?, ?, 0) \
-         ON CONFLICT(from_user_id) DO UPDATE SET \
-           encrypted_payload = excluded.encrypted_payload, iv = excluded.iv, \
-           created_at = excluded.created_at, expires_at = excluded.expires_at, used = 0",
-    )
-    .bind(&away)
-    .bind(&handoff_payload)
-    .bind(&handoff_iv)
-    .bind(now.to_rfc3339())
-    .bind(expires_at.to_rfc3339())
-    .execute(&mut *tx)
-    .await
-    .map_err(err500("insert handoff"))?;
+    stage_handoff(&mut tx, away, keeper, &handoff_payload, &handoff_iv)
+        .await
+        .map_err(err500("insert handoff"))?;
 
     // Finally, remove the merged-away user row (its FK rows are moved/cleared).
     sqlx::query("DELETE FROM users WHERE id = ?")
-        .bind(&away)
+        .bind(away)
         .execute(&mut *tx)
         .await
         .map_err(err500("delete away user"))?;
@@ -407,16 +533,16 @@ pub async fn merge_accounts(
 
     // Post-commit, idempotent: rebuild the keeper's rollups over its now-larger data.
     for (subfolder, deal_number) in &affected_boards {
-        if let Err(e) = recompute_board_history(&state.db, &keeper, subfolder, *deal_number).await {
+        if let Err(e) = recompute_board_history(&state.db, keeper, subfolder, *deal_number).await {
             tracing::error!("merge recompute board {}/{} for {}: {}", subfolder, deal_number, keeper, e);
         }
     }
     for assignment_id in &affected_assignments {
-        if let Err(e) = recompute_assignment_boards(&state.db, &keeper, assignment_id).await {
+        if let Err(e) = recompute_assignment_boards(&state.db, keeper, assignment_id).await {
             tracing::error!("merge recompute assignment {} for {}: {}", assignment_id, keeper, e);
         }
     }
-    if let Err(e) = recompute_student_summary(&state.db, &keeper).await {
+    if let Err(e) = recompute_student_summary(&state.db, keeper).await {
         tracing::error!("merge recompute summary for {}: {}", keeper, e);
     }
 
@@ -425,12 +551,12 @@ pub async fn merge_accounts(
         away, keeper, observations_moved
     );
 
-    Ok(Json(MergeResponse {
+    Ok(MergeResponse {
         success: true,
         observations_moved,
         keeper_email: Some(kemail),
         error: None,
-    }))
+    })
 }
 
 // ---------------------------------------------------------------------------
@@ -449,15 +575,17 @@ pub struct HandoffResponse {
 }
 
 /// GET /api/account-handoff?from_user_id=X — returns the wrapped keeper identity
-/// for a pending (unused, unexpired) handoff, else 404. No special auth: the
-/// payload is wrapped under the requesting device's own key.
+/// for a pending (unused, unexpired) handoff, else 404. The caller must hold
+/// a session for the away account (sessions are stateless, so a device's
+/// token outlives the merge that deleted its user row); the payload is
+/// wrapped under that device's own key besides.
 pub async fn get_account_handoff(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(q): Query<HandoffQuery>,
 ) -> Result<Json<HandoffResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    if caller.user_id != q.from_user_id {
+        return Err((StatusCode::FORBIDDEN, "Not your handoff".to_string()));
     }
     let now = chrono::Utc::now().to_rfc3339();
     let row: Option<(String, String)> = sqlx::query_as(
@@ -487,11 +615,11 @@ pub struct HandoffConsumeRequest {
 /// POST /api/account-handoff/consume { from_user_id } — single-use marker.
 pub async fn consume_account_handoff(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<HandoffConsumeRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    if caller.user_id != req.from_user_id {
+        return Err((StatusCode::FORBIDDEN, "Not your handoff".to_string()));
     }
     let result = sqlx::query("UPDATE account_handoff SET used = 1 WHERE from_user_id = ? AND used = 0")
         .bind(&req.from_user_id)
@@ -506,3 +634,73 @@ pub async fn consume_account_handoff(
     }
     Ok(Json(serde_json::json!({ "success": true })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::routes::recovery::encrypt_for_recovery;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn dry_run_reports_rows_whose_metadata_was_changed() {
+        let mut config = Config::for_tests();
+        config.recovery_secret = Some(RecoveryKeyring::new("test-recovery-secret"));
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        let key = {
+            use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+            BASE64.encode([7u8; 32])
+        };
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at, \
+             recovery_encrypted_key) VALUES ('u1', 'Test', 'Student', 'u1@example.com', '', '', ?)",
+        )
+        .bind(encrypt_for_recovery(&key, &RecoveryKeyring::new("test-recovery-secret")).unwrap())
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        for (id, format) in [("legacy", 1), ("bound", 2), ("tampered", 2)] {
+            let aad = ObservationAad {
+                observation_id: id,
+                user_id: "u1",
+                deal_subfolder: Some("Stayman"),
+                deal_number: Some(1),
+                correct: false,
+            }
+            .for_format(format)
+            .unwrap();
+            let (enc, iv) = encrypt_observation_with_aad(b"{}", &key, &aad).unwrap();
+            sqlx::query(
+                "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+                 deal_subfolder, deal_number, encrypted_data, iv, created_at, cipher_format) \
+                 VALUES (?, 'u1', '2026-01-01T00:00:00Z', 'bidding/stayman', 0, 'Stayman', 1, ?, ?, '', ?)",
+            )
+            .bind(id)
+            .bind(&enc)
+            .bind(&iv)
+            .bind(format)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        // A wrong answer relabelled as right after it was sealed.
+        sqlx::query("UPDATE observations SET correct = 1 WHERE id = 'tampered'")
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        let report = verify_user_decryption(&state, "u1").await.unwrap();
+        assert_eq!(report.total, 3);
+        assert_eq!(report.aad_bound, 2);
+        assert_eq!(report.decrypted_ok, 2);
+        assert_eq!(report.roundtrip_ok, 2);
+        assert_eq!(report.metadata_mismatch, ["tampered"]);
+        assert!(!report.success);
+    }
+}
@@ -3,18 +3,28 @@ pub mod announcements;
 pub mod assignments;
 pub mod auth;
 pub mod board_status;
+pub mod board_timeline;
+pub mod classroom_staff;
 pub mod classrooms;
 pub mod convention_cards;
 pub mod diagnostics;
 pub mod exercises;
 pub mod grants;
+pub mod join_codes;
+pub mod key_rotation;
 pub mod keys;
 pub mod lesson_mastery;
 pub mod me;
 pub mod merge;
+pub mod observation_export;
 pub mod observations;
 pub mod recovery;
+pub mod recovery_rewrap;
+pub mod report_triage;
 pub mod reports;
+pub mod review_queue;
+pub mod roster;
+pub mod rules_profiles;
 pub mod student_summaries;
 pub mod table_sessions;
 pub mod table_tickets;
@@ -27,18 +37,28 @@ pub use announcements::*;
 pub use assignments::*;
 pub use auth::*;
 pub use board_status::*;
+pub use board_timeline::*;
+pub use classroom_staff::*;
 pub use classrooms::*;
 pub use convention_cards::*;
 pub use diagnostics::*;
 pub use exercises::*;
 pub use grants::*;
+pub use join_codes::*;
+pub use key_rotation::*;
 pub use keys::*;
 pub use lesson_mastery::*;
 pub use me::*;
 pub use merge::*;
+pub use observation_export::*;
 pub use observations::*;
 pub use recovery::*;
+pub use recovery_rewrap::*;
+pub use report_triage::*;
 pub use reports::*;
+pub use review_queue::*;
+pub use roster::*;
+pub use rules_profiles::*;
 pub use student_summaries::*;
 pub use table_sessions::*;
 pub use table_tickets::*;
@@ -0,0 +1,439 @@
+//! Streaming bulk export of observations.
+//!
+//! `GET /api/observations` pages with `limit`/`offset` and buffers each
+//! page as one JSON document, which doesn't suit the nightly analytics
+//! pull. The export walks the same filters in `(timestamp, id)` order one
+//! keyset page at a time and writes each page to the response as it is
+//! read, so memory stays flat however many rows match.
+//!
+//! Each NDJSON line carries its `timestamp` and `id`; a client whose
+//! download was cut short resumes with `after_timestamp` / `after_id` set
+//! to the last line it kept. Rows inserted behind the cursor while an
+//! export runs are not revisited.
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{stream, TryStreamExt};
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use super::observations::scope_query;
+use crate::models::{ExportFormat, Observation, ObservationExportQuery, ObservationQuery};
+use crate::session::AuthUser;
+use crate::AppState;
+
+const PAGE_SIZE: i64 = 500;
+
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "timestamp",
+    "skill_path",
+    "correct",
+    "classroom",
+    "deal_subfolder",
+    "deal_number",
+    "board_result",
+    "status",
+    "wilderness",
+    "exercise_id",
+    "assignment_id",
+    "jungle",
+    "time_taken_ms",
+    "schema_version",
+    "cipher_format",
+    "created_at",
+];
+
+/// The resolved filters of one export.
+#[derive(Debug, Clone)]
+struct ExportFilter {
+    user_id: Option<String>,
+    classroom: Option<String>,
+    skill_path: Option<String>,
+    correct: Option<bool>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// The next page after `cursor` (exclusive), in `(timestamp, id)` order.
+fn page_query<'a>(
+    filter: &'a ExportFilter,
+    cursor: Option<&'a (String, String)>,
+    page_size: i64,
+) -> QueryBuilder<'a, Sqlite> {
+    let mut qb = QueryBuilder::new("SELECT * FROM observations WHERE 1=1");
+    if let Some(user_id) = &filter.user_id {
+        qb.push(" AND user_id = ").push_bind(user_id);
+    }
+    if let Some(classroom) = &filter.classroom {
+        qb.push(" AND classroom = ").push_bind(classroom);
+    }
+    if let Some(skill_path) = &filter.skill_path {
+        qb.push(" AND skill_path LIKE ")
+            .push_bind(format!("{}%", skill_path));
+    }
+    if let Some(correct) = filter.correct {
+        qb.push(" AND correct = ").push_bind(correct);
+    }
+    if let Some(from) = &filter.from {
+        qb.push(" AND timestamp >= ").push_bind(from);
+    }
+    if let Some(to) = &filter.to {
+        qb.push(" AND timestamp <= ").push_bind(to);
+    }
+    if let Some((timestamp, id)) = cursor {
+        qb.push(" AND (timestamp > ")
+            .push_bind(timestamp)
+            .push(" OR (timestamp = ")
+            .push_bind(timestamp)
+            .push(" AND id > ")
+            .push_bind(id)
+            .push("))");
+    }
+    qb.push(" ORDER BY timestamp ASC, id ASC LIMIT ")
+        .push_bind(page_size);
+    qb
+}
+
+/// Quote a CSV field when it needs it (RFC 4180).
+fn csv_field(out: &mut Vec<u8>, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        out.push(b'"');
+        out.extend_from_slice(value.replace('"', "\"\"").as_bytes());
+        out.push(b'"');
+    } else {
+        out.extend_from_slice(value.as_bytes());
+    }
+}
+
+fn csv_row(out: &mut Vec<u8>, obs: &Observation) {
+    let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+    let fields = [
+        obs.id.clone(),
+        obs.user_id.clone(),
+        obs.timestamp.clone(),
+        obs.skill_path.clone(),
+        obs.correct.to_string(),
+        opt(&obs.classroom),
+        opt(&obs.deal_subfolder),
+        obs.deal_number.map(|n| n.to_string()).unwrap_or_default(),
+        opt(&obs.board_result),
+        opt(&obs.status),
+        opt(&obs.wilderness),
+        opt(&obs.exercise_id),
+        opt(&obs.assignment_id),
+        obs.jungle.to_string(),
+        obs.time_taken_ms.map(|n| n.to_string()).unwrap_or_default(),
+        obs.schema_version.map(|n| n.to_string()).unwrap_or_default(),
+        obs.cipher_format.to_string(),
+        obs.created_at.clone(),
+    ];
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        csv_field(out, field);
+    }
+    out.extend_from_slice(b"\r\n");
+}
+
+struct ExportState {
+    db: Pool<Sqlite>,
+    filter: ExportFilter,
+    format: ExportFormat,
+    cursor: Option<(String, String)>,
+    page_size: i64,
+    started: bool,
+    done: bool,
+}
+
+type ExportError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The export body: one chunk per page.
+fn export_stream(
+    state: ExportState,
+) -> impl futures_util::Stream<Item = Result<Vec<u8>, ExportError>> {
+    stream::try_unfold(state, |mut st| async move {
+        if st.done {
+            return Ok(None);
+        }
+        let rows: Vec<Observation> = page_query(&st.filter, st.cursor.as_ref(), st.page_size)
+            .build_query_as()
+            .fetch_all(&st.db)
+            .await?;
+        st.done = (rows.len() as i64) < st.page_size;
+
+        let mut chunk = Vec::new();
+        if !st.started && st.format == ExportFormat::Csv {
+            chunk.extend_from_slice(CSV_COLUMNS.join(",").as_bytes());
+            chunk.extend_from_slice(b"\r\n");
+        }
+        st.started = true;
+        for obs in &rows {
+            match st.format {
+                ExportFormat::Ndjson => {
+                    serde_json::to_writer(&mut chunk, obs)?;
+                    chunk.push(b'\n');
+                }
+                ExportFormat::Csv => csv_row(&mut chunk, obs),
+            }
+        }
+        if let Some(last) = rows.last() {
+            st.cursor = Some((last.timestamp.clone(), last.id.clone()));
+        }
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((chunk, st)))
+    })
+}
+
+/// GET /api/observations/export
+/// Stream every matching observation as NDJSON (`format=ndjson`, the
+/// default) or its cleartext columns as CSV (`format=csv`).
+pub async fn export_observations(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Query(query): Query<ObservationExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let cursor = match (query.after_timestamp, query.after_id) {
+        (Some(timestamp), Some(id)) => Some((timestamp, id)),
+        (None, None) => None,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "after_timestamp and after_id go together".to_string(),
+            ))
+        }
+    };
+    let scoped = scope_query(
+        &state,
+        &caller,
+        ObservationQuery {
+            user_id: query.user_id,
+            classroom: query.classroom,
+            skill_path: query.skill_path,
+            correct: query.correct,
+            from: query.from,
+            to: query.to,
+            limit: None,
+            offset: None,
+            cursor: None,
+        },
+    )
+    .await?;
+    let filter = ExportFilter {
+        user_id: scoped.user_id,
+        classroom: scoped.classroom,
+        skill_path: scoped.skill_path,
+        correct: scoped.correct,
+        from: scoped.from,
+        to: scoped.to,
+    };
+
+    let content_type = match query.format {
+        ExportFormat::Ndjson => "application/x-ndjson",
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+    };
+    let body = export_stream(ExportState {
+        db: state.db.clone(),
+        filter,
+        format: query.format,
+        cursor,
+        page_size: PAGE_SIZE,
+        started: false,
+        done: false,
+    })
+    .inspect_err(|e| tracing::error!("Observation export aborted: {}", e));
+
+    Ok((
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pages_cover_every_row_once_in_keyset_order() {
+        let pool = crate::db::test_pool().await;
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+             VALUES ('u1', 'Test', 'Student', 'u1@example.com', '', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        // Two rows share a timestamp, so the id tie-break has to hold
+        // across a page boundary.
+        for (id, ts) in [
+            ("e", "2026-01-03T09:00:00Z"),
+            ("b", "2026-01-01T09:00:00Z"),
+            ("d", "2026-01-02T09:00:00Z"),
+            ("c", "2026-01-02T09:00:00Z"),
+            ("a", "2026-01-01T08:00:00Z"),
+        ] {
+            sqlx::query(
+                "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+                 deal_subfolder, deal_number, encrypted_data, iv, created_at, board_result) \
+                 VALUES (?, 'u1', ?, 'bidding/stayman', 1, 'Stayman, \"A\"', 1, '', '', '', NULL)",
+            )
+            .bind(id)
+            .bind(ts)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let filter = ExportFilter {
+            user_id: Some("u1".to_string()),
+            classroom: None,
+            skill_path: Some("bidding".to_string()),
+            correct: None,
+            from: None,
+            to: None,
+        };
+        let export = |format, cursor| {
+            export_stream(ExportState {
+                db: pool.clone(),
+                filter: filter.clone(),
+                format,
+                cursor,
+                page_size: 2,
+                started: false,
+                done: false,
+            })
+            .try_concat()
+        };
+
+        let ndjson = String::from_utf8(export(ExportFormat::Ndjson, None).await.unwrap()).unwrap();
+        let ids: Vec<String> = ndjson
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(ids, ["a", "b", "c", "d", "e"]);
+
+        let resumed = export(
+            ExportFormat::Csv,
+            Some(("2026-01-02T09:00:00Z".to_string(), "c".to_string())),
+        )
+        .await
+        .unwrap();
+        let csv = String::from_utf8(resumed).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("id,user_id,timestamp,"));
+        assert!(lines[1].starts_with(
+            "d,u1,2026-01-02T09:00:00Z,bidding/stayman,true,,\"Stayman, \"\"A\"\"\",1,"
+        ));
+        assert!(lines[2].starts_with("e,"));
+    }
+
+    #[tokio::test]
+    async fn export_requests_are_scoped_and_checked() {
+        use crate::config::Config;
+        use std::sync::Arc;
+        use std::time::Instant;
+
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for id in ["u1", "u2"] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+                 VALUES (?, 'Test', 'Student', ?, '', '')",
+            )
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .execute(&state.db)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+                 encrypted_data, iv, created_at) \
+                 VALUES (?, ?, '2026-01-01T09:00:00Z', 'bidding', 1, '', '', '')",
+            )
+            .bind(format!("{id}-obs"))
+            .bind(id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        let caller = |role: &str| AuthUser {
+            user_id: "u1".to_string(),
+            role: role.to_string(),
+        };
+        let query = |user_id: Option<&str>, format: ExportFormat| ObservationExportQuery {
+            user_id: user_id.map(str::to_string),
+            classroom: None,
+            skill_path: None,
+            correct: None,
+            from: None,
+            to: None,
+            format,
+            after_timestamp: None,
+            after_id: None,
+        };
+        let body = |caller: AuthUser, query: ObservationExportQuery| {
+            let state = state.clone();
+            async move {
+                let response = export_observations(State(state), caller, Query(query)).await?;
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                Ok::<_, (StatusCode, String)>(String::from_utf8(bytes.to_vec()).unwrap())
+            }
+        };
+
+        let half_cursor = ObservationExportQuery {
+            after_timestamp: Some("2026-01-01T09:00:00Z".to_string()),
+            ..query(None, ExportFormat::Ndjson)
+        };
+        let err = body(caller("student"), half_cursor).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        // Without a grant, another student's rows are forbidden, and an
+        // unfiltered export is the caller's own.
+        let err = body(caller("student"), query(Some("u2"), ExportFormat::Ndjson))
+            .await
+            .unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+        let own = body(caller("student"), query(None, ExportFormat::Ndjson))
+            .await
+            .unwrap();
+        assert_eq!(own.lines().count(), 1);
+        assert!(own.contains("\"u1-obs\""));
+        let all = body(caller("admin"), query(None, ExportFormat::Ndjson))
+            .await
+            .unwrap();
+        assert_eq!(all.lines().count(), 2);
+
+        // Nothing matching: an empty NDJSON body, a header-only CSV.
+        let after_everything = |format| ObservationExportQuery {
+            after_timestamp: Some("2027-01-01T00:00:00Z".to_string()),
+            after_id: Some("z".to_string()),
+            ..query(None, format)
+        };
+        let empty = body(caller("student"), after_everything(ExportFormat::Ndjson))
+            .await
+            .unwrap();
+        assert!(empty.is_empty());
+        let empty = body(caller("student"), after_everything(ExportFormat::Csv))
+            .await
+            .unwrap();
+        assert_eq!(empty, format!("{}\r\n", CSV_COLUMNS.join(",")));
+    }
+}
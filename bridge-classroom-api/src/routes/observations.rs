@@ -1,75 +1,217 @@
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
-use serde::Deserialize;
 
 use crate::{
     models::{
-        Observation, ObservationMetadata, ObservationQuery, ObservationsMetadataResponse,
-        ObservationsResponse, SubmitObservationsRequest, SubmitObservationsResponse,
+        Observation, ObservationCursor, ObservationMetadata, ObservationQuery, ObservationResult,
+        ObservationsMetadataResponse, ObservationsResponse, RejectCode, SubmitObservationsRequest,
+        SubmitObservationsResponse, SubmitOutcome,
     },
+    policy::{authorize, Relation},
+    rules,
+    session::AuthUser,
     AppState,
 };
 
-use super::board_status::{derive_wilderness, recompute_assignment_boards, recompute_board_history};
-use crate::student_summary::recompute_student_summary;
+use super::board_status::derive_wilderness;
+use crate::jobs::{self, Job};
 
-use std::collections::HashSet;
+use sqlx::SqliteConnection;
+use std::collections::{HashMap, HashSet};
 
-/// Query parameters for submit endpoint (for sendBeacon support)
-#[derive(Debug, Deserialize)]
-pub struct SubmitQuery {
-    pub api_key: Option<String>,
+/// Observations are ciphertext under the student's key, so reading someone
+/// else's rows only makes sense for a grantee holding that key: non-admins
+/// default to their own rows and need an active grant for anyone else's.
+pub(crate) async fn scope_query(
+    state: &AppState,
+    caller: &AuthUser,
+    mut query: ObservationQuery,
+) -> Result<ObservationQuery, (StatusCode, String)> {
+    if caller.is_admin() {
+        return Ok(query);
+    }
+    match query.user_id.as_deref() {
+        Some(uid) if uid != caller.user_id => {
+            authorize(&state.db, caller, Relation::GrantGrantee(uid)).await?;
+        }
+        _ => query.user_id = Some(caller.user_id.clone()),
+    }
+    Ok(query)
 }
 
-/// Validate API key from request headers or query params
-fn validate_api_key(headers: &HeaderMap, query_key: Option<&str>, expected_key: &str) -> bool {
-    // Check header first
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        if header_key == expected_key {
-            return true;
-        }
+/// Keyset condition for the newest-first `(timestamp, id)` order: rows
+/// strictly after the cursor. Rows inserted ahead of it while a client
+/// pages are not returned, and none already returned come back.
+const CURSOR_CLAUSE: &str = " AND (timestamp < ? OR (timestamp = ? AND id < ?))";
+
+fn decode_cursor(
+    query: &ObservationQuery,
+) -> Result<Option<ObservationCursor>, (StatusCode, String)> {
+    let Some(token) = query.cursor.as_deref() else {
+        return Ok(None);
+    };
+    if query.offset.unwrap_or(0) != 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Use cursor or offset, not both".to_string(),
+        ));
     }
+    ObservationCursor::decode(token)
+        .map(Some)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
 
-    // Fall back to query parameter (for sendBeacon)
-    if let Some(qk) = query_key {
-        if qk == expected_key {
-            return true;
-        }
+/// Trim a page fetched with one extra row back to `page_size`, returning
+/// the last kept row if the extra one showed there's more.
+fn next_page<T>(rows: &mut Vec<T>, page_size: usize) -> Option<&T> {
+    if rows.len() <= page_size {
+        return None;
     }
+    rows.truncate(page_size);
+    rows.last()
+}
 
-    false
+/// How `store_observation` left one row.
+enum StoreOutcome {
+    /// Written. `previous_board` is set when the id replaced an earlier
+    /// submission, with the board it was filed under.
+    Stored {
+        previous_board: Option<(Option<String>, Option<i32>)>,
+    },
+    /// The id belongs to another user's observation; nothing was written.
+    IdConflict,
+}
+
+/// Insert or replace one validated observation inside the batch
+/// transaction.
+async fn store_observation(
+    conn: &mut SqliteConnection,
+    obs: &Observation,
+    wilderness: &str,
+) -> Result<StoreOutcome, sqlx::Error> {
+    // A re-submitted id rewrites history the board walk already folded,
+    // so its board (and the one it moved off, if any) needs a replay.
+    let previous_board: Option<(Option<String>, Option<i32>)> = sqlx::query_as(
+        "SELECT deal_subfolder, deal_number FROM observations WHERE id = ? AND user_id = ?",
+    )
+    .bind(&obs.id)
+    .bind(&obs.user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO observations (
+            id, user_id, timestamp, skill_path, correct, classroom,
+            deal_subfolder, deal_number, encrypted_data, iv, created_at,
+            board_result, wilderness, exercise_id, assignment_id, jungle,
+            time_taken_ms, schema_version, cipher_format
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            encrypted_data = excluded.encrypted_data,
+            iv             = excluded.iv,
+            correct        = excluded.correct,
+            deal_subfolder = excluded.deal_subfolder,
+            deal_number    = excluded.deal_number,
+            timestamp      = excluded.timestamp,
+            board_result   = excluded.board_result,
+            wilderness     = excluded.wilderness,
+            exercise_id    = excluded.exercise_id,
+            assignment_id  = excluded.assignment_id,
+            jungle         = excluded.jungle,
+            time_taken_ms  = COALESCE(excluded.time_taken_ms, observations.time_taken_ms),
+            schema_version = excluded.schema_version,
+            cipher_format  = excluded.cipher_format
+        WHERE observations.user_id = excluded.user_id
+        "#,
+    )
+    .bind(&obs.id)
+    .bind(&obs.user_id)
+    .bind(&obs.timestamp)
+    .bind(&obs.skill_path)
+    .bind(obs.correct)
+    .bind(&obs.classroom)
+    .bind(&obs.deal_subfolder)
+    .bind(obs.deal_number)
+    .bind(&obs.encrypted_data)
+    .bind(&obs.iv)
+    .bind(&obs.created_at)
+    .bind(&obs.board_result)
+    .bind(wilderness)
+    .bind(&obs.exercise_id)
+    .bind(&obs.assignment_id)
+    .bind(obs.jungle)
+    .bind(obs.time_taken_ms)
+    .bind(obs.schema_version)
+    .bind(obs.cipher_format)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(StoreOutcome::IdConflict);
+    }
+    Ok(StoreOutcome::Stored { previous_board })
 }
 
 /// POST /api/observations
-/// Submit encrypted observations
+/// Submit encrypted observations. The session may come from `?session=`
+/// for sendBeacon, which can't set headers.
+///
+/// Every observation gets a result keyed by its id. The batch is written
+/// in one transaction: by default the valid observations are kept and the
+/// rest rejected; with `all_or_nothing` any rejection rolls the batch back.
 pub async fn submit_observations(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    Query(query): Query<SubmitQuery>,
+    caller: AuthUser,
     Json(req): Json<SubmitObservationsRequest>,
 ) -> Result<Json<SubmitObservationsResponse>, (StatusCode, String)> {
-    // Validate API key (from header or query param for sendBeacon support)
-    if !validate_api_key(&headers, query.api_key.as_deref(), &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let received = req.observations.len();
-    let mut stored = 0;
-    let mut errors = Vec::new();
+    let all_or_nothing = req.all_or_nothing;
 
     // Boards needing recomputation, and users needing a refreshed summary.
-    // Use HashSet to dedupe across multiple observations in the same batch.
-    let mut boards_to_recompute: HashSet<(String, String, i32)> = HashSet::new();
+    // Dedupe across multiple observations in the same batch. A board maps to
+    // true when it needs a full replay rather than an incremental fold.
+    let mut boards_to_recompute: HashMap<(String, String, i32), bool> = HashMap::new();
     let mut users_to_refresh: HashSet<String> = HashSet::new();
     // Assignment-scoped rollups needing recomputation: (user_id, assignment_id).
     let mut assignments_to_recompute: HashSet<(String, String)> = HashSet::new();
 
+    // Wilderness is frozen at insert time under the caller's rules profile.
+    let profile = rules::profile_for_user(&state.db, &caller.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    // Validate everything (and derive wilderness, which reads the pool)
+    // before the write transaction opens.
+    let mut results: Vec<Option<ObservationResult>> = Vec::with_capacity(received);
+    let mut accepted: Vec<(usize, Observation, String)> = Vec::new();
+    let mut seen_ids: HashSet<String> = HashSet::new();
     for encrypted_obs in req.observations {
-        let obs = Observation::from_encrypted(encrypted_obs);
+        let id = encrypted_obs.metadata.observation_id.clone();
+        let rejection = if let Err(e) = encrypted_obs.validate() {
+            Some((RejectCode::InvalidMetadata, e))
+        } else if encrypted_obs.metadata.user_id != caller.user_id {
+            // Observations are always filed under the caller.
+            tracing::warn!(
+                "Rejected observation {} for {} submitted by {}",
+                id, encrypted_obs.metadata.user_id, caller.user_id
+            );
+            Some((RejectCode::UserMismatch, "user_id does not match session".to_string()))
+        } else if !seen_ids.insert(id.clone()) {
+            Some((RejectCode::DuplicateInBatch, "id appears earlier in this batch".to_string()))
+        } else {
+            None
+        };
+        if let Some((code, message)) = rejection {
+            results.push(Some(ObservationResult::rejected(&id, code, message)));
+            continue;
+        }
 
+        let obs = Observation::from_encrypted(encrypted_obs);
         // CORRECTNESS_AND_MASTERY.md §11.2: derive wilderness from the
         // observation's context fields, frozen at insert time.
         let wilderness = derive_wilderness(
@@ -78,108 +220,140 @@ pub async fn submit_observations(
             obs.exercise_id.as_deref(),
             obs.assignment_id.as_deref(),
             obs.jungle,
+            &profile.params,
         )
         .await;
+        accepted.push((results.len(), obs, wilderness));
+        results.push(None);
+    }
 
-        match sqlx::query(
-            r#"
-            INSERT INTO observations (
-                id, user_id, timestamp, skill_path, correct, classroom,
-                deal_subfolder, deal_number, encrypted_data, iv, created_at,
-                board_result, wilderness, exercise_id, assignment_id, jungle,
-                time_taken_ms
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(id) DO UPDATE SET
-                encrypted_data = excluded.encrypted_data,
-                iv             = excluded.iv,
-                correct        = excluded.correct,
-                timestamp      = excluded.timestamp,
-                board_result   = excluded.board_result,
-                wilderness     = excluded.wilderness,
-                exercise_id    = excluded.exercise_id,
-                assignment_id  = excluded.assignment_id,
-                jungle         = excluded.jungle,
-                time_taken_ms  = COALESCE(excluded.time_taken_ms, observations.time_taken_ms)
-            "#,
-        )
-        .bind(&obs.id)
-        .bind(&obs.user_id)
-        .bind(&obs.timestamp)
-        .bind(&obs.skill_path)
-        .bind(obs.correct)
-        .bind(&obs.classroom)
-        .bind(&obs.deal_subfolder)
-        .bind(obs.deal_number)
-        .bind(&obs.encrypted_data)
-        .bind(&obs.iv)
-        .bind(&obs.created_at)
-        .bind(&obs.board_result)
-        .bind(&wilderness)
-        .bind(&obs.exercise_id)
-        .bind(&obs.assignment_id)
-        .bind(obs.jungle)
-        .bind(obs.time_taken_ms)
-        .execute(&state.db)
-        .await
-        {
-            Ok(_) => {
-                stored += 1;
-                if let (Some(ref subfolder), Some(deal_num)) = (&obs.deal_subfolder, obs.deal_number) {
-                    boards_to_recompute.insert((
-                        obs.user_id.clone(),
-                        subfolder.clone(),
-                        deal_num,
-                    ));
-                    users_to_refresh.insert(obs.user_id.clone());
+    let mut committed = !(all_or_nothing && results.iter().any(Option::is_some));
+    if committed {
+        let mut tx = state
+            .db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        for (slot, obs, wilderness) in &accepted {
+            let result = match store_observation(&mut tx, obs, wilderness).await {
+                Ok(StoreOutcome::Stored { previous_board }) => {
+                    let replaces_existing = previous_board.is_some();
+                    if let (Some(ref subfolder), Some(deal_num)) = (&obs.deal_subfolder, obs.deal_number) {
+                        *boards_to_recompute
+                            .entry((obs.user_id.clone(), subfolder.clone(), deal_num))
+                            .or_default() |= replaces_existing;
+                        users_to_refresh.insert(obs.user_id.clone());
+                    }
+                    if let Some((Some(subfolder), Some(deal_num))) = previous_board {
+                        boards_to_recompute.insert((obs.user_id.clone(), subfolder, deal_num), true);
+                        users_to_refresh.insert(obs.user_id.clone());
+                    }
+                    // Observations tagged with an assignment feed the assignment rollup.
+                    if let Some(ref assignment_id) = obs.assignment_id {
+                        assignments_to_recompute
+                            .insert((obs.user_id.clone(), assignment_id.clone()));
+                    }
+                    let outcome = if replaces_existing {
+                        SubmitOutcome::Updated
+                    } else {
+                        SubmitOutcome::Stored
+                    };
+                    ObservationResult::accepted(&obs.id, outcome)
                 }
-                // Observations tagged with an assignment feed the assignment rollup.
-                if let Some(ref assignment_id) = obs.assignment_id {
-                    assignments_to_recompute
-                        .insert((obs.user_id.clone(), assignment_id.clone()));
+                Ok(StoreOutcome::IdConflict) => ObservationResult::rejected(
+                    &obs.id,
+                    RejectCode::IdConflict,
+                    "id belongs to another user",
+                ),
+                Err(e) => {
+                    tracing::error!("Failed to store observation {}: {}", obs.id, e);
+                    ObservationResult::rejected(&obs.id, RejectCode::StorageError, e.to_string())
                 }
-            }
-            Err(e) => {
-                tracing::error!("Failed to store observation {}: {}", obs.id, e);
-                errors.push(format!("Failed to store {}: {}", obs.id, e));
+            };
+            let failed = result.outcome == SubmitOutcome::Rejected;
+            results[*slot] = Some(result);
+            if failed && all_or_nothing {
+                committed = false;
+                break;
             }
         }
-    }
-
-    // Recompute board_status (and per-observation status/wilderness) for
-    // every affected (user, board). This is the v2 walker that produces
-    // the new state machine, stars, and wild_achievement.
-    for (user_id, subfolder, deal_number) in &boards_to_recompute {
-        if let Err(e) =
-            recompute_board_history(&state.db, user_id, subfolder, *deal_number).await
-        {
-            tracing::error!(
-                "Failed to recompute board history for {}/{}/{}: {}",
-                user_id, subfolder, deal_number, e
-            );
+        if committed {
+            tx.commit()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        } else {
+            tx.rollback()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
         }
     }
 
-    // Recompute the assignment-scoped rollup for each affected (user, assignment).
-    for (user_id, assignment_id) in &assignments_to_recompute {
-        if let Err(e) = recompute_assignment_boards(&state.db, user_id, assignment_id).await {
-            tracing::error!(
-                "Failed to recompute assignment_board_status for {}/{}: {}",
-                user_id, assignment_id, e
-            );
+    if !committed {
+        // A rolled-back batch stored nothing: every observation without a
+        // rejection of its own goes back as batch_rejected.
+        for (slot, obs, _) in &accepted {
+            if results[*slot]
+                .as_ref()
+                .is_none_or(|r| r.outcome != SubmitOutcome::Rejected)
+            {
+                results[*slot] = Some(ObservationResult::rejected(
+                    &obs.id,
+                    RejectCode::BatchRejected,
+                    "batch rolled back (all_or_nothing)",
+                ));
+            }
         }
+        boards_to_recompute.clear();
+        assignments_to_recompute.clear();
+        users_to_refresh.clear();
     }
+    let results: Vec<ObservationResult> = results
+        .into_iter()
+        .map(|r| r.expect("every observation has a result"))
+        .collect();
+    let stored = results
+        .iter()
+        .filter(|r| r.outcome != SubmitOutcome::Rejected)
+        .count();
+    let errors = results
+        .iter()
+        .filter_map(|r| {
+            let message = r.message.as_deref()?;
+            Some(format!("Rejected {}: {}", r.observation_id, message))
+        })
+        .collect();
 
-    // Refresh the per-user summary row once for each affected user.
-    // student_summary is a cache; this is what keeps it in sync.
-    for user_id in &users_to_refresh {
-        if let Err(e) = recompute_student_summary(&state.db, user_id).await {
-            tracing::error!("Failed to refresh student_summary for {}: {}", user_id, e);
-        }
+    // Queue the derived-table rebuilds (board_status and per-observation
+    // status/wilderness, the assignment rollups, student_summary) for the
+    // recompute workers instead of running them inline. The observations
+    // are stored either way; if queueing fails the next submission or an
+    // admin `recompute` catches the user up.
+    let jobs = boards_to_recompute
+        .iter()
+        .map(|((user_id, subfolder, deal_number), full_replay)| Job::Board {
+            user_id: user_id.clone(),
+            deal_subfolder: subfolder.clone(),
+            deal_number: *deal_number,
+            full_replay: *full_replay,
+        })
+        .chain(
+            assignments_to_recompute
+                .iter()
+                .map(|(user_id, assignment_id)| Job::Assignment {
+                    user_id: user_id.clone(),
+                    assignment_id: assignment_id.clone(),
+                }),
+        )
+        .chain(users_to_refresh.iter().map(|user_id| Job::Summary {
+            user_id: user_id.clone(),
+        }))
+        .collect::<Vec<_>>();
+    if let Err(e) = jobs::enqueue_all(&state.db, jobs).await {
+        tracing::error!("Failed to queue recompute jobs: {}", e);
     }
 
     tracing::info!(
-        "Stored {}/{} observations ({} boards, {} assignments recomputed, {} summaries refreshed)",
+        "Stored {}/{} observations ({} boards, {} assignments, {} summaries queued for recompute)",
         stored, received, boards_to_recompute.len(), assignments_to_recompute.len(), users_to_refresh.len(),
     );
 
@@ -187,6 +361,8 @@ pub async fn submit_observations(
         received,
         stored,
         errors,
+        results,
+        committed,
     }))
 }
 
@@ -194,15 +370,13 @@ pub async fn submit_observations(
 /// Fetch observations with optional filters
 pub async fn get_observations(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<ObservationQuery>,
 ) -> Result<Json<ObservationsResponse>, (StatusCode, String)> {
-    // Validate API key
-    if !validate_api_key(&headers, None, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let query = scope_query(&state, &caller, query).await?;
+    let cursor = decode_cursor(&query)?;
 
-    let limit = query.limit.unwrap_or(100).min(10000);
+    let limit = query.limit.unwrap_or(100).clamp(1, 10000);
     let offset = query.offset.unwrap_or(0);
 
     // Build query dynamically
@@ -234,7 +408,12 @@ pub async fn get_observations(
         count_sql.push_str(" AND timestamp <= ?");
     }
 
-    sql.push_str(" ORDER BY timestamp DESC LIMIT ? OFFSET ?");
+    if cursor.is_some() {
+        sql.push_str(CURSOR_CLAUSE);
+    }
+
+    // One row past the page tells us whether there's a next one.
+    sql.push_str(" ORDER BY timestamp DESC, id DESC LIMIT ? OFFSET ?");
 
     // Build and execute the query
     let mut query_builder = sqlx::query_as::<_, Observation>(&sql);
@@ -267,12 +446,21 @@ pub async fn get_observations(
         count_builder = count_builder.bind(to);
     }
 
-    query_builder = query_builder.bind(limit).bind(offset);
+    if let Some(ref cursor) = cursor {
+        query_builder = query_builder
+            .bind(&cursor.timestamp)
+            .bind(&cursor.timestamp)
+            .bind(&cursor.id);
+    }
+
+    query_builder = query_builder.bind(limit + 1).bind(offset);
 
-    let observations = query_builder
+    let mut observations = query_builder
         .fetch_all(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let next_cursor = next_page(&mut observations, limit as usize)
+        .map(|last| ObservationCursor::after(&last.timestamp, &last.id).encode());
 
     let total = count_builder
         .fetch_one(&state.db)
@@ -284,6 +472,7 @@ pub async fn get_observations(
         total,
         limit,
         offset,
+        next_cursor,
     }))
 }
 
@@ -291,13 +480,17 @@ pub async fn get_observations(
 /// Fetch observation metadata only (no encrypted data)
 pub async fn get_observations_metadata(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<ObservationQuery>,
 ) -> Result<Json<ObservationsMetadataResponse>, (StatusCode, String)> {
-    // Validate API key
-    if !validate_api_key(&headers, None, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let query = scope_query(&state, &caller, query).await?;
+    let cursor = decode_cursor(&query)?;
+    // Unpaged unless the caller asks: older clients expect every row.
+    let page_size = match (query.limit, &cursor) {
+        (Some(limit), _) => Some(limit.clamp(1, 10000)),
+        (None, Some(_)) => Some(100),
+        (None, None) => None,
+    };
 
     // Build query dynamically
     let mut sql = String::from(
@@ -330,7 +523,14 @@ pub async fn get_observations_metadata(
         count_sql.push_str(" AND timestamp <= ?");
     }
 
-    sql.push_str(" ORDER BY timestamp DESC");
+    if cursor.is_some() {
+        sql.push_str(CURSOR_CLAUSE);
+    }
+
+    sql.push_str(" ORDER BY timestamp DESC, id DESC");
+    if page_size.is_some() {
+        sql.push_str(" LIMIT ?");
+    }
 
     // Build and execute the query
     let mut query_builder = sqlx::query_as::<_, (String, String, String, String, bool, Option<String>, Option<String>, Option<i32>, Option<String>)>(&sql);
@@ -363,10 +563,24 @@ pub async fn get_observations_metadata(
         count_builder = count_builder.bind(to);
     }
 
-    let rows = query_builder
+    if let Some(ref cursor) = cursor {
+        query_builder = query_builder
+            .bind(&cursor.timestamp)
+            .bind(&cursor.timestamp)
+            .bind(&cursor.id);
+    }
+    if let Some(page_size) = page_size {
+        query_builder = query_builder.bind(page_size + 1);
+    }
+
+    let mut rows = query_builder
         .fetch_all(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let next_cursor = page_size.and_then(|page_size| {
+        next_page(&mut rows, page_size as usize)
+            .map(|last| ObservationCursor::after(&last.2, &last.0).encode())
+    });
 
     let observations: Vec<ObservationMetadata> = rows
         .into_iter()
@@ -390,5 +604,477 @@ pub async fn get_observations_metadata(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(ObservationsMetadataResponse { observations, total }))
+    Ok(Json(ObservationsMetadataResponse {
+        observations,
+        total,
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::observation_schema::CURRENT_SCHEMA_VERSION;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    async fn insert_obs(state: &AppState, id: &str, ts: &str) {
+        sqlx::query(
+            "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+             encrypted_data, iv, created_at) VALUES (?, 'u1', ?, 'bidding', 1, '', '', '')",
+        )
+        .bind(id)
+        .bind(ts)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    }
+
+    async fn test_state() -> AppState {
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for id in ["u1", "u2"] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+                 VALUES (?, 'Test', 'Student', ?, '', '')",
+            )
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_are_stable_under_concurrent_inserts() {
+        let state = test_state().await;
+        for (id, ts) in [
+            ("a", "2026-01-01T09:00:00Z"),
+            ("b", "2026-01-02T09:00:00Z"),
+            ("c", "2026-01-02T09:00:00Z"),
+            ("d", "2026-01-03T09:00:00Z"),
+            ("e", "2026-01-04T09:00:00Z"),
+        ] {
+            insert_obs(&state, id, ts).await;
+        }
+        let caller = AuthUser {
+            user_id: "u1".to_string(),
+            role: "student".to_string(),
+        };
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let query = ObservationQuery {
+                limit: Some(2),
+                offset: None,
+                cursor: cursor.clone(),
+                ..ObservationQuery::default()
+            };
+            let Json(page) = get_observations_metadata(
+                State(state.clone()),
+                caller.clone(),
+                Query(query),
+            )
+            .await
+            .unwrap();
+            seen.extend(page.observations.into_iter().map(|o| o.observation_id));
+            if seen.len() == 2 {
+                // A sync lands mid-walk: newer rows, and one tying the
+                // cursor's timestamp on the already-returned side.
+                insert_obs(&state, "f", "2026-01-05T09:00:00Z").await;
+                insert_obs(&state, "dd", "2026-01-03T09:00:00Z").await;
+            }
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, ["e", "d", "c", "b", "a"]);
+
+        let bad = ObservationQuery {
+            cursor: Some("not-a-cursor".to_string()),
+            offset: None,
+            ..ObservationQuery::default()
+        };
+        let err = get_observations(State(state.clone()), caller, Query(bad))
+            .await
+            .unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn listing_requests_are_checked_and_limits_clamped() {
+        let state = test_state().await;
+        for (id, ts) in [
+            ("a", "2026-01-01T09:00:00Z"),
+            ("b", "2026-01-02T09:00:00Z"),
+            ("c", "2026-01-03T09:00:00Z"),
+        ] {
+            insert_obs(&state, id, ts).await;
+        }
+        sqlx::query(
+            "INSERT INTO observations (id, user_id, timestamp, skill_path, correct, \
+             encrypted_data, iv, created_at) \
+             VALUES ('theirs', 'u2', '2026-01-04T09:00:00Z', 'bidding', 1, '', '', '')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        let caller = AuthUser {
+            user_id: "u1".to_string(),
+            role: "student".to_string(),
+        };
+        let metadata = |query: ObservationQuery| {
+            get_observations_metadata(State(state.clone()), caller.clone(), Query(query))
+        };
+        let listing = |query: ObservationQuery| {
+            get_observations(State(state.clone()), caller.clone(), Query(query))
+        };
+
+        // A cursor can't be combined with an offset, and a token that isn't
+        // one of ours is refused rather than read as "from the start".
+        let cursor = ObservationCursor::after("2026-01-03T09:00:00Z", "c").encode();
+        let both = || ObservationQuery {
+            cursor: Some(cursor.clone()),
+            offset: Some(1),
+            ..ObservationQuery::default()
+        };
+        assert_eq!(
+            listing(both()).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            metadata(both()).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        let not_json = ObservationQuery {
+            cursor: Some(URL_SAFE_NO_PAD.encode("{\"id\":\"c\"}")),
+            ..ObservationQuery::default()
+        };
+        assert_eq!(
+            metadata(not_json).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+
+        // Another student's rows need a grant; no user_id means the caller's.
+        let theirs = || ObservationQuery {
+            user_id: Some("u2".to_string()),
+            ..ObservationQuery::default()
+        };
+        assert_eq!(
+            listing(theirs()).await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            metadata(theirs()).await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        let Json(own) = metadata(ObservationQuery::default()).await.unwrap();
+        assert_eq!(own.total, 3);
+        assert!(own.next_cursor.is_none());
+
+        // A page exactly as long as what's left has no next cursor.
+        let Json(page) = metadata(ObservationQuery {
+            cursor: Some(cursor.clone()),
+            limit: Some(2),
+            ..ObservationQuery::default()
+        })
+        .await
+        .unwrap();
+        let ids: Vec<_> = page
+            .observations
+            .iter()
+            .map(|o| o.observation_id.as_str())
+            .collect();
+        assert_eq!(ids, ["b", "a"]);
+        assert!(page.next_cursor.is_none());
+
+        // Pages hold at least one row, so a zero or negative limit still
+        // leaves a cursor to carry on from.
+        let Json(page) = metadata(ObservationQuery {
+            limit: Some(0),
+            ..ObservationQuery::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(page.observations.len(), 1);
+        assert!(page.next_cursor.is_some());
+        for limit in [0, -5] {
+            let Json(page) = listing(ObservationQuery {
+                limit: Some(limit),
+                ..ObservationQuery::default()
+            })
+            .await
+            .unwrap();
+            assert_eq!(page.limit, 1);
+            assert_eq!(page.observations.len(), 1);
+            assert!(page.next_cursor.is_some());
+            assert_eq!(page.total, 3);
+        }
+    }
+
+    fn submission(id: &str, user_id: &str, timestamp: &str) -> serde_json::Value {
+        serde_json::json!({
+            "encrypted_data": "ciphertext",
+            "iv": "iv",
+            "metadata": {
+                "observation_id": id,
+                "user_id": user_id,
+                "timestamp": timestamp,
+                "skill_path": "bidding/stayman",
+                "correct": true,
+                "deal_subfolder": "Stayman",
+                "deal_number": 1,
+                "board_result": "correct"
+            }
+        })
+    }
+
+    async fn submit(
+        state: &AppState,
+        observations: Vec<serde_json::Value>,
+        all_or_nothing: bool,
+    ) -> SubmitObservationsResponse {
+        let req = serde_json::from_value(serde_json::json!({
+            "observations": observations,
+            "all_or_nothing": all_or_nothing,
+        }))
+        .unwrap();
+        let caller = AuthUser {
+            user_id: "u1".to_string(),
+            role: "student".to_string(),
+        };
+        let Json(resp) = submit_observations(State(state.clone()), caller, Json(req))
+            .await
+            .unwrap();
+        resp
+    }
+
+    fn outcomes(resp: &SubmitObservationsResponse) -> Vec<(&str, SubmitOutcome, Option<RejectCode>)> {
+        resp.results
+            .iter()
+            .map(|r| (r.observation_id.as_str(), r.outcome, r.code))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn batches_report_each_observation_and_honour_all_or_nothing() {
+        let state = test_state().await;
+        insert_obs(&state, "taken", "2026-01-01T09:00:00Z").await;
+        sqlx::query("UPDATE observations SET user_id = 'u2' WHERE id = 'taken'")
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let ts = "2026-01-05T09:00:00Z";
+        let mut bad_time = submission("bad-time", "u1", "yesterday");
+        bad_time["metadata"]["deal_number"] = serde_json::json!(null);
+
+        let resp = submit(
+            &state,
+            vec![
+                submission("a", "u1", ts),
+                bad_time.clone(),
+                submission("someone-else", "u2", ts),
+                submission("taken", "u1", ts),
+                submission("a", "u1", ts),
+            ],
+            false,
+        )
+        .await;
+        use RejectCode::*;
+        assert_eq!(
+            outcomes(&resp),
+            vec![
+                ("a", SubmitOutcome::Stored, None),
+                ("bad-time", SubmitOutcome::Rejected, Some(InvalidMetadata)),
+                ("someone-else", SubmitOutcome::Rejected, Some(UserMismatch)),
+                ("taken", SubmitOutcome::Rejected, Some(IdConflict)),
+                ("a", SubmitOutcome::Rejected, Some(DuplicateInBatch)),
+            ]
+        );
+        assert_eq!((resp.stored, resp.errors.len(), resp.committed), (1, 4, true));
+
+        // All-or-nothing: one bad row keeps the good ones out too.
+        let resp = submit(
+            &state,
+            vec![submission("a", "u1", ts), submission("b", "u1", ts), bad_time],
+            true,
+        )
+        .await;
+        assert_eq!(
+            outcomes(&resp),
+            vec![
+                ("a", SubmitOutcome::Rejected, Some(BatchRejected)),
+                ("b", SubmitOutcome::Rejected, Some(BatchRejected)),
+                ("bad-time", SubmitOutcome::Rejected, Some(InvalidMetadata)),
+            ]
+        );
+        assert!(!resp.committed);
+        // ...including a conflict only the insert discovers.
+        let resp = submit(
+            &state,
+            vec![submission("b", "u1", ts), submission("taken", "u1", ts)],
+            true,
+        )
+        .await;
+        assert_eq!(
+            outcomes(&resp),
+            vec![
+                ("b", SubmitOutcome::Rejected, Some(BatchRejected)),
+                ("taken", SubmitOutcome::Rejected, Some(IdConflict)),
+            ]
+        );
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM observations WHERE user_id = 'u1'")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(stored, 1);
+
+        let resp = submit(&state, vec![submission("a", "u1", ts), submission("b", "u1", ts)], true).await;
+        assert_eq!(
+            outcomes(&resp),
+            vec![("a", SubmitOutcome::Updated, None), ("b", SubmitOutcome::Stored, None)]
+        );
+        assert!(resp.committed);
+    }
+
+    #[tokio::test]
+    async fn submissions_declaring_an_unknown_schema_version_are_rejected() {
+        let state = test_state().await;
+        let ts = "2026-01-05T09:00:00Z";
+        let versioned = |id: &str, version: i64| {
+            let mut obs = submission(id, "u1", ts);
+            obs["metadata"]["schema_version"] = serde_json::json!(version);
+            obs
+        };
+
+        let resp = submit(
+            &state,
+            vec![
+                versioned("v0", 0),
+                versioned("v1", 1),
+                versioned("current", CURRENT_SCHEMA_VERSION),
+                versioned("future", CURRENT_SCHEMA_VERSION + 1),
+            ],
+            false,
+        )
+        .await;
+        use RejectCode::*;
+        assert_eq!(
+            outcomes(&resp),
+            vec![
+                ("v0", SubmitOutcome::Rejected, Some(InvalidMetadata)),
+                ("v1", SubmitOutcome::Stored, None),
+                ("current", SubmitOutcome::Stored, None),
+                ("future", SubmitOutcome::Rejected, Some(InvalidMetadata)),
+            ]
+        );
+        assert!(resp.errors.iter().any(|e| e.contains("schema_version 3")));
+
+        let stored: Vec<(String, Option<i64>)> =
+            sqlx::query_as("SELECT id, schema_version FROM observations ORDER BY id")
+                .fetch_all(&state.db)
+                .await
+                .unwrap();
+        assert_eq!(
+            stored,
+            [
+                ("current".to_string(), Some(CURRENT_SCHEMA_VERSION)),
+                ("v1".to_string(), Some(1)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn moving_an_observation_replays_both_boards() {
+        let state = test_state().await;
+        let ts = "2026-01-05T09:00:00Z";
+        submit(&state, vec![submission("a", "u1", ts)], false).await;
+        sqlx::query("DELETE FROM recompute_jobs")
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        let mut moved = submission("a", "u1", ts);
+        moved["metadata"]["deal_number"] = serde_json::json!(2);
+        let resp = submit(&state, vec![moved], false).await;
+        assert_eq!(outcomes(&resp), vec![("a", SubmitOutcome::Updated, None)]);
+        let deal_number: i32 =
+            sqlx::query_scalar("SELECT deal_number FROM observations WHERE id = 'a'")
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert_eq!(deal_number, 2);
+
+        // The board it left loses the play; the one it joined gains it.
+        let boards: Vec<(String, bool)> = sqlx::query_as(
+            "SELECT target, full_replay FROM recompute_jobs WHERE kind = 'board' ORDER BY target",
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(
+            boards,
+            [("Stayman/1".to_string(), true), ("Stayman/2".to_string(), true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn resubmission_keeps_the_aad_columns_with_the_ciphertext() {
+        use crate::obs_crypto::{self, ObservationAad, CIPHER_FORMAT_AAD};
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+        let state = test_state().await;
+        let key = BASE64.encode([7u8; 32]);
+        let sealed = |deal_number: i32| {
+            let aad = ObservationAad {
+                observation_id: "a",
+                user_id: "u1",
+                deal_subfolder: Some("Stayman"),
+                deal_number: Some(deal_number),
+                correct: true,
+            }
+            .for_format(CIPHER_FORMAT_AAD)
+            .unwrap();
+            let (encrypted_data, iv) =
+                obs_crypto::encrypt_observation_with_aad(b"{}", &key, &aad).unwrap();
+            let mut obs = submission("a", "u1", "2026-01-05T09:00:00Z");
+            obs["encrypted_data"] = serde_json::json!(encrypted_data);
+            obs["iv"] = serde_json::json!(iv);
+            obs["metadata"]["deal_number"] = serde_json::json!(deal_number);
+            obs["metadata"]["cipher_format"] = serde_json::json!(CIPHER_FORMAT_AAD);
+            obs
+        };
+        submit(&state, vec![sealed(1)], false).await;
+        // The client moved the observation to another board and re-sealed it.
+        let resp = submit(&state, vec![sealed(2)], false).await;
+        assert_eq!(outcomes(&resp), vec![("a", SubmitOutcome::Updated, None)]);
+
+        let row: (String, String, Option<String>, Option<i32>, bool, i64) = sqlx::query_as(
+            "SELECT encrypted_data, iv, deal_subfolder, deal_number, correct, cipher_format \
+             FROM observations WHERE id = 'a'",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(row.3, Some(2));
+        let aad = ObservationAad {
+            observation_id: "a",
+            user_id: "u1",
+            deal_subfolder: row.2.as_deref(),
+            deal_number: row.3,
+            correct: row.4,
+        }
+        .for_format(row.5)
+        .unwrap();
+        assert!(obs_crypto::decrypt_observation_with_aad(&row.0, &row.1, &key, &aad).is_ok());
+    }
 }
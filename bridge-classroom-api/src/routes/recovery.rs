@@ -1,19 +1,22 @@
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Mutex;
 use std::time::Instant;
 
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderMap, StatusCode},
     Json,
 };
-use ring::aead::{self, LessSafeKey, UnboundKey, AES_256_GCM, Nonce, NONCE_LEN};
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use reqwest::Client;
 
+use crate::email::templates;
+use crate::recovery_keys::RecoveryKeyring;
 use crate::AppState;
 
 /// Rate limiting for code claims: max attempts per email within a window
@@ -43,7 +46,7 @@ static REQUEST_IP_LIMITER: std::sync::LazyLock<Mutex<HashMap<String, (Instant, u
 /// Record one hit for `key` and report whether it's within `max` per
 /// `window_secs`. Expired windows are dropped. Tolerates a poisoned mutex
 /// instead of panicking (§C12).
-fn rate_limit_allow(
+pub fn rate_limit_allow(
     limiter: &Mutex<HashMap<String, (Instant, u32)>>,
     key: &str,
     max: u32,
@@ -63,20 +66,69 @@ fn rate_limit_allow(
     true
 }
 
-/// Best-effort client IP. The API sits behind the Cloudflare Tunnel, so the
-/// socket peer is always localhost — the real caller is in `CF-Connecting-IP`
-/// (or the first `X-Forwarded-For` hop). Falls back to a constant so a missing
-/// header degrades to per-email-only limiting rather than bypassing it.
-fn client_ip(headers: &HeaderMap) -> String {
-    headers
-        .get("cf-connecting-ip")
-        .or_else(|| headers.get("x-forwarded-for"))
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
-        .filter(|s| !s.is_empty())
+/// Whether `key` has used up its `max` hits in the current window, without
+/// recording one. Lets a caller count only some outcomes with
+/// [`rate_limit_allow`] (e.g. failed lookups) but refuse everything once over.
+pub fn rate_limit_exhausted(
+    limiter: &Mutex<HashMap<String, (Instant, u32)>>,
+    key: &str,
+    max: u32,
+    window_secs: u64,
+) -> bool {
+    let map = match limiter.lock() {
+        Ok(m) => m,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    map.get(key).is_some_and(|(start, count)| {
+        start.elapsed().as_secs() < window_secs && *count >= max
+    })
+}
+
+/// Best-effort client IP. Behind the Cloudflare Tunnel the socket peer is
+/// always localhost and the real caller is in `CF-Connecting-IP` (or the
+/// first `X-Forwarded-For` hop), but any client can send those headers, so
+/// they are only read when `TRUST_PROXY_HEADERS` says a proxy in front sets
+/// them. Otherwise the peer address is used. Falls back to a constant so a
+/// missing address degrades to per-email-only limiting rather than
+/// bypassing it.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trust_proxy_headers: bool) -> String {
+    let forwarded = || {
+        headers
+            .get("cf-connecting-ip")
+            .or_else(|| headers.get("x-forwarded-for"))
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    trust_proxy_headers
+        .then(forwarded)
+        .flatten()
+        .or_else(|| peer.map(|ip| ip.to_string()))
         .unwrap_or_else(|| "unknown".to_string())
 }
 
+/// The caller's address as [`client_ip`] reads it, for handlers that rate
+/// limit by IP. The peer comes from the server's `ConnectInfo`.
+#[derive(Debug, Clone)]
+pub struct ClientIp(pub String);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        Ok(ClientIp(client_ip(
+            &parts.headers,
+            peer,
+            state.config.trust_proxy_headers,
+        )))
+    }
+}
+
 /// Request to initiate account recovery
 #[derive(Debug, Deserialize)]
 pub struct RecoveryRequest {
@@ -113,11 +165,17 @@ pub struct RecoveredUserData {
     pub first_name: String,
     pub last_name: String,
     pub email: String,
-    pub secret_key: String,
+    /// None for an account registered before recovery existed: there is no
+    /// key on file, so the device keeps its own and enrolls it on its next
+    /// (now authenticated) sync through `POST /api/users`.
+    pub secret_key: Option<String>,
     pub classroom: Option<String>,
     pub role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub viewer_private_key: Option<String>,
+    /// A completed recovery claim is proof of the account; the client
+    /// gets a session alongside the recovered key (see session.rs).
+    pub session_token: String,
 }
 
 /// Response for recovery claim
@@ -131,84 +189,30 @@ pub struct RecoveryClaimResponse {
 }
 
 /// Encrypt the user's secret key with the server's RECOVERY_SECRET
-pub fn encrypt_for_recovery(secret_key: &str, recovery_secret: &str) -> Result<String, String> {
-    // Derive a 256-bit key from recovery_secret using SHA-256
-    let mut hasher = Sha256::new();
-    hasher.update(recovery_secret.as_bytes());
-    let key_bytes = hasher.finalize();
-
-    // Create AES-256-GCM key
-    let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
-        .map_err(|e| format!("Failed to create key: {:?}", e))?;
-    let key = LessSafeKey::new(unbound_key);
-
-    // Generate random nonce
-    let rng = SystemRandom::new();
-    let mut nonce_bytes = [0u8; NONCE_LEN];
-    rng.fill(&mut nonce_bytes)
-        .map_err(|_| "Failed to generate nonce")?;
-    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
-
-    // Encrypt the secret key
-    let mut in_out = secret_key.as_bytes().to_vec();
-    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out)
-        .map_err(|e| format!("Encryption failed: {:?}", e))?;
-
-    // Combine nonce and ciphertext: base64(nonce:ciphertext)
-    let mut combined = nonce_bytes.to_vec();
-    combined.extend_from_slice(&in_out);
-
-    Ok(BASE64.encode(&combined))
+/// (the keyring's current secret; see recovery_keys.rs)
+pub fn encrypt_for_recovery(secret_key: &str, recovery_secret: &RecoveryKeyring) -> Result<String, String> {
+    recovery_secret.encrypt(secret_key)
 }
 
-/// Decrypt the user's secret key using the server's RECOVERY_SECRET
-pub fn decrypt_for_recovery(encrypted: &str, recovery_secret: &str) -> Result<String, String> {
-    // Decode from base64
-    let combined = BASE64.decode(encrypted)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
-    if combined.len() < NONCE_LEN {
-        return Err("Invalid encrypted data".to_string());
-    }
-
-    // Split nonce and ciphertext
-    let (nonce_bytes, ciphertext) = combined.split_at(NONCE_LEN);
-    let nonce_array: [u8; NONCE_LEN] = nonce_bytes.try_into()
-        .map_err(|_| "Invalid nonce length")?;
-    let nonce = Nonce::assume_unique_for_key(nonce_array);
-
-    // Derive key from recovery_secret
-    let mut hasher = Sha256::new();
-    hasher.update(recovery_secret.as_bytes());
-    let key_bytes = hasher.finalize();
-
-    // Create AES-256-GCM key
-    let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
-        .map_err(|e| format!("Failed to create key: {:?}", e))?;
-    let key = LessSafeKey::new(unbound_key);
-
-    // Decrypt
-    let mut in_out = ciphertext.to_vec();
-    let decrypted = key.open_in_place(nonce, aead::Aad::empty(), &mut in_out)
-        .map_err(|e| format!("Decryption failed: {:?}", e))?;
-
-    String::from_utf8(decrypted.to_vec())
-        .map_err(|e| format!("Invalid UTF-8: {}", e))
+/// Decrypt the user's secret key with whichever configured recovery secret
+/// sealed it
+pub fn decrypt_for_recovery(encrypted: &str, recovery_secret: &RecoveryKeyring) -> Result<String, String> {
+    recovery_secret.decrypt(encrypted)
 }
 
 /// Generate a secure random recovery token
-fn generate_recovery_token() -> String {
+pub fn generate_recovery_token() -> String {
     let rng = SystemRandom::new();
     let mut token_bytes = [0u8; 32];
     rng.fill(&mut token_bytes).expect("Failed to generate random token");
-    BASE64.encode(&token_bytes)
+    BASE64.encode(token_bytes)
         .replace('+', "-")
         .replace('/', "_")
         .replace('=', "")
 }
 
 /// Hash a token for storage
-fn hash_token(token: &str) -> String {
+pub fn hash_token(token: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(token.as_bytes());
     hex::encode(hasher.finalize())
@@ -223,93 +227,25 @@ fn generate_recovery_code() -> String {
     format!("{:06}", num)
 }
 
-/// Resend API request body
-#[derive(Debug, Serialize)]
-struct ResendEmailRequest {
-    from: String,
-    to: Vec<String>,
-    subject: String,
-    html: String,
-}
-
-/// Send recovery email via Resend API
-async fn send_recovery_email(
-    api_key: &str,
-    from_email: &str,
-    to_email: &str,
-    first_name: &str,
-    recovery_url: &str,
-    recovery_code: &str,
-) -> Result<(), String> {
-    let client = Client::new();
-
-    let html_body = format!(
-        r#"<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="utf-8">
-    <style>
-        body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .button {{ display: inline-block; background-color: #2563eb; color: white; padding: 12px 24px; text-decoration: none; border-radius: 6px; margin: 20px 0; }}
-        .button:hover {{ background-color: #1d4ed8; }}
-        .footer {{ margin-top: 30px; padding-top: 20px; border-top: 1px solid #eee; font-size: 14px; color: #666; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <h2>Account Recovery</h2>
-        <p>Hi {first_name},</p>
-        <p>You requested to recover your Bridge Classroom account. Click the button below to restore your practice history:</p>
-        <a href="{recovery_url}" class="button" style="display: inline-block; background-color: #2563eb; color: #ffffff !important; padding: 12px 24px; text-decoration: none; border-radius: 6px; margin: 20px 0;">Restore My Account</a>
-        <div style="text-align: center; margin: 24px 0; padding: 20px; background: #f0f4ff; border-radius: 8px;">
-            <p style="margin: 0 0 8px 0; font-size: 14px; color: #666;">Or enter this code in the app:</p>
-            <p style="margin: 0; font-size: 36px; font-weight: bold; letter-spacing: 8px; color: #1a237e; font-family: monospace;">{recovery_code}</p>
-        </div>
-        <p><strong>This link and code expire in 24 hours.</strong></p>
-        <div class="footer">
-            <p>If you didn't request this, you can safely ignore this email.</p>
-            <p>— Bridge Classroom</p>
-        </div>
-    </div>
-</body>
-</html>"#,
-        first_name = first_name,
-        recovery_url = recovery_url,
-        recovery_code = recovery_code,
-    );
-
-    let email_request = ResendEmailRequest {
-        from: from_email.to_string(),
-        to: vec![to_email.to_string()],
-        subject: "Restore your Bridge Classroom account".to_string(),
-        html: html_body,
-    };
-
-    let response = client
-        .post("https://api.resend.com/emails")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&email_request)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send email request: {}", e))?;
-
-    if response.status().is_success() {
-        tracing::info!("Recovery email sent to {}", to_email);
-        Ok(())
-    } else {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        tracing::error!("Resend API error: {} - {}", status, error_text);
-        Err(format!("Email service error: {} - {}", status, error_text))
-    }
+/// Base URL for links in emails — use the Origin header if it's a known
+/// allowed origin, so users on .org get .org links and users on .com get
+/// .com links.
+pub fn frontend_base_url(state: &AppState, headers: &HeaderMap) -> String {
+    let frontend_url = std::env::var("FRONTEND_URL")
+        .unwrap_or_else(|_| "https://bridge-classroom.com/solo-practice-app".to_string());
+    headers
+        .get("origin")
+        .and_then(|v| v.to_str().ok())
+        .filter(|origin| state.config.allowed_origins.iter().any(|o| o == origin))
+        .map(|origin| format!("{}/solo-practice-app", origin))
+        .unwrap_or(frontend_url)
 }
 
 /// POST /api/recovery/request
 /// Request account recovery - creates token and logs recovery link (no email yet)
 pub async fn request_recovery(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     headers: HeaderMap,
     Json(req): Json<RecoveryRequest>,
 ) -> Result<Json<RecoveryRequestResponse>, (StatusCode, String)> {
@@ -340,13 +276,11 @@ pub async fn request_recovery(
         }
     };
 
-    // Check if recovery key is available
+    // An account with no key on file still gets the email: the claim proves
+    // the address, which is the only way such an account can open a session
+    // (see auth::verify_user_secret).
     if recovery_key.is_none() {
-        return Ok(Json(RecoveryRequestResponse {
-            success: false,
-            message: "This account was created before recovery was available. Please contact your teacher.".to_string(),
-            user_id: None,
-        }));
+        tracing::info!("Recovery requested for {}, which has no key on file", user_id);
     }
 
     // §S4: throttle here — only once we know a recovery email will actually be
@@ -355,7 +289,6 @@ pub async fn request_recovery(
     // while a teacher onboarding many new students from one IP is never blocked.
     // Per-email stops hammering one address; per-IP stops one host bombing many.
     let email_key = req.email.trim().to_lowercase();
-    let ip = client_ip(&headers);
     if !rate_limit_allow(&REQUEST_EMAIL_LIMITER, &email_key, REQUEST_MAX_PER_EMAIL, REQUEST_EMAIL_WINDOW_SECS)
         || !rate_limit_allow(&REQUEST_IP_LIMITER, &ip, REQUEST_MAX_PER_IP, REQUEST_IP_WINDOW_SECS)
     {
@@ -408,33 +341,18 @@ pub async fn request_recovery(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Build recovery URL — use the Origin header if it's a known allowed origin,
-    // so users on .org get .org links and users on .com get .com links.
-    let frontend_url = std::env::var("FRONTEND_URL")
-        .unwrap_or_else(|_| "https://bridge-classroom.com/solo-practice-app".to_string());
-    let base_url = headers
-        .get("origin")
-        .and_then(|v| v.to_str().ok())
-        .filter(|origin| state.config.allowed_origins.iter().any(|o| o == origin))
-        .map(|origin| format!("{}/solo-practice-app", origin))
-        .unwrap_or(frontend_url);
+    let base_url = frontend_base_url(&state, &headers);
     let recovery_url = format!("{}?recover={}&user_id={}", base_url, token, user_id);
     // SECURITY (§S4): the recovery URL embeds the one-time token — never log it
     // on the success path. (The email-delivery-failed break-glass block below
     // still prints it as a deliberate operational escape hatch when email is
     // down; that path is rare and is the only manual recovery channel.)
 
-    // Send recovery email via Resend if API key is configured
+    // Send the recovery email if a transport is configured
     let mut email_sent = false;
-    if let Some(ref api_key) = state.config.resend_api_key {
-        match send_recovery_email(
-            api_key,
-            &state.config.from_email,
-            &req.email,
-            &first_name,
-            &recovery_url,
-            &recovery_code,
-        ).await {
+    if let Some(ref mailer) = state.config.mailer {
+        let email = templates::recovery(&req.email, &first_name, &recovery_url, &recovery_code);
+        match mailer.send(&email).await {
             Ok(_) => {
                 tracing::info!("Recovery email sent to {} for user {}", req.email, first_name);
                 email_sent = true;
@@ -454,8 +372,8 @@ pub async fn request_recovery(
             }
         }
     } else {
-        // Fallback to logging if Resend not configured
-        tracing::warn!("RESEND_API_KEY not configured - logging recovery link instead");
+        // Fallback to logging if no email transport is configured
+        tracing::warn!("No email transport configured - logging recovery link instead");
         println!("\n{}", "=".repeat(70));
         println!("RECOVERY LINK (email not configured)");
         println!("{}", "=".repeat(70));
@@ -484,6 +402,22 @@ pub async fn request_recovery(
     }))
 }
 
+/// The account's secret key from its recovery copy, or None when it has no
+/// copy on file.
+fn recovered_secret_key(
+    encrypted: Option<&str>,
+    recovery_secret: &RecoveryKeyring,
+) -> Result<Option<String>, (StatusCode, String)> {
+    encrypted
+        .map(|encrypted| {
+            decrypt_for_recovery(encrypted, recovery_secret).map_err(|e| {
+                tracing::error!("Failed to decrypt recovery key: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to decrypt recovery key".to_string())
+            })
+        })
+        .transpose()
+}
+
 /// POST /api/recovery/claim
 /// Claim account recovery - verify token and return decrypted secret key
 pub async fn claim_recovery(
@@ -549,6 +483,8 @@ pub async fn claim_recovery(
             error: Some("This recovery link has expired or was already used. Please request a new recovery email.".to_string()),
         }));
     }
+    // The token came from the account's inbox.
+    crate::policy::mark_email_verified(&state.db, &user_id).await?;
 
     // Get user data with recovery key
     let user = sqlx::query_as::<_, (String, String, String, String, Option<String>, Option<String>, String)>(
@@ -561,26 +497,11 @@ pub async fn claim_recovery(
 
     let (id, first_name, last_name, email, classroom, recovery_encrypted_key, role) = user;
 
-    let encrypted_key = match recovery_encrypted_key {
-        Some(k) => k,
-        None => {
-            return Ok(Json(RecoveryClaimResponse {
-                success: false,
-                user: None,
-                error: Some("No recovery key available for this account".to_string()),
-            }));
-        }
-    };
-
     // Decrypt the secret key
     let recovery_secret = state.config.recovery_secret.as_ref()
         .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, "Recovery not configured".to_string()))?;
 
-    let secret_key = decrypt_for_recovery(&encrypted_key, recovery_secret)
-        .map_err(|e| {
-            tracing::error!("Failed to decrypt recovery key: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to decrypt recovery key".to_string())
-        })?;
+    let secret_key = recovered_secret_key(recovery_encrypted_key.as_deref(), recovery_secret)?;
 
     // If user is teacher/admin, also recover viewer private key
     let viewer_private_key = if role == "teacher" || role == "admin" {
@@ -613,12 +534,18 @@ pub async fn claim_recovery(
 
     tracing::info!("========== Recovery claim SUCCESS ==========");
     tracing::info!("Account recovered for user: {} {} ({})", first_name, last_name, email);
-    tracing::info!("Secret key length: {} chars", secret_key.len());
+    tracing::info!("Key on file: {}", secret_key.is_some());
     tracing::info!("Returning user data with id: {}", id);
 
     Ok(Json(RecoveryClaimResponse {
         success: true,
         user: Some(RecoveredUserData {
+            session_token: crate::session::issue(
+                &state.config,
+                &id,
+                &crate::policy::resolve_role(&state.db, &id, &role).await,
+            )
+            .0,
             id,
             first_name,
             last_name,
@@ -752,6 +679,8 @@ pub async fn claim_by_code(
             error: Some("This recovery link has expired or was already used. Please request a new recovery email.".to_string()),
         }));
     }
+    // The token came from the account's inbox.
+    crate::policy::mark_email_verified(&state.db, &user_id).await?;
 
     // Get user data with recovery key
     let user = sqlx::query_as::<_, (String, String, String, String, Option<String>, Option<String>, String)>(
@@ -764,26 +693,11 @@ pub async fn claim_by_code(
 
     let (id, first_name, last_name, user_email, classroom, recovery_encrypted_key, role) = user;
 
-    let encrypted_key = match recovery_encrypted_key {
-        Some(k) => k,
-        None => {
-            return Ok(Json(RecoveryClaimResponse {
-                success: false,
-                user: None,
-                error: Some("No recovery key available for this account".to_string()),
-            }));
-        }
-    };
-
     // Decrypt the secret key
     let recovery_secret = state.config.recovery_secret.as_ref()
         .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, "Recovery not configured".to_string()))?;
 
-    let secret_key = decrypt_for_recovery(&encrypted_key, recovery_secret)
-        .map_err(|e| {
-            tracing::error!("Failed to decrypt recovery key: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to decrypt recovery key".to_string())
-        })?;
+    let secret_key = recovered_secret_key(recovery_encrypted_key.as_deref(), recovery_secret)?;
 
     // Clear rate limit on success
     {
@@ -817,6 +731,12 @@ pub async fn claim_by_code(
     Ok(Json(RecoveryClaimResponse {
         success: true,
         user: Some(RecoveredUserData {
+            session_token: crate::session::issue(
+                &state.config,
+                &id,
+                &crate::policy::resolve_role(&state.db, &id, &role).await,
+            )
+            .0,
             id,
             first_name,
             last_name,
@@ -830,14 +750,78 @@ pub async fn claim_by_code(
     }))
 }
 
+/// Spend the 6-digit code from a recovery email as proof that `user_id`
+/// reads mail at its address, for actions a leaked key alone must not
+/// unlock (see key_rotation.rs). Shares claim-by-code's attempt limit.
+pub async fn redeem_recovery_code(
+    state: &AppState,
+    user_id: &str,
+    code: &str,
+) -> Result<(), (StatusCode, String)> {
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let email = email.trim().to_lowercase();
+    if !rate_limit_allow(&CODE_RATE_LIMITER, &email, MAX_CODE_ATTEMPTS, CODE_RATE_LIMIT_SECS) {
+        tracing::warn!("Rate limit exceeded for recovery code checks: {}", email);
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many attempts. Please wait 15 minutes and try again.".to_string(),
+        ));
+    }
+
+    let marked = sqlx::query(
+        "UPDATE recovery_tokens SET used = 1 \
+         WHERE user_id = ? AND recovery_code_hash = ? AND used = 0 AND expires_at > ?",
+    )
+    .bind(user_id)
+    .bind(hash_token(code.trim()))
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if marked.rows_affected() == 0 {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Invalid or expired code. Please request a new recovery email.".to_string(),
+        ));
+    }
+    crate::policy::mark_email_verified(&state.db, user_id).await?;
+    if let Ok(mut limiter) = CODE_RATE_LIMITER.lock() {
+        limiter.remove(&email);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+    use crate::email::testing::{mail_dir, sent_emails};
+    use crate::email::EmailTransport;
+    use std::sync::Arc;
+
+    #[test]
+    fn forwarded_addresses_are_only_believed_behind_a_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.7, 10.0.0.1".parse().unwrap());
+        let peer = Some("198.51.100.2".parse().unwrap());
+
+        assert_eq!(client_ip(&headers, peer, false), "198.51.100.2");
+        assert_eq!(client_ip(&headers, peer, true), "203.0.113.7");
+        headers.insert("cf-connecting-ip", "203.0.113.9".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, true), "203.0.113.9");
+        assert_eq!(client_ip(&HeaderMap::new(), peer, true), "198.51.100.2");
+        assert_eq!(client_ip(&headers, None, false), "unknown");
+    }
 
     #[test]
     fn test_encrypt_decrypt_roundtrip() {
         let secret_key = "test-secret-key-base64";
-        let recovery_secret = "server-recovery-secret";
+        let recovery_secret = &RecoveryKeyring::new("server-recovery-secret");
 
         let encrypted = encrypt_for_recovery(secret_key, recovery_secret).unwrap();
         let decrypted = decrypt_for_recovery(&encrypted, recovery_secret).unwrap();
@@ -848,7 +832,7 @@ mod tests {
     #[test]
     fn test_different_nonces() {
         let secret_key = "test-secret-key";
-        let recovery_secret = "server-secret";
+        let recovery_secret = &RecoveryKeyring::new("server-secret");
 
         let encrypted1 = encrypt_for_recovery(secret_key, recovery_secret).unwrap();
         let encrypted2 = encrypt_for_recovery(secret_key, recovery_secret).unwrap();
@@ -864,12 +848,81 @@ mod tests {
     #[test]
     fn test_wrong_recovery_secret() {
         let secret_key = "test-secret-key";
-        let recovery_secret = "correct-secret";
-        let wrong_secret = "wrong-secret";
+        let recovery_secret = &RecoveryKeyring::new("correct-secret");
+        let wrong_secret = &RecoveryKeyring::new("wrong-secret");
 
         let encrypted = encrypt_for_recovery(secret_key, recovery_secret).unwrap();
         let result = decrypt_for_recovery(&encrypted, wrong_secret);
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn recovery_request_emails_the_link_and_code() {
+        let dir = mail_dir();
+        let mut config = Config::for_tests();
+        config.allowed_origins = vec!["https://app.example.com".to_string()];
+        config.mailer = Some(
+            EmailTransport::Dir { path: dir.clone() }
+                .mailer(&config.from_email)
+                .unwrap(),
+        );
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at, \
+             recovery_encrypted_key) VALUES ('u1', 'Ann', 'Lee', 'ann.recovery@example.com', '', '', 'blob')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("origin", "https://app.example.com".parse().unwrap());
+        let Json(response) = request_recovery(
+            State(state.clone()),
+            ClientIp("10.0.0.1".to_string()),
+            headers,
+            Json(RecoveryRequest { email: "ann.recovery@example.com".to_string() }),
+        )
+        .await
+        .unwrap();
+        assert!(response.success);
+        assert!(response.message.starts_with("Recovery link sent"));
+
+        let sent = sent_emails(&dir);
+        assert_eq!(sent.len(), 1);
+        let email = &sent[0];
+        assert!(email.headers.contains("To: ann.recovery@example.com"));
+        assert!(email.headers.contains("Subject: Restore your Bridge Classroom account"));
+        assert!(email.text.starts_with("Hi Ann,"));
+
+        // The link and code in the mail are the ones the claim will accept.
+        let link = email
+            .text
+            .lines()
+            .find(|l| l.starts_with("https://app.example.com/solo-practice-app?recover="))
+            .expect("recovery link");
+        let token = link.split("recover=").nth(1).unwrap().split('&').next().unwrap();
+        let code = email
+            .text
+            .split("Or enter this code in the app: ")
+            .nth(1)
+            .and_then(|rest| rest.lines().next())
+            .expect("recovery code");
+        let (token_hash, code_hash): (String, String) = sqlx::query_as(
+            "SELECT token_hash, recovery_code_hash FROM recovery_tokens WHERE user_id = 'u1'",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(token_hash, hash_token(token));
+        assert_eq!(code_hash, hash_token(code));
+        assert!(email.html.contains(&format!("{}&amp;user_id=u1", token)));
+        assert!(email.html.contains(code));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
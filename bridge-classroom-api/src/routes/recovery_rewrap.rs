@@ -0,0 +1,330 @@
+//! Re-wrapping every recovery blob under the current `RECOVERY_SECRET`.
+//!
+//! After the secret changes (old one moved to `RECOVERY_SECRET_PREVIOUS`),
+//! blobs sealed by the old one still open, but the old secret can't be
+//! dropped until none are left. [`rewrap_recovery_blobs`] walks every column
+//! holding one, in rowid batches, and reseals whatever isn't already a v2
+//! blob under the current secret; [`recovery_key_status`] counts blobs per
+//! key id, so progress can be watched from another request while it runs.
+//!
+//! The walk is idempotent and each row is swapped with a compare-and-set on
+//! its old value, so a rerun picks up where an interrupted one stopped and a
+//! concurrent write (a new registration, a key rotation) is never clobbered.
+
+use std::collections::BTreeMap;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::policy::RequireAdmin;
+use crate::recovery_keys::{BlobKeyId, RecoveryKeyring};
+use crate::AppState;
+
+/// Every `(table, column)` holding a blob sealed under `RECOVERY_SECRET`.
+pub const RECOVERY_COLUMNS: &[(&str, &str)] = &[
+    ("users", "recovery_encrypted_key"),
+    ("viewers", "recovery_encrypted_private_key"),
+    ("key_rotations", "old_key"),
+    ("key_rotations", "new_key"),
+];
+
+/// Rows read per batch.
+const REWRAP_BATCH: i64 = 200;
+
+/// Key id reported for pre-v2 blobs.
+const LEGACY: &str = "legacy";
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryColumnStatus {
+    /// `table.column`
+    pub column: String,
+    pub total: i64,
+    /// Blob count per key id (`legacy` for pre-v2 blobs).
+    pub by_key_id: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryKeyStatus {
+    pub current_key_id: String,
+    /// Every configured key id, current first.
+    pub accepted_key_ids: Vec<String>,
+    pub columns: Vec<RecoveryColumnStatus>,
+    /// Blobs not yet under the current secret.
+    pub remaining: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecoveryColumnRewrap {
+    pub column: String,
+    pub total: i64,
+    pub rewrapped: i64,
+    pub already_current: i64,
+    /// Changed by someone else between read and write; a rerun retries.
+    pub skipped_concurrent: i64,
+    /// Ids of rows no configured secret opens.
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RewrapRecoveryResponse {
+    pub success: bool,
+    pub current_key_id: String,
+    pub columns: Vec<RecoveryColumnRewrap>,
+}
+
+fn keyring(state: &AppState) -> Result<&RecoveryKeyring, (StatusCode, String)> {
+    state.config.recovery_secret.as_ref().ok_or((
+        StatusCode::BAD_REQUEST,
+        "RECOVERY_SECRET not configured".to_string(),
+    ))
+}
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+/// Count the blobs in every recovery column by the key id they name.
+pub async fn recovery_key_status(
+    db: &Pool<Sqlite>,
+    keyring: &RecoveryKeyring,
+) -> Result<RecoveryKeyStatus, sqlx::Error> {
+    let mut columns = Vec::new();
+    let mut remaining = 0;
+    for (table, column) in RECOVERY_COLUMNS {
+        let blobs: Vec<(String,)> = sqlx::query_as(&format!(
+            "SELECT {column} FROM {table} WHERE {column} IS NOT NULL"
+        ))
+        .fetch_all(db)
+        .await?;
+        let mut by_key_id = BTreeMap::new();
+        for (blob,) in &blobs {
+            let id = match BlobKeyId::of(blob) {
+                BlobKeyId::Legacy => LEGACY.to_string(),
+                BlobKeyId::V2(id) => id,
+            };
+            *by_key_id.entry(id).or_insert(0) += 1;
+        }
+        let total = blobs.len() as i64;
+        remaining += total
+            - by_key_id
+                .get(keyring.current_key_id())
+                .copied()
+                .unwrap_or(0);
+        columns.push(RecoveryColumnStatus {
+            column: format!("{table}.{column}"),
+            total,
+            by_key_id,
+        });
+    }
+    Ok(RecoveryKeyStatus {
+        current_key_id: keyring.current_key_id().to_string(),
+        accepted_key_ids: keyring.key_ids().iter().map(|s| s.to_string()).collect(),
+        columns,
+        remaining,
+    })
+}
+
+async fn rewrap_column(
+    db: &Pool<Sqlite>,
+    keyring: &RecoveryKeyring,
+    table: &str,
+    column: &str,
+) -> Result<RecoveryColumnRewrap, sqlx::Error> {
+    let (total,): (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM {table} WHERE {column} IS NOT NULL"
+    ))
+    .fetch_one(db)
+    .await?;
+    let mut report = RecoveryColumnRewrap {
+        column: format!("{table}.{column}"),
+        total,
+        ..Default::default()
+    };
+
+    let mut cursor = 0i64;
+    loop {
+        let batch: Vec<(i64, String, String)> = sqlx::query_as(&format!(
+            "SELECT rowid, CAST(id AS TEXT), {column} FROM {table}
+             WHERE rowid > ? AND {column} IS NOT NULL
+             ORDER BY rowid LIMIT ?"
+        ))
+        .bind(cursor)
+        .bind(REWRAP_BATCH)
+        .fetch_all(db)
+        .await?;
+        let Some(last) = batch.last() else { break };
+        cursor = last.0;
+
+        for (rowid, id, blob) in &batch {
+            if keyring.is_current(blob) {
+                report.already_current += 1;
+                continue;
+            }
+            let resealed = match keyring
+                .decrypt(blob)
+                .and_then(|plaintext| keyring.encrypt(&plaintext))
+            {
+                Ok(resealed) => resealed,
+                Err(e) => {
+                    tracing::warn!("Cannot re-wrap {}.{} for {}: {}", table, column, id, e);
+                    report.failed.push(id.clone());
+                    continue;
+                }
+            };
+            let updated = sqlx::query(&format!(
+                "UPDATE {table} SET {column} = ? WHERE rowid = ? AND {column} = ?"
+            ))
+            .bind(&resealed)
+            .bind(rowid)
+            .bind(blob)
+            .execute(db)
+            .await?
+            .rows_affected();
+            if updated == 1 {
+                report.rewrapped += 1;
+            } else {
+                report.skipped_concurrent += 1;
+            }
+        }
+        tracing::info!(
+            "Re-wrapping {}: {}/{} (failed {})",
+            report.column,
+            report.rewrapped
+                + report.already_current
+                + report.skipped_concurrent
+                + report.failed.len() as i64,
+            report.total,
+            report.failed.len()
+        );
+    }
+    Ok(report)
+}
+
+/// Reseal every recovery blob that isn't already under the current secret.
+/// Shared by the admin endpoint and `bridge-classroom-admin rewrap-recovery`.
+pub async fn rewrap_recovery_blobs(
+    state: &AppState,
+) -> Result<RewrapRecoveryResponse, (StatusCode, String)> {
+    let keyring = keyring(state)?;
+    let mut columns = Vec::new();
+    for (table, column) in RECOVERY_COLUMNS {
+        columns.push(
+            rewrap_column(&state.db, keyring, table, column)
+                .await
+                .map_err(db_err)?,
+        );
+    }
+    Ok(RewrapRecoveryResponse {
+        success: columns
+            .iter()
+            .all(|c| c.failed.is_empty() && c.skipped_concurrent == 0),
+        current_key_id: keyring.current_key_id().to_string(),
+        columns,
+    })
+}
+
+/// GET /api/admin/recovery-keys
+/// Configured recovery key ids and how many blobs each one still seals.
+pub async fn admin_recovery_key_status(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+) -> Result<Json<RecoveryKeyStatus>, (StatusCode, String)> {
+    let keyring = keyring(&state)?;
+    recovery_key_status(&state.db, keyring)
+        .await
+        .map(Json)
+        .map_err(db_err)
+}
+
+/// POST /api/admin/recovery-keys/rewrap
+pub async fn admin_rewrap_recovery(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+) -> Result<Json<RewrapRecoveryResponse>, (StatusCode, String)> {
+    rewrap_recovery_blobs(&state).await.map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn rewrap_moves_every_readable_blob_to_the_current_secret() {
+        let old = RecoveryKeyring::new("old-secret");
+        let ring = RecoveryKeyring::new("new-secret").with_previous(["old-secret"]);
+        let mut config = Config::for_tests();
+        config.recovery_secret = Some(ring.clone());
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+
+        let users = [
+            ("u1", old.encrypt("key-1").unwrap()),
+            ("u2", ring.encrypt("key-2").unwrap()),
+            ("u3", RecoveryKeyring::new("lost").encrypt("key-3").unwrap()),
+        ];
+        for (id, blob) in &users {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at, \
+                 recovery_encrypted_key) VALUES (?, 'Test', 'User', ?, '', '', ?)",
+            )
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(blob)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO viewers (id, name, email, public_key, created_at, \
+             recovery_encrypted_private_key) VALUES ('v1', 'Teacher', 'v1@example.com', 'pk', '', ?)",
+        )
+        .bind(old.encrypt("private-key").unwrap())
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        let before = recovery_key_status(&state.db, &ring).await.unwrap();
+        assert_eq!(before.remaining, 3);
+        assert_eq!(before.columns[0].by_key_id[old.current_key_id()], 1);
+
+        let report = rewrap_recovery_blobs(&state).await.unwrap();
+        assert!(!report.success);
+        assert_eq!(report.columns[0].rewrapped, 1);
+        assert_eq!(report.columns[0].already_current, 1);
+        assert_eq!(report.columns[0].failed, vec!["u3".to_string()]);
+        assert_eq!(report.columns[1].rewrapped, 1);
+
+        // Only the unreadable blob is left, and the old secret is no longer
+        // needed for anything else.
+        let after = recovery_key_status(&state.db, &ring).await.unwrap();
+        assert_eq!(after.remaining, 1);
+        let current_only = RecoveryKeyring::new("new-secret");
+        let (blob,): (String,) =
+            sqlx::query_as("SELECT recovery_encrypted_key FROM users WHERE id = 'u1'")
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert_eq!(current_only.decrypt(&blob).unwrap(), "key-1");
+        let (blob,): (String,) =
+            sqlx::query_as("SELECT recovery_encrypted_private_key FROM viewers WHERE id = 'v1'")
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert_eq!(current_only.decrypt(&blob).unwrap(), "private-key");
+
+        // A rerun has nothing left to do but the lost blob.
+        let rerun = rewrap_recovery_blobs(&state).await.unwrap();
+        assert_eq!(rerun.columns[0].rewrapped, 0);
+        assert_eq!(rerun.columns[0].already_current, 2);
+    }
+}
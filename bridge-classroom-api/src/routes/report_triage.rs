@@ -0,0 +1,503 @@
+//! Curator triage of "Report a Problem" reports.
+//!
+//! Reports name the board they're about the way observations do: the
+//! lesson id is the `deal_subfolder` and the PBN `[Board]` tag the
+//! `deal_number`. [`report_groups`] rolls reports up by lesson and board so
+//! a curator sees which boards draw complaints. A board that turns out to
+//! be broken can be put under review (`boards_under_review`); lesson
+//! mastery then leaves it out (see `LessonCatalog`) until the review is
+//! cleared, and every affected student's summary is recomputed both times.
+//!
+//! Resolution notes and telling the reporter are part of updating a report
+//! (`PATCH /api/admin/reports/:id`, in reports.rs).
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use crate::jobs::{self, Job};
+use crate::policy::RequireAdmin;
+use crate::AppState;
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+/// Query params for GET /api/admin/reports/boards
+#[derive(Debug, Deserialize)]
+pub struct ReportGroupsQuery {
+    /// open (new or filed, the default) | new | filed | resolved | all
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportBoardGroup {
+    /// The PBN [Board] tag reports gave; null when they gave none.
+    pub board_tag: Option<String>,
+    /// `board_tag` as the deal_number observations use, when numeric.
+    pub deal_number: Option<i32>,
+    pub under_review: bool,
+    /// Every matching report, duplicates included.
+    pub reports: i64,
+    /// Of those, how many are new or filed.
+    pub open: i64,
+    /// The original (non-duplicate) reports, newest first.
+    pub report_ids: Vec<i64>,
+    pub latest_note: String,
+    pub last_reported_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportLessonGroup {
+    /// lesson_id, or the scenario when the report had no lesson id.
+    pub lesson_id: Option<String>,
+    pub reports: i64,
+    pub boards: Vec<ReportBoardGroup>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportGroupsResponse {
+    pub success: bool,
+    pub lessons: Vec<ReportLessonGroup>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct GroupRow {
+    lesson: Option<String>,
+    board_tag: Option<String>,
+    reports: i64,
+    open: i64,
+    /// Comma-separated ids of the originals among them.
+    report_ids: Option<String>,
+    latest_note: String,
+    last_reported_at: String,
+}
+
+/// Under-review boards as (deal_subfolder, deal_number).
+async fn under_review_set(db: &Pool<Sqlite>) -> Result<HashSet<(String, i32)>, sqlx::Error> {
+    let rows: Vec<(String, i32)> =
+        sqlx::query_as("SELECT deal_subfolder, deal_number FROM boards_under_review")
+            .fetch_all(db)
+            .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Reports with one of `statuses` grouped by lesson, then board. Lessons
+/// and boards with the most reports come first.
+pub async fn report_groups(
+    db: &Pool<Sqlite>,
+    statuses: &[&str],
+) -> Result<Vec<ReportLessonGroup>, sqlx::Error> {
+    // With a single MAX() in the select, SQLite takes the bare note and
+    // created_at columns from the newest report in each group.
+    let mut qb = QueryBuilder::new(
+        r#"
+        SELECT COALESCE(lesson_id, scenario) AS lesson, board_tag,
+               COUNT(*) AS reports,
+               SUM(status != 'resolved') AS open,
+               GROUP_CONCAT(CASE WHEN duplicate_of IS NULL THEN id END) AS report_ids,
+               MAX(id) AS latest_id,
+               note AS latest_note,
+               created_at AS last_reported_at
+        FROM problem_reports
+        WHERE status IN ("#,
+    );
+    let mut list = qb.separated(", ");
+    for status in statuses {
+        list.push_bind(*status);
+    }
+    qb.push(
+        r#")
+        GROUP BY lesson, board_tag
+        ORDER BY lesson, reports DESC, board_tag
+        "#,
+    );
+    let rows: Vec<GroupRow> = qb.build_query_as().fetch_all(db).await?;
+    let under_review = under_review_set(db).await?;
+
+    let mut lessons: Vec<ReportLessonGroup> = Vec::new();
+    for row in rows {
+        let deal_number = row
+            .board_tag
+            .as_deref()
+            .and_then(|b| b.trim().parse::<i32>().ok());
+        let mut report_ids: Vec<i64> = row
+            .report_ids
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .collect();
+        report_ids.sort_by_key(|&id| Reverse(id));
+        let board = ReportBoardGroup {
+            under_review: match (&row.lesson, deal_number) {
+                (Some(lesson), Some(n)) => under_review.contains(&(lesson.clone(), n)),
+                _ => false,
+            },
+            board_tag: row.board_tag,
+            deal_number,
+            reports: row.reports,
+            open: row.open,
+            report_ids,
+            latest_note: row.latest_note,
+            last_reported_at: row.last_reported_at,
+        };
+        // Rows arrive grouped by lesson.
+        match lessons.last_mut() {
+            Some(lesson) if lesson.lesson_id == row.lesson => {
+                lesson.reports += board.reports;
+                lesson.boards.push(board);
+            }
+            _ => lessons.push(ReportLessonGroup {
+                lesson_id: row.lesson,
+                reports: board.reports,
+                boards: vec![board],
+            }),
+        }
+    }
+    lessons.sort_by_key(|l| Reverse(l.reports));
+    Ok(lessons)
+}
+
+/// GET /api/admin/reports/boards
+/// Problem reports grouped by lesson and board.
+pub async fn admin_report_groups(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+    Query(query): Query<ReportGroupsQuery>,
+) -> Result<Json<ReportGroupsResponse>, (StatusCode, String)> {
+    let statuses: &[&str] = match query.status.as_deref().unwrap_or("open") {
+        "open" => &["new", "filed"],
+        "new" => &["new"],
+        "filed" => &["filed"],
+        "resolved" => &["resolved"],
+        "all" => &["new", "filed", "resolved"],
+        other => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Unknown status '{}' (expected open, new, filed, resolved or all)",
+                    other
+                ),
+            ))
+        }
+    };
+    let lessons = report_groups(&state.db, statuses).await.map_err(db_err)?;
+    Ok(Json(ReportGroupsResponse {
+        success: true,
+        lessons,
+    }))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct BoardUnderReview {
+    pub deal_subfolder: String,
+    pub deal_number: i32,
+    pub reason: Option<String>,
+    /// The report that prompted the review, if any.
+    pub report_id: Option<i64>,
+    pub marked_by: String,
+    pub marked_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoardsUnderReviewResponse {
+    pub success: bool,
+    pub boards: Vec<BoardUnderReview>,
+}
+
+/// GET /api/admin/boards-under-review
+pub async fn admin_boards_under_review(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+) -> Result<Json<BoardsUnderReviewResponse>, (StatusCode, String)> {
+    let boards: Vec<BoardUnderReview> = sqlx::query_as(
+        r#"
+        SELECT deal_subfolder, deal_number, reason, report_id, marked_by, marked_at
+        FROM boards_under_review
+        ORDER BY deal_subfolder, deal_number
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+    Ok(Json(BoardsUnderReviewResponse {
+        success: true,
+        boards,
+    }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MarkUnderReviewRequest {
+    pub reason: Option<String>,
+    pub report_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoardReviewResponse {
+    pub success: bool,
+    pub deal_subfolder: String,
+    pub deal_number: i32,
+    pub under_review: bool,
+    /// Student summaries queued for recompute. Every student with a board
+    /// in the lesson is affected: the lesson's board count changed.
+    pub summaries_queued: usize,
+}
+
+/// Queue a summary recompute for every user with a board in the lesson.
+async fn requeue_lesson_summaries(
+    db: &Pool<Sqlite>,
+    deal_subfolder: &str,
+) -> Result<usize, sqlx::Error> {
+    let users: Vec<(String,)> =
+        sqlx::query_as("SELECT DISTINCT user_id FROM board_status WHERE deal_subfolder = ?")
+            .bind(deal_subfolder)
+            .fetch_all(db)
+            .await?;
+    jobs::enqueue_all(
+        db,
+        users.into_iter().map(|(user_id,)| Job::Summary { user_id }),
+    )
+    .await
+}
+
+/// Put a board under review, or update the reason on one that already is.
+pub async fn mark_board_under_review(
+    db: &Pool<Sqlite>,
+    deal_subfolder: &str,
+    deal_number: i32,
+    req: &MarkUnderReviewRequest,
+    curator_id: &str,
+) -> Result<BoardReviewResponse, (StatusCode, String)> {
+    if let Some(report_id) = req.report_id {
+        let exists: Option<(i64,)> = sqlx::query_as("SELECT id FROM problem_reports WHERE id = ?")
+            .bind(report_id)
+            .fetch_optional(db)
+            .await
+            .map_err(db_err)?;
+        if exists.is_none() {
+            return Err((StatusCode::NOT_FOUND, "Report not found".to_string()));
+        }
+    }
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    sqlx::query(
+        r#"
+        INSERT INTO boards_under_review
+            (deal_subfolder, deal_number, reason, report_id, marked_by, marked_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(deal_subfolder, deal_number) DO UPDATE SET
+            reason = COALESCE(excluded.reason, boards_under_review.reason),
+            report_id = COALESCE(excluded.report_id, boards_under_review.report_id)
+        "#,
+    )
+    .bind(deal_subfolder)
+    .bind(deal_number)
+    .bind(reason)
+    .bind(req.report_id)
+    .bind(curator_id)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(db)
+    .await
+    .map_err(db_err)?;
+    let summaries_queued = requeue_lesson_summaries(db, deal_subfolder)
+        .await
+        .map_err(db_err)?;
+    tracing::info!(
+        "Board {}/{} put under review by {}",
+        deal_subfolder,
+        deal_number,
+        curator_id
+    );
+    Ok(BoardReviewResponse {
+        success: true,
+        deal_subfolder: deal_subfolder.to_string(),
+        deal_number,
+        under_review: true,
+        summaries_queued,
+    })
+}
+
+/// Take a board out of review, so it counts toward mastery again.
+pub async fn clear_board_review(
+    db: &Pool<Sqlite>,
+    deal_subfolder: &str,
+    deal_number: i32,
+) -> Result<BoardReviewResponse, (StatusCode, String)> {
+    let removed =
+        sqlx::query("DELETE FROM boards_under_review WHERE deal_subfolder = ? AND deal_number = ?")
+            .bind(deal_subfolder)
+            .bind(deal_number)
+            .execute(db)
+            .await
+            .map_err(db_err)?
+            .rows_affected();
+    if removed == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Board is not under review".to_string(),
+        ));
+    }
+    let summaries_queued = requeue_lesson_summaries(db, deal_subfolder)
+        .await
+        .map_err(db_err)?;
+    tracing::info!("Board {}/{} review cleared", deal_subfolder, deal_number);
+    Ok(BoardReviewResponse {
+        success: true,
+        deal_subfolder: deal_subfolder.to_string(),
+        deal_number,
+        under_review: false,
+        summaries_queued,
+    })
+}
+
+/// PUT /api/admin/boards-under-review/:deal_subfolder/:deal_number
+pub async fn admin_mark_board_under_review(
+    State(state): State<AppState>,
+    RequireAdmin(curator): RequireAdmin,
+    Path((deal_subfolder, deal_number)): Path<(String, i32)>,
+    Json(req): Json<MarkUnderReviewRequest>,
+) -> Result<Json<BoardReviewResponse>, (StatusCode, String)> {
+    mark_board_under_review(
+        &state.db,
+        &deal_subfolder,
+        deal_number,
+        &req,
+        &curator.user_id,
+    )
+    .await
+    .map(Json)
+}
+
+/// DELETE /api/admin/boards-under-review/:deal_subfolder/:deal_number
+pub async fn admin_clear_board_review(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+    Path((deal_subfolder, deal_number)): Path<(String, i32)>,
+) -> Result<Json<BoardReviewResponse>, (StatusCode, String)> {
+    clear_board_review(&state.db, &deal_subfolder, deal_number)
+        .await
+        .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::routes::{get_lesson_mastery, queue_report, LessonMasteryQuery, ReportRequest};
+    use crate::session::AuthUser;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn report(note: &str, lesson: &str, board: &str) -> ReportRequest {
+        serde_json::from_value(serde_json::json!({
+            "note": note,
+            "lesson_id": lesson,
+            "board_tag": board,
+            "deal_pbn": format!("N:{lesson}-{board}"),
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reports_group_by_board_and_review_leaves_the_board_out_of_mastery() {
+        let db = crate::db::test_pool().await;
+        for (note, lesson, board) in [
+            ("hint wrong", "Stayman", "4"),
+            ("same hint", "Stayman", "4"),
+            ("typo", "Stayman", "9"),
+            ("bad lead", "Jacoby", "2"),
+        ] {
+            queue_report(&db, &report(note, lesson, board), None)
+                .await
+                .unwrap();
+        }
+
+        let groups = report_groups(&db, &["new", "filed"]).await.unwrap();
+        assert_eq!(groups[0].lesson_id.as_deref(), Some("Stayman"));
+        assert_eq!(groups[0].reports, 3);
+        let board4 = &groups[0].boards[0];
+        assert_eq!((board4.deal_number, board4.reports), (Some(4), 2));
+        assert_eq!(board4.report_ids.len(), 1);
+        assert_eq!(board4.latest_note, "same hint");
+        assert!(!board4.under_review);
+
+        // The status filter applies before grouping.
+        sqlx::query("UPDATE problem_reports SET status = 'resolved' WHERE note = 'typo'")
+            .execute(&db)
+            .await
+            .unwrap();
+        let open = report_groups(&db, &["new", "filed"]).await.unwrap();
+        assert_eq!((open[0].reports, open[0].boards.len()), (2, 1));
+        let resolved = report_groups(&db, &["resolved"]).await.unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].boards[0].deal_number, Some(9));
+        assert_eq!(resolved[0].boards[0].open, 0);
+        let all = report_groups(&db, &["new", "filed", "resolved"]).await.unwrap();
+        assert_eq!((all[0].reports, all[0].boards[1].open), (3, 0));
+
+        // Two boards played: board 4 gold, board 9 nothing yet.
+        for (deal_number, max_stars) in [(4, 2), (9, 0)] {
+            sqlx::query(
+                "INSERT INTO board_status (user_id, deal_subfolder, deal_number, max_stars, updated_at) \
+                 VALUES ('u1', 'Stayman', ?, ?, '')",
+            )
+            .bind(deal_number)
+            .bind(max_stars)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let state = AppState {
+            db: db.clone(),
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        let stayman = || async {
+            let caller = AuthUser {
+                user_id: "admin-1".to_string(),
+                role: "admin".to_string(),
+            };
+            let query = LessonMasteryQuery {
+                user_id: "u1".to_string(),
+            };
+            let Json(mastery) = get_lesson_mastery(State(state.clone()), caller, Query(query))
+                .await
+                .unwrap();
+            let lesson = &mastery.lessons[0];
+            (lesson.total_boards, lesson.gold, lesson.boards_under_review)
+        };
+        assert_eq!(stayman().await, (2, 1, 0));
+
+        let req = MarkUnderReviewRequest {
+            reason: Some("Hint contradicts the auction".to_string()),
+            report_id: Some(board4.report_ids[0]),
+        };
+        let marked = mark_board_under_review(&db, "Stayman", 4, &req, "curator-1")
+            .await
+            .unwrap();
+        assert_eq!(marked.summaries_queued, 1);
+        let groups = report_groups(&db, &["new", "filed"]).await.unwrap();
+        assert!(groups[0].boards[0].under_review);
+
+        // The reviewed board no longer counts for the student or the lesson.
+        assert_eq!(stayman().await, (1, 0, 1));
+
+        clear_board_review(&db, "Stayman", 4).await.unwrap();
+        assert!(clear_board_review(&db, "Stayman", 4).await.is_err());
+        assert_eq!(stayman().await, (2, 1, 0));
+    }
+}
@@ -1,13 +1,43 @@
+//! "Report a Problem": learner reports queued locally, then delivered.
+//!
+//! `POST /api/report` only stores the report in `problem_reports`, so a
+//! report is kept even when the sink is unconfigured or unreachable. A
+//! background worker ([`spawn_report_worker`]) hands new reports to the
+//! configured [`ReportSink`] and retries failures with backoff.
+//!
+//! Reports are deduplicated by `deal_pbn` + `scenario`: while a deal has an
+//! open (new or filed) report, later reports of it are stored as duplicates
+//! of that one and aren't delivered again. Curators browse and resolve them
+//! through `/api/admin/reports`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
     Json,
 };
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
 
+use crate::email::templates;
+use crate::policy::RequireAdmin;
+use crate::report_sink::ReportSink;
+use crate::session::AuthUser;
 use crate::AppState;
 
+/// Delivery attempts before a report is parked for a curator to requeue.
+pub const MAX_DELIVERY_ATTEMPTS: i64 = 10;
+
+const DELIVERY_BACKOFF_BASE_SECS: i64 = 30;
+const DELIVERY_BACKOFF_MAX_SECS: i64 = 6 * 3600;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const STATUSES: &[&str] = &["new", "filed", "resolved"];
+
 /// Validate API key from the `x-api-key` header.
 fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
     if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
@@ -20,8 +50,9 @@ fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
 ///
 /// The frontend captures the board state it already has while rendering, plus
 /// the learner's free-text note. Everything is optional except `note` so the
-/// endpoint stays forgiving as the lesson view evolves.
-#[derive(Debug, Deserialize)]
+/// endpoint stays forgiving as the lesson view evolves. Stored as JSON in
+/// `problem_reports.request`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ReportRequest {
     /// The learner's free-text description of the problem.
     pub note: String,
@@ -61,222 +92,1079 @@ pub struct ReportRequest {
 #[derive(Debug, Serialize)]
 pub struct ReportResponse {
     pub success: bool,
-    pub issue_url: String,
-    pub issue_number: i64,
+    pub report_id: i64,
+    /// new | filed — a duplicate reports its original's status.
+    pub status: String,
+    /// The open report of the same deal this one was folded into.
+    pub duplicate_of: Option<i64>,
+    /// Set once the report (or its original) is filed as a GitHub issue.
+    pub issue_url: Option<String>,
+    pub issue_number: Option<i64>,
 }
 
-/// Body for the GitHub "create an issue" call.
-#[derive(Debug, Serialize)]
-struct GitHubIssueRequest {
-    title: String,
-    body: String,
-    labels: Vec<String>,
+/// Hash of the deal and scenario a report is about, or None when the report
+/// carries no deal (those are never deduplicated). Whitespace in the PBN is
+/// collapsed so formatting differences don't split a deal.
+pub fn dedupe_key(req: &ReportRequest) -> Option<String> {
+    let pbn = req
+        .deal_pbn
+        .as_deref()?
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    if pbn.is_empty() {
+        return None;
+    }
+    let scenario = req
+        .scenario
+        .as_deref()
+        .or(req.lesson_id.as_deref())
+        .unwrap_or("")
+        .trim();
+    let mut hasher = Sha256::new();
+    hasher.update(scenario.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(pbn.join(" ").as_bytes());
+    Some(hex::encode(&hasher.finalize()[..16]))
 }
 
-/// The slice of GitHub's create-issue response we care about.
-#[derive(Debug, Deserialize)]
-struct GitHubIssueResponse {
-    html_url: String,
-    number: i64,
-}
+/// Store a report, folding it into an open report of the same deal if there
+/// is one. `reporter_user_id` is the signed-in learner, if any, so they can
+/// be told when it's resolved. Shared by `create_report` and the tests.
+pub async fn queue_report(
+    db: &Pool<Sqlite>,
+    req: &ReportRequest,
+    reporter_user_id: Option<&str>,
+) -> Result<ReportResponse, sqlx::Error> {
+    let key = dedupe_key(req);
+    let now = chrono::Utc::now().to_rfc3339();
+    let request = serde_json::to_string(req).unwrap_or_else(|_| "{}".to_string());
+    let mut tx = db.begin().await?;
+
+    let original: Option<(i64, String, Option<String>, Option<i64>)> = match &key {
+        Some(key) => {
+            sqlx::query_as(
+                r#"
+                SELECT id, status, issue_url, issue_number FROM problem_reports
+                WHERE dedupe_key = ? AND duplicate_of IS NULL AND status IN ('new', 'filed')
+                ORDER BY id LIMIT 1
+                "#,
+            )
+            .bind(key)
+            .fetch_optional(&mut *tx)
+            .await?
+        }
+        None => None,
+    };
+
+    let duplicate_of = original.as_ref().map(|o| o.0);
+    // Only an original is queued for delivery.
+    let next_attempt_at = duplicate_of.is_none().then(|| now.clone());
+    let (report_id,): (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO problem_reports
+            (dedupe_key, duplicate_of, scenario, lesson_id, display_number, board_tag, note,
+             request, reporter_user_id, status, next_attempt_at, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(&key)
+    .bind(duplicate_of)
+    .bind(&req.scenario)
+    .bind(&req.lesson_id)
+    .bind(req.display_number)
+    .bind(&req.board_tag)
+    .bind(&req.note)
+    .bind(&request)
+    .bind(reporter_user_id)
+    .bind(original.as_ref().map(|o| o.1.as_str()).unwrap_or("new"))
+    .bind(&next_attempt_at)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
 
-const FEEDBACK_LABEL: &str = "classroom-feedback";
+    Ok(match original {
+        Some((id, status, issue_url, issue_number)) => ReportResponse {
+            success: true,
+            report_id,
+            status,
+            duplicate_of: Some(id),
+            issue_url,
+            issue_number,
+        },
+        None => ReportResponse {
+            success: true,
+            report_id,
+            status: "new".to_string(),
+            duplicate_of: None,
+            issue_url: None,
+            issue_number: None,
+        },
+    })
+}
 
 /// POST /api/report
 ///
-/// Files a `classroom-feedback` GitHub issue in the content repo on behalf of a
-/// learner. The GitHub token lives only in server config and is never returned
-/// or logged. When no token is configured the endpoint returns 503 so the UI
-/// can show "reporting isn't set up".
+/// Queues a learner's report for delivery to the content curators (a
+/// `classroom-feedback` GitHub issue, an email, or nothing, per
+/// `REPORT_SINK`). Succeeds as soon as the report is stored. A session is
+/// optional; with one, the learner is emailed when the report is resolved.
 pub async fn create_report(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(req): Json<ReportRequest>,
+    caller: Option<AuthUser>,
+    Json(mut req): Json<ReportRequest>,
 ) -> Result<Json<ReportResponse>, (StatusCode, String)> {
     if !validate_api_key(&headers, &state.config.api_key) {
         return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
     }
 
-    let note = req.note.trim();
-    if note.is_empty() {
+    req.note = req.note.trim().to_string();
+    if req.note.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Report note is empty".to_string()));
     }
 
-    let token = match state.config.github_issues_token.as_deref() {
-        Some(t) => t,
-        None => {
-            return Err((
-                StatusCode::SERVICE_UNAVAILABLE,
-                "Reporting is not configured on this server".to_string(),
-            ));
+    let reporter = caller.as_ref().map(|c| c.user_id.as_str());
+    let response = queue_report(&state.db, &req, reporter)
+        .await
+        .map_err(db_err)?;
+    match response.duplicate_of {
+        Some(original) => tracing::info!(
+            "Queued problem report #{} (duplicate of #{})",
+            response.report_id,
+            original
+        ),
+        None => tracing::info!("Queued problem report #{}", response.report_id),
+    }
+    Ok(Json(response))
+}
+
+/// Delay before retrying a report whose delivery has failed `attempts` times.
+fn delivery_backoff(attempts: i64) -> chrono::Duration {
+    let exp = attempts.clamp(1, 16) - 1;
+    chrono::Duration::seconds((DELIVERY_BACKOFF_BASE_SECS << exp).min(DELIVERY_BACKOFF_MAX_SECS))
+}
+
+/// Deliver the oldest due report to `sink`. Returns false when none was due.
+pub async fn deliver_next(db: &Pool<Sqlite>, sink: &dyn ReportSink) -> Result<bool, sqlx::Error> {
+    let now = chrono::Utc::now();
+    let due: Option<(i64, String, i64)> = sqlx::query_as(
+        r#"
+        SELECT id, request, attempts FROM problem_reports
+        WHERE status = 'new' AND duplicate_of IS NULL
+          AND next_attempt_at IS NOT NULL AND next_attempt_at <= ?
+        ORDER BY next_attempt_at, id LIMIT 1
+        "#,
+    )
+    .bind(now.to_rfc3339())
+    .fetch_optional(db)
+    .await?;
+    let Some((id, request, attempts)) = due else {
+        return Ok(false);
+    };
+
+    let result = match serde_json::from_str::<ReportRequest>(&request) {
+        Ok(req) => sink.file(&req).await,
+        Err(e) => Err(format!("Stored report is unreadable: {}", e)),
+    };
+    let attempts = attempts + 1;
+    let updated_at = chrono::Utc::now().to_rfc3339();
+    match result {
+        Ok(filed) => {
+            // A curator may have resolved it while it was in flight; keep
+            // that status, but the issue exists either way.
+            sqlx::query(
+                r#"
+                UPDATE problem_reports
+                SET status = CASE WHEN status = 'new' THEN 'filed' ELSE status END,
+                    sink = ?, issue_url = ?, issue_number = ?,
+                    attempts = ?, next_attempt_at = NULL, last_error = NULL, updated_at = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(sink.name())
+            .bind(&filed.issue_url)
+            .bind(filed.issue_number)
+            .bind(attempts)
+            .bind(&updated_at)
+            .bind(id)
+            .execute(db)
+            .await?;
+            sqlx::query(
+                "UPDATE problem_reports SET status = 'filed', updated_at = ? \
+                 WHERE duplicate_of = ? AND status = 'new'",
+            )
+            .bind(&updated_at)
+            .bind(id)
+            .execute(db)
+            .await?;
+            match &filed.issue_url {
+                Some(url) => {
+                    tracing::info!("Filed problem report #{} via {} ({})", id, sink.name(), url)
+                }
+                None => tracing::info!("Filed problem report #{} via {}", id, sink.name()),
+            }
+        }
+        Err(e) => {
+            let next_attempt_at = (attempts < MAX_DELIVERY_ATTEMPTS)
+                .then(|| (chrono::Utc::now() + delivery_backoff(attempts)).to_rfc3339());
+            if next_attempt_at.is_some() {
+                tracing::warn!(
+                    "Problem report #{} not delivered (attempt {}): {}",
+                    id,
+                    attempts,
+                    e
+                );
+            } else {
+                tracing::error!(
+                    "Problem report #{} not delivered after {} attempts, parked: {}",
+                    id,
+                    attempts,
+                    e
+                );
+            }
+            sqlx::query(
+                "UPDATE problem_reports SET attempts = ?, next_attempt_at = ?, last_error = ?, \
+                 updated_at = ? WHERE id = ?",
+            )
+            .bind(attempts)
+            .bind(&next_attempt_at)
+            .bind(&e)
+            .bind(&updated_at)
+            .bind(id)
+            .execute(db)
+            .await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Start the worker delivering queued reports to the configured sink, for
+/// the life of the process. With no sink, reports just stay queued.
+pub fn spawn_report_worker(state: AppState) {
+    let sink = match state.config.report_sink.sink(&state.config) {
+        Ok(Some(sink)) => sink,
+        Ok(None) => {
+            tracing::info!("No report sink configured; problem reports are only queued");
+            return;
+        }
+        Err(e) => {
+            tracing::error!(
+                "Report sink unavailable, problem reports are only queued: {}",
+                e
+            );
+            return;
         }
     };
-    let repo = &state.config.github_issues_repo;
+    tracing::info!("Delivering problem reports via {}", sink.name());
+    tokio::spawn(async move {
+        loop {
+            match deliver_next(&state.db, sink.as_ref()).await {
+                Ok(true) => {}
+                Ok(false) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(e) => {
+                    tracing::error!("Report worker: queue error: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL * 4).await;
+                }
+            }
+        }
+    });
+}
 
-    let scenario = req
-        .scenario
-        .as_deref()
-        .or(req.lesson_id.as_deref())
-        .unwrap_or("Unknown scenario");
-    let deal_label = req
-        .display_number
-        .map(|n| n.to_string())
-        .or_else(|| req.board_tag.clone())
-        .unwrap_or_else(|| "?".to_string());
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
 
-    let title = format!("Classroom report: {} · Deal {}", scenario, deal_label);
-    let body = build_issue_body(&req, note);
+/// Query params for GET /api/admin/reports
+#[derive(Debug, Deserialize)]
+pub struct AdminReportsQuery {
+    /// new | filed | resolved (default: all)
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
 
-    let issue_req = GitHubIssueRequest {
-        title,
-        body,
-        labels: vec![FEEDBACK_LABEL.to_string()],
-    };
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DuplicateReport {
+    pub id: i64,
+    pub note: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProblemReport {
+    pub id: i64,
+    pub status: String,
+    pub scenario: Option<String>,
+    pub lesson_id: Option<String>,
+    pub display_number: Option<i64>,
+    pub board_tag: Option<String>,
+    pub note: String,
+    /// The full submitted ReportRequest.
+    pub request: serde_json::Value,
+    pub reporter_user_id: Option<String>,
+    pub attempts: i64,
+    /// When delivery is next tried; null once filed, resolved or parked.
+    pub next_attempt_at: Option<String>,
+    pub last_error: Option<String>,
+    pub sink: Option<String>,
+    pub issue_url: Option<String>,
+    pub issue_number: Option<i64>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<String>,
+    pub reporter_notified_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Later reports of the same deal, oldest first.
+    pub duplicates: Vec<DuplicateReport>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ProblemReportRow {
+    id: i64,
+    status: String,
+    scenario: Option<String>,
+    lesson_id: Option<String>,
+    display_number: Option<i64>,
+    board_tag: Option<String>,
+    note: String,
+    request: String,
+    reporter_user_id: Option<String>,
+    attempts: i64,
+    next_attempt_at: Option<String>,
+    last_error: Option<String>,
+    sink: Option<String>,
+    issue_url: Option<String>,
+    issue_number: Option<i64>,
+    resolution_note: Option<String>,
+    resolved_by: Option<String>,
+    resolved_at: Option<String>,
+    reporter_notified_at: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
 
-    let client = Client::new();
-    let url = format!("https://api.github.com/repos/{}/issues", repo);
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Accept", "application/vnd.github+json")
-        .header("X-GitHub-Api-Version", "2022-11-28")
-        // GitHub requires a User-Agent on every REST request.
-        .header("User-Agent", "bridge-classroom-report")
-        .json(&issue_req)
-        .send()
+#[derive(Debug, Serialize)]
+pub struct AdminReportsResponse {
+    pub success: bool,
+    pub reports: Vec<ProblemReport>,
+}
+
+/// Original reports, newest first, each with its duplicates.
+pub async fn list_problem_reports(
+    db: &Pool<Sqlite>,
+    status: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ProblemReport>, sqlx::Error> {
+    let rows: Vec<ProblemReportRow> = sqlx::query_as(
+        r#"
+        SELECT id, status, scenario, lesson_id, display_number, board_tag, note, request,
+               reporter_user_id, attempts, next_attempt_at, last_error, sink, issue_url,
+               issue_number, resolution_note, resolved_by, resolved_at, reporter_notified_at,
+               created_at, updated_at
+        FROM problem_reports
+        WHERE duplicate_of IS NULL AND (? IS NULL OR status = ?)
+        ORDER BY id DESC LIMIT ?
+        "#,
+    )
+    .bind(status)
+    .bind(status)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let mut reports = Vec::with_capacity(rows.len());
+    for row in rows {
+        let duplicates: Vec<DuplicateReport> = sqlx::query_as(
+            "SELECT id, note, created_at FROM problem_reports WHERE duplicate_of = ? ORDER BY id",
+        )
+        .bind(row.id)
+        .fetch_all(db)
+        .await?;
+        reports.push(ProblemReport {
+            id: row.id,
+            status: row.status,
+            scenario: row.scenario,
+            lesson_id: row.lesson_id,
+            display_number: row.display_number,
+            board_tag: row.board_tag,
+            note: row.note,
+            request: serde_json::from_str(&row.request).unwrap_or(serde_json::Value::Null),
+            reporter_user_id: row.reporter_user_id,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_error: row.last_error,
+            sink: row.sink,
+            issue_url: row.issue_url,
+            issue_number: row.issue_number,
+            resolution_note: row.resolution_note,
+            resolved_by: row.resolved_by,
+            resolved_at: row.resolved_at,
+            reporter_notified_at: row.reporter_notified_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            duplicates,
+        });
+    }
+    Ok(reports)
+}
+
+fn check_status(status: &str) -> Result<(), (StatusCode, String)> {
+    if STATUSES.contains(&status) {
+        return Ok(());
+    }
+    Err((
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Unknown status '{}' (expected new, filed or resolved)",
+            status
+        ),
+    ))
+}
+
+/// GET /api/admin/reports
+/// Queued problem reports for curators.
+pub async fn admin_list_reports(
+    State(state): State<AppState>,
+    _admin: RequireAdmin,
+    Query(query): Query<AdminReportsQuery>,
+) -> Result<Json<AdminReportsResponse>, (StatusCode, String)> {
+    if let Some(status) = query.status.as_deref() {
+        check_status(status)?;
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    let reports = list_problem_reports(&state.db, query.status.as_deref(), limit)
         .await
-        .map_err(|e| {
-            tracing::error!("GitHub issue request failed: {}", e);
-            (
-                StatusCode::BAD_GATEWAY,
-                "Could not reach the issue tracker".to_string(),
-            )
-        })?;
+        .map_err(db_err)?;
+    Ok(Json(AdminReportsResponse {
+        success: true,
+        reports,
+    }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateReportRequest {
+    /// new | filed | resolved
+    pub status: Option<String>,
+    /// What the curator found or changed; an empty string clears it.
+    pub resolution_note: Option<String>,
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        // Log the GitHub error but never the token.
-        tracing::error!("GitHub issue API error: {} - {}", status, error_text);
+#[derive(Debug, Serialize)]
+pub struct UpdateReportResponse {
+    pub success: bool,
+    pub id: i64,
+    pub status: String,
+    pub resolution_note: Option<String>,
+    /// Duplicates moved along with it.
+    pub duplicates_updated: u64,
+    /// Reporters emailed about the resolution by this update.
+    pub reporters_notified: usize,
+}
+
+#[derive(sqlx::FromRow)]
+struct ReportState {
+    duplicate_of: Option<i64>,
+    status: String,
+    resolution_note: Option<String>,
+    sink: Option<String>,
+}
+
+/// Update a report's status and/or resolution note. Its duplicates follow
+/// its status. Setting `new` requeues an undelivered report with a fresh
+/// set of attempts (a delivered one is reopened as `filed`); setting `resolved` records `curator_id` and emails every
+/// signed-in reporter (of it or a duplicate) not yet told.
+pub async fn update_report(
+    state: &AppState,
+    id: i64,
+    req: &UpdateReportRequest,
+    curator_id: &str,
+) -> Result<UpdateReportResponse, (StatusCode, String)> {
+    let status = req.status.as_deref().map(str::trim);
+    if let Some(status) = status {
+        check_status(status)?;
+    } else if req.resolution_note.is_none() {
         return Err((
-            StatusCode::BAD_GATEWAY,
-            "The issue tracker rejected the report".to_string(),
+            StatusCode::BAD_REQUEST,
+            "Nothing to update: give a status and/or resolution_note".to_string(),
         ));
     }
+    let found: Option<ReportState> = sqlx::query_as(
+        "SELECT duplicate_of, status, resolution_note, sink FROM problem_reports WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_err)?;
+    let (current_status, current_note, delivered_to) = match found {
+        None => return Err((StatusCode::NOT_FOUND, "Report not found".to_string())),
+        Some(ReportState {
+            duplicate_of: Some(original),
+            ..
+        }) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Report {} is a duplicate; update report {} instead",
+                    id, original
+                ),
+            ))
+        }
+        Some(report) => (report.status, report.resolution_note, report.sink),
+    };
+    // Requeueing a delivered report would file it a second time and lose
+    // the link to the first issue.
+    if let (Some("new"), Some(sink)) = (status, &delivered_to) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Report {} was already delivered to {}; set it to filed to reopen it",
+                id, sink
+            ),
+        ));
+    }
+    let resolution_note = match &req.resolution_note {
+        Some(note) => Some(note.trim().to_string()).filter(|n| !n.is_empty()),
+        None => current_note,
+    };
 
-    let issue: GitHubIssueResponse = response.json().await.map_err(|e| {
-        tracing::error!("Could not parse GitHub issue response: {}", e);
-        (
-            StatusCode::BAD_GATEWAY,
-            "Unexpected response from the issue tracker".to_string(),
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let mut duplicates_updated = 0;
+    if let Some(status) = status {
+        if status == "new" {
+            sqlx::query(
+                "UPDATE problem_reports SET status = 'new', attempts = 0, next_attempt_at = ?, \
+                 last_error = NULL, resolved_by = NULL, resolved_at = NULL, updated_at = ? \
+                 WHERE id = ?",
+            )
+            .bind(&now)
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        } else {
+            // Resolving keeps the first curator and time; anything else
+            // reopens it.
+            sqlx::query(
+                r#"
+                UPDATE problem_reports
+                SET status = ?1, next_attempt_at = NULL, updated_at = ?3,
+                    resolved_by = CASE WHEN ?1 = 'resolved' THEN COALESCE(resolved_by, ?2) END,
+                    resolved_at = CASE WHEN ?1 = 'resolved' THEN COALESCE(resolved_at, ?3) END
+                WHERE id = ?4
+                "#,
+            )
+            .bind(status)
+            .bind(curator_id)
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        }
+        duplicates_updated = sqlx::query(
+            "UPDATE problem_reports SET status = ?, updated_at = ? WHERE duplicate_of = ?",
         )
-    })?;
+        .bind(status)
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+    }
+    if req.resolution_note.is_some() {
+        sqlx::query("UPDATE problem_reports SET resolution_note = ?, updated_at = ? WHERE id = ?")
+            .bind(&resolution_note)
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)?;
 
-    tracing::info!(
-        "Filed classroom-feedback issue #{} ({})",
-        issue.number,
-        issue.html_url
-    );
+    let status = status.map(str::to_string).unwrap_or(current_status);
+    let reporters_notified = if status == "resolved" {
+        notify_reporters(state, id).await.map_err(db_err)?
+    } else {
+        0
+    };
 
-    Ok(Json(ReportResponse {
+    Ok(UpdateReportResponse {
         success: true,
-        issue_url: issue.html_url,
-        issue_number: issue.number,
-    }))
+        id,
+        status,
+        resolution_note,
+        duplicates_updated,
+        reporters_notified,
+    })
 }
 
-/// Render the captured state into a readable Markdown body: the learner's note
-/// first, then a context block, then the deal PBN as a code block.
-fn build_issue_body(req: &ReportRequest, note: &str) -> String {
-    let mut ctx: Vec<String> = Vec::new();
-
-    let tier = req.reporter_tier.as_deref().unwrap_or("learner");
-    ctx.push(format!("- **Reporter:** {}", tier));
-
-    if let Some(scenario) = req.scenario.as_deref().or(req.lesson_id.as_deref()) {
-        let pretty = req
-            .lesson_name
-            .as_deref()
-            .filter(|n| !n.is_empty() && *n != scenario)
-            .map(|n| format!(" ({})", n))
-            .unwrap_or_default();
-        ctx.push(format!("- **Scenario:** {}{}", scenario, pretty));
+/// "Stayman, deal 3", for telling a learner which report this was.
+fn board_label(
+    scenario: Option<&str>,
+    display_number: Option<i64>,
+    board_tag: Option<&str>,
+) -> String {
+    let lesson = scenario.unwrap_or("a lesson");
+    match display_number
+        .map(|n| n.to_string())
+        .or_else(|| board_tag.map(str::to_string))
+    {
+        Some(deal) => format!("{}, deal {}", lesson, deal),
+        None => lesson.to_string(),
     }
-    if let Some(collection) = &req.collection {
-        ctx.push(format!("- **Collection:** {}", collection));
+}
+
+/// Email each signed-in reporter of resolved report `id` (or one of its
+/// duplicates) who hasn't been told yet, one message per person. Without
+/// an email transport nobody is told, and a later update tries again.
+/// Returns how many people were emailed.
+pub async fn notify_reporters(state: &AppState, id: i64) -> Result<usize, sqlx::Error> {
+    let reporters: Vec<(i64, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT p.id, u.id, u.email, u.first_name
+        FROM problem_reports p
+        JOIN users u ON u.id = p.reporter_user_id
+        WHERE (p.id = ? OR p.duplicate_of = ?) AND p.reporter_notified_at IS NULL
+        ORDER BY p.id
+        "#,
+    )
+    .bind(id)
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+    if reporters.is_empty() {
+        return Ok(0);
     }
+    let Some(mailer) = &state.config.mailer else {
+        tracing::info!(
+            "Report #{} resolved; no email transport, so {} reporter(s) not told",
+            id,
+            reporters.len()
+        );
+        return Ok(0);
+    };
+
+    let (scenario, display_number, board_tag, resolution_note): (
+        Option<String>,
+        Option<i64>,
+        Option<String>,
+        Option<String>,
+    ) = sqlx::query_as(
+        "SELECT COALESCE(scenario, lesson_id), display_number, board_tag, resolution_note \
+         FROM problem_reports WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    let board = board_label(scenario.as_deref(), display_number, board_tag.as_deref());
+
+    // One message per person, however many times they reported it.
+    let mut by_user: BTreeMap<String, (String, String, Vec<i64>)> = BTreeMap::new();
+    for (report_id, user_id, email, first_name) in reporters {
+        by_user
+            .entry(user_id)
+            .or_insert_with(|| (email, first_name, Vec::new()))
+            .2
+            .push(report_id);
+    }
+
+    let mut notified = 0;
+    for (email, first_name, report_ids) in by_user.into_values() {
+        let message =
+            templates::report_resolved(&email, &first_name, &board, resolution_note.as_deref());
+        if let Err(e) = mailer.send(&message).await {
+            tracing::warn!(
+                "Could not tell {} that report #{} is resolved: {}",
+                email,
+                id,
+                e
+            );
+            continue;
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        for report_id in report_ids {
+            sqlx::query("UPDATE problem_reports SET reporter_notified_at = ? WHERE id = ?")
+                .bind(&now)
+                .bind(report_id)
+                .execute(&state.db)
+                .await?;
+        }
+        notified += 1;
+    }
+    Ok(notified)
+}
+
+/// PATCH /api/admin/reports/:id
+pub async fn admin_update_report(
+    State(state): State<AppState>,
+    RequireAdmin(curator): RequireAdmin,
+    Path(id): Path<i64>,
+    Json(req): Json<UpdateReportRequest>,
+) -> Result<Json<UpdateReportResponse>, (StatusCode, String)> {
+    update_report(&state, id, &req, &curator.user_id)
+        .await
+        .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::email::testing::{mail_dir, sent_emails};
+    use crate::email::EmailTransport;
+    use crate::report_sink::FiledReport;
+    use futures_util::future::BoxFuture;
+    use futures_util::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
 
-    // Deal identity: display number + the two board tags that pin it down.
-    let mut deal_bits: Vec<String> = Vec::new();
-    if let Some(n) = req.display_number {
-        deal_bits.push(format!("display #{}", n));
+    /// Fails the first `failures` deliveries, then files issue #7.
+    struct FlakySink {
+        failures: usize,
+        calls: AtomicUsize,
     }
-    if let Some(b) = &req.board_tag {
-        deal_bits.push(format!("[Board] {}", b));
+
+    impl ReportSink for FlakySink {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn file<'a>(
+            &'a self,
+            _report: &'a ReportRequest,
+        ) -> BoxFuture<'a, Result<FiledReport, String>> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call < self.failures {
+                    return Err("tracker unreachable".to_string());
+                }
+                Ok(FiledReport {
+                    issue_url: Some("https://example.com/issues/7".to_string()),
+                    issue_number: Some(7),
+                })
+            }
+            .boxed()
+        }
     }
-    if let Some(ob) = &req.original_board {
-        deal_bits.push(format!("[OriginalBoard] {}", ob));
+
+    /// Files issue #9, but a curator resolves every report meanwhile.
+    struct RacedSink {
+        db: Pool<Sqlite>,
     }
-    if !deal_bits.is_empty() {
-        ctx.push(format!("- **Deal:** {}", deal_bits.join(" · ")));
+
+    impl ReportSink for RacedSink {
+        fn name(&self) -> &'static str {
+            "raced"
+        }
+
+        fn file<'a>(
+            &'a self,
+            _report: &'a ReportRequest,
+        ) -> BoxFuture<'a, Result<FiledReport, String>> {
+            async move {
+                sqlx::query("UPDATE problem_reports SET status = 'resolved'")
+                    .execute(&self.db)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(FiledReport {
+                    issue_url: Some("https://example.com/issues/9".to_string()),
+                    issue_number: Some(9),
+                })
+            }
+            .boxed()
+        }
     }
 
-    if let Some(seat) = &req.student_seat {
-        ctx.push(format!("- **Student seat:** {}", seat));
+    fn report(note: &str, pbn: &str) -> ReportRequest {
+        serde_json::from_value(serde_json::json!({
+            "note": note,
+            "scenario": "Stayman",
+            "deal_pbn": pbn,
+            "display_number": 3,
+            "auction": ["1NT", "Pass"],
+        }))
+        .unwrap()
     }
-    if let Some(contract) = &req.contract {
-        if !contract.is_empty() {
-            ctx.push(format!("- **Contract:** {}", contract));
+
+    fn test_state(db: Pool<Sqlite>, config: Config) -> AppState {
+        AppState {
+            db,
+            config: Arc::new(config),
+            started_at: Instant::now(),
         }
     }
-    if !req.auction.is_empty() {
-        ctx.push(format!("- **Auction so far:** {}", req.auction.join(" – ")));
+
+    async fn set_status(
+        state: &AppState,
+        id: i64,
+        status: &str,
+    ) -> Result<UpdateReportResponse, (StatusCode, String)> {
+        let req = UpdateReportRequest {
+            status: Some(status.to_string()),
+            ..Default::default()
+        };
+        update_report(state, id, &req, "curator-1").await
     }
-    if let Some(idx) = req.step_index {
-        let prompt = req
-            .prompt
-            .as_deref()
-            .map(|p| format!(" — “{}”", truncate(p, 280)))
-            .unwrap_or_default();
-        ctx.push(format!("- **Step:** index {}{}", idx, prompt));
+
+    async fn make_due(db: &Pool<Sqlite>, id: i64) {
+        sqlx::query(
+            "UPDATE problem_reports SET next_attempt_at = '2000-01-01T00:00:00+00:00' WHERE id = ?",
+        )
+        .bind(id)
+        .execute(db)
+        .await
+        .unwrap();
     }
-    if let Some(src) = &req.source_url {
-        ctx.push(format!("- **Source:** {}", src));
+
+    #[tokio::test]
+    async fn reports_are_queued_deduplicated_and_delivered_with_retries() {
+        let db = crate::db::test_pool().await;
+        let sink = FlakySink {
+            failures: 1,
+            calls: AtomicUsize::new(0),
+        };
+
+        let first = queue_report(&db, &report("wrong hint", "N:AK.. E:..."), None)
+            .await
+            .unwrap();
+        assert_eq!((first.status.as_str(), first.duplicate_of), ("new", None));
+        // Same deal, reformatted: folded into the first.
+        let second = queue_report(&db, &report("still wrong", "N:AK..  E:..."), None)
+            .await
+            .unwrap();
+        assert_eq!(second.duplicate_of, Some(first.report_id));
+        let other = queue_report(&db, &report("typo", "N:QJ.. E:..."), None)
+            .await
+            .unwrap();
+        assert_eq!(other.duplicate_of, None);
+
+        // The first attempt fails and is pushed back; the other report is
+        // delivered meanwhile. Duplicates are never handed to the sink.
+        assert!(deliver_next(&db, &sink).await.unwrap());
+        let (attempts, last_error): (i64, Option<String>) =
+            sqlx::query_as("SELECT attempts, last_error FROM problem_reports WHERE id = ?")
+                .bind(first.report_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(
+            (attempts, last_error.as_deref()),
+            (1, Some("tracker unreachable"))
+        );
+        assert!(deliver_next(&db, &sink).await.unwrap());
+        assert!(!deliver_next(&db, &sink).await.unwrap());
+
+        make_due(&db, first.report_id).await;
+        assert!(deliver_next(&db, &sink).await.unwrap());
+        assert_eq!(sink.calls.load(Ordering::SeqCst), 3);
+
+        let filed = list_problem_reports(&db, Some("filed"), 100).await.unwrap();
+        assert_eq!(filed.len(), 2);
+        let original = filed.iter().find(|r| r.id == first.report_id).unwrap();
+        assert_eq!(original.issue_number, Some(7));
+        assert_eq!(original.sink.as_deref(), Some("flaky"));
+        assert_eq!(original.request["auction"][0], "1NT");
+        assert_eq!(original.duplicates.len(), 1);
+        assert_eq!(original.duplicates[0].note, "still wrong");
+
+        // A late duplicate picks up the filed issue.
+        let late = queue_report(&db, &report("again", "N:AK.. E:..."), None)
+            .await
+            .unwrap();
+        assert_eq!(late.status, "filed");
+        assert_eq!(late.issue_number, Some(7));
+
+        // Once resolved, the next report of that deal starts a new report.
+        let state = test_state(db.clone(), Config::for_tests());
+        let resolved = set_status(&state, first.report_id, "resolved")
+            .await
+            .unwrap();
+        assert_eq!(resolved.duplicates_updated, 2);
+        assert!(set_status(&state, second.report_id, "resolved")
+            .await
+            .is_err());
+        // It keeps its issue: it can be reopened, but not filed again.
+        let err = set_status(&state, first.report_id, "new").await.unwrap_err();
+        assert_eq!(err.0, StatusCode::CONFLICT);
+        let reopened = set_status(&state, first.report_id, "filed").await.unwrap();
+        assert_eq!(reopened.status, "filed");
+        let kept = list_problem_reports(&db, Some("filed"), 100).await.unwrap();
+        let kept = kept.iter().find(|r| r.id == first.report_id).unwrap();
+        assert_eq!(kept.issue_number, Some(7));
+        set_status(&state, first.report_id, "resolved").await.unwrap();
+        let recurred = queue_report(&db, &report("back again", "N:AK.. E:..."), None)
+            .await
+            .unwrap();
+        assert_eq!(recurred.duplicate_of, None);
+        assert_eq!(
+            list_problem_reports(&db, Some("new"), 100)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
     }
-    if let Some(sc) = &req.source_commit {
-        ctx.push(format!("- **Source commit:** {}", sc));
+
+    #[tokio::test]
+    async fn resolving_during_delivery_keeps_the_status_and_the_issue() {
+        let db = crate::db::test_pool().await;
+        let queued = queue_report(&db, &report("wrong hint", "N:AK.. E:..."), None)
+            .await
+            .unwrap();
+        make_due(&db, queued.report_id).await;
+        let sink = RacedSink { db: db.clone() };
+        assert!(deliver_next(&db, &sink).await.unwrap());
+
+        let reports = list_problem_reports(&db, None, 10).await.unwrap();
+        let delivered = &reports[0];
+        assert_eq!(delivered.status, "resolved");
+        assert_eq!(delivered.issue_number, Some(9));
+        assert_eq!(
+            delivered.issue_url.as_deref(),
+            Some("https://example.com/issues/9")
+        );
+        assert_eq!(delivered.sink.as_deref(), Some("raced"));
+        assert!(delivered.next_attempt_at.is_none());
+        assert!(!deliver_next(&db, &sink).await.unwrap());
     }
-    if req.app_version.is_some() || req.app_commit.is_some() {
-        let v = req.app_version.as_deref().unwrap_or("?");
-        let c = req
-            .app_commit
-            .as_deref()
-            .map(|c| format!(" (commit {})", c))
-            .unwrap_or_default();
-        ctx.push(format!("- **App:** v{}{}", v, c));
+
+    #[tokio::test]
+    async fn delivery_parks_after_max_attempts_until_requeued() {
+        let db = crate::db::test_pool().await;
+        let sink = FlakySink {
+            failures: usize::MAX,
+            calls: AtomicUsize::new(0),
+        };
+        let queued = queue_report(&db, &report("bad", "N:AK.. E:..."), None)
+            .await
+            .unwrap();
+        for _ in 0..MAX_DELIVERY_ATTEMPTS {
+            make_due(&db, queued.report_id).await;
+            assert!(deliver_next(&db, &sink).await.unwrap());
+        }
+        let parked = &list_problem_reports(&db, None, 10).await.unwrap()[0];
+        assert_eq!(parked.status, "new");
+        assert_eq!(parked.attempts, MAX_DELIVERY_ATTEMPTS);
+        assert!(parked.next_attempt_at.is_none());
+        assert!(!deliver_next(&db, &sink).await.unwrap());
+
+        let state = test_state(db.clone(), Config::for_tests());
+        set_status(&state, queued.report_id, "new").await.unwrap();
+        let requeued = &list_problem_reports(&db, None, 10).await.unwrap()[0];
+        assert_eq!(requeued.attempts, 0);
+        assert!(requeued.next_attempt_at.is_some());
     }
 
-    let deal_block = req
-        .deal_pbn
-        .as_deref()
-        .filter(|p| !p.is_empty())
-        .map(|p| format!("\n\n**Deal (PBN):**\n```\n{}\n```", p))
-        .unwrap_or_default();
-
-    format!(
-        "{note}\n\n---\n\n{context}{deal}\n\n_Filed automatically from the Bridge Classroom “Report a Problem” button._",
-        note = note,
-        context = ctx.join("\n"),
-        deal = deal_block,
-    )
-}
+    #[tokio::test]
+    async fn resolving_records_the_note_and_tells_each_reporter_once() {
+        let dir = mail_dir();
+        let mut config = Config::for_tests();
+        config.mailer = Some(
+            EmailTransport::Dir { path: dir.clone() }
+                .mailer(&config.from_email)
+                .unwrap(),
+        );
+        let state = test_state(crate::db::test_pool().await, config);
+        for (id, email) in [
+            ("u1", "ann.report@example.com"),
+            ("u2", "bo.report@example.com"),
+        ] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+                 VALUES (?, ?, 'Lee', ?, '', '')",
+            )
+            .bind(id)
+            .bind(if id == "u1" { "Ann" } else { "Bo" })
+            .bind(email)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        let pbn = "N:AK.. E:...";
+        let first = queue_report(&state.db, &report("wrong hint", pbn), Some("u1"))
+            .await
+            .unwrap();
+        queue_report(&state.db, &report("still wrong", pbn), Some("u1"))
+            .await
+            .unwrap();
+        queue_report(&state.db, &report("me too", pbn), Some("u2"))
+            .await
+            .unwrap();
+        queue_report(&state.db, &report("anonymous", pbn), None)
+            .await
+            .unwrap();
+
+        // A note alone doesn't resolve anything or tell anyone.
+        let noted = update_report(
+            &state,
+            first.report_id,
+            &UpdateReportRequest {
+                resolution_note: Some("Looking at the 2♦ hint".to_string()),
+                ..Default::default()
+            },
+            "curator-1",
+        )
+        .await
+        .unwrap();
+        assert_eq!(noted.status, "new");
+        assert_eq!(noted.reporters_notified, 0);
+
+        let resolved = update_report(
+            &state,
+            first.report_id,
+            &UpdateReportRequest {
+                status: Some("resolved".to_string()),
+                resolution_note: Some("Fixed the hint: 2♥ shows four hearts.".to_string()),
+            },
+            "curator-1",
+        )
+        .await
+        .unwrap();
+        assert_eq!(resolved.reporters_notified, 2);
+        assert_eq!(
+            resolved.resolution_note.as_deref(),
+            Some("Fixed the hint: 2♥ shows four hearts.")
+        );
+        let sent = sent_emails(&dir);
+        assert_eq!(sent.len(), 2);
+        let ann = sent
+            .iter()
+            .find(|e| e.headers.contains("To: ann.report@example.com"))
+            .unwrap();
+        assert!(ann.text.starts_with("Hi Ann,"));
+        assert!(ann.text.contains("Stayman, deal 3"));
+        assert!(ann.text.contains("2♥ shows four hearts"));
+
+        let report = &list_problem_reports(&state.db, Some("resolved"), 10)
+            .await
+            .unwrap()[0];
+        assert_eq!(report.resolved_by.as_deref(), Some("curator-1"));
+        assert!(report.resolved_at.is_some());
+        assert!(report.reporter_notified_at.is_some());
 
-/// Truncate a string to at most `max` chars, appending an ellipsis when cut.
-fn truncate(s: &str, max: usize) -> String {
-    if s.chars().count() <= max {
-        return s.to_string();
+        // Updating it again doesn't repeat the email; reopening clears the
+        // resolution but keeps the note.
+        let again = set_status(&state, first.report_id, "resolved")
+            .await
+            .unwrap();
+        assert_eq!(again.reporters_notified, 0);
+        assert_eq!(sent_emails(&dir).len(), 2);
+        let reopened = set_status(&state, first.report_id, "filed").await.unwrap();
+        assert!(reopened.resolution_note.is_some());
+        let report = &list_problem_reports(&state.db, Some("filed"), 10)
+            .await
+            .unwrap()[0];
+        assert!(report.resolved_by.is_none());
     }
-    let mut out: String = s.chars().take(max).collect();
-    out.push('…');
-    out
 }
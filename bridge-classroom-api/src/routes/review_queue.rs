@@ -0,0 +1,443 @@
+//! Spaced-repetition review queue.
+//!
+//! Reads `board_status` and answers "what should this student play next?"
+//! using the same rules that award progress
+//! (`documentation/CORRECTNESS_AND_MASTERY.md` §5–§7, with the spacing and
+//! cooldown from the student's rules profile). A board is due when playing
+//! it now could move it forward:
+//!
+//!   - `retry` — the board's last result was failed/corrected and the §5
+//!     cooldown has passed, so a correct play now counts as clean.
+//!   - `star`  — the board is on the star track and the spacing since
+//!     `last_star_update` has elapsed, so a clean play now earns a star.
+//!   - `paw`   — the board is known (last played correct), has no Fresh paw
+//!     yet, and has been untouched for the spacing window, so a Wild clean
+//!     play now earns Fresh.
+//!
+//! A board qualifying for several reasons is listed once, under the most
+//! urgent. Within a reason, the longest-overdue board comes first.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::policy::{authorize, Relation};
+use crate::rules::{self, RulesParams};
+use crate::session::AuthUser;
+use crate::AppState;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+const DEFAULT_PER_LESSON: usize = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQueueQuery {
+    pub user_id: String,
+    /// Boards to return (default 20, at most 100).
+    pub limit: Option<usize>,
+    /// Boards per lesson (default 5), so one neglected lesson doesn't
+    /// crowd out the rest.
+    pub per_lesson: Option<usize>,
+    /// Only boards in this exercise...
+    pub exercise_id: Option<String>,
+    /// ...or in this assignment's exercise.
+    pub assignment_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewReason {
+    // Declaration order is urgency order: most urgent first.
+    Retry,
+    Star,
+    Paw,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewItem {
+    pub deal_subfolder: String,
+    pub deal_number: i32,
+    pub lesson_name: Option<String>,
+    pub reason: ReviewReason,
+    /// When the board became due.
+    pub due_at: String,
+    pub status: String,
+    pub star_count: i64,
+    pub max_stars: i64,
+    pub wild_achievement: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewQueueResponse {
+    pub boards: Vec<ReviewItem>,
+    /// Due boards before the per-lesson and overall limits.
+    pub total_due: usize,
+    pub rules_profile: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ReviewRow {
+    deal_subfolder: String,
+    deal_number: i32,
+    lesson_name: Option<String>,
+    status: String,
+    last_error_date: Option<String>,
+    star_count: i64,
+    max_stars: i64,
+    last_star_update: Option<String>,
+    wild_achievement: Option<String>,
+    /// `latest_observed_at`, falling back to `last_observation_at` for
+    /// rows walked before it was recorded.
+    last_played_at: Option<String>,
+}
+
+fn parse(ts: &Option<String>) -> Option<DateTime<Utc>> {
+    ts.as_deref()?.parse().ok()
+}
+
+/// Why (and since when) a board is due at `now`, if it is.
+fn due_reason(
+    row: &ReviewRow,
+    rules: &RulesParams,
+    now: DateTime<Utc>,
+) -> Option<(ReviewReason, DateTime<Utc>)> {
+    let spacing = Duration::days(rules.achievement_spacing_days);
+    let failed = row.status == "failed" || row.status == "corrected";
+
+    if failed {
+        if let Some(error_at) = parse(&row.last_error_date) {
+            let due = error_at + Duration::seconds(rules.cooldown_secs);
+            if due <= now {
+                return Some((ReviewReason::Retry, due));
+            }
+        }
+    }
+    if let Some(star_at) = parse(&row.last_star_update) {
+        let due = star_at + spacing;
+        if due <= now {
+            return Some((ReviewReason::Star, due));
+        }
+    }
+    let known = row.status == "clean_correct" || row.status == "close_correct";
+    if known && row.wild_achievement.as_deref() != Some("Fresh") {
+        if let Some(played_at) = parse(&row.last_played_at) {
+            let due = played_at + spacing;
+            if due <= now {
+                return Some((ReviewReason::Paw, due));
+            }
+        }
+    }
+    None
+}
+
+/// Rank the due boards among `rows` and apply the limits. Returns the
+/// chosen boards and how many were due in total.
+fn build_queue(
+    rows: Vec<ReviewRow>,
+    rules: &RulesParams,
+    now: DateTime<Utc>,
+    limit: usize,
+    per_lesson: usize,
+) -> (Vec<ReviewItem>, usize) {
+    let mut due: Vec<(ReviewReason, DateTime<Utc>, ReviewRow)> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let (reason, due_at) = due_reason(&row, rules, now)?;
+            Some((reason, due_at, row))
+        })
+        .collect();
+    let total_due = due.len();
+    due.sort_by(|a, b| {
+        (a.0, a.1, &a.2.deal_subfolder, a.2.deal_number).cmp(&(
+            b.0,
+            b.1,
+            &b.2.deal_subfolder,
+            b.2.deal_number,
+        ))
+    });
+
+    let mut per_lesson_taken: HashMap<String, usize> = HashMap::new();
+    let boards = due
+        .into_iter()
+        .filter(|(_, _, row)| {
+            let taken = per_lesson_taken
+                .entry(row.deal_subfolder.clone())
+                .or_default();
+            *taken += 1;
+            *taken <= per_lesson
+        })
+        .take(limit)
+        .map(|(reason, due_at, row)| ReviewItem {
+            deal_subfolder: row.deal_subfolder,
+            deal_number: row.deal_number,
+            lesson_name: row.lesson_name,
+            reason,
+            due_at: due_at.to_rfc3339(),
+            status: row.status,
+            star_count: row.star_count,
+            max_stars: row.max_stars,
+            wild_achievement: row.wild_achievement,
+        })
+        .collect();
+    (boards, total_due)
+}
+
+/// GET /api/review-queue?user_id=X[&limit=][&per_lesson=][&exercise_id=|&assignment_id=]
+pub async fn get_review_queue(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Query(query): Query<ReviewQueueQuery>,
+) -> Result<Json<ReviewQueueResponse>, (StatusCode, String)> {
+    authorize(&state.db, &caller, Relation::ProgressViewer(&query.user_id)).await?;
+
+    let exercise_id = match (&query.exercise_id, &query.assignment_id) {
+        (Some(_), Some(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Pass exercise_id or assignment_id, not both".to_string(),
+            ))
+        }
+        (Some(exercise_id), None) => Some(exercise_id.clone()),
+        (None, Some(assignment_id)) => Some(
+            sqlx::query_scalar::<_, String>("SELECT exercise_id FROM assignments WHERE id = ?")
+                .bind(assignment_id)
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                .ok_or_else(|| (StatusCode::NOT_FOUND, "Assignment not found".to_string()))?,
+        ),
+        (None, None) => None,
+    };
+
+    let profile = rules::profile_for_user(&state.db, &query.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let rows: Vec<ReviewRow> = sqlx::query_as(
+        r#"
+        SELECT bs.deal_subfolder, bs.deal_number, l.name AS lesson_name, bs.status,
+               bs.last_error_date, bs.star_count, bs.max_stars, bs.last_star_update,
+               bs.wild_achievement,
+               COALESCE(bs.latest_observed_at, bs.last_observation_at) AS last_played_at
+        FROM board_status bs
+        LEFT JOIN lessons l ON l.deal_subfolder = bs.deal_subfolder
+        WHERE bs.user_id = ?1 AND bs.status <> 'not_attempted'
+          AND (?2 IS NULL OR EXISTS (
+              SELECT 1 FROM exercise_boards eb
+              WHERE eb.exercise_id = ?2
+                AND eb.deal_subfolder = bs.deal_subfolder
+                AND eb.deal_number = bs.deal_number))
+        "#,
+    )
+    .bind(&query.user_id)
+    .bind(&exercise_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let per_lesson = query.per_lesson.unwrap_or(DEFAULT_PER_LESSON).max(1);
+    let (boards, total_due) = build_queue(rows, &profile.params, Utc::now(), limit, per_lesson);
+
+    Ok(Json(ReviewQueueResponse {
+        boards,
+        total_due,
+        rules_profile: profile.id,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn row(subfolder: &str, deal: i32, status: &str) -> ReviewRow {
+        ReviewRow {
+            deal_subfolder: subfolder.to_string(),
+            deal_number: deal,
+            lesson_name: None,
+            status: status.to_string(),
+            last_error_date: None,
+            star_count: 0,
+            max_stars: 0,
+            last_star_update: None,
+            wild_achievement: None,
+            last_played_at: None,
+        }
+    }
+
+    #[test]
+    fn due_boards_are_ranked_by_reason_then_age_and_capped_per_lesson() {
+        let now: DateTime<Utc> = "2026-03-20T12:00:00Z".parse().unwrap();
+        let ago = |days: i64, hours: i64| {
+            Some((now - Duration::days(days) - Duration::hours(hours)).to_rfc3339())
+        };
+
+        // Failed two hours ago: past the one-hour cooldown.
+        let retry = ReviewRow {
+            last_error_date: ago(0, 2),
+            last_played_at: ago(0, 2),
+            ..row("Stayman", 1, "failed")
+        };
+        // Failed just now: still cooling down, and not on the star track.
+        let cooling = ReviewRow {
+            last_error_date: Some(now.to_rfc3339()),
+            last_played_at: Some(now.to_rfc3339()),
+            ..row("Stayman", 2, "failed")
+        };
+        // Star track started 8 and 7 days ago: both due, older first.
+        let star_old = ReviewRow {
+            last_star_update: ago(8, 0),
+            last_played_at: ago(8, 0),
+            ..row("Stayman", 3, "clean_correct")
+        };
+        let star_new = ReviewRow {
+            last_star_update: ago(7, 0),
+            last_played_at: ago(7, 0),
+            ..row("Stayman", 4, "clean_correct")
+        };
+        // Starred two days ago: not due for another star, nor cold.
+        let fresh_star = ReviewRow {
+            last_star_update: ago(2, 0),
+            last_played_at: ago(2, 0),
+            ..row("Drury", 1, "clean_correct")
+        };
+        // Close-correct, untouched for ten days: a Wild play earns Fresh.
+        let paw = ReviewRow {
+            last_played_at: ago(10, 0),
+            ..row("Drury", 2, "close_correct")
+        };
+        let already_fresh = ReviewRow {
+            wild_achievement: Some("Fresh".to_string()),
+            ..row("Drury", 3, "close_correct")
+        };
+
+        let rows = vec![
+            paw,
+            star_new,
+            fresh_star,
+            cooling,
+            star_old,
+            retry,
+            already_fresh,
+        ];
+        let (boards, total_due) = build_queue(rows, &RulesParams::default(), now, 10, 2);
+        assert_eq!(total_due, 4);
+        let picked: Vec<(&str, i32, ReviewReason)> = boards
+            .iter()
+            .map(|b| (b.deal_subfolder.as_str(), b.deal_number, b.reason))
+            .collect();
+        // Stayman is capped at two boards, so its newer star board drops.
+        assert_eq!(
+            picked,
+            vec![
+                ("Stayman", 1, ReviewReason::Retry),
+                ("Stayman", 3, ReviewReason::Star),
+                ("Drury", 2, ReviewReason::Paw),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_board_is_due_the_moment_its_cooldown_ends() {
+        let now: DateTime<Utc> = "2026-03-20T12:00:00Z".parse().unwrap();
+        let rules = RulesParams::default();
+        let failed_at = |secs: i64| ReviewRow {
+            last_error_date: Some((now - Duration::seconds(secs)).to_rfc3339()),
+            ..row("Stayman", 1, "failed")
+        };
+        assert_eq!(
+            due_reason(&failed_at(rules.cooldown_secs), &rules, now).map(|d| d.0),
+            Some(ReviewReason::Retry)
+        );
+        assert!(due_reason(&failed_at(rules.cooldown_secs - 1), &rules, now).is_none());
+        // A failed board with no error date recorded is never a retry.
+        assert!(due_reason(&row("Stayman", 1, "failed"), &rules, now).is_none());
+    }
+
+    #[tokio::test]
+    async fn queue_requests_are_checked_and_limits_clamped() {
+        let state = AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(Config::for_tests()),
+            started_at: Instant::now(),
+        };
+        for id in ["u1", "u2"] {
+            sqlx::query(
+                "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+                 VALUES (?, 'Test', 'Student', ?, '', '')",
+            )
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        let failed_at = (Utc::now() - Duration::hours(2)).to_rfc3339();
+        for deal_number in 1..=3 {
+            sqlx::query(
+                "INSERT INTO board_status (user_id, deal_subfolder, deal_number, status, \
+                 last_error_date, updated_at) VALUES ('u1', 'Stayman', ?, 'failed', ?, '')",
+            )
+            .bind(deal_number)
+            .bind(&failed_at)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+        let query = |user_id: &str| ReviewQueueQuery {
+            user_id: user_id.to_string(),
+            limit: None,
+            per_lesson: None,
+            exercise_id: None,
+            assignment_id: None,
+        };
+        let as_student = |user_id: &str| AuthUser {
+            user_id: user_id.to_string(),
+            role: "student".to_string(),
+        };
+        let fetch = |caller: AuthUser, query: ReviewQueueQuery| {
+            get_review_queue(State(state.clone()), caller, Query(query))
+        };
+
+        // Another student's queue is off limits.
+        let err = fetch(as_student("u2"), query("u1")).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+
+        let both = ReviewQueueQuery {
+            exercise_id: Some("e1".to_string()),
+            assignment_id: Some("a1".to_string()),
+            ..query("u1")
+        };
+        let err = fetch(as_student("u1"), both).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        let missing = ReviewQueueQuery {
+            assignment_id: Some("no-such-assignment".to_string()),
+            ..query("u1")
+        };
+        let err = fetch(as_student("u1"), missing).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::NOT_FOUND);
+
+        // Zero limits are raised to one; the total still counts every due board.
+        for (limit, per_lesson, expected) in [
+            (Some(0), None, 1),
+            (Some(1000), Some(0), 1),
+            (None, None, 3),
+        ] {
+            let capped = ReviewQueueQuery {
+                limit,
+                per_lesson,
+                ..query("u1")
+            };
+            let Json(queue) = fetch(as_student("u1"), capped).await.unwrap();
+            assert_eq!((queue.boards.len(), queue.total_due), (expected, 3));
+        }
+    }
+}
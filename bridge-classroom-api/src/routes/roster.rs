@@ -0,0 +1,835 @@
+//! Roster import: a teacher uploads a CSV of students (name, email) and
+//! each gets a personal invitation email.
+//!
+//! Invitations are tracked per classroom and email. The emails are queued
+//! on the job queue (`jobs.rs`, [`deliver_invitation`]), which mints each
+//! invitation a single-use token and mails a link carrying it. A student
+//! who registers from that link presents the token and becomes a member
+//! straight away (see [`accept_invitations`], called from `create_user`);
+//! registering with the same address but without the token does nothing,
+//! since nothing proves the registrant reads that mailbox. A student who
+//! already has an account joins through the link like any other join,
+//! except that the token spares them the teacher's approval; joining
+//! accepts the invitation too.
+//!
+//! A member made at registration hasn't shared their key yet. Their client
+//! finds the classroom in `unshared_classrooms` on its next role sync and
+//! shares with the staff then (see `classroom_staff::share_with_staff`).
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sqlx::{Pool, Sqlite};
+
+use super::classrooms::load_classroom;
+use super::recovery::{frontend_base_url, generate_recovery_token, hash_token};
+use super::rules_profiles::{requeue_changed_profiles, snapshot_profiles};
+use crate::config::Config;
+use crate::email::templates;
+use crate::jobs::{self, Job};
+use crate::models::{
+    ClassroomActionResponse, InvitationInfo, InvitationListResponse, RosterImportResponse,
+    RosterRowError,
+};
+use crate::policy::{authorize, ClassroomPermission, Relation};
+use crate::session::AuthUser;
+use crate::AppState;
+
+/// Most students one upload may invite.
+const MAX_ROSTER_ROWS: usize = 200;
+
+const INVITATION_COLUMNS: &str = "id, email, first_name, last_name, status, created_at, \
+     sent_at, accepted_at, accepted_user_id, last_error";
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+/// One usable roster line.
+#[derive(Debug, PartialEq)]
+struct RosterEntry {
+    line: usize,
+    first_name: String,
+    last_name: String,
+    email: String,
+}
+
+/// Split one CSV line into trimmed fields. Fields may be double-quoted, with
+/// `""` for a literal quote; quoted fields can't span lines.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+/// "Ann Lee" → ("Ann", "Lee"); "Lee, Ann" → ("Ann", "Lee"). A single word
+/// is the first name.
+fn split_name(name: &str) -> (String, String) {
+    if let Some((last, first)) = name.split_once(',') {
+        return (first.trim().to_string(), last.trim().to_string());
+    }
+    match name.rsplit_once(char::is_whitespace) {
+        Some((first, last)) => (first.trim().to_string(), last.to_string()),
+        None => (name.to_string(), String::new()),
+    }
+}
+
+fn looks_like_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(char::is_whitespace)
+}
+
+/// Parse a `name,email` roster. Blank lines are skipped, as is a first line
+/// without an email in it (a header). Emails repeated in the upload are
+/// reported, case-insensitively, after their first appearance.
+fn parse_roster(csv: &str) -> (Vec<RosterEntry>, Vec<RosterRowError>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut first_content_line = true;
+
+    for (index, raw) in csv.lines().enumerate() {
+        let line = index + 1;
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let is_first = std::mem::replace(&mut first_content_line, false);
+        if is_first && !raw.contains('@') {
+            continue;
+        }
+        let mut error = |error: String| errors.push(RosterRowError { line, error });
+
+        let fields = split_csv_line(raw.trim_start_matches('\u{feff}'));
+        let [name, email] = fields.as_slice() else {
+            error(format!(
+                "Expected 2 fields (name, email), found {}",
+                fields.len()
+            ));
+            continue;
+        };
+        if !looks_like_email(email) {
+            error(format!("'{}' is not an email address", email));
+            continue;
+        }
+        let (first_name, last_name) = split_name(name);
+        if first_name.is_empty() {
+            error("Name is required".to_string());
+            continue;
+        }
+        if let Some(first) = seen.get(&email.to_lowercase()) {
+            error(format!("{} is already on line {}", email, first));
+            continue;
+        }
+        seen.insert(email.to_lowercase(), line);
+        entries.push(RosterEntry {
+            line,
+            first_name,
+            last_name,
+            email: email.clone(),
+        });
+    }
+    (entries, errors)
+}
+
+async fn fetch_invitation(
+    db: &Pool<Sqlite>,
+    classroom_id: &str,
+    email: &str,
+) -> Result<InvitationInfo, (StatusCode, String)> {
+    sqlx::query_as(&format!(
+        "SELECT {INVITATION_COLUMNS} FROM classroom_invitations WHERE classroom_id = ? AND email = ?"
+    ))
+    .bind(classroom_id)
+    .bind(email)
+    .fetch_one(db)
+    .await
+    .map_err(db_err)
+}
+
+#[derive(sqlx::FromRow)]
+struct QueuedInvitation {
+    email: String,
+    status: String,
+    link_base: Option<String>,
+    classroom_id: String,
+    classroom_name: String,
+    join_code: String,
+    teacher_name: String,
+}
+
+/// Email one invitation and record the outcome: the job behind
+/// [`Job::Invitation`]. Each send mints a fresh token, which retires the
+/// link in any earlier email. An invitation that was since sent, accepted
+/// or revoked (or whose classroom is gone) is left alone. Without an email
+/// transport the link is logged and the invitation stays `pending`.
+///
+/// A failed send is recorded on the invitation and returned as an error,
+/// so the queue retries it.
+pub(crate) async fn deliver_invitation(
+    db: &Pool<Sqlite>,
+    config: &Config,
+    invitation_id: &str,
+) -> Result<(), String> {
+    let invitation: Option<QueuedInvitation> = sqlx::query_as(
+        r#"
+        SELECT i.email, i.status, i.link_base, c.id AS classroom_id, c.name AS classroom_name,
+               c.join_code,
+               COALESCE(TRIM(u.first_name || ' ' || u.last_name), 'Your teacher') AS teacher_name
+        FROM classroom_invitations i
+        JOIN classrooms c ON c.id = i.classroom_id
+        LEFT JOIN users u ON u.id = i.invited_by
+        WHERE i.id = ?
+        "#,
+    )
+    .bind(invitation_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(invitation) = invitation.filter(|i| i.status == "pending" || i.status == "failed")
+    else {
+        return Ok(());
+    };
+    let link_base = invitation
+        .link_base
+        .ok_or_else(|| format!("invitation {} has no link base", invitation_id))?;
+
+    let token = generate_recovery_token();
+    sqlx::query("UPDATE classroom_invitations SET token_hash = ? WHERE id = ?")
+        .bind(hash_token(&token))
+        .bind(invitation_id)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let join_url = format!(
+        "{}/#/join/{}?invite={}",
+        link_base, invitation.join_code, token
+    );
+
+    let Some(mailer) = &config.mailer else {
+        tracing::info!(
+            "No email transport; invitation to {} for classroom {} not sent. Link: {}",
+            invitation.email,
+            invitation.classroom_id,
+            join_url
+        );
+        return Ok(());
+    };
+
+    let email = templates::teacher_invitation(
+        &invitation.email,
+        &invitation.teacher_name,
+        &invitation.classroom_name,
+        &join_url,
+    );
+    let outcome = mailer.send(&email).await;
+    let (status, sent_at, last_error) = match &outcome {
+        Ok(()) => ("sent", Some(chrono::Utc::now().to_rfc3339()), None),
+        Err(e) => {
+            tracing::warn!(
+                "Could not send invitation to {} for classroom {}: {}",
+                invitation.email,
+                invitation.classroom_id,
+                e
+            );
+            ("failed", None, Some(e.to_string()))
+        }
+    };
+    sqlx::query(
+        "UPDATE classroom_invitations SET status = ?, sent_at = ?, last_error = ? WHERE id = ?",
+    )
+    .bind(status)
+    .bind(sent_at)
+    .bind(last_error)
+    .bind(invitation_id)
+    .execute(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    outcome.map_err(|e| e.to_string())
+}
+
+/// POST /api/classrooms/:id/roster — invite the students in a CSV body of
+/// `name,email` lines. Each invitation is queued for emailing and comes
+/// back `pending`; uploading someone again re-sends theirs with a new link.
+/// Lines that can't be used, and students who are already members, are
+/// listed in `errors` without failing the rest.
+pub async fn import_roster(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+    body: String,
+) -> Result<Json<RosterImportResponse>, (StatusCode, String)> {
+    load_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageClassroom),
+    )
+    .await?;
+
+    let (entries, mut errors) = parse_roster(&body);
+    if entries.is_empty() && errors.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Roster is empty".to_string()));
+    }
+    if entries.len() > MAX_ROSTER_ROWS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "A roster can invite at most {} students at once",
+                MAX_ROSTER_ROWS
+            ),
+        ));
+    }
+
+    let link_base = frontend_base_url(&state, &headers);
+
+    let mut invitations = Vec::new();
+    for entry in entries {
+        let is_member: bool = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) > 0 FROM classroom_members m
+            JOIN users u ON u.id = m.student_id
+            WHERE m.classroom_id = ? AND u.email = ? COLLATE NOCASE
+            "#,
+        )
+        .bind(&classroom_id)
+        .bind(&entry.email)
+        .fetch_one(&state.db)
+        .await
+        .map_err(db_err)?;
+        if is_member {
+            errors.push(RosterRowError {
+                line: entry.line,
+                error: format!("{} is already a member", entry.email),
+            });
+            continue;
+        }
+
+        // Re-inviting starts the invitation over, whatever became of it,
+        // and retires the old link.
+        sqlx::query(
+            r#"
+            INSERT INTO classroom_invitations
+                (id, classroom_id, email, first_name, last_name, status, invited_by, created_at,
+                 link_base)
+            VALUES (?, ?, ?, ?, ?, 'pending', ?, ?, ?)
+            ON CONFLICT(classroom_id, email) DO UPDATE SET
+                first_name = excluded.first_name,
+                last_name = excluded.last_name,
+                status = 'pending',
+                invited_by = excluded.invited_by,
+                sent_at = NULL,
+                accepted_at = NULL,
+                accepted_user_id = NULL,
+                last_error = NULL,
+                token_hash = NULL,
+                link_base = excluded.link_base
+            "#,
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&classroom_id)
+        .bind(&entry.email)
+        .bind(&entry.first_name)
+        .bind(&entry.last_name)
+        .bind(&caller.user_id)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(&link_base)
+        .execute(&state.db)
+        .await
+        .map_err(db_err)?;
+
+        invitations.push(fetch_invitation(&state.db, &classroom_id, &entry.email).await?);
+    }
+    errors.sort_by_key(|e| e.line);
+
+    let sends: Vec<Job> = invitations
+        .iter()
+        .map(|i| Job::Invitation {
+            user_id: caller.user_id.clone(),
+            invitation_id: i.id.clone(),
+        })
+        .collect();
+    jobs::enqueue_all(&state.db, sends).await.map_err(db_err)?;
+
+    tracing::info!(
+        "{} invited {} student(s) to classroom {} ({} line(s) skipped)",
+        caller.user_id,
+        invitations.len(),
+        classroom_id,
+        errors.len()
+    );
+
+    Ok(Json(RosterImportResponse {
+        success: true,
+        invitations,
+        errors,
+    }))
+}
+
+/// GET /api/classrooms/:id/roster — the classroom's invitations, newest
+/// first
+pub async fn list_invitations(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+) -> Result<Json<InvitationListResponse>, (StatusCode, String)> {
+    load_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ViewProgress),
+    )
+    .await?;
+
+    let invitations: Vec<InvitationInfo> = sqlx::query_as(&format!(
+        "SELECT {INVITATION_COLUMNS} FROM classroom_invitations \
+         WHERE classroom_id = ? ORDER BY created_at DESC, email"
+    ))
+    .bind(&classroom_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(InvitationListResponse {
+        success: true,
+        invitations,
+    }))
+}
+
+/// DELETE /api/classrooms/:id/roster/:invitation_id — revoke an invitation
+/// that hasn't been accepted. Its token stops working; the emailed link
+/// still carries the join code, so rotate the code to shut that too.
+pub async fn revoke_invitation(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path((classroom_id, invitation_id)): Path<(String, String)>,
+) -> Result<Json<ClassroomActionResponse>, (StatusCode, String)> {
+    load_classroom(&state, &classroom_id).await?;
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageClassroom),
+    )
+    .await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE classroom_invitations SET status = 'revoked'
+        WHERE id = ? AND classroom_id = ? AND status IN ('pending', 'sent', 'failed')
+        "#,
+    )
+    .bind(&invitation_id)
+    .bind(&classroom_id)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    if result.rows_affected() == 0 {
+        return Ok(Json(ClassroomActionResponse {
+            success: false,
+            error: Some("No open invitation with that id".to_string()),
+        }));
+    }
+
+    tracing::info!(
+        "{} revoked invitation {} to classroom {}",
+        caller.user_id,
+        invitation_id,
+        classroom_id
+    );
+
+    Ok(Json(ClassroomActionResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+/// Whether `token` is the link token of an open invitation to this
+/// classroom for the user's email.
+pub(crate) async fn holds_invitation(
+    db: &Pool<Sqlite>,
+    classroom_id: &str,
+    user_id: &str,
+    token: &str,
+) -> Result<bool, (StatusCode, String)> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) > 0 FROM classroom_invitations i
+        JOIN users u ON u.email = i.email COLLATE NOCASE
+        WHERE i.classroom_id = ? AND u.id = ? AND i.token_hash = ?
+          AND i.status IN ('pending', 'sent', 'failed')
+        "#,
+    )
+    .bind(classroom_id)
+    .bind(user_id)
+    .bind(hash_token(token))
+    .fetch_one(db)
+    .await
+    .map_err(db_err)
+}
+
+/// Make a newly registered user a member of the classroom whose invitation
+/// `token` came from, and mark the invitation accepted. The token must be
+/// for an open invitation to `email`: it was mailed there, so presenting it
+/// proves the registrant reads that mailbox. The invitation stands in for
+/// approval, but the member cap still applies; a full classroom leaves the
+/// invitation open. Returns the classroom joined, if any.
+pub(crate) async fn accept_invitations(
+    db: &Pool<Sqlite>,
+    user_id: &str,
+    email: &str,
+    token: &str,
+) -> Result<Option<String>, (StatusCode, String)> {
+    let invited: Option<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT i.id, i.classroom_id FROM classroom_invitations i
+        JOIN classrooms c ON c.id = i.classroom_id
+        WHERE i.token_hash = ? AND i.email = ? COLLATE NOCASE
+          AND i.status IN ('pending', 'sent', 'failed')
+        "#,
+    )
+    .bind(hash_token(token))
+    .bind(email)
+    .fetch_optional(db)
+    .await
+    .map_err(db_err)?;
+    let Some((invitation_id, classroom_id)) = invited else {
+        tracing::warn!(
+            "User {} registered with an invitation token that doesn't match an open invitation",
+            user_id
+        );
+        return Ok(None);
+    };
+
+    // Joining can change which rules profile governs the student.
+    let profiles_before = snapshot_profiles(db, [user_id.to_string()]).await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = db.begin().await.map_err(db_err)?;
+    let joined = sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO classroom_members (classroom_id, student_id, joined_at)
+        SELECT c.id, ?2, ?3 FROM classrooms c
+        WHERE c.id = ?1 AND (c.max_members IS NULL
+            OR (SELECT COUNT(*) FROM classroom_members m WHERE m.classroom_id = c.id) < c.max_members)
+        "#,
+    )
+    .bind(&classroom_id)
+    .bind(user_id)
+    .bind(&now)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    if joined.rows_affected() == 0 {
+        tracing::info!(
+            "Classroom {} is full; invitation {} for user {} left open",
+            classroom_id,
+            invitation_id,
+            user_id
+        );
+        return Ok(None);
+    }
+    sqlx::query(
+        "UPDATE classroom_invitations \
+         SET status = 'accepted', accepted_at = ?, accepted_user_id = ? WHERE id = ?",
+    )
+    .bind(&now)
+    .bind(user_id)
+    .bind(&invitation_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+    requeue_changed_profiles(db, profiles_before).await;
+
+    tracing::info!(
+        "User {} joined classroom {} through roster invitation {}",
+        user_id,
+        classroom_id,
+        invitation_id
+    );
+    Ok(Some(classroom_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::email::testing::{mail_dir, sent_emails};
+    use crate::email::EmailTransport;
+    use crate::models::{CreateClassroomRequest, JoinSettings, ShareWithStaffRequest};
+    use crate::policy::RequireTeacher;
+    use crate::routes::classroom_staff::unshared_classrooms;
+    use crate::routes::{create_classroom, create_user, share_with_staff};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn roster_lines_parse_with_header_quotes_and_errors() {
+        let csv = "\u{feff}Name,Email\n\
+                   Ann Lee,ann@example.com\n\
+                   \n\
+                   \"Moss, Bo\",bo@example.com\n\
+                   Cy,not-an-email\n\
+                   Dee Ray,ANN@example.com\n\
+                   just one field\n";
+        let (entries, errors) = parse_roster(csv);
+        assert_eq!(
+            entries,
+            vec![
+                RosterEntry {
+                    line: 2,
+                    first_name: "Ann".into(),
+                    last_name: "Lee".into(),
+                    email: "ann@example.com".into(),
+                },
+                RosterEntry {
+                    line: 4,
+                    first_name: "Bo".into(),
+                    last_name: "Moss".into(),
+                    email: "bo@example.com".into(),
+                },
+            ]
+        );
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![5, 6, 7]);
+        assert!(errors[1].error.contains("line 2"), "{:?}", errors[1]);
+    }
+
+    /// The invite token in an emailed link.
+    fn invite_token(text: &str) -> String {
+        let start = text.find("?invite=").expect("no invite link") + "?invite=".len();
+        text[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn invitations_are_queued_and_only_their_token_joins_at_registration() {
+        let dir = mail_dir();
+        let mut config = Config::for_tests();
+        config.mailer = Some(
+            EmailTransport::Dir { path: dir.clone() }
+                .mailer(&config.from_email)
+                .unwrap(),
+        );
+        let db = crate::db::test_pool().await;
+        let state = AppState {
+            db: db.clone(),
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        };
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+             VALUES ('t', 'Tess', 'Hart', 't@example.com', 'teacher', '', '')",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO viewers (id, name, email, public_key, created_at) \
+             VALUES ('v-t', 'Tess', 't@example.com', 'pk-t', '')",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let teacher = AuthUser {
+            user_id: "t".into(),
+            role: "teacher".into(),
+        };
+        let classroom = create_classroom(
+            State(state.clone()),
+            RequireTeacher(teacher.clone()),
+            Json(CreateClassroomRequest {
+                name: "Tuesday".into(),
+                description: None,
+                join_settings: JoinSettings {
+                    join_code_expires_at: None,
+                    max_members: None,
+                    requires_approval: true,
+                },
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+        .classroom;
+        let id = classroom.id.clone();
+
+        let import = |csv: &str| {
+            import_roster(
+                State(state.clone()),
+                HeaderMap::new(),
+                teacher.clone(),
+                Path(id.clone()),
+                csv.to_string(),
+            )
+        };
+        let imported = import("name,email\nAnn Lee,ann@example.com\nBo Moss,bo@example.com\nCy,cy@\n")
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(imported.invitations.len(), 2);
+        assert!(imported.invitations.iter().all(|i| i.status == "pending"));
+        assert_eq!(imported.errors.len(), 1);
+
+        // Nothing is mailed until the queue runs.
+        assert!(sent_emails(&dir).is_empty());
+        while jobs::run_one(&db, &state.config).await.unwrap() {}
+        let sent = sent_emails(&dir);
+        assert_eq!(sent.len(), 2);
+        let to_ann = sent
+            .iter()
+            .find(|m| m.headers.contains("ann@example.com"))
+            .unwrap();
+        assert!(to_ann.text.contains("Tess Hart has invited you"));
+        assert!(to_ann
+            .text
+            .contains(&format!("/#/join/{}?invite=", classroom.join_code)));
+        let ann_token = invite_token(&to_ann.text);
+        assert_eq!(
+            fetch_invitation(&db, &id, "ann@example.com").await.unwrap().status,
+            "sent"
+        );
+
+        // Someone who isn't on the staff can't upload a roster.
+        let student = AuthUser {
+            user_id: "s9".into(),
+            role: "student".into(),
+        };
+        let refused = import_roster(
+            State(state.clone()),
+            HeaderMap::new(),
+            student,
+            Path(id.clone()),
+            "Ann Lee,ann@example.com".to_string(),
+        )
+        .await;
+        assert_eq!(refused.unwrap_err().0, StatusCode::FORBIDDEN);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "test-api-key".parse().unwrap());
+        let register = |user_id: &str, email: &str, token: Option<&str>| {
+            let mut body = serde_json::json!({
+                "user_id": user_id,
+                "first_name": "New",
+                "last_name": "Student",
+                "email": email,
+            });
+            if let Some(token) = token {
+                body["invitation_token"] = token.into();
+            }
+            create_user(State(state.clone()), headers.clone(), None, body.to_string())
+        };
+        let is_member = |user_id: &str| {
+            sqlx::query_scalar::<_, bool>(
+                "SELECT COUNT(*) > 0 FROM classroom_members WHERE classroom_id = ? AND student_id = ?",
+            )
+            .bind(id.clone())
+            .bind(user_id.to_string())
+            .fetch_one(&db)
+        };
+
+        // The invited address alone isn't enough, nor is another
+        // invitation's token.
+        assert!(register("s2", "bo@example.com", Some(&ann_token)).await.unwrap().0.success);
+        assert!(!is_member("s2").await.unwrap());
+        assert_eq!(
+            fetch_invitation(&db, &id, "bo@example.com").await.unwrap().status,
+            "sent"
+        );
+
+        // Registering from the link (in any case) joins, approval or not.
+        let created = register("s1", "Ann@Example.com", Some(&ann_token)).await.unwrap();
+        assert!(created.0.success);
+        assert!(is_member("s1").await.unwrap());
+        let invitation = fetch_invitation(&db, &id, "ann@example.com").await.unwrap();
+        assert_eq!(invitation.status, "accepted");
+        assert_eq!(invitation.accepted_user_id.as_deref(), Some("s1"));
+
+        // She hasn't shared her key yet, so the classroom is listed for her
+        // client to share with the staff.
+        let ann = AuthUser {
+            user_id: "s1".into(),
+            role: "student".into(),
+        };
+        let unshared = unshared_classrooms(&db, "s1").await.unwrap();
+        assert_eq!(unshared.len(), 1);
+        assert_eq!(unshared[0].classroom_id, id);
+        assert_eq!(unshared[0].viewers[0].viewer_id, "v-t");
+
+        let share = |caller: AuthUser, viewer: &str| {
+            share_with_staff(
+                State(state.clone()),
+                caller,
+                Path(id.clone()),
+                Json(ShareWithStaffRequest {
+                    grant_payloads: [(viewer.to_string(), "payload".to_string())].into(),
+                }),
+            )
+        };
+        assert_eq!(
+            share(ann.clone(), "v-stranger").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        let bo = AuthUser {
+            user_id: "s2".into(),
+            role: "student".into(),
+        };
+        assert_eq!(
+            share(bo, "v-t").await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert!(share(ann, "v-t").await.unwrap().0.success);
+        assert!(unshared_classrooms(&db, "s1").await.unwrap().is_empty());
+
+        // An account whose address is stored in another case still matches
+        // its invitation, whether joining with the code or at registration.
+        let to_bo = sent
+            .iter()
+            .find(|m| m.headers.contains("bo@example.com"))
+            .unwrap();
+        let bo_token = invite_token(&to_bo.text);
+        sqlx::query("UPDATE users SET email = 'Bo@Example.COM' WHERE id = 's2'")
+            .execute(&db)
+            .await
+            .unwrap();
+        assert!(holds_invitation(&db, &id, "s2", &bo_token).await.unwrap());
+        assert!(!holds_invitation(&db, &id, "s2", &ann_token).await.unwrap());
+        assert_eq!(
+            accept_invitations(&db, "s2", "BO@EXAMPLE.COM", &bo_token)
+                .await
+                .unwrap(),
+            Some(id.clone())
+        );
+        assert!(is_member("s2").await.unwrap());
+    }
+}
@@ -0,0 +1,311 @@
+//! Mastery rules profile endpoints (see `rules.rs`).
+//!
+//! Teachers publish profile versions and pick one per classroom; picking
+//! one queues a full replay for every member whose effective profile
+//! changed. The what-if endpoint replays a student's history under another
+//! profile (or an unsaved draft) and reports the difference, writing
+//! nothing.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+use super::board_status::{replay_user_boards, BoardOutcome};
+use super::lesson_mastery::{decide_tier, LessonCatalog};
+use crate::jobs;
+use crate::policy::{authorize, ClassroomPermission, Relation, RequireTeacher};
+use crate::rules::{self, RulesParams, RulesProfile};
+use crate::session::AuthUser;
+use crate::AppState;
+
+fn internal(e: String) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RulesProfilesResponse {
+    pub profiles: Vec<RulesProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRulesProfileRequest {
+    pub name: String,
+    pub params: RulesParams,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetClassroomRulesRequest {
+    /// `None` returns the classroom to the default profile.
+    pub profile_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetClassroomRulesResponse {
+    pub success: bool,
+    pub profile_id: String,
+    /// Members whose boards were queued for a replay.
+    pub students_requeued: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WhatIfRequest {
+    pub user_id: String,
+    /// A saved profile to compare against...
+    pub profile_id: Option<String>,
+    /// ...or unsaved parameters (missing fields take the defaults).
+    pub params: Option<RulesParams>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhatIfBoard {
+    pub deal_subfolder: String,
+    pub deal_number: i32,
+    pub current: BoardOutcome,
+    pub what_if: BoardOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhatIfLesson {
+    pub deal_subfolder: String,
+    pub current_tier: String,
+    pub what_if_tier: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhatIfResponse {
+    pub user_id: String,
+    pub current_profile: String,
+    /// `None` when comparing against a draft.
+    pub what_if_profile: Option<String>,
+    pub params: RulesParams,
+    /// Only boards whose outcome differs.
+    pub changed_boards: Vec<WhatIfBoard>,
+    pub unchanged_boards: usize,
+    pub lessons: Vec<WhatIfLesson>,
+}
+
+/// GET /api/rules-profiles — every profile version.
+pub async fn list_rules_profiles(
+    State(state): State<AppState>,
+    RequireTeacher(_caller): RequireTeacher,
+) -> Result<Json<RulesProfilesResponse>, (StatusCode, String)> {
+    let profiles = rules::list_profiles(&state.db).await.map_err(internal)?;
+    Ok(Json(RulesProfilesResponse { profiles }))
+}
+
+/// POST /api/rules-profiles — publish the next version of a named profile.
+pub async fn create_rules_profile(
+    State(state): State<AppState>,
+    RequireTeacher(caller): RequireTeacher,
+    Json(req): Json<CreateRulesProfileRequest>,
+) -> Result<Json<RulesProfile>, (StatusCode, String)> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Profile name is required".to_string(),
+        ));
+    }
+    req.params
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let profile = rules::create_profile(&state.db, name, &req.params, &caller.user_id)
+        .await
+        .map_err(internal)?;
+    tracing::info!("{} published rules profile {}", caller.user_id, profile.id);
+    Ok(Json(profile))
+}
+
+/// Queue a replay for each of `students` whose effective profile is no
+/// longer `before[student]`. Failures are logged: the boards still fall
+/// back to a replay the next time they're folded.
+pub(crate) async fn requeue_changed_profiles(
+    db: &Pool<Sqlite>,
+    before: BTreeMap<String, String>,
+) -> usize {
+    let mut requeued = 0;
+    for (student_id, before_id) in before {
+        match rules::profile_id_for_user(db, &student_id).await {
+            Ok(after_id) if after_id == before_id => {}
+            Ok(_) => match jobs::enqueue_user_replay(db, &student_id).await {
+                Ok(_) => requeued += 1,
+                Err(e) => tracing::error!("Failed to queue replay for {}: {}", student_id, e),
+            },
+            Err(e) => tracing::error!("Rules profile lookup for {} failed: {}", student_id, e),
+        }
+    }
+    requeued
+}
+
+/// Each student's effective profile id, to compare after a change.
+pub(crate) async fn snapshot_profiles(
+    db: &Pool<Sqlite>,
+    students: impl IntoIterator<Item = String>,
+) -> Result<BTreeMap<String, String>, (StatusCode, String)> {
+    let mut out = BTreeMap::new();
+    for student_id in students {
+        let id = rules::profile_id_for_user(db, &student_id)
+            .await
+            .map_err(internal)?;
+        out.insert(student_id, id);
+    }
+    Ok(out)
+}
+
+/// PUT /api/classrooms/:id/rules-profile — select the classroom's profile.
+pub async fn set_classroom_rules_profile(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Path(classroom_id): Path<String>,
+    Json(req): Json<SetClassroomRulesRequest>,
+) -> Result<Json<SetClassroomRulesResponse>, (StatusCode, String)> {
+    authorize(
+        &state.db,
+        &caller,
+        Relation::ClassroomStaff(&classroom_id, ClassroomPermission::ManageClassroom),
+    )
+    .await?;
+
+    let profile_id = req
+        .profile_id
+        .unwrap_or_else(|| rules::DEFAULT_PROFILE_ID.to_string());
+    if rules::load_profile(&state.db, &profile_id)
+        .await
+        .map_err(internal)?
+        .is_none()
+    {
+        return Err((StatusCode::NOT_FOUND, "Rules profile not found".to_string()));
+    }
+
+    let members: Vec<String> =
+        sqlx::query_scalar("SELECT student_id FROM classroom_members WHERE classroom_id = ?")
+            .bind(&classroom_id)
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| internal(e.to_string()))?;
+    let before = snapshot_profiles(&state.db, members).await?;
+
+    // The default is stored as NULL so it follows the seeded row.
+    let stored = (profile_id != rules::DEFAULT_PROFILE_ID).then_some(profile_id.as_str());
+    sqlx::query("UPDATE classrooms SET rules_profile_id = ? WHERE id = ?")
+        .bind(stored)
+        .bind(&classroom_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| internal(e.to_string()))?;
+
+    let students_requeued = requeue_changed_profiles(&state.db, before).await;
+    tracing::info!(
+        "Classroom {} now uses rules profile {} ({} students requeued)",
+        classroom_id,
+        profile_id,
+        students_requeued
+    );
+
+    Ok(Json(SetClassroomRulesResponse {
+        success: true,
+        profile_id,
+        students_requeued,
+    }))
+}
+
+/// POST /api/rules-profiles/what-if
+///
+/// Replays the student's history under their current profile and under
+/// the requested one, and reports the boards and lesson tiers that would
+/// change. Nothing is written.
+pub async fn rules_what_if(
+    State(state): State<AppState>,
+    caller: AuthUser,
+    Json(req): Json<WhatIfRequest>,
+) -> Result<Json<WhatIfResponse>, (StatusCode, String)> {
+    authorize(&state.db, &caller, Relation::ProgressViewer(&req.user_id)).await?;
+
+    let (what_if_profile, params) = match (req.profile_id, req.params) {
+        (Some(id), None) => {
+            let profile = rules::load_profile(&state.db, &id)
+                .await
+                .map_err(internal)?
+                .ok_or_else(|| (StatusCode::NOT_FOUND, "Rules profile not found".to_string()))?;
+            (Some(profile.id), profile.params)
+        }
+        (None, Some(params)) => {
+            params
+                .validate()
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            (None, params)
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Give exactly one of profile_id or params".to_string(),
+            ))
+        }
+    };
+
+    let current = rules::profile_for_user(&state.db, &req.user_id)
+        .await
+        .map_err(internal)?;
+    let before = replay_user_boards(&state.db, &req.user_id, &current.params)
+        .await
+        .map_err(internal)?;
+    let mut after = replay_user_boards(&state.db, &req.user_id, &params)
+        .await
+        .map_err(internal)?;
+
+    let catalog = LessonCatalog::load(&state.db)
+        .await
+        .map_err(|e| internal(e.to_string()))?;
+    let tiers = |boards: &BTreeMap<(String, i32), BoardOutcome>, rules: &RulesParams| {
+        catalog
+            .tally(boards.iter().map(|((sub, deal), o)| {
+                (sub.as_str(), *deal, o.max_stars, o.wild_achievement.as_deref())
+            }))
+            .into_iter()
+            .map(|row| (row.deal_subfolder.clone(), decide_tier(&row, rules).to_string()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let mut what_if_tiers = tiers(&after, &params);
+    let lessons = tiers(&before, &current.params)
+        .into_iter()
+        .map(|(deal_subfolder, current_tier)| WhatIfLesson {
+            what_if_tier: what_if_tiers.remove(&deal_subfolder).unwrap_or_default(),
+            deal_subfolder,
+            current_tier,
+        })
+        .collect();
+
+    let mut changed_boards = Vec::new();
+    let mut unchanged_boards = 0;
+    for ((deal_subfolder, deal_number), current) in before {
+        let Some(what_if) = after.remove(&(deal_subfolder.clone(), deal_number)) else {
+            continue; // both replays read the same observations
+        };
+        if current == what_if {
+            unchanged_boards += 1;
+        } else {
+            changed_boards.push(WhatIfBoard {
+                deal_subfolder,
+                deal_number,
+                current,
+                what_if,
+            });
+        }
+    }
+
+    Ok(Json(WhatIfResponse {
+        user_id: req.user_id,
+        current_profile: current.id,
+        what_if_profile,
+        params,
+        changed_boards,
+        unchanged_boards,
+        lessons,
+    }))
+}
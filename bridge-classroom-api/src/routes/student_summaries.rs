@@ -6,12 +6,14 @@
 
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::policy::{authorize, Relation};
+use crate::session::AuthUser;
 use crate::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -58,14 +60,6 @@ pub struct StudentSummariesResponse {
     pub summaries: Vec<StudentSummaryEntry>,
 }
 
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(|k| k == expected_key)
-        .unwrap_or(false)
-}
-
 /// GET /api/student-summaries?user_ids=a,b,c
 ///
 /// Returns one entry per requested user_id, drawn from the
@@ -75,13 +69,9 @@ fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
 /// omitted from the response; callers should fall back to defaults.
 pub async fn get_student_summaries(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Query(query): Query<StudentSummariesQuery>,
 ) -> Result<Json<StudentSummariesResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let user_ids: Vec<&str> = query
         .user_ids
         .split(',')
@@ -92,6 +82,9 @@ pub async fn get_student_summaries(
     if user_ids.is_empty() {
         return Ok(Json(StudentSummariesResponse { summaries: vec![] }));
     }
+    for uid in &user_ids {
+        authorize(&state.db, &caller, Relation::ProgressViewer(uid)).await?;
+    }
 
     // Build a parameterised IN clause for the summary fetch.
     let placeholders = vec!["?"; user_ids.len()].join(",");
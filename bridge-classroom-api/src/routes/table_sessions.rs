@@ -27,26 +27,21 @@
 //! best-effort service-side delete) so `/play/:hostCode` is never ambiguous.
 
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Path, State},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+use crate::policy::{authorize, Relation};
+use crate::session::AuthUser;
 use crate::AppState;
 
 /// Budget for admin calls to the table service. Session creation is a
 /// user-facing action; fail fast rather than hang the join screen.
 const SERVICE_CALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|k| k == expected_key)
-}
-
 // ---- Persistent code generation ----
 
 /// Unambiguous code alphabet: uppercase alphanumerics minus 0/O, 1/I/L —
@@ -219,7 +214,6 @@ impl From<SessionRow> for SessionInfo {
 
 #[derive(Deserialize)]
 pub struct CreateSessionRequest {
-    pub owner_user_id: String,
     /// "teacher_set" (requires teacher/admin role) or "adhoc".
     pub kind: String,
     pub classroom_id: Option<String>,
@@ -244,14 +238,12 @@ pub struct CreateSessionResponse {
     pub owner_invite_code: Option<String>,
 }
 
+/// The caller owns the new session.
 pub async fn create_table_session(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<CreateSessionRequest>,
 ) -> Result<Json<CreateSessionResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
     let Some(secret) = state.config.table_ticket_secret.as_deref() else {
         // Same graceful degradation as ticket minting: frontend can show
         // "multiplayer tables aren't set up yet".
@@ -292,7 +284,7 @@ pub async fn create_table_session(
     let row: Option<(String, String, Option<String>, Option<String>)> = sqlx::query_as(
         "SELECT role, first_name, host_code, invite_code FROM users WHERE id = ?",
     )
-    .bind(&req.owner_user_id)
+    .bind(&caller.user_id)
     .fetch_optional(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -312,7 +304,7 @@ pub async fn create_table_session(
     let stale: Vec<String> = sqlx::query_scalar(
         "SELECT id FROM table_sessions WHERE owner_user_id = ? AND status = 'open'",
     )
-    .bind(&req.owner_user_id)
+    .bind(&caller.user_id)
     .fetch_all(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -322,7 +314,7 @@ pub async fn create_table_session(
              WHERE owner_user_id = ? AND status = 'open'",
         )
         .bind(&now)
-        .bind(&req.owner_user_id)
+        .bind(&caller.user_id)
         .execute(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -342,7 +334,7 @@ pub async fn create_table_session(
         &req.boards_pbn,
         table_count,
         &seat_policy,
-        &req.owner_user_id,
+        &caller.user_id,
     );
     if let Err(e) =
         service_create_session(&state.config.table_service_url, secret, &payload).await
@@ -361,7 +353,7 @@ pub async fn create_table_session(
     )
     .bind(&id)
     .bind(&req.kind)
-    .bind(&req.owner_user_id)
+    .bind(&caller.user_id)
     .bind(&req.classroom_id)
     .bind(&req.exercise_id)
     .bind(seat_policy.to_string())
@@ -380,7 +372,7 @@ pub async fn create_table_session(
         "Created table session {id} ({}, {} tables) for {}",
         req.kind,
         table_count,
-        req.owner_user_id
+        caller.user_id
     );
 
     Ok(Json(CreateSessionResponse {
@@ -388,7 +380,7 @@ pub async fn create_table_session(
         session: SessionInfo {
             id,
             kind: req.kind,
-            owner_user_id: req.owner_user_id,
+            owner_user_id: caller.user_id,
             classroom_id: req.classroom_id,
             exercise_id: req.exercise_id,
             status: "open".to_string(),
@@ -450,22 +442,12 @@ pub async fn get_table_session(
 
 // ---- DELETE /api/table-sessions/:id (close) ----
 
-#[derive(Deserialize)]
-pub struct CloseSessionQuery {
-    /// Must match the session owner (shared-API-key world has no caller
-    /// identity; this at least stops cross-user closes by URL guessing).
-    pub owner_user_id: String,
-}
-
+/// Only the session owner (or an admin) may close it.
 pub async fn close_table_session(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(session_id): Path<String>,
-    Query(q): Query<CloseSessionQuery>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
 
     let row: Option<(String, String)> =
         sqlx::query_as("SELECT owner_user_id, status FROM table_sessions WHERE id = ?")
@@ -476,20 +458,7 @@ pub async fn close_table_session(
     let Some((owner_user_id, status)) = row else {
         return Err((StatusCode::NOT_FOUND, "session not found".to_string()));
     };
-    if owner_user_id != q.owner_user_id {
-        // Allow admins to close anyone's session.
-        let role: Option<String> = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
-            .bind(&q.owner_user_id)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-        if role.as_deref() != Some("admin") {
-            return Err((
-                StatusCode::FORBIDDEN,
-                "only the session owner can close it".to_string(),
-            ));
-        }
-    }
+    authorize(&state.db, &caller, Relation::Owner(Some(&owner_user_id))).await?;
 
     if status == "open" {
         let now = chrono::Utc::now().to_rfc3339();
@@ -659,24 +628,20 @@ async fn ensure_code(
 /// POST /api/users/:id/host-code — teachers/admins only.
 pub async fn generate_host_code(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(user_id): Path<String>,
 ) -> Result<Json<CodeResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::SelfUser(&user_id)).await?;
     ensure_code(&state, &user_id, "host_code", true).await
 }
 
 /// POST /api/users/:id/invite-code — any user.
 pub async fn generate_invite_code(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(user_id): Path<String>,
 ) -> Result<Json<CodeResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::SelfUser(&user_id)).await?;
     ensure_code(&state, &user_id, "invite_code", false).await
 }
 
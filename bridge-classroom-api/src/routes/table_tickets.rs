@@ -6,8 +6,9 @@
 //! `base64url(payload_json) + "." + base64url(hmac_sha256(payload_b64))`.
 //!
 //! Two mint paths:
-//! - Registered users: `{ user_id, session_id }` — role comes from the DB
-//!   (so a caller can't self-assign "teacher" in the request body).
+//! - Registered users: `{ user_id, session_id }` — user_id must match the
+//!   caller's session; role comes from the DB (so a caller can't
+//!   self-assign "teacher" in the request body).
 //! - Guests (Shark-style type-a-name entry): `{ guest_name, session_id }` —
 //!   gets a fresh `guest-<uuid>` subject and role "guest", persisted as a
 //!   `guest_users` row (display name + session) so the identity outlives
@@ -24,6 +25,7 @@ use base64::Engine;
 use ring::hmac;
 use serde::{Deserialize, Serialize};
 
+use crate::session::AuthUser;
 use crate::AppState;
 
 /// Ticket lifetime. Long enough for a full class evening; short enough that
@@ -68,6 +70,7 @@ fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
 pub async fn mint_table_ticket(
     State(state): State<AppState>,
     headers: HeaderMap,
+    caller: Option<AuthUser>,
     Json(req): Json<TicketRequest>,
 ) -> Result<Json<TicketResponse>, (StatusCode, String)> {
     if !validate_api_key(&headers, &state.config.api_key) {
@@ -89,6 +92,12 @@ pub async fn mint_table_ticket(
 
     let (sub, name, role) = match (&req.user_id, &req.guest_name) {
         (Some(user_id), None) => {
+            if caller.as_ref().is_none_or(|c| c.user_id != *user_id) {
+                return Err((
+                    StatusCode::UNAUTHORIZED,
+                    "user tickets require that user's session".to_string(),
+                ));
+            }
             let row: Option<(String, String, String)> = sqlx::query_as(
                 "SELECT first_name, last_name, role FROM users WHERE id = ?",
             )
@@ -1,17 +1,14 @@
-use axum::{
-    extract::{Query, State},
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 
+use crate::policy::StaffRole;
+use crate::session::AuthUser;
 use crate::AppState;
 
 // ---- Clear panel types ----
 
 #[derive(Debug, Deserialize)]
 pub struct ClearPanelRequest {
-    pub teacher_id: String,
     pub panel: String,
 }
 
@@ -20,21 +17,8 @@ pub struct ClearPanelResponse {
     pub success: bool,
 }
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    if let Some(header_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return header_key == expected_key;
-    }
-    false
-}
-
 // ---- Request / Response types ----
 
-#[derive(Debug, Deserialize)]
-pub struct TeacherDashboardQuery {
-    pub teacher_id: String,
-}
-
 #[derive(Debug, Serialize)]
 pub struct TeacherDashboardResponse {
     pub success: bool,
@@ -49,6 +33,8 @@ pub struct DashboardClassroom {
     pub name: String,
     pub join_code: String,
     pub member_count: i64,
+    /// The caller's role on this classroom's staff.
+    pub role: StaffRole,
     pub open_assignment_count: i64,
     pub avg_completion_pct: i64,
     pub assignments: Vec<DashboardAssignment>,
@@ -108,6 +94,7 @@ struct ClassroomRow {
     name: String,
     join_code: String,
     member_count: i64,
+    role: String,
 }
 
 #[derive(sqlx::FromRow)]
@@ -142,17 +129,12 @@ struct ClearedTimestamps {
 
 // ---- Handler ----
 
-/// GET /api/teacher/dashboard?teacher_id=X
+/// GET /api/teacher/dashboard — the calling teacher's dashboard
 pub async fn teacher_dashboard(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    Query(query): Query<TeacherDashboardQuery>,
+    caller: AuthUser,
 ) -> Result<Json<TeacherDashboardResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let teacher_id = &query.teacher_id;
+    let teacher_id = &caller.user_id;
 
     // Fetch cleared-at timestamps for filtering
     let cleared: Option<ClearedTimestamps> = sqlx::query_as(
@@ -166,13 +148,15 @@ pub async fn teacher_dashboard(
     let attention_cleared_at = cleared.as_ref().and_then(|c| c.attention_cleared_at.clone());
     let activity_cleared_at = cleared.as_ref().and_then(|c| c.activity_cleared_at.clone());
 
-    // 1. Fetch teacher's classrooms with member counts
+    // 1. Fetch the classrooms the teacher staffs, with member counts
     let classrooms: Vec<ClassroomRow> = sqlx::query_as(
         r#"
         SELECT c.id, c.name, c.join_code,
-               (SELECT COUNT(*) FROM classroom_members cm WHERE cm.classroom_id = c.id) as member_count
+               (SELECT COUNT(*) FROM classroom_members cm WHERE cm.classroom_id = c.id) as member_count,
+               s.role
         FROM classrooms c
-        WHERE c.teacher_id = ?
+        JOIN classroom_staff s ON s.classroom_id = c.id
+        WHERE s.user_id = ?
         ORDER BY c.created_at DESC
         "#,
     )
@@ -456,6 +440,7 @@ pub async fn teacher_dashboard(
             name: classroom.name.clone(),
             join_code: classroom.join_code.clone(),
             member_count: classroom.member_count,
+            role: StaffRole::parse(&classroom.role).unwrap_or(StaffRole::Assistant),
             open_assignment_count: assignments.len() as i64,
             avg_completion_pct,
             assignments: dash_assignments,
@@ -488,13 +473,9 @@ pub async fn teacher_dashboard(
 /// POST /api/teacher/dashboard/clear
 pub async fn clear_dashboard_panel(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(body): Json<ClearPanelRequest>,
 ) -> Result<Json<ClearPanelResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let now = chrono::Utc::now().to_rfc3339();
     let column = match body.panel.as_str() {
         "attention" => "attention_cleared_at",
@@ -505,7 +486,7 @@ pub async fn clear_dashboard_panel(
     let query = format!("UPDATE users SET {} = ? WHERE id = ?", column);
     sqlx::query(&query)
         .bind(&now)
-        .bind(&body.teacher_id)
+        .bind(&caller.user_id)
         .execute(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
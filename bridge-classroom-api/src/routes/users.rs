@@ -4,10 +4,16 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
 
 use crate::{
     models::{CreateUserRequest, CreateUserResponse, SharingGrant, User, UserInfo, UsersListResponse},
+    policy::{authorize, Relation, RequireAdmin, RequireTeacher},
+    routes::auth::verify_user_secret,
     routes::recovery::{encrypt_for_recovery, decrypt_for_recovery},
+    routes::classroom_staff::unshared_classrooms,
+    routes::roster::accept_invitations,
+    session::{self, AuthUser},
     AppState,
 };
 
@@ -20,10 +26,15 @@ fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
 }
 
 /// POST /api/users
-/// Register a new user
+/// Register a new user, or sync an existing one's profile.
+///
+/// Registration is anonymous (there is no account to prove yet) and returns
+/// a session token. Updating an existing account requires either that
+/// user's session or their secret_key.
 pub async fn create_user(
     State(state): State<AppState>,
     headers: HeaderMap,
+    caller: Option<AuthUser>,
     body: String,
 ) -> Result<Json<CreateUserResponse>, (StatusCode, String)> {
     if !validate_api_key(&headers, &state.config.api_key) {
@@ -68,7 +79,9 @@ pub async fn create_user(
         })?;
 
     // Also check if email exists with a different user_id (e.g., user cleared localStorage)
-    let existing_by_email = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ? AND id != ?")
+    let existing_by_email = sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE email = ? COLLATE NOCASE AND id != ?",
+    )
         .bind(&req.email)
         .bind(&req.user_id)
         .fetch_optional(&state.db)
@@ -88,6 +101,8 @@ pub async fn create_user(
             success: false,
             user_id: existing_user.id,
             existing_user: Some(true),
+            session_token: None,
+            session_expires_at: None,
         }));
     }
 
@@ -106,6 +121,19 @@ pub async fn create_user(
         None
     };
 
+    if let Some(ref existing) = existing_by_id {
+        let is_self = caller.as_ref().is_some_and(|c| c.user_id == existing.id);
+        let proved = match (&req.secret_key, is_self) {
+            (_, true) => true,
+            (Some(secret_key), false) => verify_user_secret(&state, existing, secret_key).await?,
+            (None, false) => false,
+        };
+        if !proved {
+            tracing::warn!("create_user: unauthenticated update attempt for {}", req.user_id);
+            return Err((StatusCode::UNAUTHORIZED, "Not authorized to update this user".to_string()));
+        }
+    }
+
     if let Some(ref existing) = existing_by_id {
         // User already exists - update their info
         // If admin corrected the name, don't overwrite it from client sync
@@ -244,6 +272,13 @@ pub async fn create_user(
 
         tracing::info!("Created new user: {} (recovery: {})", user.id, recovery_encrypted_key.is_some());
 
+        // A roster invitation link makes the new user a member (non-fatal)
+        if let Some(token) = req.invitation_token.as_deref().filter(|t| !t.is_empty()) {
+            if let Err((_, e)) = accept_invitations(&state.db, &user.id, &user.email, token).await {
+                tracing::warn!("Failed to accept classroom invitation for user {}: {} (user registration still succeeded)", user.id, e);
+            }
+        }
+
         // If admin grant is provided, store it (non-fatal — user is already created)
         if let Some(admin_grant) = &req.admin_grant {
             let grant = SharingGrant {
@@ -282,10 +317,21 @@ pub async fn create_user(
         }
     }
 
+    // The stored role only: registration never checks the email, so an
+    // admin viewer's address earns nothing here. Admin sessions come from
+    // POST /api/sessions once the address is verified (policy::resolve_role).
+    let role = existing_by_id
+        .as_ref()
+        .map(|u| u.role.as_str())
+        .unwrap_or("student");
+    let (session_token, expires_at) = session::issue(&state.config, &req.user_id, role);
+
     Ok(Json(CreateUserResponse {
         success: true,
         user_id: req.user_id,
         existing_user: None,
+        session_token: Some(session_token),
+        session_expires_at: Some(expires_at),
     }))
 }
 
@@ -293,12 +339,10 @@ pub async fn create_user(
 /// Get a single user's info (lightweight role sync)
 pub async fn get_user(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Path(user_id): Path<String>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    authorize(&state.db, &caller, Relation::SelfUser(&user_id)).await?;
 
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
         .bind(&user_id)
@@ -322,8 +366,10 @@ pub async fn get_user(
             .await
             .unwrap_or_default();
 
-            // For teachers/admins, include decrypted viewer private key if available
-            let viewer_private_key = if info.role == "teacher" || info.role == "admin" {
+            // For teachers/admins, include decrypted viewer private key if
+            // available — only ever to the account holder themselves.
+            let is_self = caller.user_id == user_id;
+            let viewer_private_key = if is_self && (info.role == "teacher" || info.role == "admin") {
                 if let Some(ref recovery_secret) = state.config.recovery_secret {
                     let viewer_row = sqlx::query_scalar::<_, Option<String>>(
                         "SELECT recovery_encrypted_private_key FROM viewers WHERE email = ?"
@@ -347,10 +393,19 @@ pub async fn get_user(
                 None
             };
 
+            // Classrooms whose staff this user still has to share their key
+            // with; only the account holder can act on it.
+            let unshared = if is_self {
+                unshared_classrooms(&state.db, &user_id).await?
+            } else {
+                Vec::new()
+            };
+
             Ok(Json(serde_json::json!({
                 "success": true,
                 "user": info,
                 "classrooms": classroom_ids,
+                "unshared_classrooms": unshared,
                 "viewer_private_key": viewer_private_key
             })))
         }
@@ -359,23 +414,40 @@ pub async fn get_user(
 }
 
 /// GET /api/users
-/// Get all users (for teacher dashboard)
+/// Users for the teacher dashboard. Admins get everyone; a teacher gets
+/// themselves, the members of classrooms they're on the staff of, and
+/// anyone sharing with their viewer.
 pub async fn get_users(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    RequireTeacher(caller): RequireTeacher,
 ) -> Result<Json<UsersListResponse>, (StatusCode, String)> {
-    // Validate API key
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
-    let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY created_at DESC")
+    let users = if caller.is_admin() {
+        sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY created_at DESC")
+            .fetch_all(&state.db)
+            .await
+    } else {
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE id = ?1
+               OR id IN (SELECT m.student_id FROM classroom_members m
+                         JOIN classroom_staff s ON s.classroom_id = m.classroom_id
+                         WHERE s.user_id = ?1)
+               OR id IN (SELECT g.grantor_id FROM sharing_grants g
+                         JOIN viewers v ON v.id = g.grantee_id
+                         JOIN users me ON me.email = v.email COLLATE NOCASE
+                         WHERE me.id = ?1 AND g.revoked = 0)
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(&caller.user_id)
         .fetch_all(&state.db)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch users: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
-        })?;
+    }
+    .map_err(|e| {
+        tracing::error!("Failed to fetch users: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
 
     let user_infos: Vec<UserInfo> = users.into_iter().map(UserInfo::from).collect();
 
@@ -399,13 +471,9 @@ pub struct AdminCorrectNameRequest {
 /// Searches by email, first_name, or last_name (partial, case-insensitive)
 pub async fn admin_search_user(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
     Query(query): Query<AdminUserSearchQuery>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
-
     let pattern = format!("%{}%", query.q);
     let users = sqlx::query_as::<_, User>(
         r#"SELECT * FROM users
@@ -430,14 +498,26 @@ pub async fn admin_search_user(
 /// PATCH /api/admin/users/:id
 pub async fn admin_correct_name(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: RequireAdmin,
     Path(user_id): Path<String>,
     Json(body): Json<AdminCorrectNameRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
-    }
+    let info = correct_user_name(&state.db, &user_id, &body.first_name, &body.last_name).await?;
 
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "user": info
+    })))
+}
+
+/// Set a user's name and stamp `name_corrected_at`. Shared by
+/// `admin_correct_name` and the admin CLI.
+pub async fn correct_user_name(
+    db: &Pool<Sqlite>,
+    user_id: &str,
+    first_name: &str,
+    last_name: &str,
+) -> Result<UserInfo, (StatusCode, String)> {
     let now = chrono::Utc::now().to_rfc3339();
 
     let result = sqlx::query(
@@ -447,12 +527,12 @@ pub async fn admin_correct_name(
         WHERE id = ?
         "#,
     )
-    .bind(body.first_name.trim())
-    .bind(body.last_name.trim())
+    .bind(first_name.trim())
+    .bind(last_name.trim())
     .bind(&now)
     .bind(&now)
-    .bind(&user_id)
-    .execute(&state.db)
+    .bind(user_id)
+    .execute(db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -461,8 +541,8 @@ pub async fn admin_correct_name(
     }
 
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-        .bind(&user_id)
-        .fetch_one(&state.db)
+        .bind(user_id)
+        .fetch_one(db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -470,8 +550,174 @@ pub async fn admin_correct_name(
 
     tracing::info!("Admin corrected name for user {}: {} {}", user_id, info.first_name, info.last_name);
 
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "user": info
-    })))
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::policy;
+    use crate::recovery_keys::RecoveryKeyring;
+    use crate::routes::auth::{create_session, SessionProofRequest, NO_KEY_ON_FILE};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn user(id: &str, role: &str) -> AuthUser {
+        AuthUser {
+            user_id: id.into(),
+            role: role.into(),
+        }
+    }
+
+    async fn test_state() -> AppState {
+        let mut config = Config::for_tests();
+        config.recovery_secret = Some(RecoveryKeyring::new("recovery"));
+        AppState {
+            db: crate::db::test_pool().await,
+            config: Arc::new(config),
+            started_at: Instant::now(),
+        }
+    }
+
+    async fn insert_user(db: &Pool<Sqlite>, id: &str, role: &str) {
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, role, created_at, updated_at) \
+             VALUES (?, ?, 'X', ?, ?, '', '')",
+        )
+        .bind(id)
+        .bind(id)
+        .bind(format!("{id}@example.com"))
+        .bind(role)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn an_account_with_no_key_on_file_cannot_be_claimed_by_key() {
+        let state = test_state().await;
+        insert_user(&state.db, "legacy", "student").await;
+
+        let refused = create_session(
+            State(state.clone()),
+            Json(SessionProofRequest {
+                user_id: "legacy".into(),
+                secret_key: "attacker-key".into(),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(refused, (StatusCode::UNAUTHORIZED, NO_KEY_ON_FILE.to_string()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "test-api-key".parse().unwrap());
+        let body = r#"{"user_id":"legacy","first_name":"Eve","last_name":"X",
+            "email":"eve@example.com","secret_key":"attacker-key"}"#;
+        let refused = create_user(State(state.clone()), headers, None, body.to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(refused.0, StatusCode::UNAUTHORIZED);
+
+        let (first_name, key): (String, Option<String>) =
+            sqlx::query_as("SELECT first_name, recovery_encrypted_key FROM users WHERE id = 'legacy'")
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert_eq!(first_name, "legacy");
+        assert!(key.is_none());
+    }
+
+    #[tokio::test]
+    async fn an_admin_email_earns_admin_only_once_verified() {
+        let state = test_state().await;
+        sqlx::query(
+            "INSERT INTO viewers (id, name, email, public_key, role, created_at) \
+             VALUES ('v', 'Ada', 'ada@example.com', 'pk', 'admin', '')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        let register = |id: &str, email: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-api-key", "test-api-key".parse().unwrap());
+            let body = serde_json::json!({
+                "user_id": id,
+                "first_name": "Ada",
+                "last_name": "L",
+                "email": email,
+                "secret_key": format!("{id}-key"),
+            });
+            create_user(State(state.clone()), headers, None, body.to_string())
+        };
+        let role_of = |token: &str| {
+            session::verify_token(token, "test-session-secret", 0)
+                .unwrap()
+                .role
+        };
+        let sign_in = |id: &str| {
+            create_session(
+                State(state.clone()),
+                Json(SessionProofRequest {
+                    user_id: id.into(),
+                    secret_key: format!("{id}-key"),
+                }),
+            )
+        };
+
+        // Registering with the admin's address proves nothing about owning it.
+        let created = register("ada", "ada@example.com").await.unwrap().0;
+        assert_eq!(role_of(&created.session_token.unwrap()), "student");
+        let session = sign_in("ada").await.unwrap().0;
+        assert_eq!(session.role, "student");
+
+        // The same address in another case is the same account.
+        let twin = register("eve", "Ada@Example.COM").await.unwrap().0;
+        assert_eq!(twin.existing_user, Some(true));
+        assert_eq!(twin.user_id, "ada");
+        assert!(twin.session_token.is_none());
+
+        // A recovery or reset link verifies the address; then it counts.
+        policy::mark_email_verified(&state.db, "ada").await.unwrap();
+        let session = sign_in("ada").await.unwrap().0;
+        assert_eq!(session.role, "admin");
+        // Registration still only hands out the stored role.
+        let resynced = register("ada", "ada@example.com").await.unwrap().0;
+        assert_eq!(role_of(&resynced.session_token.unwrap()), "student");
+    }
+
+    #[tokio::test]
+    async fn user_list_is_for_staff_and_scoped_to_their_students() {
+        let state = test_state().await;
+        for (id, role) in [("t", "teacher"), ("s1", "student"), ("s2", "student")] {
+            insert_user(&state.db, id, role).await;
+        }
+        sqlx::query(
+            "INSERT INTO classrooms (id, name, teacher_id, join_code, created_at) \
+             VALUES ('c', 'Tuesday', 't', 'BRG-AAAA-AAAA', '')",
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        for sql in [
+            "INSERT INTO classroom_staff (classroom_id, user_id, role, added_by, added_at) \
+             VALUES ('c', 't', 'owner', 't', '')",
+            "INSERT INTO classroom_members (classroom_id, student_id, joined_at) VALUES ('c', 's1', '')",
+        ] {
+            sqlx::query(sql).execute(&state.db).await.unwrap();
+        }
+
+        let ids = |users: &[UserInfo]| users.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+        let listed = get_users(State(state.clone()), RequireTeacher(user("t", "teacher")))
+            .await
+            .unwrap();
+        let mut listed = ids(&listed.0.users);
+        listed.sort();
+        assert_eq!(listed, vec!["s1", "t"]);
+
+        let everyone = get_users(State(state.clone()), RequireTeacher(user("a", "admin")))
+            .await
+            .unwrap();
+        assert_eq!(everyone.0.users.len(), 3);
+    }
 }
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::Deserialize;
@@ -9,31 +9,40 @@ use crate::models::{
     CreateViewerRequest, CreateViewerResponse, Viewer, ViewerInfo, ViewerPublicKeyResponse,
 };
 use crate::routes::recovery::encrypt_for_recovery;
+use crate::session::AuthUser;
 use crate::AppState;
 
-/// Validate API key from request headers
-fn validate_api_key(headers: &HeaderMap, expected_key: &str) -> bool {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(|k| k == expected_key)
-        .unwrap_or(false)
-}
-
 /// Query parameters for viewer lookup
 #[derive(Debug, Deserialize)]
 pub struct ViewerQuery {
     pub email: Option<String>,
 }
 
-/// Create a new viewer (teacher, partner, admin)
+/// Create a new viewer (teacher, partner, admin).
+///
+/// Viewers are keyed by email and the upsert replaces the public key, so a
+/// caller may only register a viewer for their own account's email, and
+/// only admins may mint admin viewers.
 pub async fn create_viewer(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    caller: AuthUser,
     Json(req): Json<CreateViewerRequest>,
 ) -> Result<Json<CreateViewerResponse>, (StatusCode, String)> {
-    if !validate_api_key(&headers, &state.config.api_key) {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    if !caller.is_admin() {
+        let email: Option<String> = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+            .bind(&caller.user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !email.is_some_and(|e| e.eq_ignore_ascii_case(&req.email)) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Viewer email must match your account".to_string(),
+            ));
+        }
+        if req.role.as_deref() == Some("admin") {
+            return Err((StatusCode::FORBIDDEN, "Only admins can create admin viewers".to_string()));
+        }
     }
 
     let viewer = Viewer::from_request(&req);
@@ -0,0 +1,296 @@
+//! Mastery rules profiles.
+//!
+//! The thresholds behind `documentation/CORRECTNESS_AND_MASTERY.md` — the
+//! §5 error cooldown, the §6/§7 achievement spacing, the §11 wilderness
+//! share and the §13 tier cut-offs — live in `rules_profiles` rather than
+//! in constants. A profile is immutable once created: editing one means
+//! publishing the next `version` under the same `name`, so a recompute is
+//! always attributable to exact parameters.
+//!
+//! A classroom selects a profile with `classrooms.rules_profile_id`; a
+//! classroom without one, and a student in no such classroom, uses the
+//! built-in [`DEFAULT_PROFILE_ID`]. A student in several classrooms with
+//! profiles follows the one they joined most recently.
+//!
+//! `board_status.rules_profile_id` records which profile a board was
+//! walked under, so the incremental fold can tell when it has to replay.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+/// The seeded profile matching the original constants.
+pub const DEFAULT_PROFILE_ID: &str = "default";
+
+/// The tunable parameters. Missing fields deserialize to the defaults, so
+/// a draft only has to name what it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesParams {
+    /// §5: a correct result within this many seconds of the board's last
+    /// error is `close_correct`, not `clean_correct`.
+    pub cooldown_secs: i64,
+    /// §6/§7: days between star increments, and the window a board must
+    /// have been untouched for a Wild success to earn Fresh.
+    pub achievement_spacing_days: i64,
+    /// §11: a board is Wild when less than this share of its exercise's
+    /// boards come from the same lesson.
+    pub wild_share_below: f64,
+    /// §13 Learning: share of the lesson's boards with max_stars ≥ 1.
+    pub learning_silver_share: f64,
+    /// §13 Retaining: share with max_stars ≥ 2.
+    pub retaining_gold_share: f64,
+    /// §13 Mastering: share that are gold or have a Fresh paw...
+    pub mastering_deep_share: f64,
+    /// ...and share with a Fresh paw.
+    pub mastering_fresh_share: f64,
+}
+
+impl Default for RulesParams {
+    fn default() -> Self {
+        Self {
+            cooldown_secs: 3600,
+            achievement_spacing_days: 6,
+            wild_share_below: 0.25,
+            learning_silver_share: 0.50,
+            retaining_gold_share: 0.80,
+            mastering_deep_share: 0.80,
+            mastering_fresh_share: 0.25,
+        }
+    }
+}
+
+impl RulesParams {
+    /// Reject parameters the walkers can't run with.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cooldown_secs < 0 {
+            return Err("cooldown_secs must not be negative".to_string());
+        }
+        if self.achievement_spacing_days < 1 {
+            return Err("achievement_spacing_days must be at least 1".to_string());
+        }
+        for (name, share) in [
+            ("wild_share_below", self.wild_share_below),
+            ("learning_silver_share", self.learning_silver_share),
+            ("retaining_gold_share", self.retaining_gold_share),
+            ("mastering_deep_share", self.mastering_deep_share),
+            ("mastering_fresh_share", self.mastering_fresh_share),
+        ] {
+            if !(0.0..=1.0).contains(&share) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesProfile {
+    pub id: String,
+    pub name: String,
+    pub version: i64,
+    pub params: RulesParams,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+impl RulesProfile {
+    /// Used when the seeded row is missing (or for a fresh test pool that
+    /// predates it): same id, same parameters.
+    fn builtin() -> Self {
+        Self {
+            id: DEFAULT_PROFILE_ID.to_string(),
+            name: DEFAULT_PROFILE_ID.to_string(),
+            version: 1,
+            params: RulesParams::default(),
+            created_by: None,
+            created_at: String::new(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ProfileRow {
+    id: String,
+    name: String,
+    version: i64,
+    params: String,
+    created_by: Option<String>,
+    created_at: String,
+}
+
+impl TryFrom<ProfileRow> for RulesProfile {
+    type Error = String;
+
+    fn try_from(row: ProfileRow) -> Result<Self, String> {
+        let params = serde_json::from_str(&row.params)
+            .map_err(|e| format!("rules profile {} has unreadable params: {}", row.id, e))?;
+        Ok(Self {
+            id: row.id,
+            name: row.name,
+            version: row.version,
+            params,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        })
+    }
+}
+
+const PROFILE_COLUMNS: &str = "id, name, version, params, created_by, created_at";
+
+/// A profile by id.
+pub async fn load_profile(pool: &Pool<Sqlite>, id: &str) -> Result<Option<RulesProfile>, String> {
+    let row: Option<ProfileRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM rules_profiles WHERE id = ?",
+        PROFILE_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Rules profile lookup failed: {}", e))?;
+    match row {
+        Some(row) => row.try_into().map(Some),
+        None if id == DEFAULT_PROFILE_ID => Ok(Some(RulesProfile::builtin())),
+        None => Ok(None),
+    }
+}
+
+/// Every profile version, grouped by name, newest version first.
+pub async fn list_profiles(pool: &Pool<Sqlite>) -> Result<Vec<RulesProfile>, String> {
+    let rows: Vec<ProfileRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM rules_profiles ORDER BY name, version DESC",
+        PROFILE_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Rules profile lookup failed: {}", e))?;
+    rows.into_iter().map(RulesProfile::try_from).collect()
+}
+
+/// The id of the profile governing `user_id`'s progress (see module docs).
+pub async fn profile_id_for_user(pool: &Pool<Sqlite>, user_id: &str) -> Result<String, String> {
+    let id: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT c.rules_profile_id
+        FROM classroom_members cm
+        JOIN classrooms c ON c.id = cm.classroom_id
+        WHERE cm.student_id = ? AND c.rules_profile_id IS NOT NULL
+        ORDER BY cm.joined_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Rules profile lookup failed: {}", e))?;
+    Ok(id.unwrap_or_else(|| DEFAULT_PROFILE_ID.to_string()))
+}
+
+/// The profile governing `user_id`'s progress.
+pub async fn profile_for_user(pool: &Pool<Sqlite>, user_id: &str) -> Result<RulesProfile, String> {
+    let id = profile_id_for_user(pool, user_id).await?;
+    load_profile(pool, &id)
+        .await?
+        .ok_or_else(|| format!("rules profile {} no longer exists", id))
+}
+
+/// Publish `params` as the next version of `name`.
+pub async fn create_profile(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    params: &RulesParams,
+    created_by: &str,
+) -> Result<RulesProfile, String> {
+    params.validate()?;
+    let params_json = serde_json::to_string(params).map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("begin profile tx failed: {}", e))?;
+    let version: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(version), 0) + 1 FROM rules_profiles WHERE name = ?",
+    )
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| format!("Rules profile lookup failed: {}", e))?;
+    let id = format!("{}-v{}", name, version);
+    sqlx::query(
+        r#"
+        INSERT INTO rules_profiles (id, name, version, params, created_by, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&id)
+    .bind(name)
+    .bind(version)
+    .bind(&params_json)
+    .bind(created_by)
+    .bind(&now)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Rules profile insert failed: {}", e))?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("commit profile tx failed: {}", e))?;
+
+    Ok(RulesProfile {
+        id,
+        name: name.to_string(),
+        version,
+        params: params.clone(),
+        created_by: Some(created_by.to_string()),
+        created_at: now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn seeded_default_matches_the_builtin_constants() {
+        let pool = crate::db::test_pool().await;
+        let seeded = load_profile(&pool, DEFAULT_PROFILE_ID)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(seeded.params, RulesParams::default());
+        assert_eq!(seeded.version, 1);
+    }
+
+    #[tokio::test]
+    async fn new_versions_are_numbered_per_name() {
+        let pool = crate::db::test_pool().await;
+        sqlx::query(
+            "INSERT INTO users (id, first_name, last_name, email, created_at, updated_at) \
+             VALUES ('t1', 'T', 'One', 't1@example.com', '', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let strict = RulesParams {
+            achievement_spacing_days: 10,
+            ..RulesParams::default()
+        };
+        let v1 = create_profile(&pool, "strict", &strict, "t1")
+            .await
+            .unwrap();
+        let v2 = create_profile(&pool, "strict", &strict, "t1")
+            .await
+            .unwrap();
+        assert_eq!((v1.id.as_str(), v2.id.as_str()), ("strict-v1", "strict-v2"));
+
+        let bad = RulesParams {
+            wild_share_below: 1.5,
+            ..RulesParams::default()
+        };
+        assert!(create_profile(&pool, "strict", &bad, "t1").await.is_err());
+
+        // Partial drafts fill in the rest from the defaults.
+        let draft: RulesParams = serde_json::from_str(r#"{"cooldown_secs": 60}"#).unwrap();
+        assert_eq!(draft.achievement_spacing_days, 6);
+    }
+}
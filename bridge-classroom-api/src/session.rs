@@ -0,0 +1,237 @@
+//! Per-user sessions.
+//!
+//! The shared `x-api-key` ships in the frontend bundle, so on its own it
+//! only proves "this request came from some copy of our app". A session
+//! token proves *who* is calling: the client trades proof of the user's
+//! secret key (or a completed recovery claim) for a signed, expiring
+//! bearer token carrying the user id and role, and handlers take the
+//! caller from the [`AuthUser`] extractor instead of trusting `user_id` /
+//! `teacher_id` / `acting_user_id` fields in the request.
+//!
+//! Wire format is the same shape as the table-service join tickets
+//! (`routes::table_tickets`): `base64url(claims_json) + "." +
+//! base64url(hmac_sha256(claims_b64))`, signed with `SESSION_SECRET`.
+//! Tokens are stateless; a role change re-mints (see `upgrade_role`).
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::policy::Role;
+use crate::AppState;
+
+/// Claims carried inside a session token.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionClaims {
+    /// users.id of the caller
+    pub sub: String,
+    /// users.role at mint time ("student", "teacher", "admin")
+    pub role: String,
+    /// Issued-at, unix seconds
+    pub iat: i64,
+    /// Expiry, unix seconds
+    pub exp: i64,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum SessionError {
+    #[error("Malformed session token")]
+    Malformed,
+    #[error("Invalid session signature")]
+    BadSignature,
+    #[error("Session expired")]
+    Expired,
+}
+
+/// Sign `claims` with `secret`.
+pub fn mint_token(claims: &SessionClaims, secret: &str) -> String {
+    let payload_b64 = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let sig = hmac::sign(&key, payload_b64.as_bytes());
+    format!("{payload_b64}.{}", URL_SAFE_NO_PAD.encode(sig.as_ref()))
+}
+
+/// Check signature and expiry; returns the claims on success.
+pub fn verify_token(token: &str, secret: &str, now: i64) -> Result<SessionClaims, SessionError> {
+    let (payload_b64, sig_b64) = token.split_once('.').ok_or(SessionError::Malformed)?;
+    let sig = URL_SAFE_NO_PAD
+        .decode(sig_b64)
+        .map_err(|_| SessionError::Malformed)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, payload_b64.as_bytes(), &sig).map_err(|_| SessionError::BadSignature)?;
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload_b64)
+        .map_err(|_| SessionError::Malformed)?;
+    let claims: SessionClaims =
+        serde_json::from_slice(&payload).map_err(|_| SessionError::Malformed)?;
+    if claims.exp <= now {
+        return Err(SessionError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Mint a fresh session for `user_id` with the configured lifetime.
+/// Returns `(token, expires_at)`.
+pub fn issue(config: &crate::config::Config, user_id: &str, role: &str) -> (String, i64) {
+    let now = chrono::Utc::now().timestamp();
+    let claims = SessionClaims {
+        sub: user_id.to_string(),
+        role: role.to_string(),
+        iat: now,
+        exp: now + config.session_ttl_secs,
+    };
+    (mint_token(&claims, &config.session_secret), claims.exp)
+}
+
+/// Constant-time string comparison for secrets (HMAC both sides under a
+/// throwaway key and let `hmac::verify` do the comparison).
+pub fn secrets_match(a: &str, b: &str) -> bool {
+    let mut key_bytes = [0u8; 32];
+    if SystemRandom::new().fill(&mut key_bytes).is_err() {
+        return false;
+    }
+    let key = hmac::Key::new(hmac::HMAC_SHA256, &key_bytes);
+    let tag = hmac::sign(&key, b.as_bytes());
+    hmac::verify(&key, a.as_bytes(), tag.as_ref()).is_ok()
+}
+
+/// Random 32-byte secret, hex-encoded. Used when `SESSION_SECRET` is unset
+/// (sessions then don't survive a restart).
+pub fn random_secret() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Failed to generate session secret");
+    hex::encode(bytes)
+}
+
+/// The authenticated caller, taken from `Authorization: Bearer <token>`.
+///
+/// `navigator.sendBeacon` can't set headers, so a `?session=<token>` query
+/// value is accepted as a fallback (the frontend's unload flush of pending
+/// observations uses it).
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: String,
+    pub role: String,
+}
+
+impl AuthUser {
+    pub fn role(&self) -> Role {
+        Role::parse(&self.role)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role() == Role::Admin
+    }
+
+    pub fn is_teacher(&self) -> bool {
+        self.role() >= Role::Teacher
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    if let Some(v) = parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+    {
+        return v.strip_prefix("Bearer ").map(|t| t.trim().to_string());
+    }
+    parts.uri.query().and_then(|q| {
+        q.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == "session")
+            .map(|(_, v)| v.to_string())
+    })
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).ok_or((
+            StatusCode::UNAUTHORIZED,
+            "Missing session token".to_string(),
+        ))?;
+        let claims = verify_token(
+            &token,
+            &state.config.session_secret,
+            chrono::Utc::now().timestamp(),
+        )
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+        Ok(AuthUser {
+            user_id: claims.sub,
+            role: claims.role,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(exp: i64) -> SessionClaims {
+        SessionClaims {
+            sub: "user-1".into(),
+            role: "student".into(),
+            iat: 1_000,
+            exp,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let token = mint_token(&claims(2_000), "secret");
+        assert_eq!(verify_token(&token, "secret", 1_500), Ok(claims(2_000)));
+    }
+
+    #[test]
+    fn rejects_expired_and_wrong_secret() {
+        let token = mint_token(&claims(2_000), "secret");
+        assert_eq!(
+            verify_token(&token, "secret", 2_000),
+            Err(SessionError::Expired)
+        );
+        assert_eq!(
+            verify_token(&token, "other", 1_500),
+            Err(SessionError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_payload() {
+        let token = mint_token(&claims(2_000), "secret");
+        let (_, sig) = token.split_once('.').unwrap();
+        let mut forged = claims(2_000);
+        forged.role = "admin".into();
+        let forged_payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        assert_eq!(
+            verify_token(&format!("{forged_payload}.{sig}"), "secret", 1_500),
+            Err(SessionError::BadSignature)
+        );
+        assert_eq!(
+            verify_token("garbage", "secret", 1_500),
+            Err(SessionError::Malformed)
+        );
+    }
+
+    #[test]
+    fn secrets_match_compares_exactly() {
+        assert!(secrets_match("abc", "abc"));
+        assert!(!secrets_match("abc", "abd"));
+        assert!(!secrets_match("abc", "abcd"));
+    }
+}
@@ -8,6 +8,9 @@
 
 use sqlx::{Pool, Sqlite};
 
+use crate::routes::lesson_mastery::{decide_tier, lesson_stats, LessonCatalog};
+use crate::rules;
+
 /// Rebuild the `student_summary` row for the given user from scratch.
 /// Upserts on conflict. Idempotent.
 pub async fn recompute_student_summary(
@@ -107,15 +110,27 @@ pub async fn recompute_student_summary(
     // ---- Lesson mastery distribution ----
     //
     // CORRECTNESS_AND_MASTERY.md §13 defines lesson mastery in terms of
-    // "the lesson's boards" (declared boards, not attempted boards).
-    // That requires a backend catalog of which boards belong to which
-    // lesson, which lives in the frontend's PBN cache today and is not
-    // yet on the server. Leaving all four counts at 0 until that
-    // catalog exists; the column is reserved for future use.
-    let lessons_exploring: i64 = 0;
-    let lessons_learning:  i64 = 0;
-    let lessons_retaining: i64 = 0;
-    let lessons_mastering: i64 = 0;
+    // "the lesson's boards" (declared boards, not attempted boards), so
+    // only lessons in the imported catalog (lesson_catalog.rs) are
+    // counted; uncatalogued lessons have no declared boards to measure
+    // against. Thresholds come from the user's rules profile.
+    let params = rules::profile_for_user(pool, user_id).await?.params;
+    let catalog = LessonCatalog::load(pool)
+        .await
+        .map_err(|e| format!("Lesson catalog load failed: {}", e))?;
+    let lessons = lesson_stats(pool, &catalog, user_id)
+        .await
+        .map_err(|e| format!("Lesson stats failed: {}", e))?;
+    let (mut lessons_exploring, mut lessons_learning, mut lessons_retaining, mut lessons_mastering) =
+        (0i64, 0i64, 0i64, 0i64);
+    for lesson in lessons.iter().filter(|l| l.catalogued) {
+        match decide_tier(lesson, &params) {
+            "Mastering" => lessons_mastering += 1,
+            "Retaining" => lessons_retaining += 1,
+            "Learning" => lessons_learning += 1,
+            _ => lessons_exploring += 1,
+        }
+    }
 
     sqlx::query(
         r#"
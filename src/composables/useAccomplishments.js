@@ -4,8 +4,7 @@ import { useUserStore } from './useUserStore.js'
 import { useTeacherRole } from './useTeacherRole.js'
 import { getSkillFromPath, getCategoryFromPath, getCategoryInfo } from '../utils/skillPath.js'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state
 const loading = ref(false)
@@ -73,14 +72,14 @@ async function fetchAccessibleUsers() {
   if (teacherRole.isTeacher.value && teacherRole.viewerId.value) {
     try {
       const response = await fetch(`${API_URL}/grants?grantee_id=${teacherRole.viewerId.value}`, {
-        headers: { 'x-api-key': API_KEY }
+        headers: authHeaders()
       })
 
       if (response.ok) {
         const { grants } = await response.json()
 
         const usersResponse = await fetch(`${API_URL}/users`, {
-          headers: { 'x-api-key': API_KEY }
+          headers: authHeaders()
         })
 
         if (usersResponse.ok) {
@@ -147,7 +146,7 @@ async function loadAccomplishments(forceRefresh = false) {
       // compute mastery; the encrypted blob is not needed here.
       const targetId = viewingAs ? userStore.effectiveUserId.value : selectedUserId.value
       const response = await fetch(`${API_URL}/observations?user_id=${targetId}&limit=10000`, {
-        headers: { 'x-api-key': API_KEY }
+        headers: authHeaders()
       })
 
       if (!response.ok) {
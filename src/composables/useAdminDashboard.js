@@ -1,7 +1,6 @@
 import { ref } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state
 const stats = ref(null)
@@ -31,7 +30,7 @@ async function loadStats(forceRefresh = false) {
 
   try {
     const response = await fetch(`${API_URL}/admin/stats`, {
-      headers: { 'x-api-key': API_KEY }
+      headers: authHeaders()
     })
 
     if (!response.ok) {
@@ -60,7 +59,7 @@ async function loadStats(forceRefresh = false) {
 async function loadHealth() {
   try {
     const response = await fetch(`${API_URL}/admin/health`, {
-      headers: { 'x-api-key': API_KEY }
+      headers: authHeaders()
     })
 
     if (!response.ok) {
@@ -1,7 +1,6 @@
 import { ref } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state
 const announcement = ref(null)
@@ -34,7 +33,7 @@ async function setAnnouncement(message, type = 'info', expiresAt = null) {
 
   const res = await fetch(`${API_URL}/admin/announcement`, {
     method: 'POST',
-    headers: { 'Content-Type': 'application/json', 'x-api-key': API_KEY },
+    headers: { 'Content-Type': 'application/json', ...authHeaders() },
     body: JSON.stringify(body)
   })
   if (!res.ok) throw new Error(`Failed to set announcement: ${res.status}`)
@@ -47,7 +46,7 @@ async function setAnnouncement(message, type = 'info', expiresAt = null) {
 async function clearAnnouncement() {
   const res = await fetch(`${API_URL}/admin/announcement`, {
     method: 'DELETE',
-    headers: { 'x-api-key': API_KEY }
+    headers: authHeaders()
   })
   if (!res.ok) throw new Error(`Failed to clear announcement: ${res.status}`)
   announcement.value = null
@@ -1,7 +1,6 @@
 import { ref } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton reactive state
 const studentAssignments = ref([])
@@ -20,7 +19,7 @@ export function useAssignments() {
     try {
       const response = await fetch(
         `${API_URL}/assignments?student_id=${encodeURIComponent(studentId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) {
         const text = await response.text()
@@ -61,7 +60,7 @@ export function useAssignments() {
     try {
       const response = await fetch(
         `${API_URL}/assignments?assigned_by=${encodeURIComponent(teacherId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) {
         const text = await response.text()
@@ -91,7 +90,7 @@ export function useAssignments() {
     try {
       const response = await fetch(
         `${API_URL}/assignments?classroom_id=${encodeURIComponent(classroomId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) {
         const text = await response.text()
@@ -121,7 +120,7 @@ export function useAssignments() {
     try {
       const response = await fetch(
         `${API_URL}/assignments/${encodeURIComponent(assignmentId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) {
         const text = await response.text()
@@ -153,7 +152,7 @@ export function useAssignments() {
         method: 'POST',
         headers: {
           'Content-Type': 'application/json',
-          'x-api-key': API_KEY
+          ...authHeaders()
         },
         body: JSON.stringify({
           exercise_id,
@@ -192,7 +191,7 @@ export function useAssignments() {
         `${API_URL}/assignments/${encodeURIComponent(assignmentId)}`,
         {
           method: 'DELETE',
-          headers: { 'x-api-key': API_KEY }
+          headers: authHeaders()
         }
       )
       if (!response.ok) {
@@ -217,7 +216,7 @@ export function useAssignments() {
     try {
       const response = await fetch(
         `${API_URL}/exercises/${encodeURIComponent(exerciseId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) return null
       const data = await response.json()
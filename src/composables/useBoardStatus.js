@@ -1,8 +1,7 @@
 import { ref, reactive } from 'vue'
 import { useObservationStore } from './useObservationStore.js'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton cache: { "userId::subfolder" → { boards: [...], fetchedAt: timestamp } }
 const cache = reactive({})
@@ -43,7 +42,7 @@ async function fetchBoardStatus(userId, dealSubfolder = null, force = false) {
     }
 
     const response = await fetch(url, {
-      headers: { 'x-api-key': API_KEY }
+      headers: authHeaders()
     })
 
     if (!response.ok) {
@@ -274,7 +273,7 @@ async function fetchLessonMastery(userId, force = false) {
   try {
     const res = await fetch(
       `${API_URL}/lesson-mastery?user_id=${encodeURIComponent(userId)}`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
     if (!res.ok) {
       console.error('Failed to fetch lesson mastery:', res.status)
@@ -1,7 +1,6 @@
 import { ref } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton reactive state
 const teacherClassrooms = ref([])
@@ -9,6 +8,27 @@ const currentClassroom = ref(null)
 const loading = ref(false)
 const error = ref(null)
 
+/**
+ * Call a join-code management endpoint. Refusals (403, 404, 409 full)
+ * come back as plain text and are returned as { success: false, error }.
+ */
+async function joinCodeRequest(path, method = 'GET', body = undefined) {
+  try {
+    const response = await fetch(`${API_URL}${path}`, {
+      method,
+      headers: authHeaders(body ? { 'Content-Type': 'application/json' } : {}),
+      body: body ? JSON.stringify(body) : undefined
+    })
+    if (!response.ok) {
+      return { success: false, error: await response.text() }
+    }
+    return await response.json()
+  } catch (err) {
+    console.error(`Join code request ${method} ${path} failed:`, err)
+    return { success: false, error: 'Unable to connect to server' }
+  }
+}
+
 export function useClassrooms() {
   // ---- Teacher methods ----
 
@@ -19,7 +39,7 @@ export function useClassrooms() {
     try {
       const response = await fetch(
         `${API_URL}/classrooms?teacher_id=${encodeURIComponent(teacherId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       const data = await response.json()
       if (data.success) {
@@ -46,7 +66,7 @@ export function useClassrooms() {
         method: 'POST',
         headers: {
           'Content-Type': 'application/json',
-          'x-api-key': API_KEY
+          ...authHeaders()
         },
         body: JSON.stringify({
           teacher_id: teacherId,
@@ -82,7 +102,7 @@ export function useClassrooms() {
     try {
       const response = await fetch(
         `${API_URL}/classrooms/${encodeURIComponent(classroomId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       const data = await response.json()
       if (data.success) {
@@ -107,7 +127,7 @@ export function useClassrooms() {
         `${API_URL}/classrooms/${encodeURIComponent(classroomId)}/members/${encodeURIComponent(studentId)}`,
         {
           method: 'DELETE',
-          headers: { 'x-api-key': API_KEY }
+          headers: authHeaders()
         }
       )
       const data = await response.json()
@@ -129,6 +149,51 @@ export function useClassrooms() {
     }
   }
 
+  /**
+   * Replace a classroom's join code; the old one stops working at once.
+   * expiresAt (ISO string) or null for a code that never expires.
+   */
+  function regenerateJoinCode(classroomId, expiresAt = null) {
+    return joinCodeRequest(
+      `/classrooms/${encodeURIComponent(classroomId)}/join-code`,
+      'POST',
+      { join_code_expires_at: expiresAt }
+    )
+  }
+
+  /**
+   * Replace a classroom's join settings
+   * ({ join_code_expires_at, max_members, requires_approval }).
+   */
+  function updateJoinSettings(classroomId, settings) {
+    return joinCodeRequest(
+      `/classrooms/${encodeURIComponent(classroomId)}/join-settings`,
+      'PUT',
+      settings
+    )
+  }
+
+  /** Students waiting for approval, oldest first */
+  function fetchJoinRequests(classroomId) {
+    return joinCodeRequest(`/classrooms/${encodeURIComponent(classroomId)}/join-requests`)
+  }
+
+  /** Approve a join request (409 when the classroom is full) */
+  function approveJoinRequest(classroomId, studentId) {
+    return joinCodeRequest(
+      `/classrooms/${encodeURIComponent(classroomId)}/join-requests/${encodeURIComponent(studentId)}/approve`,
+      'POST'
+    )
+  }
+
+  /** Decline a join request */
+  function declineJoinRequest(classroomId, studentId) {
+    return joinCodeRequest(
+      `/classrooms/${encodeURIComponent(classroomId)}/join-requests/${encodeURIComponent(studentId)}`,
+      'DELETE'
+    )
+  }
+
   // ---- Student methods ----
 
   /** Fetch public join info for a classroom (no auth required) */
@@ -144,6 +209,11 @@ export function useClassrooms() {
         error.value = 'Classroom not found'
         return null
       }
+      // 410: the code has expired; 429: too many bad codes from this network
+      if (!response.ok) {
+        error.value = await response.text()
+        return null
+      }
       const data = await response.json()
       return data
     } catch (err) {
@@ -155,8 +225,12 @@ export function useClassrooms() {
     }
   }
 
-  /** Join a classroom (creates membership + sharing grant) */
-  async function joinClassroom(joinCode, studentId, encryptedGrantPayload) {
+  /**
+   * Join a classroom (creates membership + sharing grants). staffGrantPayloads
+   * maps each of the join info's staff_viewers to its encrypted payload.
+   * invitationToken comes from an emailed roster link and skips approval.
+   */
+  async function joinClassroom(joinCode, studentId, encryptedGrantPayload, staffGrantPayloads = {}, invitationToken = null) {
     loading.value = true
     error.value = null
     try {
@@ -166,14 +240,21 @@ export function useClassrooms() {
           method: 'POST',
           headers: {
             'Content-Type': 'application/json',
-            'x-api-key': API_KEY
+            ...authHeaders()
           },
           body: JSON.stringify({
             student_id: studentId,
-            encrypted_grant_payload: encryptedGrantPayload
+            encrypted_grant_payload: encryptedGrantPayload,
+            staff_grant_payloads: staffGrantPayloads,
+            ...(invitationToken ? { invitation_token: invitationToken } : {})
           })
         }
       )
+      // 409 (classroom full) and 410 (code expired) come back as plain text
+      if (!response.ok) {
+        error.value = await response.text()
+        return { success: false, error: error.value }
+      }
       const data = await response.json()
       if (!data.success) {
         error.value = data.error || 'Failed to join classroom'
@@ -197,7 +278,7 @@ export function useClassrooms() {
           method: 'POST',
           headers: {
             'Content-Type': 'application/json',
-            'x-api-key': API_KEY
+            ...authHeaders()
           },
           body: JSON.stringify({ student_id: studentId })
         }
@@ -226,6 +307,11 @@ export function useClassrooms() {
     createClassroom,
     fetchClassroomDetail,
     removeMember,
+    regenerateJoinCode,
+    updateJoinSettings,
+    fetchJoinRequests,
+    approveJoinRequest,
+    declineJoinRequest,
     fetchJoinInfo,
     joinClassroom,
     leaveClassroom,
@@ -1,5 +1,6 @@
 import { ref, computed, watch } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
+import { authHeaders } from '@/utils/apiSession.js'
 import { useUserStore } from './useUserStore.js'
 import { useBoardStatus } from './useBoardStatus.js'
 import {
@@ -14,7 +15,6 @@ import {
 } from '../utils/conventionCatalog.js'
 import { getTaxonomyEntry, getSubfolderForSkill } from '../utils/bakerBridgeTaxonomy.js'
 
-const API_KEY = import.meta.env.VITE_API_KEY || ''
 
 // ─── Singleton state ────────────────────────────────────────────
 const currentCard = ref(null)        // { id, name, description, owner_id, card_data, ... }
@@ -44,7 +44,7 @@ function tierToProf(tier) {
 // ─── Card loading ───────────────────────────────────────────────
 async function fetchPublicSystemCard() {
   const res = await fetch(`${API_URL}/cards?visibility=public`, {
-    headers: { 'x-api-key': API_KEY }
+    headers: authHeaders()
   })
   if (!res.ok) throw new Error(`Failed to list public cards: ${res.status}`)
   const data = await res.json()
@@ -60,7 +60,7 @@ async function fetchCardById(cardId) {
   const url = viewerId
     ? `${API_URL}/cards/${encodeURIComponent(cardId)}?viewer_id=${encodeURIComponent(viewerId)}`
     : `${API_URL}/cards/${encodeURIComponent(cardId)}`
-  const res = await fetch(url, { headers: { 'x-api-key': API_KEY } })
+  const res = await fetch(url, { headers: authHeaders() })
   if (!res.ok) throw new Error(`Failed to load card ${cardId}: ${res.status}`)
   const data = await res.json()
   const card = data.card || data
@@ -72,7 +72,7 @@ async function fetchCardById(cardId) {
 
 async function fetchUsersPrimaryCard(userId) {
   const res = await fetch(`${API_URL}/users/${encodeURIComponent(userId)}/cards`, {
-    headers: { 'x-api-key': API_KEY }
+    headers: authHeaders()
   })
   if (!res.ok) return null
   const data = await res.json()
@@ -121,7 +121,7 @@ async function loadCardForCurrentUser() {
 
 async function loadUserCardLinks(userId) {
   const res = await fetch(`${API_URL}/users/${encodeURIComponent(userId)}/cards`, {
-    headers: { 'x-api-key': API_KEY }
+    headers: authHeaders()
   })
   if (!res.ok) {
     userCardLinks.value = []
@@ -300,7 +300,7 @@ async function saveCard() {
   try {
     const res = await fetch(`${API_URL}/cards/${encodeURIComponent(card.id)}`, {
       method: 'PUT',
-      headers: { 'x-api-key': API_KEY, 'Content-Type': 'application/json' },
+      headers: { ...authHeaders(), 'Content-Type': 'application/json' },
       body: JSON.stringify({
         acting_user_id: user.id,
         card_data: editedCardData.value
@@ -348,7 +348,7 @@ async function overwriteCard(cardId, { name, description, cardData }) {
   if (!user) throw new Error('Must be signed in')
   const res = await fetch(`${API_URL}/cards/${encodeURIComponent(cardId)}`, {
     method: 'PUT',
-    headers: { 'x-api-key': API_KEY, 'Content-Type': 'application/json' },
+    headers: { ...authHeaders(), 'Content-Type': 'application/json' },
     body: JSON.stringify({
       acting_user_id: user.id,
       name: name ?? undefined,
@@ -370,7 +370,7 @@ async function createCard({ name, description = null, cardData = {}, visibility
   if (!user) throw new Error('Must be signed in to create a card')
   const res = await fetch(`${API_URL}/cards`, {
     method: 'POST',
-    headers: { 'x-api-key': API_KEY, 'Content-Type': 'application/json' },
+    headers: { ...authHeaders(), 'Content-Type': 'application/json' },
     body: JSON.stringify({
       acting_user_id: user.id,
       name: name || 'My convention card',
@@ -387,7 +387,7 @@ async function createCard({ name, description = null, cardData = {}, visibility
   // Link as primary, then switch to it
   await fetch(`${API_URL}/users/${encodeURIComponent(user.id)}/cards`, {
     method: 'POST',
-    headers: { 'x-api-key': API_KEY, 'Content-Type': 'application/json' },
+    headers: { ...authHeaders(), 'Content-Type': 'application/json' },
     body: JSON.stringify({
       card_id,
       is_primary: true,
@@ -419,7 +419,7 @@ async function deleteCurrentCard() {
   const url = `${API_URL}/cards/${encodeURIComponent(card.id)}?acting_user_id=${encodeURIComponent(user.id)}`
   const res = await fetch(url, {
     method: 'DELETE',
-    headers: { 'x-api-key': API_KEY }
+    headers: authHeaders()
   })
   if (!res.ok) {
     const text = await res.text()
@@ -6,8 +6,7 @@ import { useBoardStatus } from './useBoardStatus.js'
 import { useAssignmentStatus } from './useAssignmentStatus.js'
 import { logDiagnostic } from '../utils/diagnostics.js'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders, ensureSession, sessionToken, setSession } from '@/utils/apiSession.js'
 
 // Singleton state
 const syncState = ref('idle') // 'idle', 'syncing', 'error', 'offline'
@@ -65,6 +64,12 @@ async function registerUserWithServer(user) {
       payload.admin_grant = user.adminGrantPayload
     }
 
+    // An emailed roster invitation joins its classroom at registration
+    const invitationToken = sessionStorage.getItem('pendingInvitation')
+    if (invitationToken) {
+      payload.invitation_token = invitationToken
+    }
+
     // Include secret key for email-based recovery
     // Server will encrypt with RECOVERY_SECRET before storing
     if (user.secretKey) {
@@ -75,7 +80,7 @@ async function registerUserWithServer(user) {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
-        'x-api-key': API_KEY
+        ...authHeaders()
       },
       body: JSON.stringify(payload)
     })
@@ -97,6 +102,12 @@ async function registerUserWithServer(user) {
       }
     }
 
+    // A new registration (or a sync with a live session) comes back with a
+    // session token for this user.
+    if (result.session_token) {
+      setSession(result.user_id, result.session_token)
+    }
+
     return { success: result.success, userId: result.user_id }
   } catch (err) {
     logDiagnostic('server_registration_failed', `registerUserWithServer — fetch threw for user ${user.id} (${user.email})`, err)
@@ -134,7 +145,7 @@ async function syncObservationsToServer(observations) {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
-        'x-api-key': API_KEY
+        ...authHeaders()
       },
       body: JSON.stringify(payload)
     })
@@ -217,13 +228,28 @@ async function performSync() {
       }
     }
 
-    // 2. Encrypt any unencrypted observations
+    // 2. Make sure requests carry this user's session. An account with no
+    // key on file, or a device whose key was rotated elsewhere, can't prove
+    // one; only email recovery signs it in.
+    if (user?.serverRegistered) {
+      const sessionResult = await ensureSession(user)
+      if (!sessionResult.success) {
+        logDiagnostic('session_refused', `performSync — no session for user ${user.id}`, sessionResult.error)
+        syncState.value = 'error'
+        lastError.value = sessionResult.needsRecovery
+          ? 'Please recover your account by email to keep syncing.'
+          : `Sign-in failed: ${sessionResult.error}`
+        return result
+      }
+    }
+
+    // 3. Encrypt any unencrypted observations
     const encryptedCount = await observationStore.encryptPendingObservations()
     if (encryptedCount > 0) {
       console.log(`Encrypted ${encryptedCount} observations`)
     }
 
-    // 3. Sync pending observations
+    // 4. Sync pending observations
     let hadSyncErrors = false
     if (user?.dataConsent) {
       const pending = observationStore.getPendingObservations()
@@ -378,7 +404,7 @@ function syncBeforeUnload() {
 
   // sendBeacon is fire-and-forget, works during page unload
   const blob = new Blob([JSON.stringify(payload)], { type: 'application/json' })
-  navigator.sendBeacon(`${API_URL}/observations?api_key=${API_KEY}`, blob)
+  navigator.sendBeacon(`${API_URL}/observations?session=${encodeURIComponent(sessionToken() || '')}`, blob)
   console.log('Sent beacon with', readyToSync.length, 'observations')
 }
 
@@ -1,7 +1,6 @@
 import { ref } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton reactive state
 const exercises = ref([])
@@ -24,7 +23,7 @@ export function useExercises() {
       const url = `${API_URL}/exercises${qs ? '?' + qs : ''}`
 
       const response = await fetch(url, {
-        headers: { 'x-api-key': API_KEY }
+        headers: authHeaders()
       })
       if (!response.ok) {
         const text = await response.text()
@@ -54,7 +53,7 @@ export function useExercises() {
     try {
       const response = await fetch(
         `${API_URL}/exercises/${encodeURIComponent(exerciseId)}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (!response.ok) {
         const text = await response.text()
@@ -86,7 +85,7 @@ export function useExercises() {
         method: 'POST',
         headers: {
           'Content-Type': 'application/json',
-          'x-api-key': API_KEY
+          ...authHeaders()
         },
         body: JSON.stringify({
           name,
@@ -132,7 +131,7 @@ export function useExercises() {
           method: 'PUT',
           headers: {
             'Content-Type': 'application/json',
-            'x-api-key': API_KEY
+            ...authHeaders()
           },
           body: JSON.stringify(updates)
         }
@@ -170,7 +169,7 @@ export function useExercises() {
         `${API_URL}/exercises/${encodeURIComponent(exerciseId)}${qs ? '?' + qs : ''}`,
         {
           method: 'DELETE',
-          headers: { 'x-api-key': API_KEY }
+          headers: authHeaders()
         }
       )
       if (!response.ok) {
@@ -1,7 +1,7 @@
 import { ref, computed } from 'vue'
 import { createObservation, extractMetadata, generateSessionId, validateObservation } from '../utils/observationSchema.js'
 import { generateSkillPath } from '../utils/skillPath.js'
-import { encryptObservation } from '../utils/crypto.js'
+import { encryptObservation, observationAad, CIPHER_FORMAT_AAD } from '../utils/crypto.js'
 import { useUserStore } from './useUserStore.js'
 import { useAssignmentStore } from './useAssignmentStore.js'
 
@@ -191,6 +191,22 @@ async function recordObservation({
   return { success: true, observation }
 }
 
+/**
+ * Encrypt an observation as cipher format 2, sealing its cleartext metadata
+ * as associated data so the server can't refile it under another board or
+ * result. Marks `metadata.cipher_format` to match.
+ *
+ * @param {Object} observation - The observation to encrypt
+ * @param {Object} metadata - extractMetadata() output sent alongside it
+ * @param {string} secretKey - Base64-encoded AES secret key
+ * @returns {Promise<{encrypted_data: string, iv: string}>}
+ */
+async function sealWithMetadata(observation, metadata, secretKey) {
+  const sealed = await encryptObservation(observation, secretKey, observationAad(metadata))
+  metadata.cipher_format = CIPHER_FORMAT_AAD
+  return sealed
+}
+
 /**
  * Encrypt and queue an observation using user's secret key
  * Simple AES-256-GCM encryption - no dual-key complexity!
@@ -201,13 +217,13 @@ async function recordObservation({
  */
 async function encryptAndQueueObservation(observation, user) {
   try {
-    // Encrypt with user's secret key (AES-256-GCM)
-    const { encrypted_data, iv } = await encryptObservation(observation, user.secretKey)
-
     // Extract metadata for server-side queries
     const classroom = user.classrooms?.[0] || null
     const metadata = extractMetadata(observation, classroom)
 
+    // Encrypt with user's secret key (AES-256-GCM), bound to the metadata
+    const { encrypted_data, iv } = await sealWithMetadata(observation, metadata, user.secretKey)
+
     // Queue the encrypted observation (replace existing if same ID)
     const entry = {
       encrypted_data,
@@ -279,7 +295,7 @@ async function encryptPendingObservations() {
 
     if (!obs.encrypted && obs.observation) {
       try {
-        const { encrypted_data, iv } = await encryptObservation(obs.observation, user.secretKey)
+        const { encrypted_data, iv } = await sealWithMetadata(obs.observation, obs.metadata, user.secretKey)
 
         // Update the observation to encrypted form
         pendingObservations.value[i] = {
@@ -1,20 +1,23 @@
 import { API_URL } from '@/utils/apiUrl.js'
+import { authHeaders } from '@/utils/apiSession.js'
 
-const API_KEY = import.meta.env.VITE_API_KEY || ''
 // Reports normally go to `${API_URL}/report`. VITE_REPORT_URL lets a local dev
 // build send them elsewhere (e.g. a local helper that files the GitHub issue
 // directly) without redirecting the rest of the API. Unset in production.
 const REPORT_URL = import.meta.env.VITE_REPORT_URL || `${API_URL}/report`
 
 /**
- * Submit a "Report a Problem" report to the backend, which files a
- * classroom-feedback GitHub issue in the content repo. The learner never sees
- * any GitHub plumbing — they just get a confirmation.
+ * Submit a "Report a Problem" report to the backend, which queues it and
+ * delivers it to the curators in the background (usually as a
+ * classroom-feedback GitHub issue in the content repo). The learner never sees
+ * any of that plumbing — they just get a confirmation.
  *
  * @param {Object} report - The gathered lesson/board state plus the free-text note.
- * @returns {Promise<{ok: boolean, issueUrl?: string, issueNumber?: number, reason?: string}>}
- *   reason is 'not_configured' when the server has no GitHub token set, so the
- *   caller can degrade gracefully; otherwise a short error message.
+ * @returns {Promise<{ok: boolean, reportId?: number, issueUrl?: string, issueNumber?: number, reason?: string}>}
+ *   issueUrl is only set when the deal was already reported and filed.
+ *   reason is 'not_configured' when the endpoint answers 503 (a VITE_REPORT_URL
+ *   helper without a token), so the caller can degrade gracefully; otherwise a
+ *   short error message.
  */
 async function submitReport(report) {
   try {
@@ -22,7 +25,7 @@ async function submitReport(report) {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
-        'x-api-key': API_KEY
+        ...authHeaders()
       },
       body: JSON.stringify(report)
     })
@@ -37,7 +40,12 @@ async function submitReport(report) {
     }
 
     const result = await response.json()
-    return { ok: true, issueUrl: result.issue_url, issueNumber: result.issue_number }
+    return {
+      ok: true,
+      reportId: result.report_id,
+      issueUrl: result.issue_url,
+      issueNumber: result.issue_number
+    }
   } catch (err) {
     return { ok: false, reason: err.message || 'Network error' }
   }
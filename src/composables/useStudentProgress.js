@@ -1,10 +1,9 @@
 import { ref, computed } from 'vue'
 import { useUserStore } from './useUserStore.js'
 import { useObservationStore } from './useObservationStore.js'
-import { decryptObservation } from '../utils/crypto.js'
+import { decryptObservation, rowAad } from '../utils/crypto.js'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state
 const observations = ref([])
@@ -24,7 +23,7 @@ const CACHE_DURATION_MS = 5 * 60 * 1000
 async function fetchObservationsFromServer(userId) {
   const response = await fetch(`${API_URL}/observations?user_id=${userId}&limit=10000`, {
     headers: {
-      'x-api-key': API_KEY
+      ...authHeaders()
     }
   })
 
@@ -44,7 +43,7 @@ async function fetchObservationsFromServer(userId) {
  */
 async function decryptSingleObservation(encrypted, secretKey) {
   try {
-    const decrypted = await decryptObservation(encrypted.encrypted_data, encrypted.iv, secretKey)
+    const decrypted = await decryptObservation(encrypted.encrypted_data, encrypted.iv, secretKey, rowAad(encrypted))
     return {
       ...decrypted,
       // Include metadata from server
@@ -17,8 +17,7 @@
 
 import { ref } from 'vue'
 import { API_URL } from '../utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '../utils/apiSession.js'
 
 // Default is the production service; override for local dev with
 // VITE_TABLE_WS_URL=ws://localhost:8004/ws in .env.
@@ -108,7 +107,7 @@ async function mintTicket({ sessionId, userId, guestName }) {
     : { guest_name: guestName, session_id: sessionId }
   const res = await fetch(`${API_URL}/table-tickets`, {
     method: 'POST',
-    headers: { 'Content-Type': 'application/json', 'x-api-key': API_KEY },
+    headers: { 'Content-Type': 'application/json', ...authHeaders() },
     body: JSON.stringify(body),
   })
   if (res.status === 503) {
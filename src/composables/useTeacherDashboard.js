@@ -1,7 +1,6 @@
 import { ref, computed } from 'vue'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state for teacher lobby dashboard
 const lobbyClassrooms = ref([])
@@ -29,7 +28,7 @@ async function loadTeacherLobbyData(teacherId, forceRefresh = false) {
   try {
     const response = await fetch(
       `${API_URL}/teacher/dashboard?teacher_id=${encodeURIComponent(teacherId)}`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
 
     if (!response.ok) {
@@ -60,7 +59,7 @@ async function clearPanel(teacherId, panel) {
   try {
     const response = await fetch(`${API_URL}/teacher/dashboard/clear`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json', 'x-api-key': API_KEY },
+      headers: { 'Content-Type': 'application/json', ...authHeaders() },
       body: JSON.stringify({ teacher_id: teacherId, panel })
     })
 
@@ -2,10 +2,9 @@ import { ref, computed } from 'vue'
 import { useUserStore } from './useUserStore.js'
 import { useBoardMastery } from './useBoardMastery.js'
 import { useAccomplishments } from './useAccomplishments.js'
-import { decryptSharingGrant, decryptObservation } from '../utils/crypto.js'
+import { decryptSharingGrant, decryptObservation, rowAad } from '../utils/crypto.js'
 import { API_URL } from '@/utils/apiUrl.js'
-
-const API_KEY = import.meta.env.VITE_API_KEY || ''
+import { authHeaders } from '@/utils/apiSession.js'
 
 // Singleton state
 const isTeacher = ref(false)
@@ -43,7 +42,7 @@ async function checkTeacherStatus() {
     // 1. Look up viewer by email
     const viewerRes = await fetch(
       `${API_URL}/viewers?email=${encodeURIComponent(currentUser.email)}`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
     if (!viewerRes.ok) return
 
@@ -56,7 +55,7 @@ async function checkTeacherStatus() {
     // 2. Fetch grants where this viewer is grantee
     const grantsRes = await fetch(
       `${API_URL}/grants?grantee_id=${viewer.id}`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
     if (!grantsRes.ok) return
 
@@ -75,7 +74,7 @@ async function checkTeacherStatus() {
     // 3. Fetch user details
     const usersRes = await fetch(
       `${API_URL}/users`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
     if (!usersRes.ok) return
 
@@ -101,7 +100,7 @@ async function checkTeacherStatus() {
       try {
         await fetch(`${API_URL}/users/me`, {
           method: 'PATCH',
-          headers: { 'Content-Type': 'application/json', 'x-api-key': API_KEY },
+          headers: { 'Content-Type': 'application/json', ...authHeaders() },
           body: JSON.stringify({ user_id: currentUser.id, action: 'become_teacher' })
         })
       } catch { /* best-effort sync */ }
@@ -125,7 +124,7 @@ async function fetchStudentObservations(userId) {
   try {
     const res = await fetch(
       `${API_URL}/observations?user_id=${encodeURIComponent(userId)}&limit=10000`,
-      { headers: { 'x-api-key': API_KEY } }
+      { headers: authHeaders() }
     )
     if (!res.ok) throw new Error(`HTTP ${res.status}`)
 
@@ -174,7 +173,7 @@ async function loadAllStudentSummaries() {
     // top-3 recent lessons from board_status — exactly what the
     // roster view needs. See CORRECTNESS_AND_MASTERY.md §14.
     const url = `${API_URL}/student-summaries?user_ids=${encodeURIComponent(userIds.join(','))}`
-    const res = await fetch(url, { headers: { 'x-api-key': API_KEY } })
+    const res = await fetch(url, { headers: authHeaders() })
     if (!res.ok) throw new Error(`HTTP ${res.status}`)
     const data = await res.json()
 
@@ -305,7 +304,7 @@ async function getStudentKey(studentUserId) {
     try {
       const res = await fetch(
         `${API_URL}/grants?grantee_id=${viewerId.value}`,
-        { headers: { 'x-api-key': API_KEY } }
+        { headers: authHeaders() }
       )
       if (res.ok) {
         const data = await res.json()
@@ -343,7 +342,7 @@ async function decryptStudentObservation(studentUserId, rawObs) {
   if (!aesKey) return null
 
   try {
-    const decrypted = await decryptObservation(rawObs.encrypted_data, rawObs.iv, aesKey)
+    const decrypted = await decryptObservation(rawObs.encrypted_data, rawObs.iv, aesKey, rowAad(rawObs))
     return {
       ...decrypted,
       id: rawObs.id,
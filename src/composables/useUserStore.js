@@ -8,6 +8,7 @@ import {
   decryptObservation
 } from '../utils/crypto.js'
 import { API_URL } from '@/utils/apiUrl.js'
+import { authHeaders, clearSession, ensureSession, setSession } from '@/utils/apiSession.js'
 
 const STORAGE_KEY = 'bridgePractice'
 
@@ -20,6 +21,9 @@ const initialized = ref(false)
 // View-as state — admin "View as user" mode. Transient: never persisted to localStorage.
 const viewAsUser = ref(null)
 
+// How often rotateKey() polls a running rotation
+const ROTATION_POLL_MS = 2000
+
 // Flag to track if user just registered (for showing key backup modal)
 const showKeyBackupModal = ref(false)
 
@@ -181,6 +185,7 @@ function deleteUser(userId) {
   if (currentUserId.value === userId) {
     const remainingIds = Object.keys(users.value)
     currentUserId.value = remainingIds.length > 0 ? remainingIds[0] : null
+    clearSession()
   }
 
   saveToStorage()
@@ -198,8 +203,12 @@ function switchUser(userId) {
     return false
   }
 
+  if (currentUserId.value !== userId) {
+    clearSession()
+  }
   currentUserId.value = userId
   saveToStorage()
+  ensureSession(users.value[userId])
   return true
 }
 
@@ -257,17 +266,22 @@ function clearUsers() {
   currentUserId.value = null
   adminViewerId.value = null
   showKeyBackupModal.value = false
+  clearSession()
   saveToStorage()
   initialized.value = false
 }
 
 /**
- * Initialize the store (load from storage if not already done)
+ * Initialize the store (load from storage if not already done) and make sure
+ * the current user has an API session. Await the result before calls that
+ * need one; it resolves quickly when the stored session is still fresh.
+ * @returns {Promise<{success: boolean, needsRecovery?: boolean, error?: string}>}
  */
 function initialize() {
   if (!initialized.value) {
     loadFromStorage()
   }
+  return ensureSession(users.value[currentUserId.value])
 }
 
 /**
@@ -295,7 +309,9 @@ async function restoreFromBackup(backup) {
     existingUser.secretKey = backup.secret_key
     existingUser.updatedAt = new Date().toISOString()
     currentUserId.value = backup.user_id
+    clearSession()
     saveToStorage()
+    ensureSession(existingUser)
     return { success: true, user: existingUser }
   }
 
@@ -320,6 +336,8 @@ async function restoreFromBackup(backup) {
 
   users.value[user.id] = user
   currentUserId.value = user.id
+  // Registering on the next sync proves the key and returns a session.
+  clearSession()
   saveToStorage()
 
   return { success: true, user }
@@ -453,25 +471,7 @@ async function claimRecovery(userId, token, apiUrl) {
     }
 
     // Restore user to local storage
-    const recoveredUser = {
-      id: data.user.id,
-      firstName: data.user.first_name,
-      lastName: data.user.last_name,
-      email: data.user.email,
-      classrooms: data.user.classroom ? [data.user.classroom] : [],
-      dataConsent: true,
-      secretKey: data.user.secret_key,
-      role: data.user.role || 'student',
-      viewerPrivateKey: data.user.viewer_private_key || null,
-      serverRegistered: true,
-      recoveredAt: new Date().toISOString(),
-      createdAt: new Date().toISOString(),
-      updatedAt: new Date().toISOString()
-    }
-
-    users.value[recoveredUser.id] = recoveredUser
-    currentUserId.value = recoveredUser.id
-    saveToStorage()
+    const recoveredUser = await applyRecoveredUser(data.user)
 
     return { success: true, user: recoveredUser }
   } catch (err) {
@@ -494,8 +494,15 @@ async function claimRecovery(userId, token, apiUrl) {
  * Write a server-returned user (snake_case, incl. plaintext secret_key) into
  * localStorage and make it current. Shared by the recovery-claim paths and the
  * account-merge handoff consumer.
+ *
+ * An account registered before recovery existed has no key on file, so the
+ * claim returns `secret_key: null`. This device keeps its own key (or starts a
+ * fresh one) and stays unregistered, so the next sync enrolls that key using
+ * the session the claim returned.
  */
-function applyRecoveredUser(serverUser) {
+async function applyRecoveredUser(serverUser) {
+  const localKey = users.value[serverUser.id]?.secretKey
+  const secretKey = serverUser.secret_key || localKey || (await generateSecretKey())
   const u = {
     id: serverUser.id,
     firstName: serverUser.first_name,
@@ -503,10 +510,10 @@ function applyRecoveredUser(serverUser) {
     email: serverUser.email,
     classrooms: serverUser.classroom ? [serverUser.classroom] : [],
     dataConsent: true,
-    secretKey: serverUser.secret_key,
+    secretKey,
     role: serverUser.role || 'student',
     viewerPrivateKey: serverUser.viewer_private_key || null,
-    serverRegistered: true,
+    serverRegistered: !!serverUser.secret_key,
     recoveredAt: new Date().toISOString(),
     createdAt: new Date().toISOString(),
     updatedAt: new Date().toISOString()
@@ -514,6 +521,13 @@ function applyRecoveredUser(serverUser) {
   users.value[u.id] = u
   currentUserId.value = u.id
   saveToStorage()
+
+  clearSession()
+  if (serverUser.session_token) {
+    setSession(u.id, serverUser.session_token)
+  } else {
+    ensureSession(u)
+  }
   return u
 }
 
@@ -522,13 +536,13 @@ function applyRecoveredUser(serverUser) {
  * row: the keeper's identity (incl. the keeper's AES key) wrapped under THIS
  * account's key. Fetch it, unwrap with our local key, switch localStorage to the
  * keeper, and consume the row. Returns true if a swap happened (caller reloads).
+
  */
 async function checkAccountHandoff(awayUserId) {
-  const apiKey = import.meta.env.VITE_API_KEY || ''
   try {
     const res = await fetch(
       `${API_URL}/account-handoff?from_user_id=${encodeURIComponent(awayUserId)}`,
-      { headers: { 'x-api-key': apiKey } }
+      { headers: authHeaders() }
     )
     if (!res.ok) return false // 404 = no handoff pending
 
@@ -548,14 +562,14 @@ async function checkAccountHandoff(awayUserId) {
     }
     if (!keeper?.id || !keeper?.secret_key) return false
 
-    applyRecoveredUser(keeper)
+    await applyRecoveredUser(keeper)
     delete users.value[awayUserId]
     saveToStorage()
 
     // Single-use marker (best-effort; the swap already happened locally).
     fetch(`${API_URL}/account-handoff/consume`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json', 'x-api-key': apiKey },
+      headers: { 'Content-Type': 'application/json', ...authHeaders() },
       body: JSON.stringify({ from_user_id: awayUserId })
     }).catch(() => {})
 
@@ -586,7 +600,7 @@ async function claimRecoveryByCode(email, code, apiUrl) {
     }
 
     // Restore user to local storage — shared with the handoff consumer.
-    const recoveredUser = applyRecoveredUser(data.user)
+    const recoveredUser = await applyRecoveredUser(data.user)
 
     return { success: true, user: recoveredUser }
   } catch (err) {
@@ -598,6 +612,123 @@ async function claimRecoveryByCode(email, code, apiUrl) {
   }
 }
 
+/**
+ * Create and upload sharing grants for each classroom's staff viewers
+ * (GET /users/:id unshared_classrooms). Best-effort per classroom.
+ */
+async function shareWithStaff(user, unsharedClassrooms) {
+  for (const classroom of unsharedClassrooms) {
+    try {
+      const grantPayloads = {}
+      for (const viewer of classroom.viewers || []) {
+        grantPayloads[viewer.viewer_id] = await createSharingGrant(user.secretKey, viewer.public_key)
+      }
+      if (Object.keys(grantPayloads).length === 0) continue
+      await fetch(`${API_URL}/classrooms/${encodeURIComponent(classroom.classroom_id)}/grants`, {
+        method: 'POST',
+        headers: authHeaders({ 'Content-Type': 'application/json' }),
+        body: JSON.stringify({ grant_payloads: grantPayloads })
+      })
+    } catch (err) {
+      console.warn('Failed to share with classroom staff:', err)
+    }
+  }
+}
+
+/**
+ * Rotate the current user's AES key (POST /key-rotation) and wait for the
+ * server to re-encrypt their observations. On completion the device switches
+ * to the new key and re-shares with every viewer whose grant the rotation
+ * revoked. Other devices have to recover the account by email.
+ *
+ * The new key is kept as pendingRotationKey until the rotation completes,
+ * so calling again after a failure or reload resumes with the same key.
+ *
+ * Starting a new rotation takes the 6-digit code from a recovery email
+ * (requestRecovery), since the current key may be the one that leaked.
+ * Resuming one doesn't. Without a code the result has needsCode set when
+ * the server asks for one.
+ *
+ * @param {Function} [onProgress] - Called with each status poll
+ * @param {string} [recoveryCode] - Code from the recovery email
+ * @returns {Promise<{success: boolean, error?: string, needsCode?: boolean}>}
+ */
+async function rotateKey(onProgress, recoveryCode) {
+  const user = users.value[currentUserId.value]
+  if (!user?.secretKey || !user.serverRegistered || viewAsUser.value) {
+    return { success: false, error: 'No registered user' }
+  }
+  if (!user.pendingRotationKey) {
+    user.pendingRotationKey = await generateSecretKey()
+    saveToStorage()
+  }
+  const newKey = user.pendingRotationKey
+
+  try {
+    const started = await fetch(`${API_URL}/key-rotation`, {
+      method: 'POST',
+      headers: authHeaders({ 'Content-Type': 'application/json' }),
+      body: JSON.stringify({
+        user_id: user.id,
+        new_key: newKey,
+        recovery_code: recoveryCode || null
+      })
+    })
+    if (started.status === 403 && !recoveryCode) {
+      return { success: false, needsCode: true }
+    }
+    if (!started.ok) {
+      return { success: false, error: await started.text() }
+    }
+    let status = await started.json()
+    while (status.status === 'running') {
+      onProgress?.(status)
+      await new Promise(resolve => setTimeout(resolve, ROTATION_POLL_MS))
+      const res = await fetch(
+        `${API_URL}/key-rotation?user_id=${encodeURIComponent(user.id)}`,
+        { headers: authHeaders() }
+      )
+      if (!res.ok) return { success: false, error: await res.text() }
+      status = await res.json()
+    }
+    onProgress?.(status)
+    if (status.status !== 'completed') {
+      return { success: false, error: status.error || 'Key rotation failed' }
+    }
+
+    user.secretKey = newKey
+    user.keyRotatedAt = status.completed_at
+    delete user.pendingRotationKey
+    saveToStorage()
+    clearSession()
+    await ensureSession(user)
+
+    for (const granteeId of status.revoked_grantees || []) {
+      try {
+        const res = await fetch(
+          `${API_URL}/viewers/${encodeURIComponent(granteeId)}/public-key`,
+          { headers: authHeaders() }
+        )
+        if (!res.ok) continue
+        const { public_key: publicKey } = await res.json()
+        await fetch(`${API_URL}/grants`, {
+          method: 'POST',
+          headers: authHeaders({ 'Content-Type': 'application/json' }),
+          body: JSON.stringify({
+            grantee_id: granteeId,
+            encrypted_payload: await createSharingGrant(newKey, publicKey)
+          })
+        })
+      } catch (err) {
+        console.warn('Failed to re-share after key rotation:', err)
+      }
+    }
+    return { success: true }
+  } catch (err) {
+    return { success: false, error: err.message }
+  }
+}
+
 /**
  * Sync the current user's role from the server.
  * Called on app startup to pick up role changes made server-side.
@@ -607,11 +738,10 @@ async function syncRole() {
   const user = users.value[currentUserId.value]
   if (!user) return
 
-  const apiKey = import.meta.env.VITE_API_KEY || ''
 
   try {
     const res = await fetch(`${API_URL}/users/${encodeURIComponent(user.id)}`, {
-      headers: { 'x-api-key': apiKey }
+      headers: authHeaders()
     })
     if (res.status === 404) {
       // Our account row is gone — most likely it was merged into a keeper.
@@ -633,6 +763,17 @@ async function syncRole() {
       changed = true
     }
 
+    // A key rotation finished on another device. Drop our session and prove
+    // the key afresh: a device still on the old key is refused, and the
+    // next sync asks the user to recover the account by email.
+    if (data.user.key_rotated_at && data.user.key_rotated_at !== user.keyRotatedAt) {
+      clearSession()
+      const proof = await ensureSession(user)
+      if (!proof.success) return
+      user.keyRotatedAt = data.user.key_rotated_at
+      changed = true
+    }
+
     // Sync admin name corrections
     if (data.user.name_corrected_at && data.user.name_corrected_at !== user.nameCorrectedAt) {
       user.firstName = data.user.first_name
@@ -651,6 +792,12 @@ async function syncRole() {
       }
     }
 
+    // Share our key with classroom staff who can't read our work yet: we were
+    // joined by a roster invitation, or staff were added after we joined.
+    if (Array.isArray(data.unshared_classrooms) && user.secretKey && !viewAsUser.value) {
+      await shareWithStaff(user, data.unshared_classrooms)
+    }
+
     // Sync viewer private key for teachers/admins (enables observation decryption on new devices)
     if (data.viewer_private_key && !user.viewerPrivateKey) {
       user.viewerPrivateKey = data.viewer_private_key
@@ -761,6 +908,7 @@ export function useUserStore() {
     getAdminViewerId,
     setAdminViewerId,
     createGrantForViewer,
+    rotateKey,
 
     // Account recovery
     requestRecovery,
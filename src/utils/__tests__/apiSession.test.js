@@ -0,0 +1,79 @@
+import { describe, it, expect, beforeEach, vi } from 'vitest'
+import {
+  tokenExpiry,
+  setSession,
+  clearSession,
+  sessionToken,
+  authHeaders,
+  ensureSession
+} from '../apiSession.js'
+
+// Same shape the API mints: base64url(claims) + "." + signature
+function fakeToken(claims) {
+  const b64 = btoa(JSON.stringify(claims)).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '')
+  return `${b64}.sig`
+}
+
+const inAnHour = () => Math.floor(Date.now() / 1000) + 3600
+
+describe('apiSession', () => {
+  beforeEach(() => {
+    clearSession()
+    vi.restoreAllMocks()
+  })
+
+  it('reads the expiry out of a token', () => {
+    expect(tokenExpiry(fakeToken({ sub: 'u1', role: 'student', iat: 1, exp: 12345 }))).toBe(12345)
+    expect(tokenExpiry('not-a-token')).toBeNull()
+  })
+
+  it('sends the bearer token once a session is set', () => {
+    expect(authHeaders().Authorization).toBeUndefined()
+
+    const token = fakeToken({ sub: 'u1', exp: inAnHour() })
+    setSession('u1', token)
+
+    expect(sessionToken()).toBe(token)
+    expect(authHeaders({ 'Content-Type': 'application/json' })).toMatchObject({
+      'Content-Type': 'application/json',
+      Authorization: `Bearer ${token}`
+    })
+
+    clearSession()
+    expect(authHeaders().Authorization).toBeUndefined()
+  })
+
+  it('does not ask for a session for a user the server has not registered', async () => {
+    const fetchSpy = vi.spyOn(globalThis, 'fetch')
+    const result = await ensureSession({ id: 'u1', secretKey: 'k', serverRegistered: false })
+    expect(result.success).toBe(false)
+    expect(fetchSpy).not.toHaveBeenCalled()
+  })
+
+  it('reuses a fresh session without calling the server', async () => {
+    setSession('u1', fakeToken({ sub: 'u1', exp: inAnHour() }))
+    const fetchSpy = vi.spyOn(globalThis, 'fetch')
+    const result = await ensureSession({ id: 'u1', secretKey: 'k', serverRegistered: true })
+    expect(result.success).toBe(true)
+    expect(fetchSpy).not.toHaveBeenCalled()
+  })
+
+  it('flags an account with no key on file for recovery', async () => {
+    vi.spyOn(globalThis, 'fetch').mockResolvedValue(
+      new Response('No key is on file for this account; recover it by email to sign in', { status: 401 })
+    )
+    const result = await ensureSession({ id: 'u1', secretKey: 'k', serverRegistered: true })
+    expect(result).toMatchObject({ success: false, needsRecovery: true })
+    expect(sessionToken()).toBeNull()
+  })
+
+  it('flags a device whose key was rotated elsewhere for recovery', async () => {
+    setSession('u1', fakeToken({ sub: 'u1', exp: 1 }))
+    vi.spyOn(globalThis, 'fetch').mockResolvedValue(
+      new Response('Invalid credentials', { status: 401 })
+    )
+    const result = await ensureSession({ id: 'u1', secretKey: 'old', serverRegistered: true })
+    expect(result).toMatchObject({ success: false, needsRecovery: true })
+    expect(sessionToken()).toBeNull()
+  })
+})
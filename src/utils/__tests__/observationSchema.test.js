@@ -2,6 +2,7 @@ import { describe, it, expect, beforeEach, vi } from 'vitest'
 import {
   createObservation,
   extractMetadata,
+  OBSERVATION_SCHEMA_VERSION,
   validateObservation,
   generateSessionId
 } from '../observationSchema.js'
@@ -210,6 +211,8 @@ describe('observationSchema', () => {
       expect(metadata.classroom).toBe('tuesday-am')
       expect(metadata.deal_subfolder).toBe('Stayman')
       expect(metadata.deal_number).toBe(5)
+      expect(obs.schema_version).toBe(OBSERVATION_SCHEMA_VERSION)
+      expect(metadata.schema_version).toBe(OBSERVATION_SCHEMA_VERSION)
     })
 
     it('should handle null classroom', () => {
@@ -0,0 +1,161 @@
+// Per-user API sessions.
+//
+// Every API route takes the caller from a signed `Authorization: Bearer`
+// token (see bridge-classroom-api/src/session.rs). The shared `x-api-key`
+// is still sent alongside, since a few service endpoints check it.
+//
+// A token comes from one of three places:
+//   1. `POST /users` registration returns one for the new account.
+//   2. A recovery claim returns one alongside the recovered key.
+//   3. `POST /sessions` trades the local secret key for one (ensureSession).
+//
+// The token is kept in localStorage next to the user store and re-minted
+// shortly before it expires. `authHeaders()` is synchronous so call sites
+// stay plain `fetch`; `ensureSession()` is awaited at startup and before
+// each sync so the header is populated by the time anything is sent.
+
+import { API_URL } from '@/utils/apiUrl.js'
+
+const API_KEY = import.meta.env.VITE_API_KEY || ''
+const STORAGE_KEY = 'bridgeSession'
+// Re-mint this long before expiry so in-flight requests don't race it.
+const REFRESH_MARGIN_MS = 5 * 60 * 1000
+
+let session = loadSession()
+let pending = null
+let refreshTimer = null
+
+function loadSession() {
+  try {
+    const stored = localStorage.getItem(STORAGE_KEY)
+    return stored ? JSON.parse(stored) : null
+  } catch {
+    return null
+  }
+}
+
+/**
+ * Read `exp` (unix seconds) out of a token's claims segment.
+ * @param {string} token - base64url(claims) + "." + signature
+ * @returns {number|null}
+ */
+export function tokenExpiry(token) {
+  try {
+    const b64 = token.split('.')[0].replace(/-/g, '+').replace(/_/g, '/')
+    const claims = JSON.parse(atob(b64.padEnd(b64.length + ((4 - (b64.length % 4)) % 4), '=')))
+    return typeof claims.exp === 'number' ? claims.exp : null
+  } catch {
+    return null
+  }
+}
+
+function isFresh(s, userId) {
+  return !!s?.token && s.userId === userId && s.expiresAt * 1000 - REFRESH_MARGIN_MS > Date.now()
+}
+
+/**
+ * Store a token the server handed back (registration, recovery, role change).
+ * @param {string} userId
+ * @param {string} token
+ */
+export function setSession(userId, token) {
+  if (!userId || !token) return
+  session = { userId, token, expiresAt: tokenExpiry(token) ?? 0 }
+  localStorage.setItem(STORAGE_KEY, JSON.stringify(session))
+}
+
+/**
+ * Forget the current token (sign-out, switching to a user without one).
+ */
+export function clearSession() {
+  session = null
+  if (refreshTimer) {
+    clearTimeout(refreshTimer)
+    refreshTimer = null
+  }
+  localStorage.removeItem(STORAGE_KEY)
+}
+
+/**
+ * The current bearer token, or null. For `?session=` on sendBeacon, which
+ * can't set headers.
+ */
+export function sessionToken() {
+  return session?.token || null
+}
+
+/**
+ * Request headers for an API call: the app key plus the caller's session.
+ * @param {Object} [extra] - Additional headers, e.g. Content-Type
+ * @returns {Object}
+ */
+export function authHeaders(extra = {}) {
+  const headers = { 'x-api-key': API_KEY, ...extra }
+  if (session?.token) {
+    headers.Authorization = `Bearer ${session.token}`
+  }
+  return headers
+}
+
+function scheduleRefresh(user) {
+  if (refreshTimer) clearTimeout(refreshTimer)
+  if (!session?.expiresAt) return
+  const delay = Math.max(session.expiresAt * 1000 - REFRESH_MARGIN_MS - Date.now(), 0)
+  refreshTimer = setTimeout(() => {
+    refreshTimer = null
+    ensureSession(user)
+  }, delay)
+}
+
+/**
+ * Make sure there is an unexpired session for `user`, minting one from
+ * the local secret key if needed. Concurrent callers share one request.
+ *
+ * Users the server hasn't registered yet are skipped: registration itself
+ * returns their first token.
+ *
+ * A 401 means this device can't prove the account: it has no key on file,
+ * or the key was rotated on another device. Only email recovery signs such
+ * a device back in, so the result carries `needsRecovery`.
+ *
+ * @param {Object} user - Local user ({ id, secretKey, serverRegistered })
+ * @returns {Promise<{success: boolean, needsRecovery?: boolean, error?: string}>}
+ */
+export async function ensureSession(user) {
+  if (!user?.id || !user.secretKey || !user.serverRegistered) {
+    return { success: false, error: 'No registered user' }
+  }
+  if (isFresh(session, user.id)) {
+    if (!refreshTimer) scheduleRefresh(user)
+    return { success: true }
+  }
+  if (pending) return pending
+
+  pending = (async () => {
+    try {
+      const response = await fetch(`${API_URL}/sessions`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json', 'x-api-key': API_KEY },
+        body: JSON.stringify({ user_id: user.id, secret_key: user.secretKey })
+      })
+      if (!response.ok) {
+        const text = await response.text()
+        if (session?.userId === user.id) clearSession()
+        return {
+          success: false,
+          needsRecovery: response.status === 401,
+          error: text || `Server error: ${response.status}`
+        }
+      }
+      const data = await response.json()
+      setSession(data.user_id, data.session_token)
+      scheduleRefresh(user)
+      return { success: true }
+    } catch (err) {
+      return { success: false, error: err.message }
+    } finally {
+      pending = null
+    }
+  })()
+  return pending
+}
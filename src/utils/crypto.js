@@ -56,15 +56,49 @@ export async function importSecretKey(secretKeyBase64) {
 
 // ============ Observation Encryption ============
 
+/** Cipher format 1: the payload is sealed on its own. */
+export const CIPHER_FORMAT_LEGACY = 1
+/** Cipher format 2: the cleartext metadata is sealed as AES-GCM associated data. */
+export const CIPHER_FORMAT_AAD = 2
+
+/**
+ * Associated data binding a format-2 ciphertext to its cleartext metadata.
+ * Must match ObservationAad in bridge-classroom-api/src/obs_crypto.rs byte
+ * for byte.
+ *
+ * @param {Object} meta - Submit metadata, or an observation row from the server
+ * @returns {Uint8Array}
+ */
+export function observationAad(meta) {
+  return new TextEncoder().encode(JSON.stringify([
+    'bridge-classroom/observation/v2',
+    meta.observation_id ?? meta.id,
+    meta.user_id,
+    meta.deal_subfolder ?? null,
+    meta.deal_number ?? null,
+    meta.correct === true || meta.correct === 1
+  ]))
+}
+
+/**
+ * The associated data to decrypt a server row with (null for legacy rows).
+ * @param {Object} row - Observation row with cipher_format and metadata columns
+ * @returns {Uint8Array|null}
+ */
+export function rowAad(row) {
+  return row.cipher_format === CIPHER_FORMAT_AAD ? observationAad(row) : null
+}
+
 /**
  * Encrypt an observation with the user's secret key
  * Simple AES-256-GCM encryption - no dual-key complexity
  *
  * @param {Object} observation - The observation data to encrypt
  * @param {string} secretKeyBase64 - Base64-encoded AES secret key
+ * @param {Uint8Array|null} [aad] - observationAad(metadata) to seal as format 2
  * @returns {Promise<{encrypted_data: string, iv: string}>}
  */
-export async function encryptObservation(observation, secretKeyBase64) {
+export async function encryptObservation(observation, secretKeyBase64, aad = null) {
   const secretKey = await importSecretKey(secretKeyBase64)
   const iv = crypto.getRandomValues(new Uint8Array(12)) // 96-bit IV for GCM
 
@@ -72,7 +106,7 @@ export async function encryptObservation(observation, secretKeyBase64) {
   const data = encoder.encode(JSON.stringify(observation))
 
   const ciphertext = await crypto.subtle.encrypt(
-    { name: 'AES-GCM', iv },
+    aad ? { name: 'AES-GCM', iv, additionalData: aad } : { name: 'AES-GCM', iv },
     secretKey,
     data
   )
@@ -89,15 +123,16 @@ export async function encryptObservation(observation, secretKeyBase64) {
  * @param {string} encryptedData - Base64-encoded ciphertext
  * @param {string} iv - Base64-encoded IV
  * @param {string} secretKeyBase64 - Base64-encoded AES secret key
+ * @param {Uint8Array|null} [aad] - rowAad(row) for format-2 rows
  * @returns {Promise<Object>} Decrypted observation
  */
-export async function decryptObservation(encryptedData, iv, secretKeyBase64) {
+export async function decryptObservation(encryptedData, iv, secretKeyBase64, aad = null) {
   const secretKey = await importSecretKey(secretKeyBase64)
   const ciphertextBuffer = base64ToArrayBuffer(encryptedData)
   const ivBuffer = base64ToArrayBuffer(iv)
 
   const decrypted = await crypto.subtle.decrypt(
-    { name: 'AES-GCM', iv: ivBuffer },
+    aad ? { name: 'AES-GCM', iv: ivBuffer, additionalData: aad } : { name: 'AES-GCM', iv: ivBuffer },
     secretKey,
     ciphertextBuffer
   )
@@ -6,6 +6,13 @@
  * and correctness.
  */
 
+/**
+ * Version of the observation payload shape. Bump it (and add the new
+ * version to bridge-classroom-api/src/observation_schema.rs) whenever the
+ * fields below change.
+ */
+export const OBSERVATION_SCHEMA_VERSION = 2
+
 /**
  * @typedef {Object} DealContext
  * @property {string} subfolder - Lesson category (e.g., "Stayman")
@@ -53,6 +60,7 @@
 
 /**
  * @typedef {Object} Observation
+ * @property {number} schema_version - Payload shape version (OBSERVATION_SCHEMA_VERSION)
  * @property {string} observation_id - Unique ID for this observation
  * @property {string} timestamp - ISO8601 timestamp
  * @property {string} user_id - User who made this observation
@@ -109,6 +117,7 @@ export function createObservation({
   const studentHand = deal.hands?.[studentSeat.toLowerCase()] || ''
 
   return {
+    schema_version: OBSERVATION_SCHEMA_VERSION,
     observation_id: observationId || crypto.randomUUID(),
     timestamp: new Date().toISOString(),
     user_id: userId,
@@ -195,7 +204,9 @@ export function extractMetadata(observation, classroom) {
     // observations), else the single per-prompt time. Lifted into
     // the clear so the teacher's assignment-duration analytics
     // doesn't need to decrypt the blob (issue #7).
-    time_taken_ms: computeTotalTimeMs(observation)
+    time_taken_ms: computeTotalTimeMs(observation),
+    // Payload shape version; observations built before it existed are 1.
+    schema_version: observation.schema_version || 1
   }
 }
 